@@ -1,12 +1,45 @@
 // use unicode_width::UnicodeWidthStr;
 
+use super::assertion::{self, AssertionResult};
+use super::auth::{self, AuthMode, AUTH_MODES};
+use super::body::{self, BodyMode, Payload, BODY_MODES};
+use super::collection::{self, Environment, SavedRequest};
+use super::config::Config;
+use super::connection::{Connection, Sockets};
+use super::cookie::CookieJar;
+use super::external::External;
+use super::graphql::{self, Schema};
+use super::grpc;
+use super::har;
+use super::history::Exchange;
+use super::loader::Source;
+use super::oauth2;
+use super::protobuf::Pool;
+use super::proxy::{self, Proxies};
+use super::resolve::{self, Resolver};
+use super::settings::{self, ClientSettings};
+use super::sigv4;
+use super::stream::{self, Stream};
+use super::tls::{self, TlsInfo};
+use super::websocket::{self, Session};
+use super::wire;
+
 use tui::widgets::ListState;
 use std::collections::BTreeMap;
-use reqwest::header::HeaderMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use chrono::{Local, Utc};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, COOKIE, LOCATION, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
 
 #[derive(Clone)]
 struct Cursor {
-    x: u16,
+    // テキストの先頭からの文字数（バイト数ではない）
+    x: usize,
+    #[allow(dead_code)]
     y: u16,
 }
 
@@ -23,8 +56,58 @@ impl EditView {
             cursor: Cursor { x: 0, y: 0 },
         }
     }
+
+    fn set_text(&mut self, text: &str) {
+        self.text = text.to_string();
+        self.cursor.x = self.text.chars().count();
+    }
+
+    // テキストだけ差し替えられていたらカーソルを末尾に戻す
+    fn clamp(&mut self) {
+        self.cursor.x = self.cursor.x.min(self.text.chars().count());
+    }
+
+    // カーソルの位置のバイト位置
+    fn byte_index(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor.x)
+            .map(|(i, _)| i)
+            .unwrap_or_else(|| self.text.len())
+    }
+
+    fn insert(&mut self, c: char) {
+        self.clamp();
+        let i = self.byte_index();
+        self.text.insert(i, c);
+        self.cursor.x += 1;
+    }
+
+    fn insert_str(&mut self, s: &str) {
+        self.clamp();
+        let i = self.byte_index();
+        self.text.insert_str(i, s);
+        self.cursor.x += s.chars().count();
+    }
+
+    // カーソルの前の 1 文字を消す
+    fn delete(&mut self) {
+        self.clamp();
+        if self.cursor.x == 0 {
+            return;
+        }
+        self.cursor.x -= 1;
+        let i = self.byte_index();
+        self.text.remove(i);
+    }
+
+    // 画面上のカーソルの列（長いテキストでも足し算があふれないように抑える）
+    fn column(&self) -> u16 {
+        self.cursor.x.min(u16::MAX as usize / 2) as u16
+    }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone)]
 enum HttpMethod {
     GET,
@@ -50,7 +133,7 @@ impl ListMethod {
         let mut state = ListState::default();
         state.select(Some(0));
         ListMethod {
-            state: state,
+            state,
             items: vec![
                 "GET".to_string(),
                 "HEAD".to_string(),
@@ -115,6 +198,137 @@ impl ListMethod {
             HttpMethod::PATCH => HttpMethod::TRACE,
         };
     }
+
+    pub fn selected_name(&self) -> String {
+        let i = self.state.selected().unwrap_or(0);
+        self.items[i].to_string()
+    }
+
+    // 名前でメソッドを選択する（該当しない場合はfalseを返す）
+    pub fn select(&mut self, name: &str) -> bool {
+        let index = match self.items.iter().position(|i| i.eq_ignore_ascii_case(name)) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        *self = ListMethod::new();
+        for _ in 0..index {
+            self.next();
+        }
+        true
+    }
+}
+
+#[derive(Clone)]
+pub struct ListBodyMode {
+    state: ListState,
+    items: Vec<String>,
+    value: BodyMode,
+}
+
+impl ListBodyMode {
+    pub fn new() -> ListBodyMode {
+        let mut state = ListState::default();
+        state.select(Some(0));
+        ListBodyMode {
+            state,
+            items: BODY_MODES.iter().map(|m| m.name().to_string()).collect(),
+            value: BodyMode::Raw,
+        }
+    }
+
+    fn select_index(&mut self, i: usize) {
+        self.state.select(Some(i));
+        self.value = BODY_MODES[i];
+    }
+
+    pub fn next(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.items.len() => i + 1,
+            _ => 0,
+        };
+        self.select_index(i);
+    }
+
+    pub fn previous(&mut self) {
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.select_index(i);
+    }
+
+    // 名前でモードを選択する（空の場合は raw）
+    pub fn select(&mut self, name: &str) -> bool {
+        let mode = if name.is_empty() {
+            Some(BodyMode::Raw)
+        } else {
+            BodyMode::from_name(name)
+        };
+        match mode.and_then(|m| BODY_MODES.iter().position(|b| *b == m)) {
+            Some(i) => {
+                self.select_index(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ListAuthMode {
+    state: ListState,
+    items: Vec<String>,
+    value: AuthMode,
+}
+
+impl ListAuthMode {
+    pub fn new() -> ListAuthMode {
+        let mut state = ListState::default();
+        state.select(Some(0));
+        ListAuthMode {
+            state,
+            items: AUTH_MODES.iter().map(|m| m.name().to_string()).collect(),
+            value: AuthMode::None,
+        }
+    }
+
+    fn select_index(&mut self, i: usize) {
+        self.state.select(Some(i));
+        self.value = AUTH_MODES[i];
+    }
+
+    pub fn next(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.items.len() => i + 1,
+            _ => 0,
+        };
+        self.select_index(i);
+    }
+
+    pub fn previous(&mut self) {
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.select_index(i);
+    }
+
+    // 名前でモードを選択する（空の場合は none）
+    pub fn select(&mut self, name: &str) -> bool {
+        let mode = if name.is_empty() {
+            Some(AuthMode::None)
+        } else {
+            AuthMode::from_name(name)
+        };
+        match mode.and_then(|m| AUTH_MODES.iter().position(|b| *b == m)) {
+            Some(i) => {
+                self.select_index(i);
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -122,8 +336,24 @@ struct Request {
     url: EditView,
     params: EditView,
     header: EditView,
+    auth: EditView,
     body: EditView,
+    // graphql のボディの変数（JSON）
+    variables: EditView,
     method: ListMethod,
+    auth_mode: ListAuthMode,
+    body_mode: ListBodyMode,
+    assertion: EditView,
+    settings: EditView,
+}
+
+// reqwest は TLS のセッションを公開しないので、同じ設定で別に接続して調べる
+#[derive(Clone)]
+struct TlsProbe {
+    host: String,
+    remote: SocketAddr,
+    connection: Option<Connection>,
+    settings: ClientSettings,
 }
 
 #[derive(Clone)]
@@ -131,6 +361,218 @@ struct Response {
     status: EditView,
     header: EditView,
     body: EditView,
+    code: Option<u16>,
+    headers: HeaderMap,
+    elapsed: Duration,
+    // 接続を再利用したか（判断できなければ None）
+    connection: Option<Connection>,
+    // connection を送信の前後に開いていたソケットから推測したか
+    connection_guessed: bool,
+    // 接続先のアドレス
+    remote: Option<SocketAddr>,
+    // Unix ドメインソケットで送ったときのパス
+    unix_socket: Option<String>,
+    // 経由したプロキシ（パスワードは伏せる）
+    proxy: Option<String>,
+    // HTTPS のときの TLS 接続の情報（調べられなければエラー、まだ調べていなければ None）
+    tls: Option<Result<TlsInfo, String>>,
+    // TLS の情報を調べる接続先（TLS ペインを開くまで調べない）
+    tls_probe: Option<TlsProbe>,
+    // リダイレクトの途中も含めた各レスポンスと、ペインに表示しているもの
+    hops: Vec<Exchange>,
+    hop: usize,
+    // レスポンスボディのペインが WebSocket のフレームのログ
+    websocket: bool,
+    // text/event-stream のイベントと、一覧で選んでいるもの
+    events: Vec<stream::Event>,
+    event: usize,
+    // graphql のボディで送ったレスポンスか（data と errors を分けて表示する）
+    graphql: bool,
+    // gRPC のトレーラー（headers にも含める）
+    trailers: HeaderMap,
+    // wire で受信したバイト列と、その表示
+    raw: Option<(Vec<u8>, String)>,
+    test_results: Vec<AssertionResult>,
+}
+
+// 読み込み中のレスポンスボディ（読み終わったら検証して履歴に加える）
+#[derive(Clone)]
+struct Streaming {
+    stream: Rc<Stream>,
+    decoder: stream::Decoder,
+    // text/event-stream ならイベントに分ける
+    parser: Option<stream::EventParser>,
+    status: String,
+    exchange: Exchange,
+    started: Instant,
+    hop_started: Instant,
+}
+
+// リダイレクトを追うときに次に送るリクエスト
+struct Hop {
+    method: reqwest::Method,
+    url: url::Url,
+    // 303 などでボディを送らなくなったら false
+    body: bool,
+    // 最初のリクエストと同じホストか（違えば認証情報を送らない）
+    same_host: bool,
+}
+
+// 組み立て済みのリクエストの中身（リダイレクトのたびにここから作り直す）
+struct Outgoing {
+    client: reqwest::blocking::Client,
+    resolver: Option<Resolver>,
+    headers: HeaderMap,
+    payload: Payload,
+    // 履歴に残すボディ（ファイルと標準入力は空）
+    request_body: String,
+    digest: Option<(String, String)>,
+    aws: Option<sigv4::Settings>,
+    payload_hash: String,
+}
+
+impl Outgoing {
+    fn build(
+        &self,
+        hop: &Hop,
+        authorization: Option<HeaderValue>,
+        cookies: &CookieJar,
+    ) -> Result<reqwest::blocking::Request, String> {
+        let mut headers = self.headers.clone();
+        // 別のホストへのリダイレクトには認証情報を送らない
+        if !hop.same_host {
+            headers.remove(AUTHORIZATION);
+            headers.remove(COOKIE);
+        }
+        if let Some(authorization) = authorization {
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        let mut builder = self.client.request(hop.method.clone(), hop.url.clone());
+        if hop.body {
+            let body = match &self.payload {
+                Payload::Bytes(bytes) => reqwest::blocking::Body::from(bytes.clone()),
+                Payload::File(path) => File::open(path)
+                    .map(reqwest::blocking::Body::from)
+                    .map_err(|e| format!("failed to open {}: {}", path.display(), e))?,
+                Payload::Multipart(multipart) => {
+                    let (reader, len) = multipart.reader()?;
+                    reqwest::blocking::Body::sized(reader, len)
+                }
+                Payload::Stdin => reqwest::blocking::Body::new(io::stdin()),
+            };
+            builder = builder.body(body);
+        } else {
+            headers.remove(CONTENT_TYPE);
+        }
+        let mut request = builder
+            .headers(headers)
+            .build()
+            .map_err(|e| e.to_string())?;
+        // ヘッダーペインで指定されていなければクッキージャーから付ける
+        if !request.headers().contains_key(COOKIE) {
+            if let Some(cookie) = cookies.header_for(request.url()) {
+                if let Ok(value) = cookie.parse() {
+                    request.headers_mut().insert(COOKIE, value);
+                }
+            }
+        }
+        // 変数を展開して組み立てたリクエストに送信直前で署名する
+        if let Some(aws) = &self.aws {
+            if hop.body {
+                aws.sign(&mut request, &self.payload_hash, Utc::now())?;
+            } else {
+                aws.sign(&mut request, &sigv4::payload_hash(aws, &Payload::Bytes(Vec::new()))?, Utc::now())?;
+            }
+        }
+        Ok(request)
+    }
+
+    fn send(
+        &self,
+        hop: &Hop,
+        request: reqwest::blocking::Request,
+    ) -> Result<(Exchange, reqwest::blocking::Response), String> {
+        let target = request.url().clone();
+        let exchange = Exchange {
+            started: Utc::now(),
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            request_headers: request.headers().clone(),
+            request_body: if hop.body {
+                self.request_body.to_string()
+            } else {
+                "".to_string()
+            },
+            status: 0,
+            status_text: "".to_string(),
+            http_version: "".to_string(),
+            response_headers: HeaderMap::new(),
+            response_body: "".to_string(),
+            wait: Duration::from_millis(0),
+            receive: Duration::from_millis(0),
+        };
+        // Resolver で接続できなかったときはその理由を返す
+        self.client
+            .execute(request)
+            .map(|resp| (exchange, resp))
+            .map_err(|e| match self.resolver.as_ref().and_then(|r| r.remote(&target)) {
+                Some(Err(reason)) => reason,
+                _ => e.to_string(),
+            })
+    }
+
+    // ダイジェスト認証はチャレンジを受け取ってから送り直す
+    fn send_with_digest(
+        &self,
+        hop: &Hop,
+        cookies: &CookieJar,
+    ) -> Result<(Exchange, reqwest::blocking::Response), String> {
+        let (exchange, resp) = self.build(hop, None, cookies).and_then(|r| self.send(hop, r))?;
+        // 別のホストへのリダイレクトには Basic などと同じくダイジェストの認証情報も送らない
+        let (username, password) = match &self.digest {
+            Some(credentials) if hop.same_host && resp.status() == StatusCode::UNAUTHORIZED => credentials,
+            _ => return Ok((exchange, resp)),
+        };
+        let challenge = resp
+            .headers()
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find(|v| auth::is_digest_challenge(v))
+            .map(|v| v.to_string());
+        let challenge = match challenge {
+            Some(challenge) => challenge,
+            None => return Ok((exchange, resp)),
+        };
+        let uri = match hop.url.query() {
+            Some(query) => format!("{}?{}", hop.url.path(), query),
+            None => hop.url.path().to_string(),
+        };
+        let authorization = auth::digest_authorization(
+            &challenge,
+            &exchange.method,
+            &uri,
+            username,
+            password,
+        )?;
+        self.build(hop, Some(authorization), cookies)
+            .and_then(|r| self.send(hop, r))
+    }
+}
+
+// 最後のレスポンスと、その区間の計測を始めた時刻
+type Sent = (Exchange, reqwest::blocking::Response, Instant);
+
+// 送るときに使う認証（ダイジェストのユーザー名とパスワード、AWS の署名の設定）
+type Signing = (Option<(String, String)>, Option<sigv4::Settings>);
+
+// 3xx の Location を相対 URL も含めて解決する
+fn redirect_location(resp: &reqwest::blocking::Response) -> Option<url::Url> {
+    if !resp.status().is_redirection() || resp.status() == StatusCode::NOT_MODIFIED {
+        return None;
+    }
+    let location = resp.headers().get(LOCATION)?.to_str().ok()?;
+    resp.url().join(location).ok()
 }
 
 #[derive(Clone)]
@@ -139,7 +581,13 @@ pub enum ViewMode {
     RequestParams,
     RequestMethod,
     RequestHeader,
+    RequestAuth,
+    RequestAuthMode,
     RequestBody,
+    RequestVariables,
+    RequestBodyMode,
+    RequestAssertion,
+    RequestSettings,
 
     ResponseHeader,
     ResponseBody,
@@ -152,13 +600,78 @@ pub enum EditMode {
     RequestParams,
     RequestMethod,
     RequestHeader,
+    RequestAuth,
+    RequestAuthMode,
     RequestBody,
+    RequestVariables,
+    RequestBodyMode,
+    RequestAssertion,
+    RequestSettings,
+}
+
+// 画面中央に重ねて表示するウィンドウ
+#[derive(PartialEq, Eq, Clone)]
+#[allow(clippy::enum_variant_names)]
+pub enum Popup {
+    NoPopup,
+    RequestList,
+    Cookies,
+    Settings,
+    Redirects,
+    Tls,
+    Events,
+    GraphQL,
+    Grpc,
 }
 
 #[derive(Clone)]
 struct Mode {
     view: ViewMode,
     edit: EditMode,
+    popup: Popup,
+}
+
+// 読み込んだ保存済みリクエストの一覧
+#[derive(Clone)]
+struct SavedRequests {
+    state: ListState,
+    items: Vec<SavedRequest>,
+    // 各ペインに読み込まれているリクエスト
+    loaded: Option<usize>,
+    source: Option<Source>,
+}
+
+impl SavedRequests {
+    fn new() -> SavedRequests {
+        SavedRequests {
+            state: ListState::default(),
+            items: Vec::new(),
+            loaded: None,
+            source: None,
+        }
+    }
+
+    fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.items.len() => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
 }
 
 #[derive(Clone)]
@@ -166,6 +679,34 @@ pub struct App {
     request: Request,
     response: Response,
     mode: Mode,
+    variables: BTreeMap<String, String>,
+    saved_requests: SavedRequests,
+    history: Vec<Exchange>,
+    notice: String,
+    // 外部プログラムで開くよう要求されている
+    external: bool,
+    // OAuth 2.0 で取得したトークン（設定ごと）
+    tokens: BTreeMap<String, oauth2::Token>,
+    // 送信に使うクッキー（環境ごとに保存する）
+    cookies: CookieJar,
+    cookie_state: ListState,
+    // 設定ファイルの内容
+    config: Config,
+    // 環境ごとの設定（設定ファイルより優先し、Settings ペインで上書きする）
+    environment: ClientSettings,
+    // 接続を使い回すため、設定が変わったときだけ作り直す
+    client: Option<(ClientSettings, reqwest::blocking::Client, Option<Resolver>)>,
+    // 接続ごとの TLS の情報（再利用した接続では調べ直さない）
+    tls_sessions: BTreeMap<SocketAddr, TlsInfo>,
+    // 接続中の WebSocket
+    websocket: Option<Rc<Session>>,
+    // 読み込み中のレスポンス
+    stream: Option<Streaming>,
+    // イントロスペクションで取得した GraphQL のスキーマと、その URL
+    graphql_schema: Option<(String, Schema)>,
+    // gRPC のサービスと型（接続先と .proto の設定ごと）と、一覧で選んでいるメソッド
+    grpc_pool: Option<(String, Rc<Pool>)>,
+    grpc_method: usize,
 }
 
 impl App {
@@ -175,18 +716,61 @@ impl App {
                 url: EditView::new(),
                 params: EditView::new(),
                 header: EditView::new(),
+                auth: EditView::new(),
                 body: EditView::new(),
+                variables: EditView::new(),
                 method: ListMethod::new(),
+                auth_mode: ListAuthMode::new(),
+                body_mode: ListBodyMode::new(),
+                assertion: EditView::new(),
+                settings: EditView::new(),
             },
             response: Response {
                 status: EditView::new(),
                 header: EditView::new(),
                 body: EditView::new(),
+                code: None,
+                headers: HeaderMap::new(),
+                elapsed: Duration::from_millis(0),
+                connection: None,
+                connection_guessed: false,
+                remote: None,
+                unix_socket: None,
+                proxy: None,
+                tls: None,
+                tls_probe: None,
+                hops: Vec::new(),
+                hop: 0,
+                websocket: false,
+                events: Vec::new(),
+                event: 0,
+                graphql: false,
+                trailers: HeaderMap::new(),
+                raw: None,
+                test_results: Vec::new(),
             },
             mode: Mode {
                 view: ViewMode::RequestUrl,
                 edit: EditMode::NoMode,
+                popup: Popup::NoPopup,
             },
+            variables: BTreeMap::new(),
+            saved_requests: SavedRequests::new(),
+            history: Vec::new(),
+            notice: "".to_string(),
+            external: false,
+            tokens: BTreeMap::new(),
+            cookies: CookieJar::default(),
+            cookie_state: ListState::default(),
+            config: Config::default(),
+            environment: ClientSettings::default(),
+            client: None,
+            tls_sessions: BTreeMap::new(),
+            websocket: None,
+            stream: None,
+            graphql_schema: None,
+            grpc_pool: None,
+            grpc_method: 0,
         }
     }
 
@@ -206,10 +790,109 @@ impl App {
         &self.request.body.text
     }
 
+    // `@path` のときは中身の代わりにファイルの概要を表示する
+    pub fn request_body_display(&self) -> String {
+        match body::summary(&self.render(&self.request.body.text)) {
+            Some(summary) if self.mode.edit != EditMode::RequestBody => summary,
+            _ => self.request.body.text.to_string(),
+        }
+    }
+
+    pub fn request_variables_text(&self) -> &String {
+        &self.request.variables.text
+    }
+
+    pub fn is_graphql(&self) -> bool {
+        self.request.body_mode.value == BodyMode::GraphQL
+    }
+
+    pub fn is_wire(&self) -> bool {
+        self.request.body_mode.value == BodyMode::Wire
+    }
+
+    pub fn request_assertion_text(&self) -> &String {
+        &self.request.assertion.text
+    }
+
+    pub fn request_settings_text(&self) -> &String {
+        &self.request.settings.text
+    }
+
     pub fn response_status_text(&self) -> &String {
         &self.response.status.text
     }
 
+    // ステータスに所要時間と接続の再利用を添えて表示する
+    pub fn response_status_display(&self) -> String {
+        let warning = if self.tls_insecure() {
+            " !! TLS VERIFICATION DISABLED !!"
+        } else {
+            ""
+        };
+        if self.response.code.is_none() {
+            return format!("{}{}", self.response.status.text, warning);
+        }
+        let mut lines = vec![format!("{}{}", self.response.status.text, warning)];
+        let proxy = match &self.response.proxy {
+            Some(proxy) => format!(" via {}", proxy),
+            None => "".to_string(),
+        };
+        let tls = match &self.response.tls {
+            Some(Ok(info)) => format!(", {} {}", info.version, info.cipher),
+            _ => "".to_string(),
+        };
+        let target = self
+            .response
+            .unix_socket
+            .as_ref()
+            .map(|path| format!("unix:{}", path));
+        // reqwest の接続は送信前後のソケットから推測したもの
+        let guessed = if self.response.connection_guessed {
+            " (guessed from open sockets)"
+        } else {
+            ""
+        };
+        match self.response.connection {
+            Some(connection) => lines.push(format!(
+                "{} ms, {}{}{}{}",
+                self.response.elapsed.as_millis(),
+                match &target {
+                    Some(target) => connection.describe_to(target),
+                    None => connection.describe(),
+                },
+                guessed,
+                proxy,
+                tls
+            )),
+            None => match self.response_remote() {
+                Some(remote) => lines.push(format!(
+                    "{} ms, remote {}{}{}",
+                    self.response.elapsed.as_millis(),
+                    remote,
+                    proxy,
+                    tls
+                )),
+                None => lines.push(format!("{} ms{}{}", self.response.elapsed.as_millis(), proxy, tls)),
+            },
+        }
+        if self.response.hops.len() > 1 {
+            let chain = self
+                .response
+                .hops
+                .iter()
+                .map(|hop| hop.status.to_string())
+                .collect::<Vec<String>>()
+                .join(" -> ");
+            lines.push(format!(
+                "redirects: {} [{}/{}]",
+                chain,
+                self.response.hop + 1,
+                self.response.hops.len()
+            ));
+        }
+        lines.join("\n")
+    }
+
     pub fn response_header_text(&self) -> &String {
         &self.response.header.text
     }
@@ -218,370 +901,2212 @@ impl App {
         &self.response.body.text
     }
 
-    pub fn request_url_cursor_x(&self) -> &u16 {
-        &self.request.url.cursor.x
+    // wire で受信したバイト列
+    pub fn response_raw_bytes(&self) -> Option<&Vec<u8>> {
+        self.response.raw.as_ref().map(|(bytes, _)| bytes)
     }
 
-    pub fn request_method_items_vec(&self) -> &Vec<String> {
-        &self.request.method.items
+    pub fn response_raw_text(&self) -> Option<&String> {
+        self.response.raw.as_ref().map(|(_, text)| text)
     }
 
-    pub fn request_method_state(&self) -> &ListState {
-        &self.request.method.state
+    pub fn response_status_code(&self) -> Option<u16> {
+        self.response.code
     }
 
-    pub fn request_params_cursor_x(&self) -> &u16 {
-        &self.request.params.cursor.x
+    pub fn response_test_results(&self) -> &Vec<AssertionResult> {
+        &self.response.test_results
     }
 
-    pub fn request_header_cursor_x(&self) -> &u16 {
-        &self.request.header.cursor.x
+    pub fn response_headers(&self) -> &HeaderMap {
+        &self.response.headers
     }
 
-    pub fn request_body_cursor_x(&self) -> &u16 {
-        &self.request.body.cursor.x
+    pub fn response_elapsed(&self) -> &Duration {
+        &self.response.elapsed
     }
 
-    pub fn request_params_map(&self) -> BTreeMap<String, String> {
-        let request_params_text = self.request_params_text();
-        let request_params_text = request_params_text.replace("\n", "&");
-
-        let mut params = BTreeMap::new();
-        for query_str in request_params_text.split('&') {
-            let q:Vec<String> = query_str.split('=').map(|s| s.to_string()).collect();
-            let key = q.get(0).unwrap_or(&"".to_string()).to_string();
-            let value = q.get(1).unwrap_or(&"".to_string()).to_string();
-            if key.len() == 0 && value.len() == 0 {
-                continue;
-            }
-            params.insert(key, value);
-        }
+    pub fn variables(&self) -> &BTreeMap<String, String> {
+        &self.variables
+    }
 
-        params
+    pub fn history(&self) -> &Vec<Exchange> {
+        &self.history
     }
 
-    pub fn request_header_map(&self) -> HeaderMap {
-        let request_header_text = self.request_header_text();
+    pub fn notice(&self) -> &String {
+        &self.notice
+    }
 
-        let mut headers = HeaderMap::new();
-        for header_str in request_header_text.split('\n') {
-            let h:Vec<String> = header_str.split(':').map(|s| s.to_string()).collect();
-            let key = h.get(0).unwrap_or(&"".to_string()).to_string();
-            let value = h.get(1).unwrap_or(&"".to_string()).to_string();
+    pub fn set_notice(&mut self, notice: &str) {
+        self.notice = notice.to_string();
+    }
 
-            if key == "" || value == "" {
-                break;
-            }
+    pub fn open_external(&mut self) {
+        self.external = true;
+    }
 
-            let key = Box::leak(key.into_boxed_str());
+    // 要求されていれば、選択中のペインを外部プログラムで開く内容を返す
+    pub fn take_external(&mut self) -> Option<External> {
+        if !self.external {
+            return None;
+        }
+        self.external = false;
 
-            headers.entry(&*key).or_insert(value.parse().unwrap());
+        if self.mode.popup == Popup::Cookies {
+            return Some(External::Editor(self.cookies.to_text(), "txt"));
+        }
+        if self.mode.popup == Popup::Settings {
+            let text = self.config.client.to_text();
+            let template = settings::KEYS
+                .iter()
+                .filter(|key| !text.lines().any(|line| line.starts_with(&format!("{}=", key))))
+                .map(|key| format!("{}=", key))
+                .collect::<Vec<String>>();
+            let text = text.lines().map(|l| l.to_string()).chain(template).collect::<Vec<String>>();
+            return Some(External::Editor(text.join("\n"), "txt"));
+        }
+        match self.mode.view {
+            ViewMode::RequestUrl => Some(External::Editor(self.request.url.text.to_string(), "txt")),
+            ViewMode::RequestParams => Some(External::Editor(self.request.params.text.to_string(), "txt")),
+            ViewMode::RequestHeader => Some(External::Editor(self.request.header.text.to_string(), "txt")),
+            ViewMode::RequestAuth => Some(External::Editor(self.request.auth.text.to_string(), "txt")),
+            ViewMode::RequestBody => {
+                let extension = match self.request.body_mode.value {
+                    BodyMode::Json => "json",
+                    BodyMode::GraphQL => "graphql",
+                    BodyMode::Wire => "http",
+                    _ => "txt",
+                };
+                Some(External::Editor(self.request.body.text.to_string(), extension))
+            }
+            ViewMode::RequestVariables => Some(External::Editor(self.request.variables.text.to_string(), "json")),
+            ViewMode::RequestAssertion => Some(External::Editor(self.request.assertion.text.to_string(), "txt")),
+            ViewMode::RequestSettings => Some(External::Editor(self.request.settings.text.to_string(), "txt")),
+            ViewMode::ResponseHeader => Some(External::Pager(self.response.header.text.to_string())),
+            ViewMode::ResponseBody => match &self.response.raw {
+                Some((_, text)) => Some(External::Pager(text.to_string())),
+                None => Some(External::Pager(self.response.body.text.to_string())),
+            },
+            ViewMode::RequestMethod | ViewMode::RequestAuthMode | ViewMode::RequestBodyMode => {
+                self.notice = "this pane cannot be opened in an external program".to_string();
+                None
+            }
+        }
+    }
 
-            // if let Entry::Vacant(v) = headers.entry("x-hello") {
-            //     let mut e = v.insert_entry("world".parse().unwrap());
-            //     e.insert("world2".parse().unwrap());
-            // }
+    // 外部エディターで編集した内容を選択中のペインに読み込む
+    pub fn finish_external(&mut self, result: Result<Option<String>, String>) {
+        let text = match result {
+            Ok(Some(text)) => text,
+            Ok(None) => return,
+            Err(err) => {
+                self.notice = err;
+                return;
+            }
+        };
 
-            // let key = *key;
-            // let key = HeaderName::from_str(key);
+        if self.mode.popup == Popup::Cookies {
+            match self.cookies.set_text(&text) {
+                Ok(()) => {
+                    self.select_cookie(self.cookie_state.selected().unwrap_or(0));
+                    self.save_cookies();
+                }
+                Err(err) => self.notice = err,
+            }
+            return;
+        }
+        if self.mode.popup == Popup::Settings {
+            match ClientSettings::parse(&text) {
+                Ok(settings) => {
+                    self.config.client = settings;
+                    self.notice = match self.config.save() {
+                        Ok(path) => format!("saved settings to {}", path.display()),
+                        Err(err) => err,
+                    };
+                }
+                Err(err) => self.notice = err,
+            }
+            return;
+        }
+        match self.mode.view {
+            ViewMode::RequestUrl => self.set_request_url_text(text.trim_end()),
+            ViewMode::RequestParams => self.set_request_params_text(&text),
+            ViewMode::RequestHeader => self.set_request_header_text(&text),
+            ViewMode::RequestAuth => self.set_request_auth_text(&text),
+            ViewMode::RequestBody => self.set_request_body_text(&text),
+            ViewMode::RequestVariables => self.set_request_variables_text(&text),
+            ViewMode::RequestAssertion => self.set_request_assertion_text(&text),
+            ViewMode::RequestSettings => self.set_request_settings_text(&text),
+            _ => {}
+        }
+    }
 
-            // let value = h.get(1).unwrap_or(&"");
-            // let value = HeaderValue::from_str(*value);
-            // headers.append(key, value);
+    // 送信履歴をカレントディレクトリにHARファイルとして書き出す
+    pub fn export_har(&mut self) {
+        let path = format!("ruzz-{}.har", Local::now().format("%Y%m%d-%H%M%S"));
+        match har::export(&self.history, std::path::Path::new(&path)) {
+            Ok(()) => self.notice = format!("exported {} entries to {}", self.history.len(), path),
+            Err(err) => self.notice = err,
         }
-        headers
     }
 
-    pub fn view_mode(&self) -> &ViewMode {
-        &self.mode.view
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_string(), value.to_string());
     }
 
-    pub fn edit_mode(&self) -> &EditMode {
-        &self.mode.edit
+    // 保存済みリクエストを各ペインに読み込む
+    pub fn load_request(&mut self, saved: &SavedRequest) -> bool {
+        self.set_request_url_text(&saved.url);
+        self.set_request_params_text(&saved.params.join("\n"));
+        self.set_request_header_text(&saved.headers.join("\n"));
+        self.set_request_auth_text(&saved.auth.join("\n"));
+        self.set_request_auth_mode(&saved.auth_mode);
+        self.set_request_body_text(&saved.body);
+        self.set_request_variables_text(&saved.graphql_variables);
+        self.set_request_assertion_text(&saved.assertions.join("\n"));
+        self.set_request_settings_text(&saved.settings.join("\n"));
+        self.set_request_body_mode(&saved.body_mode);
+        self.set_request_method(&saved.method)
     }
 
-    pub fn is_mode_view(&self) -> bool {
-        self.mode.edit == EditMode::NoMode
+    pub fn set_saved_requests(&mut self, items: Vec<SavedRequest>, source: Source) {
+        self.saved_requests.items = items;
+        self.saved_requests.loaded = None;
+        self.saved_requests.source = Some(source);
+        self.saved_requests.state.select(if self.saved_requests.items.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
     }
 
-    pub fn is_mode_edit(&self) -> bool {
-        !self.is_mode_view()
+    pub fn saved_request_titles(&self) -> Vec<String> {
+        self.saved_requests.items.iter().map(|r| r.title()).collect()
     }
 
-    pub fn is_request_url_edit(&self) -> bool {
-        self.mode.edit == EditMode::RequestUrl
+    pub fn saved_request_state(&self) -> &ListState {
+        &self.saved_requests.state
     }
 
-    pub fn is_request_method_edit(&self) -> bool {
-        self.mode.edit == EditMode::RequestMethod
+    pub fn next_saved_request(&mut self) {
+        self.saved_requests.next();
     }
 
-    pub fn next_view(&mut self) {
-        self.change_view_mode();
-        self.mode.view = match self.mode.view {
-            ViewMode::RequestUrl => ViewMode::RequestParams,
-            ViewMode::RequestParams => ViewMode::RequestMethod,
-            ViewMode::RequestMethod => ViewMode::RequestHeader,
-            ViewMode::RequestHeader => ViewMode::RequestBody,
-            ViewMode::RequestBody => ViewMode::ResponseHeader,
+    pub fn prev_saved_request(&mut self) {
+        self.saved_requests.previous();
+    }
 
-            ViewMode::ResponseHeader => ViewMode::ResponseBody,
-            ViewMode::ResponseBody => ViewMode::RequestUrl,
+    // 一覧で選択中のリクエストを各ペインに読み込む
+    pub fn load_selected_request(&mut self) {
+        let i = match self.saved_requests.state.selected() {
+            Some(i) => i,
+            None => return,
         };
+        let saved = self.saved_requests.items[i].clone();
+        self.load_request(&saved);
+        self.saved_requests.loaded = Some(i);
+        self.close_popup();
     }
 
-    pub fn prev_view(&mut self) {
-        self.change_view_mode();
-        self.mode.view = match self.mode.view {
-            ViewMode::ResponseBody => ViewMode::ResponseHeader,
-            ViewMode::ResponseHeader => ViewMode::RequestBody,
-
-            ViewMode::RequestBody => ViewMode::RequestHeader,
-            ViewMode::RequestHeader => ViewMode::RequestMethod,
-            ViewMode::RequestMethod => ViewMode::RequestParams,
-            ViewMode::RequestParams => ViewMode::RequestUrl,
-            ViewMode::RequestUrl => ViewMode::ResponseBody,
+    // 各ペインの内容を保存済みリクエストの形式にする
+    pub fn current_request(&self) -> SavedRequest {
+        let lines = |text: &String| -> Vec<String> {
+            text.lines()
+                .filter(|l| !l.trim().is_empty())
+                .map(|l| l.to_string())
+                .collect()
+        };
+        let mut saved = match self.saved_requests.loaded {
+            Some(i) => self.saved_requests.items[i].clone(),
+            None => SavedRequest::default(),
         };
+        saved.method = self.request.method.selected_name();
+        saved.url = self.request.url.text.to_string();
+        saved.params = lines(&self.request.params.text);
+        saved.headers = lines(&self.request.header.text);
+        saved.auth_mode = match self.request.auth_mode.value {
+            AuthMode::None => "".to_string(),
+            mode => mode.name().to_string(),
+        };
+        saved.auth = lines(&self.request.auth.text);
+        saved.body = self.request.body.text.to_string();
+        saved.graphql_variables = self.request.variables.text.to_string();
+        saved.body_mode = match self.request.body_mode.value {
+            BodyMode::Raw => "".to_string(),
+            mode => mode.name().to_string(),
+        };
+        saved.assertions = lines(&self.request.assertion.text);
+        saved.settings = lines(&self.request.settings.text);
+        saved
     }
 
-    pub fn change_edit_mode(&mut self) {
-        self.mode.edit = match self.mode.view {
-            ViewMode::RequestUrl => EditMode::RequestUrl,
-            ViewMode::RequestParams => EditMode::RequestParams,
-            ViewMode::RequestMethod => EditMode::RequestMethod,
-            ViewMode::RequestHeader => EditMode::RequestHeader,
-            ViewMode::RequestBody => EditMode::RequestBody,
-            _ => EditMode::NoMode,
+    // 各ペインの内容を読み込み元のファイルに書き戻す
+    pub fn save_request(&mut self) {
+        let i = match self.saved_requests.loaded {
+            Some(i) => i,
+            None => {
+                self.notice = "no saved request is loaded".to_string();
+                return;
+            }
+        };
+
+        let saved = self.current_request();
+        self.saved_requests.items[i] = saved.clone();
+        self.notice = match self.saved_requests.source.as_mut() {
+            Some(source) => match source.save(i, &saved) {
+                Ok(path) => format!("saved {} to {}", saved.name, path.display()),
+                Err(err) => err,
+            },
+            None => "no file is open".to_string(),
         };
     }
 
-    pub fn change_view_mode(&mut self) {
-        self.mode.edit = EditMode::NoMode;
+    pub fn popup(&self) -> &Popup {
+        &self.mode.popup
     }
 
-    fn jump_view(&mut self, view: ViewMode) {
-        self.change_view_mode();
-        self.mode.view = view;
+    pub fn is_popup_open(&self) -> bool {
+        self.mode.popup != Popup::NoPopup
     }
 
-    pub fn jump_request_url(&mut self) {
+    pub fn open_request_list(&mut self) {
+        if self.saved_requests.items.is_empty() {
+            return;
+        }
+        self.change_view_mode();
+        self.mode.popup = Popup::RequestList;
+    }
+
+    pub fn open_cookies(&mut self) {
+        self.change_view_mode();
+        self.select_cookie(0);
+        self.mode.popup = Popup::Cookies;
+        self.notice = match self.cookies.path() {
+            Some(path) => format!("{} cookies in {}", self.cookies.cookies().len(), path.display()),
+            None => format!("{} cookies (not saved)", self.cookies.cookies().len()),
+        };
+    }
+
+    // 環境ごとのクッキーのファイルを読み込む（以降の変更はそこに保存する）
+    pub fn open_cookie_jar(&mut self, name: &str) {
+        match CookieJar::open(name) {
+            Ok(jar) => self.cookies = jar,
+            Err(err) => self.notice = err,
+        }
+    }
+
+    pub fn cookie_summaries(&self) -> Vec<String> {
+        self.cookies.cookies().iter().map(|c| c.summary()).collect()
+    }
+
+    pub fn cookie_state(&self) -> &ListState {
+        &self.cookie_state
+    }
+
+    fn select_cookie(&mut self, i: usize) {
+        let len = self.cookies.cookies().len();
+        self.cookie_state.select(if len == 0 { None } else { Some(i.min(len - 1)) });
+    }
+
+    pub fn next_cookie(&mut self) {
+        let len = self.cookies.cookies().len();
+        match self.cookie_state.selected() {
+            Some(i) if i + 1 < len => self.select_cookie(i + 1),
+            _ => self.select_cookie(0),
+        }
+    }
+
+    pub fn prev_cookie(&mut self) {
+        let len = self.cookies.cookies().len();
+        match self.cookie_state.selected() {
+            Some(0) | None => self.select_cookie(len.saturating_sub(1)),
+            Some(i) => self.select_cookie(i - 1),
+        }
+    }
+
+    pub fn delete_selected_cookie(&mut self) {
+        let i = match self.cookie_state.selected() {
+            Some(i) => i,
+            None => return,
+        };
+        if let Some(cookie) = self.cookies.remove(i) {
+            self.notice = format!("deleted cookie {} for {}", cookie.name, cookie.domain);
+            self.select_cookie(i);
+            self.save_cookies();
+        }
+    }
+
+    pub fn clear_cookies(&mut self) {
+        self.cookies.clear();
+        self.select_cookie(0);
+        self.notice = "cleared all cookies".to_string();
+        self.save_cookies();
+    }
+
+    // 保存先があれば書き出す（失敗したときだけ通知する）
+    fn save_cookies(&mut self) {
+        if let Err(err) = self.cookies.save() {
+            self.notice = err;
+        }
+    }
+
+    pub fn open_settings(&mut self) {
+        self.change_view_mode();
+        self.mode.popup = Popup::Settings;
+    }
+
+    // 設定ファイルを読み込む
+    pub fn load_config(&mut self) -> Result<(), String> {
+        self.set_config(Config::load()?);
+        Ok(())
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+    }
+
+    // 環境の変数と設定を反映する（設定の値にも変数を使える）
+    pub fn load_environment(&mut self, environment: &Environment) -> Result<(), String> {
+        for (name, value) in &environment.variables {
+            self.set_variable(name, value);
+        }
+        self.environment = ClientSettings::parse(&self.render(&environment.settings.join("\n")))
+            .map_err(|e| format!("environment {}: {}", environment.name, e))?;
+        Ok(())
+    }
+
+    // Settings ペインで上書きする前の設定（設定ファイルと環境）
+    fn client_defaults(&self) -> ClientSettings {
+        self.config.client.merge(&self.environment)
+    }
+
+    // 設定ポップアップの各行（Settings ペインが不正なら設定ファイルと環境の値だけ）
+    pub fn settings_lines(&self) -> Vec<String> {
+        match self.request_settings() {
+            Ok(overrides) => ClientSettings::describe(&self.config.client, &self.environment, &overrides),
+            Err(err) => {
+                let mut lines =
+                    ClientSettings::describe(&self.config.client, &self.environment, &ClientSettings::default());
+                lines.push(format!("settings pane: {}", err));
+                lines
+            }
+        }
+    }
+
+    // 証明書の検証を無効にしているか（Settings ペインが不正なら設定ファイルと環境の値）
+    pub fn tls_insecure(&self) -> bool {
+        let overrides = self.request_settings().unwrap_or_default();
+        self.client_defaults().merge(&overrides).insecure == Some(true)
+    }
+
+    pub fn response_remote(&self) -> Option<String> {
+        match &self.response.unix_socket {
+            Some(path) => Some(format!("unix:{}", path)),
+            None => self.response.remote.map(|remote| remote.to_string()),
+        }
+    }
+
+    pub fn response_proxy(&self) -> Option<&String> {
+        self.response.proxy.as_ref()
+    }
+
+    pub fn open_tls(&mut self) {
+        if self.response.tls.is_none() && self.response.tls_probe.is_none() {
+            self.notice = "the last response was not received over TLS".to_string();
+            return;
+        }
+        self.probe_tls();
+        self.change_view_mode();
+        self.mode.popup = Popup::Tls;
+    }
+
+    // まだ調べていなければ、別のハンドシェイクで TLS の情報を調べる
+    pub fn probe_tls(&mut self) {
+        if let Some(probe) = self.response.tls_probe.take() {
+            self.response.tls = Some(self.tls_info(&probe.host, probe.remote, probe.connection, &probe.settings));
+        }
+    }
+
+    // TLS のバージョン、暗号スイート、サーバー証明書のチェーン（probe_tls で調べたもの）
+    pub fn tls_lines(&self) -> Vec<String> {
+        match &self.response.tls {
+            Some(Ok(info)) => {
+                let mut lines = vec!["probe handshake (a separate connection with the same settings)".to_string()];
+                lines.extend(info.lines());
+                lines
+            }
+            Some(Err(err)) => vec![format!("TLS details unavailable: {}", err)],
+            None => Vec::new(),
+        }
+    }
+
+    pub fn open_redirects(&mut self) {
+        if self.response.hops.len() < 2 {
+            self.notice = "the last request was not redirected".to_string();
+            return;
+        }
+        self.change_view_mode();
+        self.mode.popup = Popup::Redirects;
+    }
+
+    // リダイレクトの各レスポンス（ステータス、URL、Location、時間）
+    pub fn redirect_lines(&self) -> Vec<String> {
+        self.response
+            .hops
+            .iter()
+            .enumerate()
+            .map(|(i, hop)| {
+                let location = hop
+                    .response_headers
+                    .get(LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| format!(" -> {}", v))
+                    .unwrap_or_default();
+                format!(
+                    "{} {} {} {} {}{} ({} ms)",
+                    i + 1,
+                    hop.status,
+                    hop.status_text,
+                    hop.method,
+                    hop.url,
+                    location,
+                    (hop.wait + hop.receive).as_millis()
+                )
+            })
+            .collect()
+    }
+
+    pub fn redirect_state(&self) -> ListState {
+        let mut state = ListState::default();
+        state.select(Some(self.response.hop));
+        state
+    }
+
+    pub fn next_redirect(&mut self) {
+        if self.response.hop + 1 < self.response.hops.len() {
+            self.select_hop(self.response.hop + 1);
+        }
+    }
+
+    pub fn prev_redirect(&mut self) {
+        if self.response.hop > 0 {
+            self.select_hop(self.response.hop - 1);
+        }
+    }
+
+    // 選択したレスポンスをヘッダーとボディのペインに表示する
+    fn select_hop(&mut self, i: usize) {
+        let hop = match self.response.hops.get(i) {
+            Some(hop) => hop,
+            None => return,
+        };
+        self.response.header.text = format!("{:#?}", hop.response_headers);
+        self.response.body.text = hop.response_body.to_string();
+        self.response.hop = i;
+    }
+
+    pub fn close_popup(&mut self) {
+        self.mode.popup = Popup::NoPopup;
+    }
+
+    // `{{name}}` を変数の値に置き換える
+    fn render(&self, text: &str) -> String {
+        collection::render(text, &self.variables)
+    }
+
+    pub fn set_request_url_text(&mut self, text: &str) {
+        self.request.url.set_text(text);
+    }
+
+    pub fn set_request_params_text(&mut self, text: &str) {
+        self.request.params.set_text(text);
+    }
+
+    pub fn set_request_header_text(&mut self, text: &str) {
+        self.request.header.set_text(text);
+    }
+
+    pub fn set_request_auth_text(&mut self, text: &str) {
+        self.request.auth.set_text(text);
+    }
+
+    pub fn set_request_auth_mode(&mut self, name: &str) -> bool {
+        self.request.auth_mode.select(name)
+    }
+
+    pub fn is_request_auth_mode(&self, name: &str) -> bool {
+        self.request.auth_mode.value.name() == name
+    }
+
+    pub fn set_request_body_text(&mut self, text: &str) {
+        self.request.body.set_text(text);
+    }
+
+    pub fn set_request_variables_text(&mut self, text: &str) {
+        self.request.variables.set_text(text);
+    }
+
+    pub fn set_request_assertion_text(&mut self, text: &str) {
+        self.request.assertion.set_text(text);
+    }
+
+    pub fn set_request_settings_text(&mut self, text: &str) {
+        self.request.settings.set_text(text);
+    }
+
+    pub fn set_request_body_mode(&mut self, name: &str) -> bool {
+        self.request.body_mode.select(name)
+    }
+
+    pub fn set_request_method(&mut self, name: &str) -> bool {
+        self.request.method.select(name)
+    }
+
+    pub fn request_url_cursor_x(&self) -> u16 {
+        self.request.url.column()
+    }
+
+    pub fn request_method_items_vec(&self) -> &Vec<String> {
+        &self.request.method.items
+    }
+
+    pub fn request_method_state(&self) -> &ListState {
+        &self.request.method.state
+    }
+
+    pub fn request_auth_text(&self) -> &String {
+        &self.request.auth.text
+    }
+
+    pub fn request_auth_mode_items_vec(&self) -> &Vec<String> {
+        &self.request.auth_mode.items
+    }
+
+    pub fn request_auth_mode_state(&self) -> &ListState {
+        &self.request.auth_mode.state
+    }
+
+    pub fn request_body_mode_items_vec(&self) -> &Vec<String> {
+        &self.request.body_mode.items
+    }
+
+    pub fn request_body_mode_state(&self) -> &ListState {
+        &self.request.body_mode.state
+    }
+
+    pub fn request_params_cursor_x(&self) -> u16 {
+        self.request.params.column()
+    }
+
+    pub fn request_header_cursor_x(&self) -> u16 {
+        self.request.header.column()
+    }
+
+    pub fn request_auth_cursor_x(&self) -> u16 {
+        self.request.auth.column()
+    }
+
+    pub fn request_body_cursor_x(&self) -> u16 {
+        self.request.body.column()
+    }
+
+    pub fn request_variables_cursor_x(&self) -> u16 {
+        self.request.variables.column()
+    }
+
+    pub fn request_assertion_cursor_x(&self) -> u16 {
+        self.request.assertion.column()
+    }
+
+    pub fn request_settings_cursor_x(&self) -> u16 {
+        self.request.settings.column()
+    }
+
+    pub fn request_params_map(&self) -> BTreeMap<String, String> {
+        let request_params_text = self.render(self.request_params_text());
+        let request_params_text = request_params_text.replace("\n", "&");
+
+        let mut params = BTreeMap::new();
+        for query_str in request_params_text.split('&') {
+            // 値に `=` が含まれていてもよい
+            let mut q = query_str.splitn(2, '=');
+            let key = q.next().unwrap_or("").to_string();
+            let value = q.next().unwrap_or("").to_string();
+            if key.is_empty() && value.is_empty() {
+                continue;
+            }
+            params.insert(key, value);
+        }
+
+        params
+    }
+
+    pub fn request_header_map(&self) -> Result<HeaderMap, String> {
+        let request_header_text = self.render(self.request_header_text());
+
+        let mut headers = HeaderMap::new();
+        for header_str in request_header_text.split('\n') {
+            if header_str.trim().is_empty() {
+                continue;
+            }
+
+            // 値に `:` が含まれていてもよい（例: URL や時刻）
+            let mut h = header_str.splitn(2, ':');
+            let key = h.next().unwrap_or("").trim();
+            let value = h.next().unwrap_or("").trim();
+
+            let name = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|_| format!("invalid header name: {}", key))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid header value: {}", header_str))?;
+            headers.append(name, value);
+        }
+        Ok(headers)
+    }
+
+    pub fn view_mode(&self) -> &ViewMode {
+        &self.mode.view
+    }
+
+    pub fn edit_mode(&self) -> &EditMode {
+        &self.mode.edit
+    }
+
+    pub fn is_mode_view(&self) -> bool {
+        self.mode.edit == EditMode::NoMode
+    }
+
+    pub fn is_mode_edit(&self) -> bool {
+        !self.is_mode_view()
+    }
+
+    pub fn is_request_url_edit(&self) -> bool {
+        self.mode.edit == EditMode::RequestUrl
+    }
+
+    pub fn is_request_method_edit(&self) -> bool {
+        self.mode.edit == EditMode::RequestMethod
+    }
+
+    pub fn is_request_auth_mode_edit(&self) -> bool {
+        self.mode.edit == EditMode::RequestAuthMode
+    }
+
+    pub fn is_request_body_edit(&self) -> bool {
+        self.mode.edit == EditMode::RequestBody
+    }
+
+    pub fn is_request_body_mode_edit(&self) -> bool {
+        self.mode.edit == EditMode::RequestBodyMode
+    }
+
+    pub fn next_view(&mut self) {
+        self.change_view_mode();
+        self.mode.view = match self.mode.view {
+            ViewMode::RequestUrl => ViewMode::RequestParams,
+            ViewMode::RequestParams => ViewMode::RequestMethod,
+            ViewMode::RequestMethod => ViewMode::RequestHeader,
+            ViewMode::RequestHeader => ViewMode::RequestAuth,
+            ViewMode::RequestAuth => ViewMode::RequestAuthMode,
+            ViewMode::RequestAuthMode => ViewMode::RequestBody,
+            ViewMode::RequestBody if self.is_graphql() => ViewMode::RequestVariables,
+            ViewMode::RequestBody => ViewMode::RequestBodyMode,
+            ViewMode::RequestVariables => ViewMode::RequestBodyMode,
+            ViewMode::RequestBodyMode => ViewMode::RequestAssertion,
+            ViewMode::RequestAssertion => ViewMode::RequestSettings,
+            ViewMode::RequestSettings => ViewMode::ResponseHeader,
+
+            ViewMode::ResponseHeader => ViewMode::ResponseBody,
+            ViewMode::ResponseBody => ViewMode::RequestUrl,
+        };
+    }
+
+    pub fn prev_view(&mut self) {
+        self.change_view_mode();
+        self.mode.view = match self.mode.view {
+            ViewMode::ResponseBody => ViewMode::ResponseHeader,
+            ViewMode::ResponseHeader => ViewMode::RequestSettings,
+
+            ViewMode::RequestSettings => ViewMode::RequestAssertion,
+            ViewMode::RequestAssertion => ViewMode::RequestBodyMode,
+            ViewMode::RequestBodyMode if self.is_graphql() => ViewMode::RequestVariables,
+            ViewMode::RequestBodyMode => ViewMode::RequestBody,
+            ViewMode::RequestVariables => ViewMode::RequestBody,
+            ViewMode::RequestBody => ViewMode::RequestAuthMode,
+            ViewMode::RequestAuthMode => ViewMode::RequestAuth,
+            ViewMode::RequestAuth => ViewMode::RequestHeader,
+            ViewMode::RequestHeader => ViewMode::RequestMethod,
+            ViewMode::RequestMethod => ViewMode::RequestParams,
+            ViewMode::RequestParams => ViewMode::RequestUrl,
+            ViewMode::RequestUrl => ViewMode::ResponseBody,
+        };
+    }
+
+    pub fn change_edit_mode(&mut self) {
+        self.mode.edit = match self.mode.view {
+            ViewMode::RequestUrl => EditMode::RequestUrl,
+            ViewMode::RequestParams => EditMode::RequestParams,
+            ViewMode::RequestMethod => EditMode::RequestMethod,
+            ViewMode::RequestHeader => EditMode::RequestHeader,
+            ViewMode::RequestAuth => EditMode::RequestAuth,
+            ViewMode::RequestAuthMode => EditMode::RequestAuthMode,
+            ViewMode::RequestBody => EditMode::RequestBody,
+            ViewMode::RequestVariables => EditMode::RequestVariables,
+            ViewMode::RequestBodyMode => EditMode::RequestBodyMode,
+            ViewMode::RequestAssertion => EditMode::RequestAssertion,
+            ViewMode::RequestSettings => EditMode::RequestSettings,
+            _ => EditMode::NoMode,
+        };
+    }
+
+    pub fn change_view_mode(&mut self) {
+        self.mode.edit = EditMode::NoMode;
+    }
+
+    fn jump_view(&mut self, view: ViewMode) {
+        self.change_view_mode();
+        self.mode.view = view;
+    }
+
+    pub fn jump_request_url(&mut self) {
         self.jump_view(ViewMode::RequestUrl);
     }
 
-    pub fn jump_request_params(&mut self) {
-        self.jump_view(ViewMode::RequestParams);
+    pub fn jump_request_params(&mut self) {
+        self.jump_view(ViewMode::RequestParams);
+    }
+
+    pub fn jump_request_method(&mut self) {
+        self.jump_view(ViewMode::RequestMethod);
+    }
+
+    pub fn jump_request_header(&mut self) {
+        self.jump_view(ViewMode::RequestHeader);
+    }
+
+    pub fn jump_request_auth(&mut self) {
+        self.jump_view(ViewMode::RequestAuth);
+    }
+
+    pub fn jump_request_auth_mode(&mut self) {
+        self.jump_view(ViewMode::RequestAuthMode);
+    }
+
+    pub fn jump_request_body(&mut self) {
+        self.jump_view(ViewMode::RequestBody);
+    }
+
+    pub fn jump_request_body_mode(&mut self) {
+        self.jump_view(ViewMode::RequestBodyMode);
+    }
+
+    pub fn jump_request_assertion(&mut self) {
+        self.jump_view(ViewMode::RequestAssertion);
+    }
+
+    pub fn jump_request_settings(&mut self) {
+        self.jump_view(ViewMode::RequestSettings);
+    }
+
+    pub fn jump_response_header(&mut self) {
+        self.jump_view(ViewMode::ResponseHeader);
+    }
+
+    pub fn jump_response_body(&mut self) {
+        self.jump_view(ViewMode::ResponseBody);
+    }
+
+    pub fn insert_text(&mut self, c: char) {
+        if let Some(view) = self.edit_view() {
+            view.insert(c);
+        }
+    }
+
+    // 編集中のペイン
+    fn edit_view(&mut self) -> Option<&mut EditView> {
+        match self.mode.edit {
+            EditMode::RequestUrl => Some(&mut self.request.url),
+            EditMode::RequestParams => Some(&mut self.request.params),
+            EditMode::RequestHeader => Some(&mut self.request.header),
+            EditMode::RequestAuth => Some(&mut self.request.auth),
+            EditMode::RequestBody => Some(&mut self.request.body),
+            EditMode::RequestVariables => Some(&mut self.request.variables),
+            EditMode::RequestAssertion => Some(&mut self.request.assertion),
+            EditMode::RequestSettings => Some(&mut self.request.settings),
+            _ => None,
+        }
+    }
+
+    // TODO: 2文字以上の移動+削除が想定外の動きをする
+    // pub fn right_move_cursor(&mut self) {
+    //     match self.mode.edit {
+    //         EditMode::RequestUrl => {
+    //             if self.request.url.cursor.x < self.request.url.text.width() as u16 {
+    //                 self.request.url.cursor.x += 1;
+    //             }
+    //         }
+    //         EditMode::RequestParams => {
+    //             if self.request.params.cursor.x < self.request.params.text.width() as u16 {
+    //                 self.request.params.cursor.x += 1;
+    //             }
+    //         }
+    //         EditMode::RequestHeader => {
+    //             if self.request.header.cursor.x < self.request.header.text.width() as u16 {
+    //                 self.request.header.cursor.x += 1;
+    //             }
+    //         }
+    //         EditMode::RequestBody => {
+    //             if self.request.body.cursor.x < self.request.body.text.width() as u16 {
+    //                 self.request.body.cursor.x += 1;
+    //             }
+    //         }
+    //         _ => {}
+    //     }
+    // }
+
+    // pub fn left_move_cursor(&mut self) {
+    //     match self.mode.edit {
+    //         EditMode::RequestUrl => {
+    //             if 0 < self.request.url.cursor.x {
+    //                 self.request.url.cursor.x -= 1;
+    //             }
+    //         }
+    //         EditMode::RequestParams => {
+    //             if 0 < self.request.params.cursor.x {
+    //                 self.request.params.cursor.x -= 1;
+    //             }
+    //         }
+    //         EditMode::RequestHeader => {
+    //             if 0 < self.request.header.cursor.x {
+    //                 self.request.header.cursor.x -= 1;
+    //             }
+    //         }
+    //         EditMode::RequestBody => {
+    //             if 0 < self.request.body.cursor.x {
+    //                 self.request.body.cursor.x -= 1;
+    //             }
+    //         }
+    //         _ => {}
+    //     }
+    // }
+
+    pub fn delete_text(&mut self) {
+        if let Some(view) = self.edit_view() {
+            view.delete();
+        }
+    }
+
+    fn reqwest_method(&self) -> reqwest::Method {
+        match self.request.method.value {
+            HttpMethod::GET => reqwest::Method::GET,
+            HttpMethod::HEAD => reqwest::Method::HEAD,
+            HttpMethod::POST => reqwest::Method::POST,
+            HttpMethod::PUT => reqwest::Method::PUT,
+            HttpMethod::DELETE => reqwest::Method::DELETE,
+            HttpMethod::CONNECT => reqwest::Method::CONNECT,
+            HttpMethod::OPTIONS => reqwest::Method::OPTIONS,
+            HttpMethod::TRACE => reqwest::Method::TRACE,
+            HttpMethod::PATCH => reqwest::Method::PATCH,
+        }
+    }
+
+    // Settings ペインの値
+    fn request_settings(&self) -> Result<ClientSettings, String> {
+        ClientSettings::parse(&self.render(&self.request.settings.text))
+    }
+
+    // 送信に使うクライアント（設定が前回と同じなら同じものを使う）
+    fn client(&mut self, settings: &ClientSettings) -> Result<reqwest::blocking::Client, String> {
+        if let Some((built, client, _)) = &self.client {
+            if built == settings {
+                return Ok(client.clone());
+            }
+        }
+        let (client, resolver) = settings.build()?;
+        self.client = Some((settings.clone(), client.clone(), resolver));
+        self.tls_sessions.clear();
+        Ok(client)
+    }
+
+    // 名前解決を上書きしているときの Resolver
+    fn resolver(&self) -> Option<Resolver> {
+        self.client.as_ref().and_then(|(_, _, resolver)| resolver.clone())
+    }
+
+    pub fn request(&mut self) {
+        // 読み込み中のレスポンスは止める
+        self.stream = None;
+        let mut settings = match self.request_settings() {
+            Ok(overrides) => self.client_defaults().merge(&overrides),
+            Err(err) => {
+                self.set_error(&err);
+                return;
+            }
+        };
+        let url = self.render(self.request_url_text());
+        // unix:// の URL は http://localhost としてソケットに送る
+        let url = match resolve::unix_url(&url) {
+            Some((socket, url)) => {
+                settings.unix_socket = Some(socket);
+                url
+            }
+            None => url,
+        };
+        if url.starts_with("ws://") || url.starts_with("wss://") {
+            self.websocket_request(&url, &settings);
+            return;
+        }
+        // WebSocket 以外を送るなら接続中の WebSocket は閉じる
+        self.websocket = None;
+        if grpc::is_grpc_url(&url) {
+            if let Err(err) = self.grpc_request(&url, &settings) {
+                self.set_error(&err);
+            }
+            return;
+        }
+        if self.request.body_mode.value == BodyMode::Wire {
+            if let Err(err) = self.wire_request(&url, &settings) {
+                self.set_error(&err);
+            }
+            return;
+        }
+        let (outgoing, url) = match self.outgoing(&url, &settings) {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                self.set_error(&err);
+                return;
+            }
+        };
+
+        let started = Instant::now();
+        let sockets = Sockets::snapshot();
+        let (response, hops) = self.send_with_redirects(&outgoing, &url, settings.redirect_limit());
+        self.record_response(&settings, outgoing.resolver.as_ref(), sockets, started, response, hops);
+    }
+
+    // ペインの内容からクライアント、ヘッダー、ボディ、送り先の URL を組み立てる
+    fn outgoing(&mut self, url: &str, settings: &ClientSettings) -> Result<(Outgoing, url::Url), String> {
+        let client = self.client(settings)?;
+
+        let mut params = self.request_params_map();
+        let headers = self.request_header_map();
+        let body = match self.request.body_mode.value {
+            BodyMode::GraphQL => body::encode_graphql(
+                &self.render(self.request_body_text()),
+                &self.render(&self.request.variables.text),
+            ),
+            mode => body::encode(mode, &self.render(self.request_body_text())),
+        };
+        // スキーマを取得していれば送る前に検証する（問題があっても送る）
+        let problems = self.graphql_problems();
+        if !problems.is_empty() {
+            self.notice = match problems.len() {
+                1 => format!("GraphQL: {}", problems[0]),
+                n => format!("GraphQL: {} (G to list {} problems)", problems[0], n),
+            };
+        }
+        let (mut headers, body) = headers.and_then(|h| body.map(|b| (h, b)))?;
+
+        // ヘッダーペインで指定されていなければボディの種類に合わせる
+        if let Some(content_type) = &body.content_type {
+            if !headers.contains_key(CONTENT_TYPE) {
+                if let Ok(value) = content_type.parse() {
+                    headers.insert(CONTENT_TYPE, value);
+                }
+            }
+        }
+
+        let (digest, aws) = self.apply_auth(settings, &mut headers, &mut params)?;
+
+        // ファイルと標準入力は読み込まずにそのまま流す（履歴にはボディを残さない）
+        let mut payload = body.payload;
+        if let Payload::Stdin = payload {
+            if termion::is_tty(&io::stdin()) {
+                return Err("stdin is a terminal".to_string());
+            }
+            // ダイジェスト認証では送り直し、AWS の署名ではハッシュを計算するので先に読み込んでおく
+            if digest.is_some() || aws.as_ref().map(|aws| !aws.unsigned_payload()).unwrap_or(false) {
+                let mut bytes = Vec::new();
+                io::stdin()
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| format!("failed to read stdin: {}", e))?;
+                payload = Payload::Bytes(bytes);
+            }
+        }
+        let request_body = match &payload {
+            Payload::Bytes(bytes) => String::from_utf8_lossy(bytes).to_string(),
+            _ => "".to_string(),
+        };
+        let payload_hash = match &aws {
+            Some(aws) => sigv4::payload_hash(aws, &payload)?,
+            None => "".to_string(),
+        };
+
+        // URL params ペインの値をクエリに加える
+        let mut url = url::Url::parse(url).map_err(|e| format!("invalid url {}: {}", url, e))?;
+        if !params.is_empty() {
+            url.query_pairs_mut().extend_pairs(params.iter());
+        }
+
+        let outgoing = Outgoing {
+            client,
+            resolver: self.resolver(),
+            headers,
+            payload,
+            request_body,
+            digest,
+            aws,
+            payload_hash,
+        };
+        Ok((outgoing, url))
+    }
+
+    // Auth ペインの認証方式を反映する（ダイジェストと AWS は送るときに使う）
+    fn apply_auth(
+        &mut self,
+        settings: &ClientSettings,
+        headers: &mut HeaderMap,
+        params: &mut BTreeMap<String, String>,
+    ) -> Result<Signing, String> {
+        let auth_mode = self.request.auth_mode.value;
+        let auth_text = self.render(&self.request.auth.text);
+        auth::apply(auth_mode, &auth_text, headers, params)?;
+        let digest = auth::digest_credentials(auth_mode, &auth_text);
+        let aws = if auth_mode == AuthMode::AwsSigV4 {
+            Some(sigv4::Settings::parse(&auth_text)?)
+        } else {
+            None
+        };
+        if auth_mode == AuthMode::OAuth2 {
+            let token = self.oauth2_token(&auth_text, settings)?;
+            headers.insert(AUTHORIZATION, token.authorization()?);
+        }
+        Ok((digest, aws))
+    }
+
+    // リダイレクトを追って送る（途中のレスポンスも返す）
+    fn send_with_redirects(
+        &mut self,
+        outgoing: &Outgoing,
+        url: &url::Url,
+        redirect_limit: usize,
+    ) -> (Result<Sent, String>, Vec<Exchange>) {
+        let mut hop = Hop {
+            method: self.reqwest_method(),
+            url: url.clone(),
+            body: true,
+            same_host: true,
+        };
+        // 途中のリダイレクトのレスポンス
+        let mut hops: Vec<Exchange> = Vec::new();
+        let response = loop {
+            let hop_started = Instant::now();
+            let (mut exchange, resp) = match outgoing.send_with_digest(&hop, &self.cookies) {
+                Ok(response) => response,
+                Err(err) => break Err(err),
+            };
+            exchange.wait = hop_started.elapsed();
+            exchange.status = resp.status().as_u16();
+            exchange.status_text = resp.status().canonical_reason().unwrap_or("").to_string();
+            exchange.http_version = format!("{:?}", resp.version());
+            exchange.response_headers = resp.headers().clone();
+
+            if self.cookies.store(resp.url(), resp.headers()) {
+                self.save_cookies();
+            }
+
+            let next = match redirect_location(&resp) {
+                Some(next) if hops.len() < redirect_limit => next,
+                Some(_) if redirect_limit > 0 => {
+                    self.notice = format!("stopped after {} redirects", redirect_limit);
+                    break Ok((exchange, resp, hop_started));
+                }
+                _ => break Ok((exchange, resp, hop_started)),
+            };
+            // 307 と 308 はメソッドとボディをそのまま送り直す（標準入力は読み直せない）
+            let keep_method = match resp.status() {
+                StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT => true,
+                StatusCode::SEE_OTHER => false,
+                _ => hop.method == reqwest::Method::HEAD,
+            };
+            if keep_method && hop.body {
+                if let Payload::Stdin = outgoing.payload {
+                    self.notice = "stdin cannot be sent again to follow the redirect".to_string();
+                    break Ok((exchange, resp, hop_started));
+                }
+            }
+
+            exchange.response_body = resp.text().unwrap_or_default();
+            exchange.receive = hop_started.elapsed() - exchange.wait;
+            hops.push(exchange);
+            let same_host = hop.same_host
+                && next.host_str() == url.host_str()
+                && next.port_or_known_default() == url.port_or_known_default();
+            hop = Hop {
+                method: if keep_method {
+                    hop.method
+                } else {
+                    reqwest::Method::GET
+                },
+                url: next,
+                body: keep_method && hop.body,
+                same_host,
+            };
+        };
+        (response, hops)
+    }
+
+    // 最後のレスポンスをペインと履歴に反映する
+    fn record_response(
+        &mut self,
+        settings: &ClientSettings,
+        resolver: Option<&Resolver>,
+        sockets: Option<Sockets>,
+        started: Instant,
+        response: Result<Sent, String>,
+        mut hops: Vec<Exchange>,
+    ) {
+        match response {
+            Ok((mut exchange, resp, hop_started)) => {
+                // Unix ドメインソケットや名前解決の上書きが当たればプロキシは通っていない
+                let overridden = resolve::Overrides::new(settings)
+                    .map(|overrides| overrides.applies(resp.url()))
+                    .unwrap_or(false);
+                let proxy = Proxies::new(settings)
+                    .ok()
+                    .and_then(|proxies| proxies.for_url(resp.url()))
+                    .filter(|_| !overridden);
+                // Resolver を経由したときは、その先で実際に接続したアドレス
+                let remote = match (&proxy, resolver) {
+                    (None, Some(resolver)) => resolver.remote(resp.url()).and_then(|r| r.ok()),
+                    _ => resp.remote_addr(),
+                };
+                let connection = resp
+                    .remote_addr()
+                    .and_then(|via| sockets.as_ref()?.connection(&Sockets::snapshot()?, via))
+                    .map(|connection| match remote {
+                        Some(remote) => connection.to(remote),
+                        None => connection,
+                    });
+                // IPv6 のアドレスは角括弧を外して証明書と照合する
+                let host = resp.url().host().map(|host| match host {
+                    url::Host::Domain(domain) => domain.to_string(),
+                    url::Host::Ipv4(ip) => ip.to_string(),
+                    url::Host::Ipv6(ip) => ip.to_string(),
+                });
+                let unix_socket = settings.unix_socket.clone().filter(|_| proxy.is_none());
+                let (tls, tls_probe) = match (resp.url().scheme(), host, remote) {
+                    ("https", Some(_), _) if unix_socket.is_some() => {
+                        (Some(Err("not inspected over a unix socket".to_string())), None)
+                    }
+                    // プロキシ経由では接続先に直接つないで調べられない
+                    ("https", Some(_), Some(_)) if proxy.is_some() => {
+                        (Some(Err("not inspected through a proxy".to_string())), None)
+                    }
+                    ("https", Some(host), Some(remote)) => {
+                        (None, Some(TlsProbe { host, remote, connection, settings: settings.clone() }))
+                    }
+                    _ => (None, None),
+                };
+
+                self.response.status.text = format!("{:?}", resp.status());
+                self.response.header.text = format!("{:#?}", resp.headers());
+                self.response.code = Some(resp.status().as_u16());
+                self.response.headers = resp.headers().clone();
+                self.response.elapsed = started.elapsed();
+                self.response.connection = connection;
+                self.response.connection_guessed = connection.is_some();
+                self.response.remote = remote;
+                self.response.unix_socket = unix_socket;
+                self.response.proxy = proxy.as_ref().map(proxy::display);
+                self.response.tls = tls;
+                self.response.tls_probe = tls_probe;
+                self.response.events = Vec::new();
+                self.response.event = 0;
+                self.response.graphql = self.is_graphql();
+                self.response.trailers = HeaderMap::new();
+                self.response.raw = None;
+
+                // SSE や chunked のボディは届いた分から表示し、読み終わってから検証する
+                if stream::is_stream(resp.headers()) {
+                    let status = self.response.status.text.to_string();
+                    self.response.status.text = format!("{} (streaming)", status);
+                    self.response.body.text = "".to_string();
+                    // 止めるときに shutdown する接続
+                    let socket = resp
+                        .remote_addr()
+                        .and_then(|remote| Sockets::snapshot()?.only_pair_to(remote));
+                    self.stream = Some(Streaming {
+                        parser: if stream::is_event_stream(resp.headers()) {
+                            Some(stream::EventParser::default())
+                        } else {
+                            None
+                        },
+                        stream: Rc::new(stream::start(resp, socket)),
+                        decoder: stream::Decoder::default(),
+                        status,
+                        exchange,
+                        started,
+                        hop_started,
+                    });
+                    self.history.extend(hops.iter().cloned());
+                    self.response.hops = hops;
+                    self.wait_stream(Some(Duration::from_millis(stream::INITIAL_WAIT)));
+                    return;
+                }
+                self.response.body.text = resp.text().unwrap_or_default();
+                self.run_assertions();
+
+                exchange.receive = hop_started.elapsed() - exchange.wait;
+                exchange.response_body = self.response.body.text.to_string();
+                hops.push(exchange);
+            }
+            Err(err) => self.set_error(&err),
+        }
+        self.history.extend(hops.iter().cloned());
+        self.response.hop = hops.len().saturating_sub(1);
+        self.response.hops = hops;
+    }
+
+    // 接続の TLS の情報（新しい接続ならプローブのハンドシェイクで調べ直す）
+    fn tls_info(
+        &mut self,
+        host: &str,
+        remote: SocketAddr,
+        connection: Option<Connection>,
+        settings: &ClientSettings,
+    ) -> Result<TlsInfo, String> {
+        if let Some(Connection::Reused(_)) = connection {
+            if let Some(info) = self.tls_sessions.get(&remote) {
+                return Ok(info.clone());
+            }
+        }
+        let info = tls::inspect(host, remote, settings)?;
+        self.tls_sessions.insert(remote, info.clone());
+        Ok(info)
+    }
+
+    // WebSocket の URL への送信（接続していなければ接続し、接続中ならボディペインをメッセージとして送る）
+    fn websocket_request(&mut self, url: &str, settings: &ClientSettings) {
+        let mut params = self.request_params_map();
+        let mut headers = match self.request_header_map() {
+            Ok(headers) => headers,
+            Err(err) => {
+                self.set_error(&err);
+                return;
+            }
+        };
+        let auth_text = self.render(&self.request.auth.text);
+        if let Err(err) = auth::apply(self.request.auth_mode.value, &auth_text, &mut headers, &mut params) {
+            self.set_error(&err);
+            return;
+        }
+        let url = match url::Url::parse(url) {
+            Ok(mut url) => {
+                if !params.is_empty() {
+                    url.query_pairs_mut().extend_pairs(params.iter());
+                }
+                url
+            }
+            Err(e) => {
+                self.set_error(&format!("invalid url {}: {}", url, e));
+                return;
+            }
+        };
+
+        if let Some(session) = &self.websocket {
+            if session.url == url.as_str() {
+                let session = session.clone();
+                let result = websocket::message(&self.render(self.request_body_text()))
+                    .and_then(|message| session.send(websocket::Command::Send(message)));
+                if let Err(err) = result {
+                    self.notice = err;
+                }
+                self.poll_websocket_after(Duration::from_millis(100));
+                return;
+            }
+        }
+
+        // 別の URL なら前の接続は閉じる
+        self.websocket = None;
+        if !headers.contains_key(COOKIE) {
+            if let Some(cookie) = self.cookies.header_for(&url) {
+                if let Ok(value) = cookie.parse() {
+                    headers.insert(COOKIE, value);
+                }
+            }
+        }
+        let handshake_headers: Vec<(String, String)> = headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).to_string(),
+                )
+            })
+            .collect();
+
+        let started = Instant::now();
+        let (session, handshake) = match websocket::connect(&url, &handshake_headers, settings) {
+            Ok(connected) => connected,
+            Err(err) => {
+                self.set_error(&err);
+                return;
+            }
+        };
+        let mut response_headers = HeaderMap::new();
+        for (name, value) in &handshake.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                response_headers.append(name, value);
+            }
+        }
+        if self.cookies.store(&url, &response_headers) {
+            self.save_cookies();
+        }
+
+        self.response.status.text = format!("{} {} (WebSocket open)", handshake.status, handshake.status_text);
+        self.response.header.text = format!("{:#?}", response_headers);
+        self.response.code = Some(handshake.status);
+        self.response.headers = response_headers;
+        self.response.elapsed = started.elapsed();
+        self.response.connection = handshake.remote.map(Connection::New);
+        self.response.connection_guessed = false;
+        self.response.remote = handshake.remote;
+        self.response.unix_socket = None;
+        self.response.proxy = None;
+        self.response.tls = None;
+        self.response.tls_probe = None;
+        self.response.hops = Vec::new();
+        self.response.hop = 0;
+        self.response.websocket = true;
+        self.response.trailers = HeaderMap::new();
+        self.response.raw = None;
+        self.response.body.text = format!(
+            "{} connected to {}",
+            handshake.at.format("%H:%M:%S%.3f"),
+            url
+        );
+        self.run_assertions();
+        self.websocket = Some(Rc::new(session));
+    }
+
+    // gRPC の URL への送信（パスがなければサービスとメソッドの一覧を表示する）
+    fn grpc_request(&mut self, url: &str, settings: &ClientSettings) -> Result<(), String> {
+        let url = url::Url::parse(url).map_err(|e| format!("invalid url {}: {}", url, e))?;
+        let metadata = self.grpc_metadata(settings)?;
+        let started = Instant::now();
+        let mut channel = grpc::Channel::connect(&url, settings)?;
+        let method = url.path().trim_matches('/').to_string();
+        // 一覧を表示するときは読み込み直す
+        let pool = self.grpc_pool(&url, settings, &mut channel, &metadata, method.is_empty())?;
+
+        self.response.connection = channel.remote.map(Connection::New);
+        self.response.connection_guessed = false;
+        self.response.remote = channel.remote;
+        self.response.unix_socket = None;
+        self.response.proxy = None;
+        self.response.tls = None;
+        self.response.tls_probe = match (url.scheme(), url.host_str(), channel.remote) {
+            ("grpcs", Some(host), Some(remote)) => Some(TlsProbe {
+                host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+                remote,
+                connection: self.response.connection,
+                settings: settings.clone(),
+            }),
+            _ => None,
+        };
+        self.response.hops = Vec::new();
+        self.response.hop = 0;
+        self.response.websocket = false;
+        self.response.events = Vec::new();
+        self.response.event = 0;
+        self.response.graphql = false;
+        self.response.raw = None;
+
+        if method.is_empty() {
+            let count: usize = pool.services.iter().map(|s| s.methods.len()).sum();
+            self.response.status.text = format!(
+                "0 OK ({} services, {} methods, m: list)",
+                pool.services.len(),
+                count
+            );
+            self.response.header.text = "".to_string();
+            self.response.body.text = grpc::method_lines(&pool).join("\n");
+            self.response.code = Some(0);
+            self.response.headers = HeaderMap::new();
+            self.response.trailers = HeaderMap::new();
+            self.response.elapsed = started.elapsed();
+            self.run_assertions();
+            return Ok(());
+        }
+
+        let body = self.render(self.request_body_text());
+        let (reply, message) = grpc::invoke(&mut channel, &pool, &method, &body, &metadata)?;
+        let map = |pairs: &[(String, String)]| {
+            let mut map = HeaderMap::new();
+            for (name, value) in pairs {
+                if let (Ok(name), Ok(value)) = (
+                    HeaderName::from_bytes(name.as_bytes()),
+                    HeaderValue::from_str(value),
+                ) {
+                    map.append(name, value);
+                }
+            }
+            map
+        };
+        let trailers = map(&reply.trailers);
+        let mut headers = map(&reply.headers);
+        for (name, value) in trailers.iter() {
+            headers.append(name.clone(), value.clone());
+        }
+        self.response.status.text = reply.status();
+        self.response.header.text = format!("{:#?}\ntrailers {:#?}", map(&reply.headers), trailers);
+        self.response.body.text = message
+            .map(|message| serde_json::to_string_pretty(&message).unwrap_or_default())
+            .unwrap_or_default();
+        // 検証の status は gRPC のステータスコード
+        self.response.code = Some(reply.code);
+        self.response.headers = headers;
+        self.response.trailers = trailers;
+        self.response.elapsed = started.elapsed();
+        self.run_assertions();
+        Ok(())
+    }
+
+    // ボディペインの内容をリクエスト全体としてそのまま送る（空なら雛形を入れる）
+    fn wire_request(&mut self, url: &str, settings: &ClientSettings) -> Result<(), String> {
+        let url = url::Url::parse(url).map_err(|e| format!("invalid url {}: {}", url, e))?;
+        if self.request.body.text.trim().is_empty() {
+            let headers = self
+                .request
+                .header
+                .text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.to_string())
+                .collect::<Vec<String>>();
+            let template = wire::template(&self.request.method.selected_name(), &url, &headers);
+            self.request.body.set_text(&template);
+            self.notice = "filled in the request; send again to send it as is".to_string();
+            return Ok(());
+        }
+        let payload = body::encode(BodyMode::Wire, &self.render(self.request_body_text()))?.payload;
+        if let Payload::Stdin = payload {
+            if termion::is_tty(&io::stdin()) {
+                return Err("stdin is a terminal".to_string());
+            }
+        }
+        let request = payload.into_bytes()?;
+        let started = Instant::now();
+        let exchange = wire::send(&url, &request, settings)?;
+        let elapsed = started.elapsed();
+        if exchange.bytes.is_empty() {
+            return Err("the connection was closed without a response".to_string());
+        }
+
+        // タイムアウトしたときは読めたところまでを解釈する
+        let head = request.starts_with(b"HEAD ");
+        let responses = wire::parse_all(&exchange.bytes, head, exchange.eof || exchange.timed_out);
+        // 1xx を除いた最初のレスポンスを表示する
+        let shown = responses
+            .iter()
+            .position(|r| r.code / 100 != 1)
+            .or_else(|| responses.len().checked_sub(1));
+        let parsed = shown.map(|i| responses[i].clone()).unwrap_or_default();
+        let parsed_end = responses.last().and_then(|r| r.end).unwrap_or(0);
+
+        let mut status = match shown {
+            Some(_) => parsed.status_line.to_string(),
+            None => format!("unparsed response ({} bytes)", exchange.bytes.len()),
+        };
+        let following = shown.map(|i| responses.len() - i - 1).unwrap_or(0);
+        if following > 0 {
+            status = format!("{} (+{} more responses)", status, following);
+        }
+        if exchange.timed_out {
+            status = format!("{} (timed out)", status);
+        }
+
+        let mut lines = responses[..shown.unwrap_or(0)]
+            .iter()
+            .map(|interim| format!("# {}", interim.status_line))
+            .collect::<Vec<String>>();
+        lines.extend(parsed.headers.iter().map(|(name, value)| format!("{}: {}", name, value)));
+        let mut notes = parsed.notes.clone();
+        if shown.is_some() && parsed_end < exchange.bytes.len() {
+            notes.push(format!(
+                "{} bytes after the last response",
+                exchange.bytes.len() - parsed_end
+            ));
+        }
+        if !notes.is_empty() {
+            lines.push("".to_string());
+            lines.extend(notes.iter().map(|note| format!("! {}", note)));
+        }
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &parsed.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+
+        self.response.connection = exchange.remote.map(Connection::New);
+        self.response.connection_guessed = false;
+        self.response.remote = exchange.remote;
+        self.response.unix_socket = settings.unix_socket.clone();
+        self.response.proxy = None;
+        self.response.tls = None;
+        self.response.tls_probe = match (url.scheme(), url.host_str(), exchange.remote) {
+            ("https", Some(host), Some(remote)) => Some(TlsProbe {
+                host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+                remote,
+                connection: self.response.connection,
+                settings: settings.clone(),
+            }),
+            _ => None,
+        };
+        self.response.hops = Vec::new();
+        self.response.hop = 0;
+        self.response.websocket = false;
+        self.response.events = Vec::new();
+        self.response.event = 0;
+        self.response.graphql = false;
+        self.response.trailers = HeaderMap::new();
+        self.response.status.text = status;
+        self.response.header.text = lines.join("\n");
+        self.response.body.text = String::from_utf8_lossy(&parsed.body).to_string();
+        self.response.code = shown.map(|_| parsed.code);
+        self.response.headers = headers;
+        self.response.elapsed = elapsed;
+        let text = wire::escape(&exchange.bytes);
+        self.response.raw = Some((exchange.bytes, text));
+        self.run_assertions();
+        Ok(())
+    }
+
+    // ヘッダーペインと Auth ペインの値を gRPC のメタデータにする
+    fn grpc_metadata(&mut self, settings: &ClientSettings) -> Result<Vec<(String, String)>, String> {
+        let mut params = self.request_params_map();
+        let mut headers = self.request_header_map()?;
+        let auth_text = self.render(&self.request.auth.text);
+        auth::apply(self.request.auth_mode.value, &auth_text, &mut headers, &mut params)?;
+        if self.request.auth_mode.value == AuthMode::OAuth2 {
+            let authorization = self
+                .oauth2_token(&auth_text, settings)
+                .and_then(|token| token.authorization())?;
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        Ok(headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).to_string(),
+                )
+            })
+            .collect())
     }
 
-    pub fn jump_request_method(&mut self) {
-        self.jump_view(ViewMode::RequestMethod);
+    // 接続先と .proto の設定が前回と同じなら読み込んだ型を使い回す
+    fn grpc_pool(
+        &mut self,
+        url: &url::Url,
+        settings: &ClientSettings,
+        channel: &mut grpc::Channel,
+        metadata: &[(String, String)],
+        reload: bool,
+    ) -> Result<Rc<Pool>, String> {
+        let key = format!(
+            "{}://{} {:?} {:?}",
+            url.scheme(),
+            url.host_str().unwrap_or(""),
+            settings.proto,
+            settings.proto_path
+        );
+        let key = match url.port() {
+            Some(port) => format!("{}:{}", key, port),
+            None => key,
+        };
+        if let Some((loaded, pool)) = &self.grpc_pool {
+            if *loaded == key && !reload {
+                return Ok(pool.clone());
+            }
+        }
+        let pool = Rc::new(grpc::load_pool(channel, metadata, settings)?);
+        self.grpc_pool = Some((key, pool.clone()));
+        self.grpc_method = 0;
+        Ok(pool)
     }
 
-    pub fn jump_request_header(&mut self) {
-        self.jump_view(ViewMode::RequestHeader);
+    // リクエストの URL の gRPC のメソッドの一覧（読み込んでいなければ読み込む）
+    pub fn open_grpc(&mut self) {
+        let result = self.request_settings().and_then(|overrides| {
+            let settings = self.client_defaults().merge(&overrides);
+            let url = self.render(self.request_url_text());
+            if !grpc::is_grpc_url(&url) {
+                return Err("not a grpc:// or grpcs:// url".to_string());
+            }
+            let url = url::Url::parse(&url).map_err(|e| format!("invalid url {}: {}", url, e))?;
+            let metadata = self.grpc_metadata(&settings)?;
+            let mut channel = grpc::Channel::connect(&url, &settings)?;
+            self.grpc_pool(&url, &settings, &mut channel, &metadata, false)
+        });
+        let pool = match result {
+            Ok(pool) => pool,
+            Err(err) => {
+                self.notice = err;
+                return;
+            }
+        };
+        // URL のメソッドを選んでおく
+        let current = self.render(self.request_url_text());
+        if let Some(i) = grpc::methods(&pool)
+            .iter()
+            .position(|(service, method)| current.ends_with(&format!("/{}/{}", service, method.name)))
+        {
+            self.grpc_method = i;
+        }
+        self.change_view_mode();
+        self.mode.popup = Popup::Grpc;
     }
 
-    pub fn jump_request_body(&mut self) {
-        self.jump_view(ViewMode::RequestBody);
+    fn grpc_loaded(&self) -> Option<&Pool> {
+        self.grpc_pool.as_ref().map(|(_, pool)| pool.as_ref())
     }
 
-    pub fn jump_response_header(&mut self) {
-        self.jump_view(ViewMode::ResponseHeader);
+    // `package.Service/Method(Input) returns (Output)` の一覧
+    pub fn grpc_lines(&self) -> Vec<String> {
+        match self.grpc_loaded() {
+            Some(pool) => grpc::methods(pool)
+                .iter()
+                .map(|(service, method)| format!("{}/{}", service, Pool::signature(method)))
+                .collect(),
+            None => Vec::new(),
+        }
     }
 
-    pub fn jump_response_body(&mut self) {
-        self.jump_view(ViewMode::ResponseBody);
+    pub fn grpc_state(&self) -> ListState {
+        let mut state = ListState::default();
+        state.select(Some(self.grpc_method));
+        state
     }
 
-    pub fn insert_text(&mut self, c: char) {
-        match self.mode.edit {
-            EditMode::RequestUrl => {
-                self.request
-                    .url
-                    .text
-                    .insert(self.request.url.cursor.x as usize, c);
-                self.request.url.cursor.x += 1;
-            }
-            EditMode::RequestParams => {
-                self.request
-                    .params
-                    .text
-                    .insert(self.request.params.cursor.x as usize, c);
-                self.request.params.cursor.x += 1;
-            }
-            EditMode::RequestHeader => {
-                self.request
-                    .header
-                    .text
-                    .insert(self.request.header.cursor.x as usize, c);
-                self.request.header.cursor.x += 1;
-            }
-            EditMode::RequestBody => {
-                self.request
-                    .body
-                    .text
-                    .insert(self.request.body.cursor.x as usize, c);
-                self.request.body.cursor.x += 1;
+    // 選んだメソッドのリクエストとレスポンスのメッセージ
+    pub fn grpc_detail_lines(&self) -> Vec<String> {
+        let pool = match self.grpc_loaded() {
+            Some(pool) => pool,
+            None => return Vec::new(),
+        };
+        match grpc::methods(pool).get(self.grpc_method) {
+            Some((_, method)) => {
+                let mut lines = pool.describe(&method.input);
+                if method.output != method.input {
+                    lines.extend(pool.describe(&method.output));
+                }
+                lines
             }
-            _ => {}
+            None => Vec::new(),
         }
     }
 
-    // TODO: 2文字以上の移動+削除が想定外の動きをする
-    // pub fn right_move_cursor(&mut self) {
-    //     match self.mode.edit {
-    //         EditMode::RequestUrl => {
-    //             if self.request.url.cursor.x < self.request.url.text.width() as u16 {
-    //                 self.request.url.cursor.x += 1;
-    //             }
-    //         }
-    //         EditMode::RequestParams => {
-    //             if self.request.params.cursor.x < self.request.params.text.width() as u16 {
-    //                 self.request.params.cursor.x += 1;
-    //             }
-    //         }
-    //         EditMode::RequestHeader => {
-    //             if self.request.header.cursor.x < self.request.header.text.width() as u16 {
-    //                 self.request.header.cursor.x += 1;
-    //             }
-    //         }
-    //         EditMode::RequestBody => {
-    //             if self.request.body.cursor.x < self.request.body.text.width() as u16 {
-    //                 self.request.body.cursor.x += 1;
-    //             }
-    //         }
-    //         _ => {}
-    //     }
-    // }
+    pub fn next_grpc_method(&mut self) {
+        let count = self.grpc_lines().len();
+        if self.grpc_method + 1 < count {
+            self.grpc_method += 1;
+        }
+    }
 
-    // pub fn left_move_cursor(&mut self) {
-    //     match self.mode.edit {
-    //         EditMode::RequestUrl => {
-    //             if 0 < self.request.url.cursor.x {
-    //                 self.request.url.cursor.x -= 1;
-    //             }
-    //         }
-    //         EditMode::RequestParams => {
-    //             if 0 < self.request.params.cursor.x {
-    //                 self.request.params.cursor.x -= 1;
-    //             }
-    //         }
-    //         EditMode::RequestHeader => {
-    //             if 0 < self.request.header.cursor.x {
-    //                 self.request.header.cursor.x -= 1;
-    //             }
-    //         }
-    //         EditMode::RequestBody => {
-    //             if 0 < self.request.body.cursor.x {
-    //                 self.request.body.cursor.x -= 1;
-    //             }
-    //         }
-    //         _ => {}
-    //     }
-    // }
+    pub fn prev_grpc_method(&mut self) {
+        if self.grpc_method > 0 {
+            self.grpc_method -= 1;
+        }
+    }
 
-    pub fn delete_text(&mut self) {
-        match self.mode.edit {
-            EditMode::RequestUrl => {
-                if self.request.url.text.len() == 0 || self.request.url.cursor.x == 0 {
-                    return;
-                }
+    // 選んだメソッドを URL のパスにし、ボディが空ならリクエストの雛形を入れる
+    pub fn select_grpc_method(&mut self) {
+        let pool = match &self.grpc_pool {
+            Some((_, pool)) => pool.clone(),
+            None => return,
+        };
+        if let Some((service, method)) = grpc::methods(&pool).get(self.grpc_method) {
+            let url = self.request.url.text.to_string();
+            let authority_end = url
+                .find("://")
+                .map(|i| i + 3)
+                .and_then(|start| url[start..].find('/').map(|i| start + i))
+                .unwrap_or(url.len());
+            let url = format!("{}/{}/{}", &url[..authority_end], service, method.name);
+            self.request.url.set_text(&url);
+            if self.request.body.text.trim().is_empty() {
+                let template = serde_json::to_string_pretty(&pool.template(&method.input)).unwrap_or_default();
+                self.request.body.set_text(&template);
+            }
+        }
+        self.close_popup();
+    }
 
-                self.request
-                    .url
-                    .text
-                    .remove(self.request.url.cursor.x as usize - 1);
+    // 直近の gRPC のレスポンスのトレーラー
+    pub fn trailer_lines(&self) -> Vec<String> {
+        self.response
+            .trailers
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, String::from_utf8_lossy(value.as_bytes())))
+            .collect()
+    }
 
-                if self.request.url.cursor.x < self.request.url.text.len() as u16 {
-                    return;
-                }
+    pub fn is_websocket_open(&self) -> bool {
+        self.websocket.is_some()
+    }
 
-                self.request.url.cursor.x -= 1;
-            }
-            EditMode::RequestParams => {
-                if self.request.params.text.len() == 0 || self.request.params.cursor.x == 0 {
-                    return;
-                }
+    // レスポンスボディのペインを末尾までスクロールして表示するか
+    pub fn follows_response_body(&self) -> bool {
+        self.response.websocket || self.stream.is_some()
+    }
 
-                self.request
-                    .params
-                    .text
-                    .remove(self.request.params.cursor.x as usize - 1);
+    pub fn ping_websocket(&mut self) {
+        self.websocket_command(websocket::Command::Ping);
+    }
 
-                if self.request.params.cursor.x < self.request.params.text.len() as u16 {
-                    return;
-                }
+    // 読み込み中のレスポンスか WebSocket を止める
+    pub fn stop(&mut self) {
+        if self.stream.is_some() {
+            self.stop_stream();
+        } else {
+            self.close_websocket();
+        }
+    }
+
+    // 1000（正常終了）で閉じる
+    pub fn close_websocket(&mut self) {
+        self.websocket_command(websocket::Command::Close(1000));
+    }
+
+    fn websocket_command(&mut self, command: websocket::Command) {
+        let result = match &self.websocket {
+            Some(session) => session.send(command),
+            None => Err("no WebSocket connection".to_string()),
+        };
+        if let Err(err) = result {
+            self.notice = err;
+        }
+        self.poll_websocket_after(Duration::from_millis(100));
+    }
+
+    // 届いたフレームをログに加える（表示を更新するなら true）
+    pub fn poll_websocket(&mut self) -> bool {
+        let events = match &self.websocket {
+            Some(session) => session.poll(),
+            None => return false,
+        };
+        let updated = !events.is_empty();
+        for event in events {
+            self.websocket_event(event);
+        }
+        updated
+    }
+
+    // 送った直後の応答も表示できるよう、少しだけ待つ
+    fn poll_websocket_after(&mut self, timeout: Duration) {
+        if let Some(event) = self.websocket.as_ref().and_then(|session| session.wait(timeout)) {
+            self.websocket_event(event);
+        }
+        self.poll_websocket();
+    }
 
-                self.request.params.cursor.x -= 1;
+    // 接続が閉じられるか timeout が過ぎるまでログを集める
+    pub fn wait_websocket(&mut self, timeout: Duration) {
+        let started = Instant::now();
+        while self.websocket.is_some() {
+            let wait = match timeout.checked_sub(started.elapsed()) {
+                Some(wait) if wait > Duration::from_secs(0) => wait,
+                _ => break,
+            };
+            if let Some(event) = self.websocket.as_ref().and_then(|session| session.wait(wait)) {
+                self.websocket_event(event);
             }
-            EditMode::RequestHeader => {
-                if self.request.header.text.len() == 0 || self.request.header.cursor.x == 0 {
-                    return;
+        }
+    }
+
+    fn websocket_event(&mut self, event: websocket::Event) {
+        let line = match event {
+            websocket::Event::Frame(frame) => frame.line(),
+            websocket::Event::Closed(reason) => {
+                self.websocket = None;
+                if let Some(code) = self.response.code {
+                    self.response.status.text = format!(
+                        "{} {} (WebSocket closed)",
+                        code,
+                        StatusCode::from_u16(code)
+                            .ok()
+                            .and_then(|status| status.canonical_reason())
+                            .unwrap_or("")
+                    );
                 }
+                format!("{} -- {}", Local::now().format("%H:%M:%S%.3f"), reason)
+            }
+        };
+        if self.response.websocket {
+            self.response.body.text.push('\n');
+            self.response.body.text.push_str(&line);
+        }
+    }
 
-                self.request
-                    .header
-                    .text
-                    .remove(self.request.header.cursor.x as usize - 1);
+    pub fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
 
-                if self.request.header.cursor.x < self.request.params.text.len() as u16 {
-                    return;
-                }
+    // 届いたチャンクをボディに加える（表示を更新するなら true）
+    pub fn poll_stream(&mut self) -> bool {
+        let chunks = match &self.stream {
+            Some(streaming) => streaming.stream.poll(),
+            None => return false,
+        };
+        let updated = !chunks.is_empty();
+        for chunk in chunks {
+            self.stream_chunk(chunk);
+        }
+        updated
+    }
 
-                self.request.header.cursor.x -= 1;
+    // 読み終わるか timeout が過ぎるまで待つ（None なら読み終わるまで）
+    pub fn wait_stream(&mut self, timeout: Option<Duration>) {
+        let started = Instant::now();
+        while let Some(streaming) = &self.stream {
+            let wait = match timeout.map(|timeout| timeout.checked_sub(started.elapsed())) {
+                Some(Some(wait)) if wait > Duration::from_secs(0) => wait,
+                Some(_) => break,
+                None => Duration::from_secs(1),
+            };
+            if let Some(chunk) = streaming.stream.wait(wait) {
+                self.stream_chunk(chunk);
             }
-            EditMode::RequestBody => {
-                if self.request.body.text.len() == 0 || self.request.body.cursor.x == 0 {
-                    return;
-                }
+        }
+    }
 
-                self.request
-                    .body
-                    .text
-                    .remove(self.request.body.cursor.x as usize - 1);
+    // 読み込みをやめ、届いた分までをレスポンスとする
+    pub fn stop_stream(&mut self) {
+        if let Some(streaming) = &self.stream {
+            streaming.stream.stop();
+            self.poll_stream();
+            self.finish_stream(Some("stopped".to_string()));
+        }
+    }
 
-                if self.request.body.cursor.x < self.request.params.text.len() as u16 {
-                    return;
+    fn stream_chunk(&mut self, chunk: stream::Chunk) {
+        let streaming = match &mut self.stream {
+            Some(streaming) => streaming,
+            None => return,
+        };
+        match chunk {
+            stream::Chunk::Data(bytes) => {
+                let text = streaming.decoder.decode(&bytes);
+                if let Some(parser) = &mut streaming.parser {
+                    self.response.events.extend(parser.feed(&text));
                 }
+                self.response.body.text.push_str(&text);
+            }
+            stream::Chunk::End(error) => self.finish_stream(error.map(|e| format!("error: {}", e))),
+        }
+    }
+
+    // 読み終わったレスポンスを検証して履歴に加える
+    fn finish_stream(&mut self, reason: Option<String>) {
+        let mut streaming = match self.stream.take() {
+            Some(streaming) => streaming,
+            None => return,
+        };
+        self.response.status.text = match reason {
+            Some(reason) => format!("{} ({})", streaming.status, reason),
+            None => streaming.status,
+        };
+        self.response.elapsed = streaming.started.elapsed();
+        self.run_assertions();
+
+        let exchange = &mut streaming.exchange;
+        exchange.receive = streaming.hop_started.elapsed() - exchange.wait;
+        exchange.response_body = self.response.body.text.to_string();
+        self.history.push(exchange.clone());
+        self.response.hops.push(streaming.exchange);
+        self.response.hop = self.response.hops.len() - 1;
+    }
+
+    pub fn open_events(&mut self) {
+        if self.response.events.is_empty() {
+            self.notice = "no Server-Sent Events in the response".to_string();
+            return;
+        }
+        self.change_view_mode();
+        self.mode.popup = Popup::Events;
+    }
+
+    // text/event-stream のイベントの一覧
+    pub fn event_lines(&self) -> Vec<String> {
+        self.response
+            .events
+            .iter()
+            .enumerate()
+            .map(|(i, event)| format!("{} {}", i + 1, event.summary()))
+            .collect()
+    }
+
+    pub fn event_state(&self) -> ListState {
+        let mut state = ListState::default();
+        state.select(Some(self.response.event));
+        state
+    }
+
+    // 選んだイベントの詳細
+    pub fn event_detail_lines(&self) -> Vec<String> {
+        self.response
+            .events
+            .get(self.response.event)
+            .map(|event| event.lines())
+            .unwrap_or_default()
+    }
+
+    pub fn next_event(&mut self) {
+        if self.response.event + 1 < self.response.events.len() {
+            self.response.event += 1;
+        }
+    }
+
+    pub fn prev_event(&mut self) {
+        if self.response.event > 0 {
+            self.response.event -= 1;
+        }
+    }
 
-                self.request.body.cursor.x -= 1;
+    // リクエストの URL にイントロスペクションのクエリを送ってスキーマを取得する
+    pub fn fetch_graphql_schema(&mut self) {
+        self.notice = match self.introspect() {
+            Ok((url, schema)) => {
+                let notice = format!(
+                    "loaded the GraphQL schema of {} ({} types)",
+                    url,
+                    schema.types.len()
+                );
+                self.graphql_schema = Some((url, schema));
+                notice
             }
-            _ => {}
+            Err(err) => err,
+        };
+    }
+
+    fn introspect(&mut self) -> Result<(String, Schema), String> {
+        let mut settings = self.client_defaults().merge(&self.request_settings()?);
+        let target = self.render(self.request_url_text());
+        let url = match resolve::unix_url(&target) {
+            Some((socket, url)) => {
+                settings.unix_socket = Some(socket);
+                url
+            }
+            None => target.to_string(),
+        };
+        let mut params = self.request_params_map();
+        let mut headers = self.request_header_map()?;
+        let auth_text = self.render(&self.request.auth.text);
+        auth::apply(self.request.auth_mode.value, &auth_text, &mut headers, &mut params)?;
+        if self.request.auth_mode.value == AuthMode::OAuth2 {
+            let authorization = self
+                .oauth2_token(&auth_text, &settings)
+                .and_then(|token| token.authorization())?;
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let mut url = url::Url::parse(&url).map_err(|e| format!("invalid url {}: {}", url, e))?;
+        if !params.is_empty() {
+            url.query_pairs_mut().extend_pairs(params.iter());
+        }
+        let client = self.client(&settings)?;
+        let resp = client
+            .post(url)
+            .headers(headers)
+            .body(graphql::introspection_body())
+            .send()
+            .map_err(|e| format!("failed to fetch the GraphQL schema: {}", e))?;
+        let status = resp.status();
+        let body = resp.text().map_err(|e| e.to_string())?;
+        match Schema::from_response(&body) {
+            Ok(schema) => Ok((target, schema)),
+            Err(err) if !status.is_success() => Err(format!("{} ({})", err, status)),
+            Err(err) => Err(err),
         }
     }
 
-    fn reqwest_method(&self) -> reqwest::Method {
-        match self.request.method.value {
-            HttpMethod::GET => reqwest::Method::GET,
-            HttpMethod::HEAD => reqwest::Method::HEAD,
-            HttpMethod::POST => reqwest::Method::POST,
-            HttpMethod::PUT => reqwest::Method::PUT,
-            HttpMethod::DELETE => reqwest::Method::DELETE,
-            HttpMethod::CONNECT => reqwest::Method::CONNECT,
-            HttpMethod::OPTIONS => reqwest::Method::OPTIONS,
-            HttpMethod::TRACE => reqwest::Method::TRACE,
-            HttpMethod::PATCH => reqwest::Method::PATCH,
+    // リクエストの URL のスキーマ（取得していなければ None）
+    fn graphql_schema(&self) -> Option<&Schema> {
+        let (url, schema) = self.graphql_schema.as_ref()?;
+        if *url == self.render(self.request_url_text()) {
+            Some(schema)
+        } else {
+            None
         }
     }
 
-    pub fn request(&mut self) {
-        let client = reqwest::blocking::Client::new();
+    // クエリと変数をスキーマで検証した結果（graphql でないかスキーマがなければ空）
+    pub fn graphql_problems(&self) -> Vec<String> {
+        match self.graphql_schema() {
+            Some(schema) if self.is_graphql() => graphql::validate(
+                schema,
+                &self.render(&self.request.body.text),
+                &self.render(&self.request.variables.text),
+            ),
+            _ => Vec::new(),
+        }
+    }
 
-        let url = self.request_url_text();
-        let params = self.request_params_map();
-        let headers = self.request_header_map();
-        let body = self.request_body_text().to_string();
+    // Request Body ペインのタイトルに添える検証の結果
+    pub fn graphql_status(&self) -> Option<String> {
+        self.graphql_schema()?;
+        Some(match self.graphql_problems().len() {
+            0 => "valid".to_string(),
+            1 => "1 problem".to_string(),
+            n => format!("{} problems", n),
+        })
+    }
+
+    pub fn open_graphql(&mut self) {
+        if !self.is_graphql() {
+            self.notice = "the body mode is not graphql".to_string();
+            return;
+        }
+        if self.graphql_schema().is_none() {
+            self.notice = "no GraphQL schema for this URL (press g to fetch it)".to_string();
+            return;
+        }
+        self.change_view_mode();
+        self.mode.popup = Popup::GraphQL;
+    }
+
+    // 検証の結果の一覧
+    pub fn graphql_lines(&self) -> Vec<String> {
+        let problems = self.graphql_problems();
+        if problems.is_empty() {
+            return vec!["the query is valid against the schema".to_string()];
+        }
+        problems
+    }
+
+    // カーソルの前の名前をスキーマのフィールドや引数で補完する
+    pub fn complete_graphql(&mut self) {
+        let schema = match self.graphql_schema() {
+            Some(schema) => schema,
+            None => {
+                self.notice = "no GraphQL schema for this URL (press g to fetch it)".to_string();
+                return;
+            }
+        };
+        let cursor = self.request.body.byte_index();
+        let (word, candidates) = graphql::complete(schema, &self.request.body.text[..cursor]);
+        let insert = match candidates.as_slice() {
+            [] => {
+                self.notice = "no completion".to_string();
+                return;
+            }
+            [candidate] => {
+                self.notice = candidate.detail.to_string();
+                let suffix = if candidate.argument { ": " } else { "" };
+                format!("{}{}", &candidate.name[word.len()..], suffix)
+            }
+            _ => {
+                self.notice = graphql::candidate_list(&candidates);
+                graphql::common_prefix(&candidates)[word.len()..].to_string()
+            }
+        };
+        self.request.body.insert_str(&insert);
+    }
 
-        let response = client.request(self.reqwest_method(), url)
-        .query(&params)
-        .headers(headers)
-        .body(body)
-        .send();
+    // GraphQL のレスポンスの errors と data（そうでなければ None）
+    pub fn response_graphql_sections(&self) -> Option<Vec<(String, Vec<String>)>> {
+        if !self.response.graphql {
+            return None;
+        }
+        graphql::sections(&self.response.body.text)
+    }
 
-        if let Ok(resp) = response {
-            self.response.status.text = format!("{:?}", resp.status());
-            self.response.header.text = format!("{:#?}", resp.headers());
-            self.response.body.text = resp.text().unwrap_or("".to_string());
+    // キャッシュしたトークンを返す（期限切れならリフレッシュするか取り直す）
+    //
+    // トークンエンドポイントにはリクエストと同じ TLS、プロキシ、名前解決の設定で送る（Unix ソケットは使わない）
+    fn oauth2_token(&mut self, text: &str, client_settings: &ClientSettings) -> Result<oauth2::Token, String> {
+        let settings = oauth2::Settings::parse(text)?;
+        let key = settings.cache_key();
+        if let Some(token) = self.tokens.get(&key) {
+            if !token.is_expired() {
+                return Ok(token.clone());
+            }
         }
+
+        let mut client_settings = client_settings.clone();
+        client_settings.unix_socket = None;
+        let client = self.client(&client_settings)?;
+        let token = match self.tokens.get(&key) {
+            Some(oauth2::Token {
+                refresh_token: Some(refresh_token),
+                ..
+            }) => settings
+                .refresh(&client, refresh_token)
+                .or_else(|_| settings.fetch(&client))?,
+            _ => settings.fetch(&client)?,
+        };
+
+        self.notice = match token.expires_at {
+            Some(expires_at) => format!(
+                "obtained an oauth2 token (expires at {})",
+                expires_at.with_timezone(&Local).format("%H:%M:%S")
+            ),
+            None => "obtained an oauth2 token".to_string(),
+        };
+        self.tokens.insert(key, token.clone());
+        Ok(token)
+    }
+
+    // 送信できなかったときの表示
+    fn set_error(&mut self, message: &str) {
+        self.response.status.text = message.to_string();
+        self.response.header.text = "".to_string();
+        self.response.body.text = "".to_string();
+        self.response.code = None;
+        self.response.headers = HeaderMap::new();
+        self.response.connection = None;
+        self.response.connection_guessed = false;
+        self.response.remote = None;
+        self.response.unix_socket = None;
+        self.response.proxy = None;
+        self.response.tls = None;
+        self.response.tls_probe = None;
+        self.response.hops = Vec::new();
+        self.response.hop = 0;
+        self.response.websocket = false;
+        self.response.events = Vec::new();
+        self.response.event = 0;
+        self.response.graphql = false;
+        self.response.trailers = HeaderMap::new();
+        self.response.raw = None;
+        self.response.test_results = Vec::new();
+    }
+
+    // リクエストに設定された検証を直近のレスポンスに対して実行する
+    fn run_assertions(&mut self) {
+        let status = match self.response.code {
+            Some(status) => status,
+            None => return,
+        };
+
+        let target = assertion::Target {
+            status,
+            headers: &self.response.headers,
+            body: &self.response.body.text,
+            elapsed: self.response.elapsed,
+        };
+        self.response.test_results = assertion::evaluate(&self.request.assertion.text, &target);
     }
 
     pub fn next_select_on_request_method(&mut self) {
@@ -591,4 +3116,115 @@ impl App {
     pub fn prev_select_on_request_method(&mut self) {
         self.request.method.previous();
     }
+
+    pub fn next_select_on_request_auth_mode(&mut self) {
+        let previous = self.request.auth_mode.value;
+        self.request.auth_mode.next();
+        self.fill_auth_template(previous);
+    }
+
+    pub fn prev_select_on_request_auth_mode(&mut self) {
+        let previous = self.request.auth_mode.value;
+        self.request.auth_mode.previous();
+        self.fill_auth_template(previous);
+    }
+
+    // Auth ペインが空か前のモードの雛形のままなら、新しいモードの雛形に入れ替える
+    fn fill_auth_template(&mut self, previous: AuthMode) {
+        let text = self.request.auth.text.trim();
+        if text.is_empty() || text == previous.template() {
+            let template = self.request.auth_mode.value.template();
+            self.request.auth.set_text(template);
+        }
+    }
+
+    pub fn next_select_on_request_body_mode(&mut self) {
+        self.request.body_mode.next();
+    }
+
+    pub fn prev_select_on_request_body_mode(&mut self) {
+        self.request.body_mode.previous();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_after_loading_multibyte_text() {
+        let mut view = EditView::new();
+        view.set_text("名前=値");
+        assert_eq!(view.cursor.x, 4);
+        view.insert('！');
+        assert_eq!(view.text, "名前=値！");
+        view.delete();
+        view.delete();
+        assert_eq!(view.text, "名前=");
+        view.insert('é');
+        assert_eq!(view.text, "名前=é");
+        assert_eq!(view.column(), 4);
+    }
+
+    #[test]
+    fn long_text_keeps_the_cursor_at_the_end() {
+        let mut view = EditView::new();
+        view.set_text(&"a".repeat(70_000));
+        view.insert('b');
+        assert!(view.text.ends_with("ab"));
+        view.delete();
+        view.delete();
+        assert_eq!(view.text.len(), 69_999);
+        assert!(view.column() < u16::MAX / 2 + 1);
+    }
+
+    #[test]
+    fn delete_at_the_start_does_nothing() {
+        let mut view = EditView::new();
+        view.delete();
+        view.insert('あ');
+        view.delete();
+        view.delete();
+        assert_eq!(view.text, "");
+        assert_eq!(view.cursor.x, 0);
+    }
+
+    #[test]
+    fn environment_settings_sit_between_config_and_pane() {
+        let mut app = App::default();
+        app.config.client = ClientSettings::parse("proxy=http://config:1\ntimeout=5").unwrap();
+        let environment = Environment {
+            name: "staging".to_string(),
+            variables: vec![("proxy_host".to_string(), "staging-proxy".to_string())].into_iter().collect(),
+            settings: vec!["proxy=http://{{proxy_host}}:3128".to_string(), "no_proxy=.internal".to_string()],
+        };
+        app.load_environment(&environment).unwrap();
+        let settings = app.client_defaults();
+        assert_eq!(settings.proxy.as_deref(), Some("http://staging-proxy:3128"));
+        assert_eq!(settings.no_proxy.as_deref(), Some(".internal"));
+        assert_eq!(settings.timeout, Some(5.0));
+        // 設定ファイルには書き込まない
+        assert_eq!(app.config.client.proxy.as_deref(), Some("http://config:1"));
+
+        let lines = app.settings_lines();
+        assert!(lines.iter().any(|l| l.starts_with("proxy ") && l.ends_with("(environment)")));
+
+        let invalid = Environment {
+            name: "bad".to_string(),
+            settings: vec!["timeout=x".to_string()],
+            ..Environment::default()
+        };
+        assert!(app.load_environment(&invalid).unwrap_err().starts_with("environment bad: "));
+    }
+
+    #[test]
+    fn replaced_text_clamps_the_cursor() {
+        let mut view = EditView::new();
+        view.set_text("ながいテキスト");
+        view.text = "短い".to_string();
+        view.delete();
+        assert_eq!(view.text, "短");
+        view.insert('く');
+        assert_eq!(view.text, "短く");
+    }
 }
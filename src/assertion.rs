@@ -0,0 +1,345 @@
+use super::json_path;
+
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+// レスポンスに対する検証内容
+//
+// 1行に1つ、以下の形式で記述する
+//   status == 200
+//   status != 500
+//   status in 200..299
+//   header Content-Type == application/json
+//   header Content-Type contains json
+//   header X-Request-Id exists
+//   json $.data.id == 42
+//   json $.data.items exists
+//   body contains hello
+//   time < 500
+#[derive(Clone, Debug)]
+pub enum Assertion {
+    StatusEquals(u16),
+    StatusNotEquals(u16),
+    StatusInRange(u16, u16),
+    HeaderEquals(String, String),
+    HeaderContains(String, String),
+    HeaderExists(String),
+    JsonEquals(String, Value),
+    JsonExists(String),
+    BodyContains(String),
+    TimeBelow(u128),
+}
+
+// 検証結果
+#[derive(Clone, Debug, Serialize)]
+pub struct AssertionResult {
+    pub source: String,
+    pub passed: bool,
+    pub message: String,
+}
+
+// 検証対象のレスポンス
+pub struct Target<'a> {
+    pub status: u16,
+    pub headers: &'a HeaderMap,
+    pub body: &'a str,
+    pub elapsed: Duration,
+}
+
+fn parse_status(s: &str) -> Result<u16, String> {
+    s.trim()
+        .parse::<u16>()
+        .map_err(|_| format!("invalid status code: {}", s.trim()))
+}
+
+fn parse_json_value(s: &str) -> Value {
+    let s = s.trim();
+    serde_json::from_str(s).unwrap_or_else(|_| Value::String(s.to_string()))
+}
+
+impl Assertion {
+    pub fn parse(line: &str) -> Result<Assertion, String> {
+        let line = line.trim();
+        let (subject, rest) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+
+        match subject {
+            "status" => {
+                if let Some(expected) = rest.strip_prefix("==") {
+                    Ok(Assertion::StatusEquals(parse_status(expected)?))
+                } else if let Some(expected) = rest.strip_prefix("!=") {
+                    Ok(Assertion::StatusNotEquals(parse_status(expected)?))
+                } else if let Some(range) = rest.strip_prefix("in ") {
+                    let mut bounds = range.splitn(2, "..");
+                    let low = parse_status(bounds.next().unwrap_or(""))?;
+                    let high = parse_status(bounds.next().unwrap_or(""))?;
+                    Ok(Assertion::StatusInRange(low, high))
+                } else {
+                    Err(format!("unknown status assertion: {}", line))
+                }
+            }
+            "header" => {
+                let (name, op) = match rest.find(' ') {
+                    Some(i) => (&rest[..i], rest[i + 1..].trim()),
+                    None => (rest, ""),
+                };
+                if name.is_empty() {
+                    return Err(format!("header name is missing: {}", line));
+                }
+                let name = name.to_string();
+                if op == "exists" {
+                    Ok(Assertion::HeaderExists(name))
+                } else if let Some(expected) = op.strip_prefix("==") {
+                    Ok(Assertion::HeaderEquals(name, expected.trim().to_string()))
+                } else if let Some(expected) = op.strip_prefix("contains ") {
+                    Ok(Assertion::HeaderContains(name, expected.trim().to_string()))
+                } else {
+                    Err(format!("unknown header assertion: {}", line))
+                }
+            }
+            "json" => {
+                let (path, op) = match rest.find(' ') {
+                    Some(i) => (&rest[..i], rest[i + 1..].trim()),
+                    None => (rest, ""),
+                };
+                if !path.starts_with('$') {
+                    return Err(format!("json path must start with '$': {}", line));
+                }
+                let path = path.to_string();
+                if op == "exists" {
+                    Ok(Assertion::JsonExists(path))
+                } else if let Some(expected) = op.strip_prefix("==") {
+                    Ok(Assertion::JsonEquals(path, parse_json_value(expected)))
+                } else {
+                    Err(format!("unknown json assertion: {}", line))
+                }
+            }
+            "body" => match rest.strip_prefix("contains ") {
+                Some(expected) => Ok(Assertion::BodyContains(expected.trim().to_string())),
+                None => Err(format!("unknown body assertion: {}", line)),
+            },
+            "time" => match rest.strip_prefix('<') {
+                Some(ms) => ms
+                    .trim()
+                    .trim_end_matches("ms")
+                    .parse::<u128>()
+                    .map(Assertion::TimeBelow)
+                    .map_err(|_| format!("invalid time: {}", line)),
+                None => Err(format!("unknown time assertion: {}", line)),
+            },
+            _ => Err(format!("unknown assertion: {}", line)),
+        }
+    }
+
+    // 成否と失敗時のメッセージを返す
+    fn check(&self, target: &Target) -> (bool, String) {
+        match self {
+            Assertion::StatusEquals(expected) => (
+                target.status == *expected,
+                format!("status was {}", target.status),
+            ),
+            Assertion::StatusNotEquals(expected) => (
+                target.status != *expected,
+                format!("status was {}", target.status),
+            ),
+            Assertion::StatusInRange(low, high) => (
+                *low <= target.status && target.status <= *high,
+                format!("status was {}", target.status),
+            ),
+            Assertion::HeaderEquals(name, expected) => match header_value(target, name) {
+                Some(actual) => (actual == *expected, format!("{} was {}", name, actual)),
+                None => (false, format!("{} is missing", name)),
+            },
+            Assertion::HeaderContains(name, expected) => match header_value(target, name) {
+                Some(actual) => (
+                    actual.contains(expected.as_str()),
+                    format!("{} was {}", name, actual),
+                ),
+                None => (false, format!("{} is missing", name)),
+            },
+            Assertion::HeaderExists(name) => (
+                target.headers.contains_key(name.as_str()),
+                format!("{} is missing", name),
+            ),
+            Assertion::JsonEquals(path, expected) => match json_value(target, path) {
+                Ok(Some(actual)) => (actual == *expected, format!("{} was {}", path, actual)),
+                Ok(None) => (false, format!("{} not found", path)),
+                Err(err) => (false, err),
+            },
+            Assertion::JsonExists(path) => match json_value(target, path) {
+                Ok(found) => (found.is_some(), format!("{} not found", path)),
+                Err(err) => (false, err),
+            },
+            Assertion::BodyContains(expected) => (
+                target.body.contains(expected.as_str()),
+                "body did not contain the text".to_string(),
+            ),
+            Assertion::TimeBelow(ms) => {
+                let elapsed = target.elapsed.as_millis();
+                (elapsed < *ms, format!("took {}ms", elapsed))
+            }
+        }
+    }
+}
+
+fn header_value(target: &Target, name: &str) -> Option<String> {
+    target
+        .headers
+        .get(name)
+        .map(|v| v.to_str().unwrap_or("").to_string())
+}
+
+fn json_value(target: &Target, path: &str) -> Result<Option<Value>, String> {
+    let body: Value =
+        serde_json::from_str(target.body).map_err(|e| format!("body is not json: {}", e))?;
+    json_path::lookup(&body, path).map(|v| v.cloned())
+}
+
+// 複数行の検証内容をすべて評価する（空行と`#`で始まる行は無視する）
+pub fn evaluate(text: &str, target: &Target) -> Vec<AssertionResult> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match Assertion::parse(line) {
+            Ok(assertion) => {
+                let (passed, message) = assertion.check(target);
+                AssertionResult {
+                    source: line.to_string(),
+                    passed,
+                    message: if passed { "".to_string() } else { message },
+                }
+            }
+            Err(err) => AssertionResult {
+                source: line.to_string(),
+                passed: false,
+                message: err,
+            },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderValue, CONTENT_TYPE};
+
+    fn headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json; charset=utf-8"));
+        headers
+    }
+
+    fn results(text: &str, body: &str) -> Vec<(bool, String)> {
+        let headers = headers();
+        let target = Target {
+            status: 201,
+            headers: &headers,
+            body,
+            elapsed: Duration::from_millis(120),
+        };
+        evaluate(text, &target)
+            .into_iter()
+            .map(|result| (result.passed, result.message))
+            .collect()
+    }
+
+    fn passes(line: &str) -> bool {
+        let (passed, _) = results(line, r#"{"data": {"id": 42, "items": ["a", "b"], "name": "ruzz"}}"#)[0].clone();
+        passed
+    }
+
+    #[test]
+    fn parse_reads_every_operator() {
+        assert!(matches!(Assertion::parse("status == 200"), Ok(Assertion::StatusEquals(200))));
+        assert!(matches!(Assertion::parse("status != 500"), Ok(Assertion::StatusNotEquals(500))));
+        assert!(matches!(Assertion::parse("status in 200..299"), Ok(Assertion::StatusInRange(200, 299))));
+        assert!(matches!(Assertion::parse("header X-Id exists"), Ok(Assertion::HeaderExists(n)) if n == "X-Id"));
+        assert!(matches!(
+            Assertion::parse("header Content-Type == text/plain"),
+            Ok(Assertion::HeaderEquals(n, v)) if n == "Content-Type" && v == "text/plain"
+        ));
+        assert!(matches!(
+            Assertion::parse("header Content-Type contains json"),
+            Ok(Assertion::HeaderContains(n, v)) if n == "Content-Type" && v == "json"
+        ));
+        assert!(matches!(Assertion::parse("json $.a exists"), Ok(Assertion::JsonExists(p)) if p == "$.a"));
+        assert!(matches!(
+            Assertion::parse("json $.a == {\"b\": [1]}"),
+            Ok(Assertion::JsonEquals(p, v)) if p == "$.a" && v == serde_json::json!({"b": [1]})
+        ));
+        // JSON として読めない値は文字列
+        assert!(matches!(Assertion::parse("json $.a == abc"), Ok(Assertion::JsonEquals(_, v)) if v == "abc"));
+        assert!(matches!(Assertion::parse("body contains hello world"), Ok(Assertion::BodyContains(v)) if v == "hello world"));
+        assert!(matches!(Assertion::parse("time < 500"), Ok(Assertion::TimeBelow(500))));
+        assert!(matches!(Assertion::parse("  time < 500ms  "), Ok(Assertion::TimeBelow(500))));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_lines() {
+        let error = |line: &str| Assertion::parse(line).unwrap_err();
+        assert_eq!(error("status == ok"), "invalid status code: ok");
+        assert_eq!(error("status in 200"), "invalid status code: ");
+        assert_eq!(error("status > 200"), "unknown status assertion: status > 200");
+        assert_eq!(error("header"), "header name is missing: header");
+        assert_eq!(error("header X-Id matches a"), "unknown header assertion: header X-Id matches a");
+        assert_eq!(error("json data exists"), "json path must start with '$': json data exists");
+        assert_eq!(error("json $.a != 1"), "unknown json assertion: json $.a != 1");
+        assert_eq!(error("body == a"), "unknown body assertion: body == a");
+        assert_eq!(error("time < soon"), "invalid time: time < soon");
+        assert_eq!(error("time > 5"), "unknown time assertion: time > 5");
+        assert_eq!(error("size < 5"), "unknown assertion: size < 5");
+    }
+
+    #[test]
+    fn evaluate_checks_each_operator() {
+        assert!(passes("status == 201"));
+        assert!(!passes("status == 200"));
+        assert!(passes("status != 200"));
+        assert!(!passes("status != 201"));
+        assert!(passes("status in 200..299"));
+        assert!(passes("status in 201..201"));
+        assert!(!passes("status in 300..399"));
+        assert!(passes("header content-type exists"));
+        assert!(!passes("header X-Id exists"));
+        assert!(passes("header Content-Type == application/json; charset=utf-8"));
+        assert!(!passes("header Content-Type == application/json"));
+        assert!(passes("header Content-Type contains json"));
+        assert!(!passes("header Content-Type contains xml"));
+        assert!(passes("json $.data.id == 42"));
+        assert!(!passes("json $.data.id == \"42\""));
+        assert!(passes("json $.data.items[1] == b"));
+        assert!(passes("json $.data.items == [\"a\", \"b\"]"));
+        assert!(passes("json $.data.name exists"));
+        assert!(!passes("json $.data.items[2] exists"));
+        assert!(passes("body contains \"ruzz\""));
+        assert!(!passes("body contains nope"));
+        assert!(passes("time < 500"));
+        assert!(!passes("time < 100"));
+    }
+
+    #[test]
+    fn evaluate_reports_failures_and_skips_comments() {
+        let text = "# comment\n\nstatus == 200\nheader X-Id == 1\njson $.missing == 1\njson $.id exists\ntime < 100\nbogus\n";
+        assert_eq!(
+            results(text, r#"{"id": 1}"#),
+            vec![
+                (false, "status was 201".to_string()),
+                (false, "X-Id is missing".to_string()),
+                (false, "$.missing not found".to_string()),
+                (true, "".to_string()),
+                (false, "took 120ms".to_string()),
+                (false, "unknown assertion: bogus".to_string()),
+            ]
+        );
+        let (passed, message) = results("json $.id exists", "not json")[0].clone();
+        assert!(!passed);
+        assert!(message.starts_with("body is not json: "));
+        let (passed, message) = results("json $[ exists", "{}")[0].clone();
+        assert!(!passed);
+        assert_eq!(message, "unclosed '[' in json path: $[");
+    }
+}
@@ -0,0 +1,361 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use std::collections::BTreeMap;
+
+use openssl::hash::{hash, MessageDigest};
+
+// Auth ペインの認証方式
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuthMode {
+    None,
+    Basic,
+    Bearer,
+    ApiKey,
+    Digest,
+    OAuth2,
+    AwsSigV4,
+}
+
+pub const AUTH_MODES: [AuthMode; 7] = [
+    AuthMode::None,
+    AuthMode::Basic,
+    AuthMode::Bearer,
+    AuthMode::ApiKey,
+    AuthMode::Digest,
+    AuthMode::OAuth2,
+    AuthMode::AwsSigV4,
+];
+
+impl AuthMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuthMode::None => "none",
+            AuthMode::Basic => "basic",
+            AuthMode::Bearer => "bearer",
+            AuthMode::ApiKey => "apikey",
+            AuthMode::Digest => "digest",
+            AuthMode::OAuth2 => "oauth2",
+            AuthMode::AwsSigV4 => "aws",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AuthMode> {
+        AUTH_MODES
+            .iter()
+            .find(|m| m.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    // モードを切り替えたときに Auth ペインに入れる雛形
+    pub fn template(&self) -> &'static str {
+        match self {
+            AuthMode::None => "",
+            AuthMode::Basic | AuthMode::Digest => "username=\npassword=",
+            AuthMode::Bearer => "token=",
+            AuthMode::ApiKey => "in=header\nname=X-API-Key\nvalue=",
+            AuthMode::OAuth2 => {
+                "grant=client_credentials\ntoken_url=\nclient_id=\nclient_secret=\nscope="
+            }
+            AuthMode::AwsSigV4 => {
+                "access_key=\nsecret_key=\nsession_token=\nregion=us-east-1\nservice=execute-api"
+            }
+        }
+    }
+}
+
+// `key=value` 形式の行を取り出す（同じキーは後のものを使う）
+pub fn fields(text: &str) -> BTreeMap<String, String> {
+    field_lines(text).into_iter().collect()
+}
+
+// `key=value` 形式の行を書かれた順に取り出す（同じキーも残す）
+pub fn field_lines(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next()?.trim().to_string();
+            let value = parts.next().unwrap_or("").trim().to_string();
+            Some((key, value))
+        })
+        .collect()
+}
+
+fn field<'a>(fields: &'a BTreeMap<String, String>, key: &str) -> &'a str {
+    fields.get(key).map(|v| v.as_str()).unwrap_or("")
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid auth header value: {}", value))
+}
+
+// 送信前に決まる方式をヘッダーまたはクエリに反映する（digest, oauth2, aws は App::request で扱う）
+pub fn apply(
+    mode: AuthMode,
+    text: &str,
+    headers: &mut HeaderMap,
+    params: &mut BTreeMap<String, String>,
+) -> Result<(), String> {
+    let fields = fields(text);
+    match mode {
+        AuthMode::None | AuthMode::Digest | AuthMode::OAuth2 | AuthMode::AwsSigV4 => {}
+        AuthMode::Basic => {
+            let credentials = format!(
+                "{}:{}",
+                field(&fields, "username"),
+                field(&fields, "password")
+            );
+            let value = format!("Basic {}", base64::encode(credentials));
+            headers.insert(AUTHORIZATION, header_value(&value)?);
+        }
+        AuthMode::Bearer => {
+            let value = format!("Bearer {}", field(&fields, "token"));
+            headers.insert(AUTHORIZATION, header_value(&value)?);
+        }
+        AuthMode::ApiKey => {
+            let name = field(&fields, "name");
+            if name.is_empty() {
+                return Err("api key name is empty".to_string());
+            }
+            let value = field(&fields, "value");
+            match field(&fields, "in") {
+                "query" => {
+                    params.insert(name.to_string(), value.to_string());
+                }
+                "" | "header" => {
+                    let name = HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|_| format!("invalid api key header name: {}", name))?;
+                    headers.insert(name, header_value(value)?);
+                }
+                other => return Err(format!("unknown api key location: {}", other)),
+            }
+        }
+    }
+    Ok(())
+}
+
+// ダイジェスト認証のユーザー名とパスワード
+pub fn digest_credentials(mode: AuthMode, text: &str) -> Option<(String, String)> {
+    if mode != AuthMode::Digest {
+        return None;
+    }
+    let fields = fields(text);
+    Some((
+        field(&fields, "username").to_string(),
+        field(&fields, "password").to_string(),
+    ))
+}
+
+// `WWW-Authenticate: Digest realm="...", nonce="..."` の各値を取り出す
+fn challenge_params(challenge: &str) -> BTreeMap<String, String> {
+    let mut params = BTreeMap::new();
+    let mut rest = challenge.trim();
+    rest = rest.get(6..).unwrap_or("").trim_start();
+
+    while !rest.is_empty() {
+        let eq = match rest.find('=') {
+            Some(eq) => eq,
+            None => break,
+        };
+        let key = rest[..eq].trim().to_lowercase();
+        rest = rest[eq + 1..].trim_start();
+
+        let value;
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut end = 0;
+            let mut escaped = false;
+            let mut text = String::new();
+            for (i, c) in quoted.char_indices() {
+                end = i + c.len_utf8();
+                if escaped {
+                    text.push(c);
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == '"' {
+                    break;
+                } else {
+                    text.push(c);
+                }
+            }
+            value = text;
+            rest = &quoted[end..];
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            value = rest[..end].trim().to_string();
+            rest = &rest[end..];
+        }
+        params.insert(key, value);
+        rest = rest.trim_start().trim_start_matches(',').trim_start();
+    }
+    params
+}
+
+// チャレンジがダイジェスト認証か
+pub fn is_digest_challenge(challenge: &str) -> bool {
+    challenge
+        .trim_start()
+        .get(..7)
+        .map(|s| s.eq_ignore_ascii_case("digest "))
+        .unwrap_or(false)
+}
+
+fn hex_digest(algorithm: MessageDigest, text: &str) -> Result<String, String> {
+    let bytes = hash(algorithm, text.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// quoted-string の中の `"` と `\` をエスケープする
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// チャレンジに対する Authorization ヘッダーを作る（RFC 7616, qop=auth のみ）
+pub fn digest_authorization(
+    challenge: &str,
+    method: &str,
+    uri: &str,
+    username: &str,
+    password: &str,
+) -> Result<HeaderValue, String> {
+    let mut cnonce = [0; 8];
+    openssl::rand::rand_bytes(&mut cnonce).map_err(|e| e.to_string())?;
+    let cnonce: String = cnonce.iter().map(|b| format!("{:02x}", b)).collect();
+    digest_authorization_with(challenge, method, uri, username, password, &cnonce)
+}
+
+fn digest_authorization_with(
+    challenge: &str,
+    method: &str,
+    uri: &str,
+    username: &str,
+    password: &str,
+    cnonce: &str,
+) -> Result<HeaderValue, String> {
+    let params = challenge_params(challenge);
+    let realm = params.get("realm").map(|s| s.as_str()).unwrap_or("");
+    let nonce = params
+        .get("nonce")
+        .ok_or_else(|| "digest challenge has no nonce".to_string())?;
+    let algorithm_name = params
+        .get("algorithm")
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| "MD5".to_string());
+    let (algorithm, session) = match algorithm_name.to_uppercase().as_str() {
+        "MD5" => (MessageDigest::md5(), false),
+        "MD5-SESS" => (MessageDigest::md5(), true),
+        "SHA-256" => (MessageDigest::sha256(), false),
+        "SHA-256-SESS" => (MessageDigest::sha256(), true),
+        other => return Err(format!("digest algorithm {} is not supported", other)),
+    };
+    let qop = match params.get("qop") {
+        Some(qop) if qop.split(',').any(|q| q.trim() == "auth") => Some("auth"),
+        Some(qop) => return Err(format!("digest qop {} is not supported", qop)),
+        None => None,
+    };
+
+    let nc = "00000001";
+
+    let mut ha1 = hex_digest(algorithm, &format!("{}:{}:{}", username, realm, password))?;
+    if session {
+        ha1 = hex_digest(algorithm, &format!("{}:{}:{}", ha1, nonce, cnonce))?;
+    }
+    let ha2 = hex_digest(algorithm, &format!("{}:{}", method, uri))?;
+    let response = match qop {
+        Some(qop) => hex_digest(
+            algorithm,
+            &format!("{}:{}:{}:{}:{}:{}", ha1, nonce, nc, cnonce, qop, ha2),
+        )?,
+        None => hex_digest(algorithm, &format!("{}:{}:{}", ha1, nonce, ha2))?,
+    };
+
+    let mut value = format!(
+        "Digest username={}, realm={}, nonce={}, uri={}, algorithm={}, response=\"{}\"",
+        quote(username),
+        quote(realm),
+        quote(nonce),
+        quote(uri),
+        algorithm_name,
+        response
+    );
+    if let Some(qop) = qop {
+        value.push_str(&format!(", qop={}, nc={}, cnonce={}", qop, nc, quote(cnonce)));
+    }
+    if let Some(opaque) = params.get("opaque") {
+        value.push_str(&format!(", opaque={}", quote(opaque)));
+    }
+    header_value(&value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7616 3.9.1 の例
+    fn rfc7616(algorithm: &str) -> String {
+        digest_authorization_with(
+            &format!(
+                "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", algorithm={}, \
+                 nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                 opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+                algorithm
+            ),
+            "GET",
+            "/dir/index.html",
+            "Mufasa",
+            "Circle of Life",
+            "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+        )
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string()
+    }
+
+    #[test]
+    fn digest_md5_matches_rfc7616() {
+        assert_eq!(
+            rfc7616("MD5"),
+            "Digest username=\"Mufasa\", realm=\"http-auth@example.org\", \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", uri=\"/dir/index.html\", algorithm=MD5, \
+             response=\"8ca523f5e9506fed4657c9700eebdbec\", qop=auth, nc=00000001, \
+             cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+        );
+    }
+
+    #[test]
+    fn digest_sha256_matches_rfc7616() {
+        assert!(rfc7616("SHA-256")
+            .contains("response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\""));
+    }
+
+    #[test]
+    fn digest_escapes_quoted_strings() {
+        let value = digest_authorization_with(
+            "Digest realm=\"a \\\"b\\\" c\", nonce=\"n\"",
+            "GET",
+            "/\"x\"",
+            "us\"er\\",
+            "pw",
+            "c",
+        )
+        .unwrap();
+        let value = value.to_str().unwrap();
+        assert!(value.starts_with(r#"Digest username="us\"er\\", realm="a \"b\" c", nonce="n", uri="/\"x\"""#), "{}", value);
+        // エスケープしたヘッダーを読み直すと元の値に戻る
+        let params = challenge_params(value);
+        assert_eq!(params["username"], "us\"er\\");
+        assert_eq!(params["realm"], "a \"b\" c");
+        assert_eq!(params["uri"], "/\"x\"");
+    }
+
+    #[test]
+    fn digest_rejects_unsupported_challenges() {
+        let err = |challenge: &str| digest_authorization_with(challenge, "GET", "/", "u", "p", "c").unwrap_err();
+        assert_eq!(err("Digest realm=\"r\""), "digest challenge has no nonce");
+        assert!(err("Digest nonce=\"n\", algorithm=SHA-512-256").contains("SHA-512-256"));
+        assert!(err("Digest nonce=\"n\", qop=\"auth-int\"").contains("auth-int"));
+    }
+}
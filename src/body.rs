@@ -0,0 +1,483 @@
+use super::graphql;
+use super::wire;
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+// Request Body ペインの内容の送り方
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BodyMode {
+    Raw,
+    Json,
+    Form,
+    Multipart,
+    GraphQL,
+    // ペインの内容がリクエスト全体
+    Wire,
+}
+
+pub const BODY_MODES: [BodyMode; 6] = [
+    BodyMode::Raw,
+    BodyMode::Json,
+    BodyMode::Form,
+    BodyMode::Multipart,
+    BodyMode::GraphQL,
+    BodyMode::Wire,
+];
+
+impl BodyMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BodyMode::Raw => "raw",
+            BodyMode::Json => "json",
+            BodyMode::Form => "form",
+            BodyMode::Multipart => "multipart",
+            BodyMode::GraphQL => "graphql",
+            BodyMode::Wire => "wire",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BodyMode> {
+        BODY_MODES
+            .iter()
+            .find(|m| m.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+}
+
+// 送信するボディの中身
+pub enum Payload {
+    Bytes(Vec<u8>),
+    // 送信時にファイルから読み出す
+    File(PathBuf),
+    // ファイルを含む multipart（ファイルの部分は送信時に読み出す）
+    Multipart(Multipart),
+    // 送信時に標準入力から読み出す
+    Stdin,
+}
+
+impl Payload {
+    // 中身をすべて読み込む
+    pub fn into_bytes(self) -> Result<Vec<u8>, String> {
+        match self {
+            Payload::Bytes(bytes) => Ok(bytes),
+            Payload::File(path) => {
+                fs::read(&path).map_err(|e| format!("failed to read {}: {}", path.display(), e))
+            }
+            Payload::Multipart(multipart) => {
+                let (mut reader, _) = multipart.reader()?;
+                let mut bytes = Vec::new();
+                reader
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| format!("failed to read the multipart body: {}", e))?;
+                Ok(bytes)
+            }
+            Payload::Stdin => {
+                let mut bytes = Vec::new();
+                io::stdin().read_to_end(&mut bytes)
+                    .map_err(|e| format!("failed to read stdin: {}", e))?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+// 送信するボディ
+pub struct EncodedBody {
+    pub content_type: Option<String>,
+    pub payload: Payload,
+}
+
+// ボディが `@path` だけならファイルの参照として扱う（`@-` は標準入力、`@@` で始まれば `@` そのもの）
+pub fn file_reference(text: &str) -> Option<&str> {
+    let text = text.trim();
+    if text.contains('\n') || text.starts_with("@@") {
+        return None;
+    }
+    text.strip_prefix('@')
+        .map(|path| path.trim())
+        .filter(|path| !path.is_empty())
+}
+
+pub fn unescape(text: &str) -> &str {
+    match text.trim_start().strip_prefix("@@") {
+        Some(_) => &text.trim_start()[1..],
+        None => text,
+    }
+}
+
+fn guess_type(path: &str) -> String {
+    mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .to_string()
+}
+
+// ボディペインに表示する参照先の概要
+pub fn summary(text: &str) -> Option<String> {
+    let path = file_reference(text)?;
+    if path == "-" {
+        return Some("file: <stdin>".to_string());
+    }
+    Some(match fs::metadata(path) {
+        Ok(meta) => format!(
+            "file: {}\nsize: {} bytes\ntype: {}",
+            path,
+            meta.len(),
+            guess_type(path)
+        ),
+        Err(e) => format!("file: {}\nerror: {}", path, e),
+    })
+}
+
+// `key=value` 形式の行を取り出す（空行と`#`で始まる行は無視する）
+fn fields(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| {
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim().to_string();
+            let value = parts.next().unwrap_or("").to_string();
+            (key, value)
+        })
+        .collect()
+}
+
+// ペインの内容をモードに応じてエンコードする
+//
+//   form:      1行に1つ `key=value`
+//   multipart: 1行に1つ `key=value` または `key=@path/to/file[;type=mime]`
+//   graphql:   クエリ（変数は encode_graphql で渡す）
+//   wire:      HTTP/1.1 のリクエスト全体（wire::encode）
+pub fn encode(mode: BodyMode, text: &str) -> Result<EncodedBody, String> {
+    if let Some(path) = file_reference(text) {
+        return encode_file(mode, path);
+    }
+    encode_text(mode, unescape(text))
+}
+
+fn encode_text(mode: BodyMode, text: &str) -> Result<EncodedBody, String> {
+    match mode {
+        BodyMode::Raw => Ok(EncodedBody {
+            content_type: None,
+            payload: Payload::Bytes(text.as_bytes().to_vec()),
+        }),
+        BodyMode::Json => Ok(EncodedBody {
+            content_type: Some("application/json".to_string()),
+            payload: Payload::Bytes(text.as_bytes().to_vec()),
+        }),
+        BodyMode::Form => {
+            let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+            for (key, value) in fields(text) {
+                serializer.append_pair(&key, &value);
+            }
+            Ok(EncodedBody {
+                content_type: Some("application/x-www-form-urlencoded".to_string()),
+                payload: Payload::Bytes(serializer.finish().into_bytes()),
+            })
+        }
+        BodyMode::Multipart => multipart(text),
+        BodyMode::GraphQL => encode_graphql(text, ""),
+        BodyMode::Wire => Ok(EncodedBody {
+            content_type: None,
+            payload: Payload::Bytes(wire::encode(text)?),
+        }),
+    }
+}
+
+// クエリと変数を `{"query", "variables"}` の JSON にする（クエリは `@path` でファイルからも読める）
+pub fn encode_graphql(query: &str, variables: &str) -> Result<EncodedBody, String> {
+    let query = match file_reference(query) {
+        Some("-") => return Err("a GraphQL query cannot be read from stdin".to_string()),
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?,
+        None => unescape(query).to_string(),
+    };
+    Ok(EncodedBody {
+        content_type: Some("application/json".to_string()),
+        payload: Payload::Bytes(graphql::encode(&query, variables)?),
+    })
+}
+
+// `@path` で指定されたファイルをボディにする
+//
+// raw と json と wire はファイルをそのまま送り、form と multipart はファイルの中身を行として解釈する
+fn encode_file(mode: BodyMode, path: &str) -> Result<EncodedBody, String> {
+    let payload = if path == "-" {
+        Payload::Stdin
+    } else {
+        if !Path::new(path).is_file() {
+            return Err(format!("{} is not a file", path));
+        }
+        Payload::File(PathBuf::from(path))
+    };
+
+    match mode {
+        BodyMode::Raw => Ok(EncodedBody {
+            content_type: match payload {
+                Payload::Stdin => None,
+                _ => Some(guess_type(path)),
+            },
+            payload,
+        }),
+        BodyMode::Json => Ok(EncodedBody {
+            content_type: Some("application/json".to_string()),
+            payload,
+        }),
+        BodyMode::GraphQL => encode_graphql(&format!("@{}", path), ""),
+        BodyMode::Wire => Ok(EncodedBody {
+            content_type: None,
+            payload,
+        }),
+        BodyMode::Form | BodyMode::Multipart => {
+            let text = match payload {
+                Payload::Stdin => {
+                    let mut text = String::new();
+                    io::stdin().read_to_string(&mut text)
+                        .map_err(|e| format!("failed to read stdin: {}", e))?;
+                    text
+                }
+                _ => fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read {}: {}", path, e))?,
+            };
+            encode_text(mode, &text)
+        }
+    }
+}
+
+// multipart のボディの断片（区切りとヘッダーはバイト列、ファイルの中身はパス）
+enum Segment {
+    Bytes(Vec<u8>),
+    File(PathBuf),
+}
+
+pub struct Multipart {
+    segments: Vec<Segment>,
+}
+
+impl Multipart {
+    // ファイルを開き、断片をつなげた読み出し口と全体の長さを返す（送り直すときは開き直す）
+    pub fn reader(&self) -> Result<(Box<dyn Read + Send>, u64), String> {
+        let mut reader: Box<dyn Read + Send> = Box::new(io::empty());
+        let mut len = 0;
+        for segment in &self.segments {
+            match segment {
+                Segment::Bytes(bytes) => {
+                    len += bytes.len() as u64;
+                    reader = Box::new(reader.chain(io::Cursor::new(bytes.clone())));
+                }
+                Segment::File(path) => {
+                    let file = File::open(path)
+                        .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+                    len += file
+                        .metadata()
+                        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?
+                        .len();
+                    reader = Box::new(reader.chain(file));
+                }
+            }
+        }
+        Ok((reader, len))
+    }
+}
+
+// 128ビットの乱数を含む区切り（ファイルの中身と重なることはまずないので、ファイルは読んで調べない）
+fn boundary() -> Result<String, String> {
+    let mut bytes = [0; 16];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| e.to_string())?;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("----ruzz-boundary-{}", hex))
+}
+
+// Content-Disposition の name と filename の値（RFC 7578 のとおり `"` と改行はパーセントエンコードする）
+fn disposition_value(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+// 1つのパートの中身
+enum Content {
+    Text(String),
+    File(PathBuf),
+}
+
+// メモリ上のヘッダーとテキストに現れない区切り（現れたら作り直す。ファイルは送るときまで開かない）
+fn unused_boundary(
+    parts: &[(String, Content)],
+    mut generate: impl FnMut() -> Result<String, String>,
+) -> Result<String, String> {
+    loop {
+        let boundary = generate()?;
+        let found = parts.iter().any(|(head, content)| {
+            head.contains(&boundary)
+                || match content {
+                    Content::Text(text) => text.contains(&boundary),
+                    Content::File(_) => false,
+                }
+        });
+        if !found {
+            return Ok(boundary);
+        }
+    }
+}
+
+fn multipart(text: &str) -> Result<EncodedBody, String> {
+    let mut parts = Vec::new();
+    for (key, value) in fields(text) {
+        let name = disposition_value(&key);
+        match value.strip_prefix('@') {
+            Some(file) => {
+                let mut split = file.splitn(2, ";type=");
+                let path = PathBuf::from(split.next().unwrap_or("").trim());
+                let mime = match split.next() {
+                    Some(mime) => mime.trim().to_string(),
+                    None => guess_type(&path.to_string_lossy()),
+                };
+                if !path.is_file() {
+                    return Err(format!("{} is not a file", path.display()));
+                }
+                let file_name = path
+                    .file_name()
+                    .map(|n| disposition_value(&n.to_string_lossy()))
+                    .unwrap_or_default();
+                let head = format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+                    name, file_name, mime
+                );
+                parts.push((head, Content::File(path)));
+            }
+            None => {
+                let head = format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name);
+                parts.push((head, Content::Text(value)));
+            }
+        }
+    }
+
+    let boundary = unused_boundary(&parts, boundary)?;
+    let mut segments = Vec::new();
+    let mut bytes = Vec::new();
+    for (head, content) in parts {
+        bytes.extend_from_slice(format!("--{}\r\n{}", boundary, head).as_bytes());
+        match content {
+            Content::Text(text) => bytes.extend_from_slice(text.as_bytes()),
+            Content::File(path) => {
+                segments.push(Segment::Bytes(std::mem::take(&mut bytes)));
+                segments.push(Segment::File(path));
+            }
+        }
+        bytes.extend_from_slice(b"\r\n");
+    }
+    bytes.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    Ok(EncodedBody {
+        content_type: Some(format!("multipart/form-data; boundary={}", boundary)),
+        // ファイルがなければすべてメモリ上にある
+        payload: if segments.is_empty() {
+            Payload::Bytes(bytes)
+        } else {
+            segments.push(Segment::Bytes(bytes));
+            Payload::Multipart(Multipart { segments })
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ruzz-body-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn boundary_of(body: &EncodedBody) -> String {
+        let content_type = body.content_type.as_ref().unwrap();
+        content_type.strip_prefix("multipart/form-data; boundary=").unwrap().to_string()
+    }
+
+    #[test]
+    fn multipart_text_parts_stay_in_memory() {
+        let body = encode(BodyMode::Multipart, "a=1\n# comment\nb=x=y").unwrap();
+        let boundary = boundary_of(&body);
+        let bytes = match body.payload {
+            Payload::Bytes(bytes) => bytes,
+            _ => panic!("expected bytes"),
+        };
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            format!(
+                "--{b}\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n\
+                 --{b}\r\nContent-Disposition: form-data; name=\"b\"\r\n\r\nx=y\r\n--{b}--\r\n",
+                b = boundary
+            )
+        );
+    }
+
+    #[test]
+    fn multipart_files_are_read_when_sent() {
+        let dir = temp_dir("file");
+        let path = dir.join("data.bin");
+        fs::write(&path, [0u8, 1, 2, 255]).unwrap();
+        let body = encode(BodyMode::Multipart, &format!("note=hi\nfile=@{};type=application/x-test", path.display())).unwrap();
+        let boundary = boundary_of(&body);
+        let multipart = match &body.payload {
+            Payload::Multipart(multipart) => multipart,
+            _ => panic!("expected a multipart payload"),
+        };
+        let (mut reader, len) = multipart.reader().unwrap();
+        let mut sent = Vec::new();
+        reader.read_to_end(&mut sent).unwrap();
+        assert_eq!(sent.len() as u64, len);
+
+        let mut expected = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nhi\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"data.bin\"\r\n\
+             Content-Type: application/x-test\r\n\r\n",
+            b = boundary
+        )
+        .into_bytes();
+        expected.extend_from_slice(&[0, 1, 2, 255]);
+        expected.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+        assert_eq!(sent, expected);
+
+        // 送り直すときはファイルを読み直す
+        fs::write(&path, b"new").unwrap();
+        assert!(body.payload.into_bytes().unwrap().windows(3).any(|w| w == b"new"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn multipart_names_are_percent_encoded() {
+        let dir = temp_dir("names");
+        let path = dir.join("say \"hi\".txt");
+        fs::write(&path, "x").unwrap();
+        let body = encode(BodyMode::Multipart, &format!("q\"u\rote=v\nf=@{}", path.display())).unwrap();
+        let bytes = body.payload.into_bytes().unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.contains("Content-Disposition: form-data; name=\"q%22u%0Dote\"\r\n"), "{}", text);
+        assert!(text.contains("name=\"f\"; filename=\"say %22hi%22.txt\"\r\n"), "{}", text);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn boundaries_found_in_a_text_part_are_replaced() {
+        let parts = vec![
+            ("".to_string(), Content::Text("has first inside".to_string())),
+            ("name=\"second\"".to_string(), Content::Text("".to_string())),
+            // ファイルは開かない（存在しなくても調べに行かない）
+            ("".to_string(), Content::File(PathBuf::from("/nonexistent/third"))),
+        ];
+        let mut candidates = vec!["first", "second", "third"].into_iter();
+        let boundary = unused_boundary(&parts, || Ok(candidates.next().unwrap().to_string())).unwrap();
+        assert_eq!(boundary, "third");
+    }
+
+    #[test]
+    fn boundaries_are_long_and_random() {
+        let first = boundary().unwrap();
+        assert!(first.len() >= 32 + "----ruzz-boundary-".len(), "{}", first);
+        assert_ne!(first, boundary().unwrap());
+    }
+}
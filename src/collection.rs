@@ -0,0 +1,200 @@
+use super::app;
+use super::json_path;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// 保存済みリクエスト
+//
+// 各項目は画面のペインと同じ書式で保持する
+//   params:  "key=value"
+//   headers: "Name:value"
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SavedRequest {
+    pub name: String,
+    // フォルダの階層（例: "Users/Admin"）
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub folder: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub headers: Vec<String>,
+    // none（空）, basic, bearer, apikey, digest
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub auth_mode: String,
+    // 認証方式ごとの設定（例: "username=alice"）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub auth: Vec<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body: String,
+    // raw（空）, json, form, multipart, graphql
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body_mode: String,
+    // graphql の変数（JSON のオブジェクト）
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub graphql_variables: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<String>,
+    // クライアントの設定の上書き（例: "timeout=5"）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settings: Vec<String>,
+    // レスポンスから変数を取り出す（例: "token = json $.token"）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub captures: Vec<String>,
+}
+
+impl SavedRequest {
+    // 一覧に表示する名前
+    pub fn title(&self) -> String {
+        if self.folder.is_empty() {
+            format!("{} {}", self.method, self.name)
+        } else {
+            format!("{} {}/{}", self.method, self.folder, self.name)
+        }
+    }
+}
+
+// 保存済みリクエストの集まり
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Collection {
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub stop_on_failure: bool,
+    // 環境の変数より先に設定される変数
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
+    #[serde(default)]
+    pub requests: Vec<SavedRequest>,
+}
+
+impl Collection {
+    pub fn load(path: &Path) -> Result<Collection, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text).map_err(|e| format!("invalid collection {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+}
+
+// 環境ごとの変数
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Environment {
+    pub name: String,
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
+    // クライアントの設定の上書き（例: "proxy=http://proxy.staging:3128"）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settings: Vec<String>,
+}
+
+impl Environment {
+    pub fn load(path: &Path) -> Result<Environment, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| format!("invalid environment {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    // `--env` の値から環境ファイルを探す
+    //
+    // ファイルパスとして存在すればそれを、なければコレクションと同じディレクトリの
+    // `<name>.env.json` を読み込む
+    pub fn find(collection_path: &Path, name: &str) -> Result<Environment, String> {
+        let path = PathBuf::from(name);
+        if path.is_file() {
+            return Environment::load(&path);
+        }
+
+        let dir = collection_path.parent().unwrap_or_else(|| Path::new("."));
+        Environment::load(&dir.join(format!("{}.env.json", name)))
+    }
+}
+
+// `{{name}}` を変数の値に置き換える（未定義の変数はそのまま残す）
+pub fn render(text: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut rendered = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start + 2..].find("}}") {
+            Some(end) => start + 2 + end,
+            None => break,
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..end].trim();
+        match variables.get(name) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+// 直近のレスポンスから変数を取り出す
+//
+//   name = status
+//   name = header X-Request-Id
+//   name = json $.data.token
+//   name = body
+pub fn capture(expr: &str, app: &app::App) -> Result<(String, String), String> {
+    let mut parts = expr.splitn(2, '=');
+    let name = parts.next().unwrap_or("").trim();
+    let source = parts.next().unwrap_or("").trim();
+    if name.is_empty() || source.is_empty() {
+        return Err(format!("invalid capture: {}", expr));
+    }
+
+    let (kind, arg) = match source.find(' ') {
+        Some(i) => (&source[..i], source[i + 1..].trim()),
+        None => (source, ""),
+    };
+    let value = match kind {
+        "status" => app
+            .response_status_code()
+            .map(|code| code.to_string())
+            .ok_or_else(|| "no response".to_string())?,
+        "header" => app
+            .response_headers()
+            .get(arg)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+            .ok_or_else(|| format!("header {} not found", arg))?,
+        "json" => {
+            let body: serde_json::Value = serde_json::from_str(app.response_body_text())
+                .map_err(|e| format!("body is not json: {}", e))?;
+            match json_path::lookup(&body, arg)? {
+                Some(serde_json::Value::String(s)) => s.to_string(),
+                Some(v) => v.to_string(),
+                None => return Err(format!("{} not found", arg)),
+            }
+        }
+        "body" => app.response_body_text().to_string(),
+        _ => return Err(format!("unknown capture source: {}", source)),
+    };
+
+    Ok((name.to_string(), value))
+}
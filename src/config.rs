@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use super::settings::ClientSettings;
+
+const CONFIG_FILE: &str = "config.json";
+
+// 設定やクッキーを置くディレクトリ（$XDG_CONFIG_HOME/ruzz か ~/.config/ruzz）
+pub fn dir() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("ruzz")),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("ruzz")),
+    }
+}
+
+// 設定ディレクトリ内のファイルのパス
+pub fn path(name: &str) -> Option<PathBuf> {
+    dir().map(|dir| dir.join(name))
+}
+
+// 設定ファイル（config.json）
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Config {
+    // クライアントの既定の設定
+    #[serde(default)]
+    pub client: ClientSettings,
+}
+
+impl Config {
+    // ファイルがなければ既定値
+    pub fn load() -> Result<Config, String> {
+        let path = match path(CONFIG_FILE) {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Config::default()),
+        };
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let config: Config =
+            serde_json::from_str(&text).map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+        // Settings ペインと同じ検証をする（タイムアウトの秒数など）
+        ClientSettings::parse(&config.client.to_text())
+            .map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let path = path(CONFIG_FILE).ok_or_else(|| "cannot find the config directory".to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeSet;
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::mem;
+use std::net::SocketAddr;
+#[cfg(target_os = "linux")]
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+#[cfg(target_os = "linux")]
+use std::os::unix::io::RawFd;
+
+// プロセスが開いている TCP 接続（ローカルとリモートのアドレスの組）
+//
+// reqwest は接続を再利用したかを返さないので、送信の前後の接続を比べて推測する。
+// 同じリモートへの接続を別のスレッドが同時に開閉すると外れることがある
+pub struct Sockets {
+    pairs: BTreeSet<(SocketAddr, SocketAddr)>,
+}
+
+// 送信に使った接続
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Connection {
+    New(SocketAddr),
+    Reused(SocketAddr),
+}
+
+impl Connection {
+    // 接続先のアドレスを置き換える（プロセス内の中継を経由したとき）
+    pub fn to(self, addr: SocketAddr) -> Connection {
+        match self {
+            Connection::New(_) => Connection::New(addr),
+            Connection::Reused(_) => Connection::Reused(addr),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Connection::New(addr) | Connection::Reused(addr) => self.describe_to(&addr.to_string()),
+        }
+    }
+
+    // 接続先をアドレスの代わりに target と表示する
+    pub fn describe_to(&self, target: &str) -> String {
+        match self {
+            Connection::New(_) => format!("new connection to {}", target),
+            Connection::Reused(_) => format!("reused connection to {}", target),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn to_socket_addr(storage: &libc::sockaddr_storage) -> Option<SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Some(SocketAddr::V4(SocketAddrV4::new(ip, u16::from_be(addr.sin_port))))
+        }
+        libc::AF_INET6 => {
+            let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+            Some(SocketAddr::V6(SocketAddrV6::new(
+                ip,
+                u16::from_be(addr.sin6_port),
+                addr.sin6_flowinfo,
+                addr.sin6_scope_id,
+            )))
+        }
+        _ => None,
+    }
+}
+
+// ファイルディスクリプターが接続済みのソケットならアドレスの組を返す
+#[cfg(target_os = "linux")]
+fn socket_pair(fd: RawFd) -> Option<(SocketAddr, SocketAddr)> {
+    let address = |peer: bool| -> Option<SocketAddr> {
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let result = unsafe {
+            let addr = &mut storage as *mut _ as *mut libc::sockaddr;
+            if peer {
+                libc::getpeername(fd, addr, &mut len)
+            } else {
+                libc::getsockname(fd, addr, &mut len)
+            }
+        };
+        if result != 0 {
+            return None;
+        }
+        to_socket_addr(&storage)
+    };
+    let peer = address(true)?;
+    let local = address(false)?;
+    Some((local, peer))
+}
+
+// アドレスの組が一致するソケットを shutdown する（読み込み中の read を終わらせる。fd は閉じない）
+#[cfg(target_os = "linux")]
+pub fn shutdown(pair: (SocketAddr, SocketAddr)) -> bool {
+    let fds = match fs::read_dir("/dev/fd") {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str()?.parse::<RawFd>().ok())
+            .collect::<Vec<RawFd>>(),
+        Err(_) => return false,
+    };
+    fds.into_iter()
+        .filter(|fd| socket_pair(*fd) == Some(pair))
+        .any(|fd| unsafe { libc::shutdown(fd, libc::SHUT_RDWR) } == 0)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn shutdown(_pair: (SocketAddr, SocketAddr)) -> bool {
+    false
+}
+
+impl Sockets {
+    // /dev/fd のソケットを調べる（Linux 以外では調べられないので None）
+    #[cfg(target_os = "linux")]
+    pub fn snapshot() -> Option<Sockets> {
+        let pairs = fs::read_dir("/dev/fd")
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str()?.parse::<RawFd>().ok())
+            .filter_map(socket_pair)
+            .collect();
+        Some(Sockets { pairs })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn snapshot() -> Option<Sockets> {
+        None
+    }
+
+    // リモートへの接続が1つだけならそのアドレスの組（ローカル、リモート）
+    pub fn only_pair_to(&self, remote: SocketAddr) -> Option<(SocketAddr, SocketAddr)> {
+        let mut pairs = self.pairs.iter().filter(|pair| pair.1 == remote);
+        match (pairs.next(), pairs.next()) {
+            (Some(pair), None) => Some(*pair),
+            _ => None,
+        }
+    }
+
+    // 送信後の接続と比べて、リモートへの接続が新しく開かれたかを返す
+    pub fn connection(&self, after: &Sockets, remote: SocketAddr) -> Option<Connection> {
+        if after
+            .pairs
+            .iter()
+            .any(|pair| pair.1 == remote && !self.pairs.contains(pair))
+        {
+            return Some(Connection::New(remote));
+        }
+        if self.pairs.iter().any(|pair| pair.1 == remote) {
+            return Some(Connection::Reused(remote));
+        }
+        None
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn snapshots_tell_new_and_reused_connections_apart() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let remote = listener.local_addr().unwrap();
+        let before = Sockets::snapshot().unwrap();
+        assert_eq!(before.connection(&Sockets::snapshot().unwrap(), remote), None);
+
+        let _stream = TcpStream::connect(remote).unwrap();
+        let after = Sockets::snapshot().unwrap();
+        assert_eq!(before.connection(&after, remote), Some(Connection::New(remote)));
+        assert_eq!(after.connection(&Sockets::snapshot().unwrap(), remote), Some(Connection::Reused(remote)));
+    }
+}
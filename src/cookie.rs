@@ -0,0 +1,505 @@
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use super::config;
+
+// 保存されたクッキー
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    // 先頭の `.` は付けない
+    pub domain: String,
+    // Domain 属性がなければ送信元のホストだけに送る
+    #[serde(default)]
+    pub host_only: bool,
+    pub path: String,
+    // None はセッションクッキー
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub same_site: String,
+}
+
+impl Cookie {
+    fn is_expired(&self) -> bool {
+        self.expires.map(|e| e <= Utc::now()).unwrap_or(false)
+    }
+
+    fn domain_matches(&self, host: &str) -> bool {
+        if self.host_only {
+            return host == self.domain;
+        }
+        host == self.domain || host.ends_with(&format!(".{}", self.domain))
+    }
+
+    fn path_matches(&self, path: &str) -> bool {
+        path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')))
+    }
+
+    // クッキーペインに表示する1行
+    pub fn summary(&self) -> String {
+        let mut flags = Vec::new();
+        if self.secure {
+            flags.push("Secure".to_string());
+        }
+        if self.http_only {
+            flags.push("HttpOnly".to_string());
+        }
+        if !self.same_site.is_empty() {
+            flags.push(format!("SameSite={}", self.same_site));
+        }
+        let expires = match self.expires {
+            Some(expires) => expires.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None => "session".to_string(),
+        };
+        format!(
+            "{}{} {} {}={} [{}] {}",
+            if self.host_only { "" } else { "." },
+            self.domain,
+            self.path,
+            self.name,
+            self.value,
+            expires,
+            flags.join(" ")
+        )
+        .trim_end()
+        .to_string()
+    }
+}
+
+// 送信元ごとのクッキーを保持し、環境ごとのファイルに保存する
+#[derive(Clone, Debug, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+    path: Option<PathBuf>,
+}
+
+// `Path` 属性がないときの既定のパス（RFC 6265 5.1.4）
+fn default_path(url: &url::Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+// Expires 属性の日付（RFC 1123 と古い形式）
+fn parse_expires(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc2822(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    let value = value.trim_end_matches(" GMT").trim_end_matches(" UTC");
+    ["%a, %d-%b-%Y %H:%M:%S", "%A, %d-%b-%y %H:%M:%S", "%a %b %e %H:%M:%S %Y", "%a, %d %b %Y %H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|date| Utc.from_utc_datetime(&date))
+}
+
+// Max-Age の期限（RFC 6265 のとおり、0 以下なら期限切れにし、大きすぎれば最も遅い日時にする）
+fn max_age_expiry(seconds: i64) -> DateTime<Utc> {
+    let latest = Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap();
+    if seconds <= 0 {
+        return DateTime::<Utc>::UNIX_EPOCH;
+    }
+    Duration::try_seconds(seconds)
+        .and_then(|duration| Utc::now().checked_add_signed(duration))
+        .filter(|expires| *expires < latest)
+        .unwrap_or(latest)
+}
+
+// 登録できるドメインの上にある公開サフィックス（完全な Public Suffix List は持たないので、
+// 1ラベルのドメインとよく使われる2ラベル以上のものだけ）
+const PUBLIC_SUFFIXES: [&str; 24] = [
+    "co.uk", "org.uk", "ac.uk", "gov.uk", "co.jp", "ne.jp", "or.jp", "ac.jp", "go.jp", "com.au",
+    "net.au", "org.au", "co.nz", "com.br", "com.cn", "com.tw", "co.kr", "co.in", "github.io",
+    "herokuapp.com", "appspot.com", "netlify.app", "vercel.app", "pages.dev",
+];
+
+fn is_public_suffix(domain: &str) -> bool {
+    !domain.contains('.') || PUBLIC_SUFFIXES.contains(&domain)
+}
+
+fn is_ip(host: &str) -> bool {
+    host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().is_ok()
+}
+
+// `Set-Cookie` の値を解釈する
+fn parse_set_cookie(value: &str, url: &url::Url) -> Option<Cookie> {
+    let host = url.host_str()?.to_lowercase();
+    let mut parts = value.split(';');
+    let mut pair = parts.next()?.splitn(2, '=');
+    let name = pair.next()?.trim().to_string();
+    let value = pair.next()?.trim().trim_matches('"').to_string();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name,
+        value,
+        domain: host.to_string(),
+        host_only: true,
+        path: default_path(url),
+        expires: None,
+        secure: false,
+        http_only: false,
+        same_site: "".to_string(),
+    };
+    let mut max_age = None;
+
+    for attribute in parts {
+        let mut attribute = attribute.splitn(2, '=');
+        let key = attribute.next().unwrap_or("").trim().to_lowercase();
+        let value = attribute.next().unwrap_or("").trim();
+        match key.as_str() {
+            "expires" => {
+                if let Some(expires) = parse_expires(value) {
+                    cookie.expires = Some(expires);
+                }
+            }
+            "max-age" => max_age = value.parse::<i64>().ok(),
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_lowercase();
+                // 送信元そのものが IP アドレスや公開サフィックスなら、送信元だけのクッキーにする（RFC 6265 5.3）
+                if domain == host && (is_ip(&host) || is_public_suffix(&domain)) {
+                    continue;
+                }
+                // 送信元と関係のないドメイン、IP アドレス、公開サフィックスのクッキーは受け付けない
+                if is_ip(&host) || is_public_suffix(&domain) {
+                    return None;
+                }
+                if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "samesite" => cookie.same_site = value.to_string(),
+            _ => {}
+        }
+    }
+    // Max-Age は Expires より優先する
+    if let Some(seconds) = max_age {
+        cookie.expires = Some(max_age_expiry(seconds));
+    }
+    Some(cookie)
+}
+
+impl CookieJar {
+    // 環境ごとのファイルから読み込む（ファイルがなければ空）
+    pub fn open(name: &str) -> Result<CookieJar, String> {
+        let name = name.replace(['/', '\\'], "_");
+        let path = config::path(&format!("cookies/{}.json", name))
+            .ok_or_else(|| "cannot find the config directory".to_string())?;
+        let mut jar = CookieJar {
+            cookies: Vec::new(),
+            path: Some(path.to_path_buf()),
+        };
+        if path.exists() {
+            let text = fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+            jar.cookies = serde_json::from_str(&text)
+                .map_err(|e| format!("invalid cookie jar {}: {}", path.display(), e))?;
+            jar.cookies.retain(|c| !c.is_expired());
+        }
+        Ok(jar)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn cookies(&self) -> &Vec<Cookie> {
+        &self.cookies
+    }
+
+    // 保存先が決まっていれば書き出す
+    pub fn save(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(&self.cookies).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    // レスポンスの `Set-Cookie` を取り込む（変化があれば true）
+    pub fn store(&mut self, url: &url::Url, headers: &HeaderMap) -> bool {
+        let mut changed = false;
+        for value in headers.get_all(SET_COOKIE).iter() {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            let cookie = match parse_set_cookie(value, url) {
+                Some(cookie) => cookie,
+                None => continue,
+            };
+            self.cookies.retain(|c| {
+                !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path)
+            });
+            // 期限切れのクッキーは削除の指示
+            if !cookie.is_expired() {
+                self.cookies.push(cookie);
+            }
+            changed = true;
+        }
+        if changed {
+            self.sort();
+        }
+        changed
+    }
+
+    // リクエストに付ける `Cookie` ヘッダーの値
+    pub fn header_for(&self, url: &url::Url) -> Option<String> {
+        let host = url.host_str()?.to_lowercase();
+        let path = if url.path().is_empty() { "/" } else { url.path() };
+        let secure = url.scheme() == "https" || url.scheme() == "wss";
+
+        let mut cookies: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| !c.is_expired())
+            .filter(|c| c.domain_matches(&host) && c.path_matches(path))
+            .filter(|c| secure || !c.secure)
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+        // パスが長いものから送る
+        cookies.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+        Some(
+            cookies
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect::<Vec<String>>()
+                .join("; "),
+        )
+    }
+
+    fn sort(&mut self) {
+        self.cookies.sort_by(|a, b| {
+            (&a.domain, &a.path, &a.name).cmp(&(&b.domain, &b.path, &b.name))
+        });
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cookie> {
+        if index < self.cookies.len() {
+            Some(self.cookies.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+
+    // Netscape の cookies.txt 形式（エディターでの編集用）
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            "# domain\tinclude subdomains\tpath\tsecure\texpires\tname\tvalue".to_string(),
+            "# `#HttpOnly_` prefix marks HttpOnly cookies, expires 0 means a session cookie".to_string(),
+        ];
+        for c in &self.cookies {
+            lines.push(format!(
+                "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                if c.http_only { "#HttpOnly_" } else { "" },
+                if c.host_only { "" } else { "." },
+                c.domain,
+                if c.host_only { "FALSE" } else { "TRUE" },
+                c.path,
+                if c.secure { "TRUE" } else { "FALSE" },
+                c.expires.map(|e| e.timestamp()).unwrap_or(0),
+                c.name,
+                c.value
+            ));
+        }
+        lines.push("".to_string());
+        lines.join("\n")
+    }
+
+    // cookies.txt 形式の内容で置き換える（SameSite は同じクッキーのものを引き継ぐ）
+    pub fn set_text(&mut self, text: &str) -> Result<(), String> {
+        let mut cookies = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let (http_only, line) = match line.strip_prefix("#HttpOnly_") {
+                Some(line) => (true, line),
+                None => (false, line),
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 7 {
+                return Err(format!("line {}: expected 7 tab separated fields", i + 1));
+            }
+            let expires = fields[4]
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| format!("line {}: invalid expires {}", i + 1, fields[4]))?;
+            let mut cookie = Cookie {
+                name: fields[5].to_string(),
+                value: fields[6..].join("\t"),
+                domain: fields[0].trim_start_matches('.').to_lowercase(),
+                host_only: !fields[1].eq_ignore_ascii_case("TRUE"),
+                path: fields[2].to_string(),
+                expires: if expires == 0 {
+                    None
+                } else {
+                    Utc.timestamp_opt(expires, 0).single()
+                },
+                secure: fields[3].eq_ignore_ascii_case("TRUE"),
+                http_only,
+                same_site: "".to_string(),
+            };
+            if let Some(old) = self.cookies.iter().find(|c| {
+                c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+            }) {
+                cookie.same_site = old.same_site.to_string();
+            }
+            cookies.push(cookie);
+        }
+        self.cookies = cookies;
+        self.sort();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_max_age_is_clamped() {
+        let url = url::Url::parse("http://example.com/").unwrap();
+        let cookie = parse_set_cookie("a=b; Max-Age=99999999999999999", &url).unwrap();
+        assert_eq!(cookie.expires, Some(Utc.with_ymd_and_hms(9999, 12, 31, 23, 59, 59).unwrap()));
+        let cookie = parse_set_cookie("a=b; Max-Age=-99999999999999999", &url).unwrap();
+        assert!(cookie.expires.unwrap() < Utc::now());
+        let cookie = parse_set_cookie("a=b; Max-Age=60", &url).unwrap();
+        assert!(cookie.expires.unwrap() > Utc::now());
+    }
+
+    fn url(url: &str) -> url::Url {
+        url::Url::parse(url).unwrap()
+    }
+
+    fn jar(url: &url::Url, set_cookies: &[&str]) -> CookieJar {
+        let mut headers = HeaderMap::new();
+        for value in set_cookies {
+            headers.append(SET_COOKIE, value.parse().unwrap());
+        }
+        let mut jar = CookieJar::default();
+        jar.store(url, &headers);
+        jar
+    }
+
+    #[test]
+    fn domain_attribute_must_cover_the_host() {
+        let host = url("https://www.example.com/");
+        let cookie = parse_set_cookie("a=b; Domain=.Example.com", &host).unwrap();
+        assert_eq!((cookie.domain.as_str(), cookie.host_only), ("example.com", false));
+        let cookie = parse_set_cookie("a=b", &host).unwrap();
+        assert_eq!((cookie.domain.as_str(), cookie.host_only), ("www.example.com", true));
+
+        assert!(parse_set_cookie("a=b; Domain=api.example.com", &host).is_none());
+        assert!(parse_set_cookie("a=b; Domain=other.com", &host).is_none());
+        assert!(parse_set_cookie("a=b; Domain=ample.com", &host).is_none());
+    }
+
+    #[test]
+    fn domain_attribute_is_rejected_for_ips_and_public_suffixes() {
+        let ip = url("http://192.168.0.1/");
+        assert!(parse_set_cookie("a=b; Domain=0.1", &ip).is_none());
+        assert!(parse_set_cookie("a=b; Domain=168.0.1", &ip).is_none());
+        let cookie = parse_set_cookie("a=b; Domain=192.168.0.1", &ip).unwrap();
+        assert!(cookie.host_only);
+        let cookie = parse_set_cookie("a=b; Domain=[::1]", &url("http://[::1]/")).unwrap();
+        assert!(cookie.host_only);
+
+        assert!(parse_set_cookie("a=b; Domain=com", &url("https://example.com/")).is_none());
+        assert!(parse_set_cookie("a=b; Domain=co.uk", &url("https://shop.co.uk/")).is_none());
+        assert!(parse_set_cookie("a=b; Domain=github.io", &url("https://me.github.io/")).is_none());
+        let cookie = parse_set_cookie("a=b; Domain=co.uk", &url("https://shop.example.co.uk/"));
+        assert!(cookie.is_none());
+        let cookie = parse_set_cookie("a=b; Domain=example.co.uk", &url("https://shop.example.co.uk/")).unwrap();
+        assert!(!cookie.host_only);
+
+        // 1ラベルのホストは自分自身だけ
+        let cookie = parse_set_cookie("a=b; Domain=localhost", &url("http://localhost:8080/")).unwrap();
+        assert_eq!((cookie.domain.as_str(), cookie.host_only), ("localhost", true));
+    }
+
+    #[test]
+    fn path_matches_whole_segments() {
+        let cookie = parse_set_cookie("a=b; Path=/a", &url("http://example.com/")).unwrap();
+        assert!(cookie.path_matches("/a"));
+        assert!(cookie.path_matches("/a/b"));
+        assert!(!cookie.path_matches("/ab"));
+        assert!(!cookie.path_matches("/"));
+        let cookie = parse_set_cookie("a=b; Path=/a/", &url("http://example.com/")).unwrap();
+        assert!(cookie.path_matches("/a/b"));
+        // Path 属性がなければリクエストのディレクトリ
+        let cookie = parse_set_cookie("a=b", &url("http://example.com/a/b/c")).unwrap();
+        assert_eq!(cookie.path, "/a/b");
+        let cookie = parse_set_cookie("a=b", &url("http://example.com/c")).unwrap();
+        assert_eq!(cookie.path, "/");
+    }
+
+    #[test]
+    fn header_for_filters_by_domain_path_secure_and_expiry() {
+        let origin = url("https://www.example.com/");
+        let jar = jar(
+            &origin,
+            &[
+                "host=1",
+                "shared=2; Domain=example.com",
+                "deep=3; Path=/api/v1",
+                "secure=4; Secure",
+                "old=5; Expires=Thu, 01 Jan 1970 00:00:00 GMT",
+            ],
+        );
+        assert_eq!(jar.cookies().len(), 4);
+
+        // パスの長いものが先、同じ長さならジャーの順（ドメイン、パス、名前）
+
+        assert_eq!(
+            jar.header_for(&url("https://www.example.com/api/v1/items")).unwrap(),
+            "deep=3; shared=2; host=1; secure=4"
+        );
+        assert_eq!(jar.header_for(&url("http://www.example.com/")).unwrap(), "shared=2; host=1");
+        assert_eq!(jar.header_for(&url("https://api.example.com/")).unwrap(), "shared=2");
+        assert_eq!(jar.header_for(&url("wss://www.example.com/")).unwrap(), "shared=2; host=1; secure=4");
+        assert_eq!(jar.header_for(&url("https://example.org/")), None);
+    }
+
+    #[test]
+    fn expired_set_cookie_removes_the_cookie() {
+        let origin = url("http://example.com/");
+        let mut jar = jar(&origin, &["a=1", "b=2"]);
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, "a=; Max-Age=0".parse().unwrap());
+        assert!(jar.store(&origin, &headers));
+        assert_eq!(jar.header_for(&origin).unwrap(), "b=2");
+        assert!(!jar.store(&origin, &HeaderMap::new()));
+    }
+}
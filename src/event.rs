@@ -1,4 +1,5 @@
-use std::io;
+use std::io::{self, Read};
+use std::os::unix::io::AsRawFd;
 use termion::event::Key;
 
 use std::sync::mpsc;
@@ -18,10 +19,12 @@ use std::sync::{
     Arc,
 };
 
+#[allow(dead_code)]
 pub struct Events {
     rx: mpsc::Receiver<Event<Key>>,
     input_handle: thread::JoinHandle<()>,
     ignore_exit_key: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
     tick_handle: thread::JoinHandle<()>,
 }
 
@@ -46,13 +49,27 @@ impl Events {
     pub fn with_config(config: Config) -> Events {
         let (tx, rx) = mpsc::channel();
         let ignore_exit_key = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
         let input_handle = {
             let tx = tx.clone();
-            let ignore_exit_key = ignore_exit_key.clone();
+            let paused = paused.clone();
             thread::spawn(move || {
                 let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    if let Ok(key) = evt {
+                let mut buf = [0; 1024];
+                loop {
+                    // 外部プログラムの実行中は標準入力を読まない
+                    if paused.load(Ordering::SeqCst) {
+                        thread::sleep(config.tick_rate);
+                        continue;
+                    }
+                    if !readable(&stdin, config.tick_rate) || paused.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    let n = match stdin.lock().read(&mut buf) {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => n,
+                    };
+                    for key in (&buf[..n]).keys().flatten() {
                         if let Err(err) = tx.send(Event::Input(key)) {
                             eprintln!("{}", err);
                             return;
@@ -72,6 +89,7 @@ impl Events {
         Events {
             rx,
             ignore_exit_key,
+            paused,
             input_handle,
             tick_handle,
         }
@@ -80,4 +98,28 @@ impl Events {
     pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
         self.rx.recv()
     }
+
+    // キー入力の読み取りを止める
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        // 読み取り中のキー入力を待つ
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // キー入力の読み取りを再開する（止めている間に届いたイベントは捨てる）
+    pub fn resume(&self) {
+        while self.rx.try_recv().is_ok() {}
+        self.paused.store(false, Ordering::SeqCst);
+    }
+}
+
+// 標準入力が読み取れるようになるまで待つ（閉じられた場合も true）
+fn readable(stdin: &io::Stdin, timeout: Duration) -> bool {
+    let mut fds = libc::pollfd {
+        fd: stdin.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let result = unsafe { libc::poll(&mut fds, 1, timeout.as_millis() as libc::c_int) };
+    result > 0
 }
@@ -0,0 +1,63 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{self, Command, Stdio};
+
+// 外部プログラムで開く内容
+pub enum External {
+    // 編集してペインに読み込み直す（拡張子はエディターの言語判定用）
+    Editor(String, &'static str),
+    // 表示するだけ
+    Pager(String),
+}
+
+// `$EDITOR` や `$PAGER` の値を引数ごとに分ける（`code --wait` のような指定がある）
+fn command(var: &str, default: &str) -> Result<Command, String> {
+    let value = env::var(var)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| default.to_string());
+    let mut parts = value.split_whitespace();
+    let program = parts.next().ok_or_else(|| format!("${} is empty", var))?;
+    let mut command = Command::new(program);
+    command.args(parts);
+    Ok(command)
+}
+
+// 一時ファイルに書き出して `$EDITOR` で開き、保存された内容を返す
+pub fn edit(text: &str, extension: &str) -> Result<String, String> {
+    let path = env::temp_dir().join(format!("ruzz-{}.{}", process::id(), extension));
+    fs::write(&path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+
+    let status = command("EDITOR", "vi")?
+        .arg(&path)
+        .status()
+        .map_err(|e| format!("failed to start editor: {}", e));
+    let edited = fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e));
+    let _ = fs::remove_file(&path);
+
+    match status? {
+        status if status.success() => {}
+        status => return Err(format!("editor exited with {}", status)),
+    }
+    // エディターが末尾に付ける改行は取り除く
+    let edited = edited?;
+    Ok(edited.strip_suffix('\n').unwrap_or(&edited).to_string())
+}
+
+// `$PAGER` の標準入力に流して表示する
+pub fn page(text: &str) -> Result<(), String> {
+    let mut child = command("PAGER", "less")?
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to start pager: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // ページャーを途中で閉じると書き込みに失敗するが問題ない
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child
+        .wait()
+        .map(|_| ())
+        .map_err(|e| format!("failed to wait for pager: {}", e))
+}
@@ -0,0 +1,1369 @@
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+// スキーマを取得するイントロスペクションのクエリ
+pub const INTROSPECTION_QUERY: &str = "query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types {
+      kind
+      name
+      fields(includeDeprecated: true) {
+        name
+        args { name defaultValue type { ...TypeRef } }
+        type { ...TypeRef }
+      }
+      inputFields { name defaultValue type { ...TypeRef } }
+      enumValues(includeDeprecated: true) { name }
+      possibleTypes { name }
+    }
+  }
+}
+
+fragment TypeRef on __Type {
+  kind name
+  ofType { kind name ofType { kind name ofType { kind name ofType { kind name
+    ofType { kind name ofType { kind name ofType { kind name } } } } } } }
+}";
+
+// 補完の候補として表示する最大数
+const MAX_CANDIDATES: usize = 8;
+
+// クエリと変数ペインの内容を標準の JSON のボディにする
+pub fn encode(query: &str, variables: &str) -> Result<Vec<u8>, String> {
+    let mut body = Map::new();
+    body.insert("query".to_string(), Value::String(query.to_string()));
+    let variables = parse_variables(variables)?;
+    if !variables.is_empty() {
+        body.insert("variables".to_string(), Value::Object(variables));
+    }
+    // 名前付きの操作が複数あれば最初のものを実行する
+    let names: Vec<String> = parse(query)
+        .map(|document| document.operations.into_iter().filter_map(|o| o.name).collect())
+        .unwrap_or_default();
+    if names.len() > 1 {
+        body.insert("operationName".to_string(), Value::String(names[0].to_string()));
+    }
+    serde_json::to_vec(&Value::Object(body)).map_err(|e| e.to_string())
+}
+
+// 変数ペインは JSON のオブジェクト（空なら変数なし）
+pub fn parse_variables(text: &str) -> Result<Map<String, Value>, String> {
+    if text.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str(text) {
+        Ok(Value::Object(variables)) => Ok(variables),
+        Ok(_) => Err("GraphQL variables must be a JSON object".to_string()),
+        Err(e) => Err(format!("invalid GraphQL variables: {}", e)),
+    }
+}
+
+pub fn introspection_body() -> Vec<u8> {
+    json!({ "query": INTROSPECTION_QUERY }).to_string().into_bytes()
+}
+
+// 引数と入力フィールド
+#[derive(Clone, Debug)]
+pub struct InputValue {
+    pub name: String,
+    // `[ID!]!` のような型の表記
+    pub ty: String,
+    pub default: Option<String>,
+}
+
+impl InputValue {
+    fn required(&self) -> bool {
+        self.ty.ends_with('!') && self.default.is_none()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: String,
+    pub args: Vec<InputValue>,
+    pub ty: String,
+}
+
+impl Field {
+    fn signature(&self) -> String {
+        if self.args.is_empty() {
+            return format!("{}: {}", self.name, self.ty);
+        }
+        let args: Vec<String> = self
+            .args
+            .iter()
+            .map(|arg| format!("{}: {}", arg.name, arg.ty))
+            .collect();
+        format!("{}({}): {}", self.name, args.join(", "), self.ty)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Type {
+    // OBJECT, INTERFACE, UNION, SCALAR, ENUM, INPUT_OBJECT
+    pub kind: String,
+    pub fields: Vec<Field>,
+    pub input_fields: Vec<InputValue>,
+    pub enum_values: Vec<String>,
+    pub possible_types: Vec<String>,
+}
+
+impl Type {
+    fn is_leaf(&self) -> bool {
+        self.kind == "SCALAR" || self.kind == "ENUM"
+    }
+
+    fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+}
+
+// イントロスペクションで取得したスキーマ
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    pub query_type: Option<String>,
+    pub mutation_type: Option<String>,
+    pub subscription_type: Option<String>,
+    pub types: BTreeMap<String, Type>,
+}
+
+// `{kind, name, ofType}` を `[User!]!` のような表記にする
+fn type_ref(value: &Value) -> String {
+    match value["kind"].as_str() {
+        Some("NON_NULL") => format!("{}!", type_ref(&value["ofType"])),
+        Some("LIST") => format!("[{}]", type_ref(&value["ofType"])),
+        _ => value["name"].as_str().unwrap_or("?").to_string(),
+    }
+}
+
+// `[User!]!` から `User` を取り出す
+fn named_type(ty: &str) -> &str {
+    ty.trim_matches(|c| c == '[' || c == ']' || c == '!')
+}
+
+fn input_values(value: &Value) -> Vec<InputValue> {
+    value
+        .as_array()
+        .map(|values| {
+            values
+                .iter()
+                .map(|v| InputValue {
+                    name: v["name"].as_str().unwrap_or("").to_string(),
+                    ty: type_ref(&v["type"]),
+                    default: v["defaultValue"].as_str().map(|d| d.to_string()),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn names(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(|v| v["name"].as_str().map(|n| n.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Schema {
+    // イントロスペクションのレスポンスから読み込む
+    pub fn from_response(body: &str) -> Result<Schema, String> {
+        let value: Value =
+            serde_json::from_str(body).map_err(|e| format!("the schema response is not JSON: {}", e))?;
+        let schema = &value["data"]["__schema"];
+        if !schema.is_object() {
+            let message = value["errors"][0]["message"]
+                .as_str()
+                .unwrap_or("no data.__schema in the response");
+            return Err(format!("introspection failed: {}", message));
+        }
+        let mut types = BTreeMap::new();
+        for ty in schema["types"].as_array().into_iter().flatten() {
+            let name = match ty["name"].as_str() {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let fields = ty["fields"]
+                .as_array()
+                .map(|fields| {
+                    fields
+                        .iter()
+                        .map(|f| Field {
+                            name: f["name"].as_str().unwrap_or("").to_string(),
+                            args: input_values(&f["args"]),
+                            ty: type_ref(&f["type"]),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            types.insert(
+                name,
+                Type {
+                    kind: ty["kind"].as_str().unwrap_or("").to_string(),
+                    fields,
+                    input_fields: input_values(&ty["inputFields"]),
+                    enum_values: names(&ty["enumValues"]),
+                    possible_types: names(&ty["possibleTypes"]),
+                },
+            );
+        }
+        let root = |key: &str| schema[key]["name"].as_str().map(|n| n.to_string());
+        Ok(Schema {
+            query_type: root("queryType"),
+            mutation_type: root("mutationType"),
+            subscription_type: root("subscriptionType"),
+            types,
+        })
+    }
+
+    fn root(&self, operation: &str) -> Option<&String> {
+        match operation {
+            "mutation" => self.mutation_type.as_ref(),
+            "subscription" => self.subscription_type.as_ref(),
+            _ => self.query_type.as_ref(),
+        }
+    }
+
+    // 親の型のフィールドの型の名前
+    fn field_type(&self, parent: &str, field: &str) -> Option<String> {
+        if field == "__typename" {
+            return Some("String".to_string());
+        }
+        let field = self.types.get(parent)?.field(field)?;
+        Some(named_type(&field.ty).to_string())
+    }
+}
+
+// クエリの字句
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    // ! $ & ( ) : = @ [ ] { | }
+    Punct(char),
+    Spread,
+    // 数値と文字列
+    Value(String),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Name(name) => format!("'{}'", name),
+            Token::Punct(c) => format!("'{}'", c),
+            Token::Spread => "'...'".to_string(),
+            Token::Value(value) => value.to_string(),
+        }
+    }
+}
+
+// 字句に分ける（途中でエラーになれば、それまでの字句とエラー）
+fn tokenize(text: &str) -> (Vec<(Token, usize)>, Option<String>) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => {
+                line += 1;
+                i += 1;
+            }
+            // カンマは空白と同じ
+            ' ' | '\t' | '\r' | ',' | '\u{feff}' => i += 1,
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '!' | '$' | '&' | '(' | ')' | ':' | '=' | '@' | '[' | ']' | '{' | '|' | '}' => {
+                tokens.push((Token::Punct(c), line));
+                i += 1;
+            }
+            '.' => {
+                if chars[i..].starts_with(&['.', '.', '.']) {
+                    tokens.push((Token::Spread, line));
+                    i += 3;
+                } else {
+                    return (tokens, Some(format!("line {}: unexpected '.'", line)));
+                }
+            }
+            '"' => {
+                let start = line;
+                let block = chars[i..].starts_with(&['"', '"', '"']);
+                let (quote, mut j) = if block { (3, i + 3) } else { (1, i + 1) };
+                loop {
+                    if j >= chars.len() || (!block && chars[j] == '\n') {
+                        return (tokens, Some(format!("line {}: unterminated string", start)));
+                    }
+                    if chars[j] == '\\' {
+                        j += 2;
+                        continue;
+                    }
+                    if block && chars[j..].starts_with(&['"', '"', '"']) || !block && chars[j] == '"' {
+                        break;
+                    }
+                    if chars[j] == '\n' {
+                        line += 1;
+                    }
+                    j += 1;
+                }
+                let value: String = chars[i..j + quote].iter().collect();
+                tokens.push((Token::Value(value), start));
+                i = j + quote;
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                let mut j = i + 1;
+                while j < chars.len()
+                    && (chars[j].is_ascii_alphanumeric() || chars[j] == '.' || chars[j] == '+' || chars[j] == '-')
+                {
+                    j += 1;
+                }
+                tokens.push((Token::Value(chars[i..j].iter().collect()), line));
+                i = j;
+            }
+            c if c == '_' || c.is_ascii_alphabetic() => {
+                let mut j = i + 1;
+                while j < chars.len() && (chars[j] == '_' || chars[j].is_ascii_alphanumeric()) {
+                    j += 1;
+                }
+                tokens.push((Token::Name(chars[i..j].iter().collect()), line));
+                i = j;
+            }
+            c => return (tokens, Some(format!("line {}: unexpected '{}'", line, c))),
+        }
+    }
+    (tokens, None)
+}
+
+// 構文木
+struct Document {
+    operations: Vec<Operation>,
+    fragments: Vec<Fragment>,
+}
+
+struct Operation {
+    kind: String,
+    name: Option<String>,
+    // 変数の名前、型、既定値があるか
+    variables: Vec<(String, String, bool)>,
+    selections: Vec<Selection>,
+    line: usize,
+}
+
+struct Fragment {
+    name: String,
+    type_condition: String,
+    selections: Vec<Selection>,
+    line: usize,
+}
+
+enum Selection {
+    Field {
+        name: String,
+        arguments: Vec<String>,
+        selections: Option<Vec<Selection>>,
+        line: usize,
+    },
+    Spread {
+        name: String,
+        line: usize,
+    },
+    Inline {
+        type_condition: Option<String>,
+        selections: Vec<Selection>,
+        line: usize,
+    },
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    // 参照された変数と行
+    variables: Vec<(String, usize)>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map(|(_, line)| *line)
+            .unwrap_or(1)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(token) => format!("line {}: expected {}, found {}", self.line(), expected, token.describe()),
+            None => format!("line {}: expected {}, found the end of the query", self.line(), expected),
+        }
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), String> {
+        if self.is_punct(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", c)))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Name(name)) => {
+                let name = name.to_string();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    fn document(&mut self) -> Result<Document, String> {
+        let mut document = Document {
+            operations: Vec::new(),
+            fragments: Vec::new(),
+        };
+        while let Some(token) = self.peek() {
+            let line = self.line();
+            match token {
+                Token::Punct('{') => document.operations.push(Operation {
+                    kind: "query".to_string(),
+                    name: None,
+                    variables: Vec::new(),
+                    selections: self.selection_set()?,
+                    line,
+                }),
+                Token::Name(keyword) if keyword == "fragment" => {
+                    self.pos += 1;
+                    let name = self.name()?;
+                    if self.name()? != "on" {
+                        return Err(format!("line {}: expected 'on' after the fragment name", line));
+                    }
+                    let type_condition = self.name()?;
+                    self.directives()?;
+                    document.fragments.push(Fragment {
+                        name,
+                        type_condition,
+                        selections: self.selection_set()?,
+                        line,
+                    });
+                }
+                Token::Name(keyword) if ["query", "mutation", "subscription"].contains(&keyword.as_str()) => {
+                    let kind = keyword.to_string();
+                    self.pos += 1;
+                    let name = match self.peek() {
+                        Some(Token::Name(_)) => Some(self.name()?),
+                        _ => None,
+                    };
+                    let variables = self.variable_definitions()?;
+                    self.directives()?;
+                    document.operations.push(Operation {
+                        kind,
+                        name,
+                        variables,
+                        selections: self.selection_set()?,
+                        line,
+                    });
+                }
+                _ => return Err(self.unexpected("query, mutation, subscription, fragment or '{'")),
+            }
+        }
+        if document.operations.is_empty() {
+            return Err("the query has no operation".to_string());
+        }
+        Ok(document)
+    }
+
+    fn variable_definitions(&mut self) -> Result<Vec<(String, String, bool)>, String> {
+        let mut variables = Vec::new();
+        if !self.is_punct('(') {
+            return Ok(variables);
+        }
+        self.pos += 1;
+        while !self.is_punct(')') {
+            self.expect_punct('$')?;
+            let name = self.name()?;
+            self.expect_punct(':')?;
+            let ty = self.type_ref()?;
+            let default = self.is_punct('=');
+            if default {
+                self.pos += 1;
+                self.value(true)?;
+            }
+            self.directives()?;
+            variables.push((name, ty, default));
+        }
+        self.pos += 1;
+        Ok(variables)
+    }
+
+    fn type_ref(&mut self) -> Result<String, String> {
+        let ty = if self.is_punct('[') {
+            self.pos += 1;
+            let inner = self.type_ref()?;
+            self.expect_punct(']')?;
+            format!("[{}]", inner)
+        } else {
+            self.name()?
+        };
+        if self.is_punct('!') {
+            self.pos += 1;
+            return Ok(format!("{}!", ty));
+        }
+        Ok(ty)
+    }
+
+    // 値（const なら変数は使えない）
+    fn value(&mut self, constant: bool) -> Result<(), String> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Punct('$')) if !constant => {
+                let name = self.name()?;
+                self.variables.push((name, line));
+            }
+            Some(Token::Name(_)) | Some(Token::Value(_)) => {}
+            Some(Token::Punct('[')) => {
+                while !self.is_punct(']') {
+                    if self.peek().is_none() {
+                        return Err(self.unexpected("']'"));
+                    }
+                    self.value(constant)?;
+                }
+                self.pos += 1;
+            }
+            Some(Token::Punct('{')) => {
+                while !self.is_punct('}') {
+                    self.name()?;
+                    self.expect_punct(':')?;
+                    self.value(constant)?;
+                }
+                self.pos += 1;
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.unexpected("a value"));
+            }
+        }
+        Ok(())
+    }
+
+    fn arguments(&mut self) -> Result<Vec<String>, String> {
+        let mut arguments = Vec::new();
+        if !self.is_punct('(') {
+            return Ok(arguments);
+        }
+        self.pos += 1;
+        while !self.is_punct(')') {
+            arguments.push(self.name()?);
+            self.expect_punct(':')?;
+            self.value(false)?;
+        }
+        self.pos += 1;
+        Ok(arguments)
+    }
+
+    fn directives(&mut self) -> Result<(), String> {
+        while self.is_punct('@') {
+            self.pos += 1;
+            self.name()?;
+            self.arguments()?;
+        }
+        Ok(())
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Selection>, String> {
+        self.expect_punct('{')?;
+        let mut selections = Vec::new();
+        while !self.is_punct('}') {
+            selections.push(self.selection()?);
+        }
+        if selections.is_empty() {
+            return Err(format!("line {}: empty selection set", self.line()));
+        }
+        self.pos += 1;
+        Ok(selections)
+    }
+
+    fn selection(&mut self) -> Result<Selection, String> {
+        let line = self.line();
+        if self.peek() == Some(&Token::Spread) {
+            self.pos += 1;
+            return match self.peek() {
+                Some(Token::Name(name)) if name != "on" => {
+                    let name = self.name()?;
+                    self.directives()?;
+                    Ok(Selection::Spread { name, line })
+                }
+                Some(Token::Name(_)) => {
+                    self.pos += 1;
+                    let type_condition = Some(self.name()?);
+                    self.directives()?;
+                    Ok(Selection::Inline {
+                        type_condition,
+                        selections: self.selection_set()?,
+                        line,
+                    })
+                }
+                _ => {
+                    self.directives()?;
+                    Ok(Selection::Inline {
+                        type_condition: None,
+                        selections: self.selection_set()?,
+                        line,
+                    })
+                }
+            };
+        }
+        let mut name = self.name()?;
+        // 別名
+        if self.is_punct(':') {
+            self.pos += 1;
+            name = self.name()?;
+        }
+        let arguments = self.arguments()?;
+        self.directives()?;
+        let selections = if self.is_punct('{') {
+            Some(self.selection_set()?)
+        } else {
+            None
+        };
+        Ok(Selection::Field {
+            name,
+            arguments,
+            selections,
+            line,
+        })
+    }
+}
+
+fn parse_document(query: &str) -> Result<(Document, Vec<(String, usize)>), String> {
+    let (tokens, error) = tokenize(query);
+    if let Some(error) = error {
+        return Err(error);
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        variables: Vec::new(),
+    };
+    let document = parser.document()?;
+    Ok((document, parser.variables))
+}
+
+fn parse(query: &str) -> Result<Document, String> {
+    parse_document(query).map(|(document, _)| document)
+}
+
+// 似た名前（編集距離が 2 以下）
+fn suggestion<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> String {
+    let distance = |a: &str, b: &str| {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut prev = row[0];
+            row[0] = i + 1;
+            for (j, cb) in b.iter().enumerate() {
+                let cur = row[j + 1];
+                row[j + 1] = if ca == *cb {
+                    prev
+                } else {
+                    1 + prev.min(row[j]).min(row[j + 1])
+                };
+                prev = cur;
+            }
+        }
+        row[b.len()]
+    };
+    candidates
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(d, _)| *d <= 2)
+        .min()
+        .map(|(_, candidate)| format!(" (did you mean '{}'?)", candidate))
+        .unwrap_or_default()
+}
+
+struct Validator<'a> {
+    schema: &'a Schema,
+    fragments: BTreeSet<String>,
+    problems: Vec<String>,
+}
+
+impl<'a> Validator<'a> {
+    fn selections(&mut self, parent: &str, selections: &[Selection]) {
+        let ty = match self.schema.types.get(parent) {
+            Some(ty) => ty,
+            None => return,
+        };
+        for selection in selections {
+            match selection {
+                Selection::Field {
+                    name,
+                    arguments,
+                    selections,
+                    line,
+                } => {
+                    if name == "__typename" {
+                        continue;
+                    }
+                    // イントロスペクションのフィールドは調べない
+                    if name == "__schema" || name == "__type" {
+                        continue;
+                    }
+                    let field = match ty.field(name) {
+                        Some(field) => field,
+                        None if ty.kind == "UNION" => {
+                            self.problems.push(format!(
+                                "line {}: {} is a union; select '{}' in an inline fragment",
+                                line, parent, name
+                            ));
+                            continue;
+                        }
+                        None => {
+                            let names: Vec<&String> = ty.fields.iter().map(|f| &f.name).collect();
+                            self.problems.push(format!(
+                                "line {}: {} has no field '{}'{}",
+                                line,
+                                parent,
+                                name,
+                                suggestion(name, names.into_iter())
+                            ));
+                            continue;
+                        }
+                    };
+                    for argument in arguments {
+                        if !field.args.iter().any(|a| a.name == *argument) {
+                            let names: Vec<&String> = field.args.iter().map(|a| &a.name).collect();
+                            self.problems.push(format!(
+                                "line {}: '{}' has no argument '{}'{}",
+                                line,
+                                name,
+                                argument,
+                                suggestion(argument, names.into_iter())
+                            ));
+                        }
+                    }
+                    for arg in field.args.iter().filter(|a| a.required()) {
+                        if !arguments.contains(&arg.name) {
+                            self.problems.push(format!(
+                                "line {}: '{}' requires the argument '{}: {}'",
+                                line, name, arg.name, arg.ty
+                            ));
+                        }
+                    }
+                    let child = named_type(&field.ty);
+                    let leaf = self.schema.types.get(child).map(|t| t.is_leaf()).unwrap_or(true);
+                    match selections {
+                        Some(_) if leaf => self.problems.push(format!(
+                            "line {}: '{}' is a {} and cannot have a selection",
+                            line, name, field.ty
+                        )),
+                        Some(selections) => self.selections(child, selections),
+                        None if !leaf => self.problems.push(format!(
+                            "line {}: '{}' returns {} and needs a selection of fields",
+                            line, name, field.ty
+                        )),
+                        None => {}
+                    }
+                }
+                Selection::Spread { name, line } => {
+                    if !self.fragments.contains(name) {
+                        self.problems.push(format!("line {}: unknown fragment '{}'", line, name));
+                    }
+                }
+                Selection::Inline {
+                    type_condition,
+                    selections,
+                    line,
+                } => match type_condition {
+                    Some(condition) if !self.schema.types.contains_key(condition) => self
+                        .problems
+                        .push(format!("line {}: unknown type '{}'", line, condition)),
+                    Some(condition) => self.selections(condition, selections),
+                    None => self.selections(parent, selections),
+                },
+            }
+        }
+    }
+}
+
+// クエリをスキーマと変数ペインの内容に照らして検証する（問題がなければ空）
+pub fn validate(schema: &Schema, query: &str, variables: &str) -> Vec<String> {
+    let (document, used) = match parse_document(query) {
+        Ok(parsed) => parsed,
+        Err(err) => return vec![err],
+    };
+    let mut validator = Validator {
+        schema,
+        fragments: document.fragments.iter().map(|f| f.name.to_string()).collect(),
+        problems: Vec::new(),
+    };
+    for fragment in &document.fragments {
+        if schema.types.contains_key(&fragment.type_condition) {
+            validator.selections(&fragment.type_condition, &fragment.selections);
+        } else {
+            validator.problems.push(format!(
+                "line {}: unknown type '{}' in fragment {}",
+                fragment.line, fragment.type_condition, fragment.name
+            ));
+        }
+    }
+    let values = parse_variables(variables);
+    if let Err(err) = &values {
+        validator.problems.push(err.to_string());
+    }
+    for operation in &document.operations {
+        match schema.root(&operation.kind) {
+            Some(root) => {
+                let root = root.to_string();
+                validator.selections(&root, &operation.selections);
+            }
+            None => validator.problems.push(format!(
+                "line {}: the schema has no {} type",
+                operation.line, operation.kind
+            )),
+        }
+        for (name, ty, default) in &operation.variables {
+            if !schema.types.contains_key(named_type(ty)) {
+                validator
+                    .problems
+                    .push(format!("line {}: unknown type '{}' of ${}", operation.line, ty, name));
+            }
+            let missing = values
+                .as_ref()
+                .map(|values| values.get(name).is_none_or(|v| v.is_null()))
+                .unwrap_or(false);
+            if ty.ends_with('!') && !default && missing {
+                validator
+                    .problems
+                    .push(format!("${} ({}) is not set in the variables", name, ty));
+            }
+        }
+    }
+    // 操作がひとつなら、使った変数がすべて定義されているか
+    if let [operation] = document.operations.as_slice() {
+        for (name, line) in used {
+            if !operation.variables.iter().any(|(n, _, _)| *n == name) {
+                validator
+                    .problems
+                    .push(format!("line {}: variable ${} is not defined", line, name));
+            }
+        }
+    }
+    validator.problems
+}
+
+// 補完の候補
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub name: String,
+    pub detail: String,
+    // 引数なら名前のあとに `: ` を付ける
+    pub argument: bool,
+}
+
+// 括弧の中の状態
+struct Paren {
+    // フィールドの引数なら親の型とフィールド
+    field: Option<(String, String)>,
+    // 引数か入力オブジェクトのフィールドの名前を待っているか、値の中の括弧の深さ
+    expect_name: bool,
+    depth: usize,
+    // 値を書いている引数
+    argument: Option<String>,
+}
+
+// カーソルまでの文字列から、入力中の名前と候補を求める
+pub fn complete(schema: &Schema, text: &str) -> (String, Vec<Candidate>) {
+    let word: String = {
+        let rev: String = text
+            .chars()
+            .rev()
+            .take_while(|c| *c == '_' || c.is_ascii_alphanumeric())
+            .collect();
+        rev.chars().rev().collect()
+    };
+    let (tokens, _) = tokenize(&text[..text.len() - word.len()]);
+
+    let mut stack: Vec<Option<String>> = Vec::new();
+    let mut pending: Option<String> = None;
+    let mut parens: Vec<Paren> = Vec::new();
+    let mut last_field: Option<(String, String)> = None;
+    let mut after_spread = false;
+    let mut after_on = false;
+    let mut skip_name = false;
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i].0;
+        let next = tokens.get(i + 1).map(|(t, _)| t);
+        let was_spread = after_spread;
+        let was_on = after_on;
+        after_spread = false;
+        after_on = false;
+        if let Some(paren) = parens.last_mut() {
+            match token {
+                Token::Punct('(') => parens.push(Paren {
+                    field: None,
+                    expect_name: false,
+                    depth: 0,
+                    argument: None,
+                }),
+                Token::Punct(')') => {
+                    parens.pop();
+                }
+                Token::Punct('[') => {
+                    paren.depth += 1;
+                    paren.expect_name = false;
+                }
+                Token::Punct('{') => {
+                    paren.depth += 1;
+                    paren.expect_name = true;
+                }
+                Token::Punct(']') | Token::Punct('}') => {
+                    paren.depth = paren.depth.saturating_sub(1);
+                    paren.expect_name = true;
+                }
+                Token::Punct(':') => paren.expect_name = false,
+                Token::Name(name) if paren.expect_name && paren.depth == 0 => {
+                    paren.argument = Some(name.to_string());
+                }
+                Token::Name(_) if paren.expect_name => {}
+                Token::Name(_) | Token::Value(_) => paren.expect_name = true,
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+        match token {
+            Token::Name(_) if skip_name => skip_name = false,
+            Token::Name(name) if stack.is_empty() => match name.as_str() {
+                "query" | "mutation" | "subscription" => pending = schema.root(name).cloned(),
+                "fragment" => {
+                    // fragment 名前 on 型
+                    if let Some((Token::Name(ty), _)) = tokens.get(i + 3) {
+                        pending = Some(ty.to_string());
+                    }
+                    i += 3;
+                }
+                _ => {}
+            },
+            Token::Name(name) if was_spread && name == "on" => after_on = true,
+            Token::Name(name) if was_on => pending = Some(name.to_string()),
+            Token::Name(_) if was_spread => {}
+            // 別名
+            Token::Name(_) if next == Some(&Token::Punct(':')) => i += 1,
+            Token::Name(name) => {
+                let parent = stack.last().cloned().flatten();
+                pending = parent
+                    .as_ref()
+                    .and_then(|parent| schema.field_type(parent, name));
+                last_field = parent.map(|parent| (parent, name.to_string()));
+            }
+            Token::Spread => after_spread = true,
+            Token::Punct('@') => skip_name = true,
+            Token::Punct('(') => parens.push(Paren {
+                field: if stack.is_empty() { None } else { last_field.clone() },
+                expect_name: true,
+                depth: 0,
+                argument: None,
+            }),
+            Token::Punct('{') => {
+                let ty = pending.take().or_else(|| {
+                    if stack.is_empty() {
+                        schema.query_type.clone()
+                    } else {
+                        None
+                    }
+                });
+                stack.push(ty);
+            }
+            Token::Punct('}') => {
+                stack.pop();
+                pending = None;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let matches = |name: &str| name.starts_with(&word) && name != word;
+    let mut candidates: Vec<Candidate> = Vec::new();
+    if let Some(paren) = parens.last() {
+        let field = paren.field.as_ref().and_then(|(parent, field)| {
+            schema.types.get(parent).and_then(|ty| ty.field(field))
+        });
+        let field = match field {
+            Some(field) => field,
+            None => return (word, candidates),
+        };
+        let argument = paren
+            .argument
+            .as_ref()
+            .and_then(|name| field.args.iter().find(|a| a.name == *name))
+            .and_then(|arg| schema.types.get(named_type(&arg.ty)));
+        if paren.expect_name && paren.depth == 0 {
+            candidates = field
+                .args
+                .iter()
+                .filter(|arg| matches(&arg.name))
+                .map(|arg| Candidate {
+                    name: arg.name.to_string(),
+                    detail: format!("{}: {}", arg.name, arg.ty),
+                    argument: true,
+                })
+                .collect();
+        } else if let Some(ty) = argument {
+            // 入力オブジェクトならフィールド、列挙型なら値の候補
+            candidates = if paren.expect_name && paren.depth == 1 {
+                ty.input_fields
+                    .iter()
+                    .filter(|input| matches(&input.name))
+                    .map(|input| Candidate {
+                        name: input.name.to_string(),
+                        detail: format!("{}: {}", input.name, input.ty),
+                        argument: true,
+                    })
+                    .collect()
+            } else if !paren.expect_name {
+                ty.enum_values
+                    .iter()
+                    .filter(|value| matches(value))
+                    .map(|value| Candidate {
+                        name: value.to_string(),
+                        detail: value.to_string(),
+                        argument: false,
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+        }
+    } else if after_on || after_spread && word == "on" {
+        // インラインフラグメントの型
+        let parent = stack.last().cloned().flatten();
+        let types: Vec<String> = match parent.as_ref().and_then(|p| schema.types.get(p)) {
+            Some(ty) if !ty.possible_types.is_empty() => ty.possible_types.clone(),
+            _ => parent.into_iter().collect(),
+        };
+        candidates = types
+            .into_iter()
+            .filter(|ty| matches(ty))
+            .map(|ty| Candidate {
+                detail: ty.to_string(),
+                name: ty,
+                argument: false,
+            })
+            .collect();
+    } else if let Some(parent) = stack.last() {
+        let ty = parent.as_ref().and_then(|p| schema.types.get(p));
+        candidates = ty
+            .map(|ty| ty.fields.iter().map(|f| (f.name.to_string(), f.signature())).collect())
+            .unwrap_or_else(Vec::new)
+            .into_iter()
+            .chain(std::iter::once(("__typename".to_string(), "__typename: String!".to_string())))
+            .filter(|(name, _)| matches(name))
+            .map(|(name, detail)| Candidate {
+                name,
+                detail,
+                argument: false,
+            })
+            .collect();
+    } else {
+        candidates = ["query", "mutation", "subscription", "fragment"]
+            .iter()
+            .filter(|keyword| matches(keyword))
+            .map(|keyword| Candidate {
+                name: keyword.to_string(),
+                detail: keyword.to_string(),
+                argument: false,
+            })
+            .collect();
+    }
+    (word, candidates)
+}
+
+// 候補の名前に共通する先頭部分
+pub fn common_prefix(candidates: &[Candidate]) -> String {
+    let first = match candidates.first() {
+        Some(first) => first.name.to_string(),
+        None => return "".to_string(),
+    };
+    candidates.iter().fold(first, |prefix, candidate| {
+        prefix
+            .chars()
+            .zip(candidate.name.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    })
+}
+
+// お知らせに表示する候補の一覧
+pub fn candidate_list(candidates: &[Candidate]) -> String {
+    let mut list: Vec<String> = candidates
+        .iter()
+        .take(MAX_CANDIDATES)
+        .map(|c| c.detail.to_string())
+        .collect();
+    if candidates.len() > MAX_CANDIDATES {
+        list.push(format!("... {} more", candidates.len() - MAX_CANDIDATES));
+    }
+    list.join("  ")
+}
+
+// レスポンスの errors と data を分けて表示する（GraphQL のレスポンスでなければ None）
+pub fn sections(body: &str) -> Option<Vec<(String, Vec<String>)>> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    let object = value.as_object()?;
+    if !object.contains_key("data") && !object.contains_key("errors") {
+        return None;
+    }
+    let mut keys: Vec<&String> = Vec::new();
+    keys.extend(object.keys().filter(|k| *k == "errors"));
+    keys.extend(object.keys().filter(|k| *k == "data"));
+    keys.extend(object.keys().filter(|k| *k != "errors" && *k != "data"));
+    let sections = keys
+        .into_iter()
+        .map(|key| {
+            let value = &object[key];
+            let title = match value.as_array() {
+                Some(errors) if key == "errors" => format!("errors ({})", errors.len()),
+                _ => key.to_string(),
+            };
+            let text = serde_json::to_string_pretty(value).unwrap_or_default();
+            (title, text.lines().map(|line| format!("  {}", line)).collect())
+        })
+        .collect();
+    Some(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // イントロスペクションのレスポンスの小さな例
+    const FIXTURE: &str = r#"{"data": {"__schema": {
+        "queryType": {"name": "Query"},
+        "mutationType": null,
+        "subscriptionType": null,
+        "types": [
+            {"kind": "OBJECT", "name": "Query", "fields": [
+                {"name": "user", "args": [
+                    {"name": "id", "defaultValue": null,
+                     "type": {"kind": "NON_NULL", "name": null, "ofType": {"kind": "SCALAR", "name": "ID", "ofType": null}}}
+                ], "type": {"kind": "OBJECT", "name": "User", "ofType": null}},
+                {"name": "search", "args": [
+                    {"name": "term", "defaultValue": null,
+                     "type": {"kind": "NON_NULL", "name": null, "ofType": {"kind": "SCALAR", "name": "String", "ofType": null}}},
+                    {"name": "limit", "defaultValue": "10",
+                     "type": {"kind": "NON_NULL", "name": null, "ofType": {"kind": "SCALAR", "name": "Int", "ofType": null}}}
+                ], "type": {"kind": "NON_NULL", "name": null, "ofType": {"kind": "LIST", "name": null, "ofType":
+                    {"kind": "NON_NULL", "name": null, "ofType": {"kind": "UNION", "name": "SearchResult", "ofType": null}}}}}
+            ], "inputFields": null, "enumValues": null, "possibleTypes": null},
+            {"kind": "OBJECT", "name": "User", "fields": [
+                {"name": "id", "args": [],
+                 "type": {"kind": "NON_NULL", "name": null, "ofType": {"kind": "SCALAR", "name": "ID", "ofType": null}}},
+                {"name": "name", "args": [], "type": {"kind": "SCALAR", "name": "String", "ofType": null}},
+                {"name": "role", "args": [], "type": {"kind": "ENUM", "name": "Role", "ofType": null}},
+                {"name": "friends", "args": [
+                    {"name": "first", "defaultValue": null, "type": {"kind": "SCALAR", "name": "Int", "ofType": null}}
+                ], "type": {"kind": "LIST", "name": null, "ofType": {"kind": "OBJECT", "name": "User", "ofType": null}}}
+            ], "inputFields": null, "enumValues": null, "possibleTypes": null},
+            {"kind": "OBJECT", "name": "Post", "fields": [
+                {"name": "title", "args": [], "type": {"kind": "SCALAR", "name": "String", "ofType": null}}
+            ], "inputFields": null, "enumValues": null, "possibleTypes": null},
+            {"kind": "UNION", "name": "SearchResult", "fields": null, "inputFields": null, "enumValues": null,
+             "possibleTypes": [{"name": "User"}, {"name": "Post"}]},
+            {"kind": "ENUM", "name": "Role", "fields": null, "inputFields": null,
+             "enumValues": [{"name": "ADMIN"}, {"name": "MEMBER"}], "possibleTypes": null},
+            {"kind": "SCALAR", "name": "ID"},
+            {"kind": "SCALAR", "name": "String"},
+            {"kind": "SCALAR", "name": "Int"},
+            {"kind": "SCALAR", "name": "Boolean"}
+        ]
+    }}}"#;
+
+    fn schema() -> Schema {
+        Schema::from_response(FIXTURE).unwrap()
+    }
+
+    #[test]
+    fn schema_reads_type_references() {
+        let schema = schema();
+        assert_eq!(schema.query_type.as_deref(), Some("Query"));
+        assert_eq!(schema.mutation_type, None);
+        let query = &schema.types["Query"];
+        assert_eq!(query.fields[1].ty, "[SearchResult!]!");
+        assert_eq!(query.fields[1].signature(), "search(term: String!, limit: Int!): [SearchResult!]!");
+        assert!(query.fields[1].args[0].required());
+        assert!(!query.fields[1].args[1].required());
+        assert_eq!(schema.types["SearchResult"].possible_types, ["User", "Post"]);
+        assert_eq!(schema.types["Role"].enum_values, ["ADMIN", "MEMBER"]);
+        assert_eq!(
+            Schema::from_response(r#"{"errors": [{"message": "introspection is disabled"}]}"#).unwrap_err(),
+            "introspection failed: introspection is disabled"
+        );
+    }
+
+    #[test]
+    fn parses_fragments_variables_and_directives() {
+        let query = r#"
+            query Find($id: ID!, $first: Int = 3, $tags: [String!]) @cached(ttl: 60) {
+                me: user(id: $id) {
+                    ...UserParts @include(if: true)
+                    friends(first: $first) @skip(if: false) { id }
+                    ... on User { role }
+                    ... @defer { name }
+                }
+            }
+
+            fragment UserParts on User @deprecated {
+                id
+                name
+            }
+        "#;
+        let (document, used) = parse_document(query).unwrap();
+        let operation = &document.operations[0];
+        assert_eq!(operation.kind, "query");
+        assert_eq!(operation.name.as_deref(), Some("Find"));
+        assert_eq!(operation.line, 2);
+        assert_eq!(
+            operation.variables,
+            [
+                ("id".to_string(), "ID!".to_string(), false),
+                ("first".to_string(), "Int".to_string(), true),
+                ("tags".to_string(), "[String!]".to_string(), false),
+            ]
+        );
+        assert_eq!(used, [("id".to_string(), 3), ("first".to_string(), 5)]);
+
+        let fragment = &document.fragments[0];
+        assert_eq!((fragment.name.as_str(), fragment.type_condition.as_str()), ("UserParts", "User"));
+        assert_eq!(fragment.line, 11);
+        assert_eq!(fragment.selections.len(), 2);
+
+        // 別名ではなくフィールドの名前で持つ
+        let selections = match &operation.selections[0] {
+            Selection::Field { name, arguments, selections: Some(selections), .. } => {
+                assert_eq!(name, "user");
+                assert_eq!(arguments, &["id"]);
+                selections
+            }
+            _ => panic!("expected the user field"),
+        };
+        assert!(matches!(&selections[0], Selection::Spread { name, line: 4 } if name == "UserParts"));
+        assert!(matches!(&selections[2], Selection::Inline { type_condition: Some(t), .. } if t == "User"));
+        assert!(matches!(&selections[3], Selection::Inline { type_condition: None, .. }));
+    }
+
+    #[test]
+    fn parse_errors_report_the_line() {
+        let error = |query: &str| parse(query).err().unwrap();
+        assert_eq!(error("{\n  user(id: 1 {\n    id\n  }\n}"), "line 2: expected a name, found '{'");
+        assert_eq!(error("query {\n  user\n"), "line 2: expected a name, found the end of the query");
+        assert_eq!(error("{\n  a\n  b ? c\n}"), "line 3: unexpected '?'");
+        assert_eq!(error("{ a(s: \"x\n\") }"), "line 1: unterminated string");
+        assert_eq!(error("{\n}"), "line 2: empty selection set");
+        assert_eq!(error("fragment F User { id }"), "line 1: expected 'on' after the fragment name");
+        assert_eq!(error("fragment F on User { id }"), "the query has no operation");
+        assert_eq!(error("query Q($id ID) { a }"), "line 1: expected ':', found 'ID'");
+        assert_eq!(error("{\n  a(x: $v)\n  ..b\n}"), "line 3: unexpected '.'");
+    }
+
+    #[test]
+    fn valid_queries_have_no_problems() {
+        let schema = schema();
+        let query = r#"
+            query Find($id: ID!) {
+                user(id: $id) { ...UserParts friends(first: 2) { __typename name } }
+                search(term: "a") {
+                    ... on User { id role }
+                    ... on Post { title }
+                }
+            }
+            fragment UserParts on User { id name }
+        "#;
+        assert_eq!(validate(&schema, query, r#"{"id": "1"}"#), Vec::<String>::new());
+        assert_eq!(validate(&schema, "{ __schema { types { name } } }", ""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn validation_reports_schema_problems() {
+        let schema = schema();
+        let query = "query Find($id: ID!, $x: Missing) {
+  user(id: $id, idd: 1) { nmae friends }
+  search(limit: 1) { title }
+  user2: user(id: \"1\") { role { x } }
+  ...Nope
+  ... on Ghost { id }
+}
+fragment Bad on Nowhere { id }
+mutation { a }";
+        assert_eq!(
+            validate(&schema, query, "{}"),
+            [
+                "line 8: unknown type 'Nowhere' in fragment Bad",
+                "line 2: 'user' has no argument 'idd' (did you mean 'id'?)",
+                "line 2: User has no field 'nmae' (did you mean 'name'?)",
+                "line 2: 'friends' returns [User] and needs a selection of fields",
+                "line 3: 'search' requires the argument 'term: String!'",
+                "line 3: SearchResult is a union; select 'title' in an inline fragment",
+                "line 4: 'role' is a Role and cannot have a selection",
+                "line 5: unknown fragment 'Nope'",
+                "line 6: unknown type 'Ghost'",
+                "$id (ID!) is not set in the variables",
+                "line 1: unknown type 'Missing' of $x",
+                "line 9: the schema has no mutation type",
+            ]
+        );
+    }
+
+    #[test]
+    fn validation_checks_variables() {
+        let schema = schema();
+        assert_eq!(
+            validate(&schema, "{\n  user(id: $id) { id }\n}", ""),
+            ["line 2: variable $id is not defined"]
+        );
+        assert_eq!(
+            validate(&schema, "query ($id: ID!) { user(id: $id) { id } }", "[1]"),
+            ["GraphQL variables must be a JSON object"]
+        );
+        assert_eq!(
+            validate(&schema, "query ($id: ID!) { user(id: $id) { id } }", r#"{"id": null}"#),
+            ["$id (ID!) is not set in the variables"]
+        );
+        // 既定値があれば変数ペインになくてよい
+        assert!(validate(&schema, "query ($id: ID! = 1) { user(id: $id) { id } }", "").is_empty());
+    }
+
+    #[test]
+    fn encode_names_the_first_operation() {
+        let body: Value = serde_json::from_slice(&encode("query A { a } query B { b }", r#"{"x": 1}"#).unwrap()).unwrap();
+        assert_eq!(body["operationName"], "A");
+        assert_eq!(body["variables"], json!({"x": 1}));
+        let body: Value = serde_json::from_slice(&encode("{ a }", "").unwrap()).unwrap();
+        assert_eq!(body, json!({"query": "{ a }"}));
+    }
+}
@@ -0,0 +1,725 @@
+use bytes::Bytes;
+use h2::client::SendRequest;
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+use super::protobuf::{self, File, Method, Pool, Wire};
+use super::resolve;
+use super::settings::ClientSettings;
+use super::tls;
+
+const DEFAULT_TIMEOUT: f64 = 30.0;
+const USER_AGENT: &str = concat!("ruzz/", env!("CARGO_PKG_VERSION"));
+const REFLECTION: [&str; 2] = [
+    "/grpc.reflection.v1.ServerReflection/ServerReflectionInfo",
+    "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo",
+];
+// 一覧に出さないサービス
+const REFLECTION_SERVICES: [&str; 2] = [
+    "grpc.reflection.v1.ServerReflection",
+    "grpc.reflection.v1alpha.ServerReflection",
+];
+
+// grpc-status の名前（番号の順）
+const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+// trailers-only のレスポンスでもヘッダーとして扱うもの
+const HTTP_HEADERS: [&str; 4] = ["content-type", "content-length", "date", "server"];
+const UNKNOWN: u16 = 2;
+const UNIMPLEMENTED: u16 = 12;
+
+pub fn status_name(code: u16) -> &'static str {
+    STATUS_NAMES
+        .get(code as usize)
+        .copied()
+        .unwrap_or("UNKNOWN")
+}
+
+// grpc-status がないときに HTTP のステータスから決める
+fn status_from_http(status: u16) -> u16 {
+    match status {
+        400 => 13,
+        401 => 16,
+        403 => 7,
+        404 => UNIMPLEMENTED,
+        429 | 502 | 503 | 504 => 14,
+        _ => UNKNOWN,
+    }
+}
+
+pub fn is_grpc_url(url: &str) -> bool {
+    url.starts_with("grpc://") || url.starts_with("grpcs://")
+}
+
+// 1回の呼び出しのレスポンス
+pub struct Reply {
+    pub headers: Vec<(String, String)>,
+    pub trailers: Vec<(String, String)>,
+    // 長さのプレフィックスを外したメッセージ
+    pub messages: Vec<Vec<u8>>,
+    pub code: u16,
+    pub message: String,
+}
+
+impl Reply {
+    // `0 OK` や `5 NOT_FOUND: message`
+    pub fn status(&self) -> String {
+        let mut status = format!("{} {}", self.code, status_name(self.code));
+        if !self.message.is_empty() {
+            status.push_str(&format!(": {}", self.message));
+        }
+        status
+    }
+}
+
+// gRPC サーバーへの HTTP/2 接続（grpc:// は平文の prior knowledge、grpcs:// は TLS の ALPN）
+pub struct Channel {
+    runtime: Runtime,
+    sender: SendRequest<Bytes>,
+    scheme: &'static str,
+    authority: String,
+    pub remote: Option<SocketAddr>,
+    timeout: Option<Duration>,
+    user_agent: String,
+}
+
+fn headers(map: &http::HeaderMap) -> Vec<(String, String)> {
+    map.iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).to_string(),
+            )
+        })
+        .collect()
+}
+
+impl Channel {
+    pub fn connect(url: &url::Url, settings: &ClientSettings) -> Result<Channel, String> {
+        if settings.unix_socket.is_some() {
+            return Err("gRPC over a unix socket is not supported".to_string());
+        }
+        let tls = match url.scheme() {
+            "grpc" => false,
+            "grpcs" => true,
+            scheme => return Err(format!("unsupported gRPC scheme: {}", scheme)),
+        };
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("no host in {}", url))?
+            .to_string();
+        let port = url.port().unwrap_or(if tls { 443 } else { 80 });
+        // 既定のポートを持たないスキームなので、接続には http(s) の URL を使う
+        let target = url
+            .host()
+            .map(|host| {
+                format!(
+                    "{}://{}:{}/",
+                    if tls { "https" } else { "http" },
+                    host,
+                    port
+                )
+            })
+            .and_then(|target| url::Url::parse(&target).ok())
+            .ok_or_else(|| format!("invalid gRPC url {}", url))?;
+
+        let stream = resolve::connect_tcp(&target, settings)?;
+        let remote = stream.peer_addr().ok();
+        stream.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .map_err(|e| format!("failed to start the gRPC runtime: {}", e))?;
+        let stream = runtime
+            .enter(|| tokio::net::TcpStream::from_std(stream))
+            .map_err(|e| e.to_string())?;
+
+        let timeout = match settings.timeout.unwrap_or(DEFAULT_TIMEOUT) {
+            t if t > 0.0 => Some(Duration::from_secs_f64(t)),
+            _ => None,
+        };
+        let sender = if tls {
+            let mut builder = tls::native_builder(settings)?;
+            builder.request_alpns(&["h2"]);
+            let connector = builder
+                .build()
+                .map_err(|e| format!("failed to build the TLS connector: {}", e))?;
+            let connector = tokio_tls::TlsConnector::from(connector);
+            let (sender, connection) = within(&mut runtime, timeout, async {
+                let stream = connector
+                    .connect(&host, stream)
+                    .await
+                    .map_err(|e| format!("TLS handshake failed: {}", e))?;
+                h2::client::handshake(stream)
+                    .await
+                    .map_err(|e| format!("HTTP/2 handshake failed: {}", e))
+            })?;
+            runtime.spawn(connection);
+            sender
+        } else {
+            let (sender, connection) = within(&mut runtime, timeout, async {
+                h2::client::handshake(stream)
+                    .await
+                    .map_err(|e| format!("HTTP/2 handshake failed: {}", e))
+            })?;
+            runtime.spawn(connection);
+            sender
+        };
+
+        let authority = if url.port().is_some() {
+            format!("{}:{}", url.host_str().unwrap_or(&host), port)
+        } else {
+            url.host_str().unwrap_or(&host).to_string()
+        };
+        Ok(Channel {
+            runtime,
+            sender,
+            scheme: if tls { "https" } else { "http" },
+            authority,
+            remote,
+            timeout,
+            user_agent: settings
+                .user_agent
+                .clone()
+                .unwrap_or_else(|| USER_AGENT.to_string()),
+        })
+    }
+
+    // path（`/package.Service/Method`）にメッセージを送り、ストリームの終わりまで読む
+    pub fn call(
+        &mut self,
+        path: &str,
+        metadata: &[(String, String)],
+        messages: &[Vec<u8>],
+    ) -> Result<Reply, String> {
+        let mut builder = http::Request::builder()
+            .method("POST")
+            .uri(format!("{}://{}{}", self.scheme, self.authority, path))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers");
+        if !metadata
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("user-agent"))
+        {
+            builder = builder.header("user-agent", self.user_agent.as_str());
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.header("grpc-timeout", grpc_timeout(timeout));
+        }
+        for (name, value) in metadata {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let request = builder
+            .body(())
+            .map_err(|e| format!("invalid metadata: {}", e))?;
+
+        // 1バイトの圧縮フラグと4バイトの長さを付ける
+        let mut body = Vec::new();
+        for message in messages {
+            body.push(0);
+            body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+            body.extend_from_slice(message);
+        }
+
+        let sender = self.sender.clone();
+        let (parts, data, trailers) = within(&mut self.runtime, self.timeout, async move {
+            let mut sender = sender
+                .ready()
+                .await
+                .map_err(|e| format!("connection closed: {}", e))?;
+            let (response, mut stream) = sender
+                .send_request(request, false)
+                .map_err(|e| format!("failed to send the request: {}", e))?;
+            stream
+                .send_data(Bytes::from(body), true)
+                .map_err(|e| format!("failed to send the request: {}", e))?;
+            let response = response
+                .await
+                .map_err(|e| format!("gRPC call failed: {}", e))?;
+            let (parts, mut body) = response.into_parts();
+            let mut data = Vec::new();
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk.map_err(|e| format!("failed to read the response: {}", e))?;
+                let _ = body.flow_control().release_capacity(chunk.len());
+                data.extend_from_slice(&chunk);
+            }
+            let trailers = body
+                .trailers()
+                .await
+                .map_err(|e| format!("failed to read the trailers: {}", e))?;
+            Ok((parts, data, trailers))
+        })?;
+
+        let mut messages = Vec::new();
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            if rest.len() < 5 {
+                return Err("truncated gRPC message".to_string());
+            }
+            if rest[0] != 0 {
+                return Err("compressed gRPC messages are not supported".to_string());
+            }
+            let len = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]) as usize;
+            if rest.len() < 5 + len {
+                return Err("truncated gRPC message".to_string());
+            }
+            messages.push(rest[5..5 + len].to_vec());
+            rest = &rest[5 + len..];
+        }
+
+        // trailers-only のレスポンスではステータスがヘッダーに入る
+        let http_status = parts.status.as_u16();
+        let (headers, trailers) = match trailers {
+            Some(trailers) => (headers(&parts.headers), headers(&trailers)),
+            // trailers-only のレスポンスは HTTP のヘッダー以外をトレーラーとして扱う
+            None => headers(&parts.headers)
+                .into_iter()
+                .partition(|(name, _)| HTTP_HEADERS.contains(&name.as_str())),
+        };
+        let find = |name: &str| {
+            trailers
+                .iter()
+                .chain(headers.iter())
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+        };
+        let (code, message) = match find("grpc-status") {
+            Some(code) => (
+                code.trim().parse().unwrap_or(UNKNOWN),
+                find("grpc-message")
+                    .map(|m| percent_decode_str(&m).decode_utf8_lossy().to_string())
+                    .unwrap_or_default(),
+            ),
+            None if http_status != 200 => (
+                status_from_http(http_status),
+                format!("HTTP status {}", http_status),
+            ),
+            None => (UNKNOWN, "missing grpc-status".to_string()),
+        };
+        Ok(Reply {
+            headers,
+            trailers,
+            messages,
+            code,
+            message,
+        })
+    }
+
+    // サーバーリフレクションでサービスと型を読み込む（v1 がなければ v1alpha）
+    pub fn reflect(&mut self, metadata: &[(String, String)]) -> Result<Pool, String> {
+        let mut last = String::new();
+        for path in REFLECTION.iter() {
+            match self.reflect_with(path, metadata) {
+                Ok(pool) => return Ok(pool),
+                Err((code, err)) if code == Some(UNIMPLEMENTED) => last = err,
+                Err((_, err)) => return Err(err),
+            }
+        }
+        Err(format!(
+            "server reflection is not available ({}); set proto= in the Settings pane",
+            last
+        ))
+    }
+
+    fn reflect_with(
+        &mut self,
+        path: &str,
+        metadata: &[(String, String)],
+    ) -> Result<Pool, (Option<u16>, String)> {
+        let no_code = |err: String| (None, err);
+        // ServerReflectionRequest の list_services = 7
+        let response = self.reflection(path, metadata, &protobuf::string_field(7, ""))?;
+        let services: Vec<String> = protobuf::fields(&response)
+            .map_err(no_code)?
+            .into_iter()
+            .filter_map(|(number, wire)| match (number, wire) {
+                (6, Wire::Bytes(list)) => Some(list),
+                _ => None,
+            })
+            .flat_map(|list| protobuf::fields(list).unwrap_or_default())
+            .filter_map(|(number, wire)| match (number, wire) {
+                (1, Wire::Bytes(service)) => protobuf::fields(service)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|(number, _)| *number == 1)
+                    .and_then(|(_, name)| protobuf::wire_string(name).ok()),
+                _ => None,
+            })
+            .filter(|name| !REFLECTION_SERVICES.contains(&name.as_str()))
+            .collect();
+
+        let mut pool = Pool::default();
+        let mut files = Vec::new();
+        for service in &services {
+            // file_containing_symbol = 4
+            let request = protobuf::string_field(4, service);
+            for file in self.reflection_files(path, metadata, &request)? {
+                pool.add(file.clone());
+                files.push(file);
+            }
+        }
+        // 足りない依存ファイルを名前で取得する
+        let mut requested = BTreeSet::new();
+        loop {
+            let missing: Vec<String> = pool
+                .missing(&files)
+                .into_iter()
+                .filter(|name| requested.insert(name.clone()))
+                .collect();
+            if missing.is_empty() {
+                break;
+            }
+            for name in missing {
+                // file_by_filename = 3
+                let request = protobuf::string_field(3, &name);
+                for file in self.reflection_files(path, metadata, &request)? {
+                    pool.add(file.clone());
+                    files.push(file);
+                }
+            }
+        }
+        pool.resolve().map_err(no_code)?;
+        Ok(pool)
+    }
+
+    // リフレクションの要求を1つ送り、レスポンスのメッセージを返す
+    fn reflection(
+        &mut self,
+        path: &str,
+        metadata: &[(String, String)],
+        request: &[u8],
+    ) -> Result<Vec<u8>, (Option<u16>, String)> {
+        let reply = self
+            .call(path, metadata, &[request.to_vec()])
+            .map_err(|err| (None, err))?;
+        if reply.code != 0 {
+            return Err((
+                Some(reply.code),
+                format!("reflection failed: {}", reply.status()),
+            ));
+        }
+        let message = reply
+            .messages
+            .into_iter()
+            .next()
+            .ok_or_else(|| (None, "empty reflection response".to_string()))?;
+        let fields = protobuf::fields(&message).map_err(|err| (None, err))?;
+        // error_response = 7（error_code = 1, error_message = 2）
+        if let Some((_, Wire::Bytes(error))) = fields.iter().find(|(number, _)| *number == 7) {
+            let mut code = UNKNOWN;
+            let mut text = String::new();
+            for (number, wire) in protobuf::fields(error).unwrap_or_default() {
+                match number {
+                    1 => code = protobuf::wire_u64(wire) as u16,
+                    2 => text = protobuf::wire_string(wire).unwrap_or_default(),
+                    _ => {}
+                }
+            }
+            return Err((
+                None,
+                format!("reflection error {} {}: {}", code, status_name(code), text),
+            ));
+        }
+        Ok(message)
+    }
+
+    fn reflection_files(
+        &mut self,
+        path: &str,
+        metadata: &[(String, String)],
+        request: &[u8],
+    ) -> Result<Vec<File>, (Option<u16>, String)> {
+        let mut files = Vec::new();
+        let response = self.reflection(path, metadata, request)?;
+        for (number, wire) in protobuf::fields(&response).map_err(|err| (None, err))? {
+            // file_descriptor_response = 4（file_descriptor_proto = 1）
+            if let (4, Wire::Bytes(response)) = (number, wire) {
+                for (number, wire) in protobuf::fields(response).map_err(|err| (None, err))? {
+                    if let (1, Wire::Bytes(file)) = (number, wire) {
+                        files.push(protobuf::decode_file(file).map_err(|err| (None, err))?);
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+// grpc-timeout の値（TimeoutValue は8桁までなので、収まる単位を選んで切り上げる）
+fn grpc_timeout(timeout: Duration) -> String {
+    const MAX: u128 = 99_999_999;
+    let millis = timeout.as_nanos().div_ceil(1_000_000);
+    [(1, 'm'), (1_000, 'S'), (60_000, 'M'), (3_600_000, 'H')]
+        .iter()
+        .map(|(unit, name)| (millis.div_ceil(*unit), name))
+        .find(|(value, _)| *value <= MAX)
+        .map(|(value, name)| format!("{}{}", value, name))
+        .unwrap_or_else(|| format!("{}H", MAX))
+}
+
+// タイムアウトを付けて future を実行する
+fn within<T, F>(runtime: &mut Runtime, timeout: Option<Duration>, future: F) -> Result<T, String>
+where
+    F: Future<Output = Result<T, String>>,
+{
+    match timeout {
+        Some(timeout) => runtime.block_on(async {
+            tokio::time::timeout(timeout, future)
+                .await
+                .unwrap_or_else(|_| Err(format!("timed out after {:?}", timeout)))
+        }),
+        None => runtime.block_on(future),
+    }
+}
+
+// .proto ファイルの設定があればそれを、なければサーバーリフレクションで型を読み込む
+pub fn load_pool(
+    channel: &mut Channel,
+    metadata: &[(String, String)],
+    settings: &ClientSettings,
+) -> Result<Pool, String> {
+    match &settings.proto {
+        Some(files) => {
+            let files: Vec<String> = files.split_whitespace().map(|f| f.to_string()).collect();
+            let paths: Vec<String> = settings
+                .proto_path
+                .iter()
+                .flat_map(|p| p.split_whitespace())
+                .map(|p| p.to_string())
+                .collect();
+            protobuf::load(&files, &paths)
+        }
+        None => channel.reflect(metadata),
+    }
+}
+
+// すべてのサービスのメソッド（サービスの名前と組にする）
+pub fn methods(pool: &Pool) -> Vec<(&str, &Method)> {
+    pool.services
+        .iter()
+        .flat_map(|service| {
+            service
+                .methods
+                .iter()
+                .map(move |method| (service.name.as_str(), method))
+        })
+        .collect()
+}
+
+// サービスとメソッドの一覧
+pub fn method_lines(pool: &Pool) -> Vec<String> {
+    let mut lines = Vec::new();
+    for service in &pool.services {
+        lines.push(service.name.clone());
+        for method in &service.methods {
+            lines.push(format!("  {}", Pool::signature(method)));
+        }
+    }
+    if lines.is_empty() {
+        lines.push("no services".to_string());
+    }
+    lines
+}
+
+// 単項呼び出し（ボディの JSON をリクエストのメッセージにして送り、レスポンスを JSON にする）
+pub fn invoke(
+    channel: &mut Channel,
+    pool: &Pool,
+    method: &str,
+    json: &str,
+    metadata: &[(String, String)],
+) -> Result<(Reply, Option<Value>), String> {
+    let (service, method) = pool.method(method)?;
+    if method.client_streaming || method.server_streaming {
+        return Err(format!(
+            "{} is a streaming method; only unary calls are supported",
+            Pool::signature(method)
+        ));
+    }
+    let value: Value = if json.trim().is_empty() {
+        Value::Object(serde_json::Map::new())
+    } else {
+        serde_json::from_str(json).map_err(|e| format!("invalid JSON body: {}", e))?
+    };
+    let request = pool.encode(&method.input, &value)?;
+    let path = format!("/{}/{}", service.name, method.name);
+    let reply = channel.call(&path, metadata, &[request])?;
+    let response = match reply.messages.first() {
+        Some(message) => Some(pool.decode(&method.output, message)?),
+        None => None,
+    };
+    Ok((reply, response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::net::TcpListener;
+
+    const ECHO: &str = r#"
+        syntax = "proto3";
+        package echo.v1;
+        message Note {
+            string text = 1;
+            repeated int32 tags = 2;
+        }
+        service Echo {
+            rpc Say (Note) returns (Note);
+            rpc Missing (Note) returns (Note);
+            rpc Watch (Note) returns (stream Note);
+        }
+    "#;
+
+    // 1つの接続で、Say にはリクエストのメッセージをそのまま返し、ほかは trailers-only の NOT_FOUND を返す
+    fn serve_once() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            stream.set_nonblocking(true).unwrap();
+            let mut runtime = tokio::runtime::Builder::new()
+                .basic_scheduler()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let stream = tokio::net::TcpStream::from_std(stream).unwrap();
+                let mut connection = h2::server::handshake(stream).await.unwrap();
+                while let Some(Ok((request, mut respond))) = connection.accept().await {
+                    let path = request.uri().path().to_string();
+                    let timeout = request.headers().get("grpc-timeout").cloned();
+                    let mut body = request.into_body();
+                    let mut data = Vec::new();
+                    while let Some(Ok(chunk)) = body.data().await {
+                        let _ = body.flow_control().release_capacity(chunk.len());
+                        data.extend_from_slice(&chunk);
+                    }
+                    if path == "/echo.v1.Echo/Say" {
+                        let mut response = http::Response::builder()
+                            .status(200)
+                            .header("content-type", "application/grpc");
+                        if let Some(timeout) = timeout {
+                            response = response.header("x-grpc-timeout", timeout);
+                        }
+                        let mut stream = respond.send_response(response.body(()).unwrap(), false).unwrap();
+                        stream.send_data(Bytes::from(data), false).unwrap();
+                        let mut trailers = http::HeaderMap::new();
+                        trailers.insert("grpc-status", "0".parse().unwrap());
+                        stream.send_trailers(trailers).unwrap();
+                    } else {
+                        let response = http::Response::builder()
+                            .status(200)
+                            .header("content-type", "application/grpc")
+                            .header("grpc-status", "5")
+                            .header("grpc-message", "no%20such%20note")
+                            .body(())
+                            .unwrap();
+                        respond.send_response(response, true).unwrap();
+                    }
+                }
+            });
+        });
+        port
+    }
+
+    fn pool() -> Pool {
+        let mut pool = Pool::default();
+        pool.add(protobuf::parse_file("echo.proto", ECHO).unwrap());
+        pool.resolve().unwrap();
+        pool
+    }
+
+    fn channel(port: u16, settings: &str) -> Channel {
+        let url = url::Url::parse(&format!("grpc://127.0.0.1:{}/", port)).unwrap();
+        Channel::connect(&url, &ClientSettings::parse(settings).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn unary_call_round_trips_the_message() {
+        let pool = pool();
+        let mut channel = channel(serve_once(), "timeout=2.5");
+        let (reply, response) = invoke(
+            &mut channel,
+            &pool,
+            "/echo.v1.Echo/Say",
+            r#"{"text": "hello", "tags": [1, 2, 3]}"#,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(reply.status(), "0 OK");
+        assert_eq!(reply.messages.len(), 1);
+        assert_eq!(response, Some(json!({"text": "hello", "tags": [1, 2, 3]})));
+        // timeout の設定は grpc-timeout として送る
+        assert!(reply
+            .headers
+            .contains(&("x-grpc-timeout".to_string(), "2500m".to_string())));
+        assert_eq!(reply.trailers, [("grpc-status".to_string(), "0".to_string())]);
+
+        // 同じ接続で trailers-only のエラーも受け取れる
+        let (reply, response) = invoke(&mut channel, &pool, "echo.v1.Echo/Missing", "", &[]).unwrap();
+        assert_eq!(reply.status(), "5 NOT_FOUND: no such note");
+        assert_eq!(response, None);
+    }
+
+    #[test]
+    fn streaming_methods_are_refused() {
+        let pool = pool();
+        let mut channel = channel(serve_once(), "");
+        let err = invoke(&mut channel, &pool, "/echo.v1.Echo/Watch", "{}", &[]).err().unwrap();
+        assert!(err.contains("only unary calls are supported"), "{}", err);
+        assert!(invoke(&mut channel, &pool, "/echo.v1.Echo/Say", "[1]", &[])
+            .err()
+            .unwrap()
+            .contains("expected an object"));
+    }
+
+    #[test]
+    fn grpc_timeout_fits_in_eight_digits() {
+        assert_eq!(grpc_timeout(Duration::from_millis(2500)), "2500m");
+        assert_eq!(grpc_timeout(Duration::from_micros(1)), "1m");
+        assert_eq!(grpc_timeout(Duration::from_millis(99_999_999)), "99999999m");
+        assert_eq!(grpc_timeout(Duration::from_millis(100_000_000)), "100000S");
+        assert_eq!(grpc_timeout(Duration::from_secs(100_000_000)), "1666667M");
+        // 1年の上限
+        assert_eq!(grpc_timeout(Duration::from_secs(365 * 24 * 3600)), "31536000S");
+        assert_eq!(grpc_timeout(Duration::from_secs(u64::MAX)), "99999999H");
+    }
+
+    #[test]
+    fn method_lines_list_services() {
+        assert_eq!(
+            method_lines(&pool()),
+            [
+                "echo.v1.Echo",
+                "  Say(echo.v1.Note) returns (echo.v1.Note)",
+                "  Missing(echo.v1.Note) returns (echo.v1.Note)",
+                "  Watch(echo.v1.Note) returns (stream echo.v1.Note)",
+            ]
+        );
+        assert_eq!(method_lines(&Pool::default()), ["no services"]);
+    }
+}
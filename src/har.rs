@@ -0,0 +1,312 @@
+use super::collection::{Collection, SavedRequest};
+use super::history::Exchange;
+
+use chrono::SecondsFormat;
+use reqwest::header::HeaderMap;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+// 読み込み時に除外するヘッダー（送信時に自動で付与される）
+const SKIP_HEADERS: [&str; 3] = ["content-length", "host", "connection"];
+
+// HARファイルかどうか
+pub fn is_har(json: &Value) -> bool {
+    json["log"]["entries"].is_array()
+}
+
+// HARの各エントリーを保存済みリクエストに変換する
+pub fn import(json: &Value) -> Collection {
+    let mut requests = Vec::new();
+    for entry in json["log"]["entries"].as_array().unwrap_or(&Vec::new()) {
+        requests.push(import_entry(&entry["request"]));
+    }
+
+    Collection {
+        name: json["log"]["creator"]["name"]
+            .as_str()
+            .unwrap_or("HAR")
+            .to_string(),
+        stop_on_failure: true,
+        requests,
+        ..Collection::default()
+    }
+}
+
+fn import_entry(request: &Value) -> SavedRequest {
+    let raw_url = request["url"].as_str().unwrap_or("");
+    let mut saved = SavedRequest {
+        method: request["method"].as_str().unwrap_or("GET").to_uppercase(),
+        ..SavedRequest::default()
+    };
+
+    // クエリは URL params ペインに移す。ペインの `key=value` に戻せない値（`&` を含む値、
+    // `=` を含むキー、重複したキーなど）があれば、元のエンコードのまま URL に残す
+    match url::Url::parse(raw_url) {
+        Ok(mut url) => {
+            saved.name = url.path().to_string();
+            let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+            let mut keys = std::collections::BTreeSet::new();
+            let representable = pairs.iter().all(|(key, value)| {
+                !key.is_empty()
+                    && !key.contains(['=', '&', '\n'])
+                    && !value.contains(['&', '\n'])
+                    && keys.insert(key.to_string())
+            });
+            if representable {
+                saved.params = pairs.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+                url.set_query(None);
+            }
+            saved.url = url.to_string();
+        }
+        Err(_) => {
+            saved.name = raw_url.to_string();
+            saved.url = raw_url.to_string();
+        }
+    }
+
+    for header in request["headers"].as_array().unwrap_or(&Vec::new()) {
+        let name = header["name"].as_str().unwrap_or("");
+        // HTTP/2 の疑似ヘッダーは送れないので除外する
+        if name.is_empty()
+            || name.starts_with(':')
+            || SKIP_HEADERS.contains(&name.to_lowercase().as_str())
+        {
+            continue;
+        }
+        saved.headers.push(format!(
+            "{}:{}",
+            name,
+            header["value"].as_str().unwrap_or("")
+        ));
+    }
+
+    // Cookie ヘッダーがなければ cookies から組み立てる
+    let has_cookie = saved.headers.iter().any(|h| h.to_lowercase().starts_with("cookie:"));
+    let cookies: Vec<String> = request["cookies"]
+        .as_array()
+        .unwrap_or(&Vec::new())
+        .iter()
+        .filter_map(|cookie| {
+            let name = cookie["name"].as_str().filter(|name| !name.is_empty())?;
+            Some(format!("{}={}", name, cookie["value"].as_str().unwrap_or("")))
+        })
+        .collect();
+    if !has_cookie && !cookies.is_empty() {
+        saved.headers.push(format!("Cookie:{}", cookies.join("; ")));
+    }
+
+    let post_data = &request["postData"];
+    if post_data.is_object() {
+        let mime_type = post_data["mimeType"].as_str().unwrap_or("");
+        if let Some(text) = post_data["text"].as_str() {
+            saved.body = text.to_string();
+
+            let has_content_type = saved
+                .headers
+                .iter()
+                .any(|h| h.to_lowercase().starts_with("content-type:"));
+            if !has_content_type && !mime_type.is_empty() {
+                saved.headers.push(format!("Content-Type:{}", mime_type));
+            }
+        } else if let Some(params) = post_data["params"].as_array() {
+            // パラメーター形式は Body Mode で送る（Content-Type は送信時に付く）
+            let lines: Vec<String> = params
+                .iter()
+                .map(|param| {
+                    let name = param["name"].as_str().unwrap_or("");
+                    match param["fileName"].as_str() {
+                        Some(file) => format!("{}=@{}", name, file),
+                        None => format!("{}={}", name, param["value"].as_str().unwrap_or("")),
+                    }
+                })
+                .collect();
+            saved.body = lines.join("\n");
+            saved.body_mode = if mime_type.starts_with("multipart/") {
+                "multipart".to_string()
+            } else {
+                "form".to_string()
+            };
+            saved
+                .headers
+                .retain(|h| !h.to_lowercase().starts_with("content-type:"));
+        }
+    }
+
+    saved
+}
+
+fn headers_json(headers: &HeaderMap) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            json!({
+                "name": name.as_str(),
+                "value": value.to_str().unwrap_or(""),
+            })
+        })
+        .collect()
+}
+
+fn query_json(url: &str) -> Vec<Value> {
+    match url::Url::parse(url) {
+        Ok(url) => url
+            .query_pairs()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn millis(duration: &std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn export_entry(exchange: &Exchange) -> Value {
+    let request_mime = exchange
+        .request_headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let response_mime = exchange
+        .response_headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let redirect_url = exchange
+        .response_headers
+        .get("location")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    let mut request = json!({
+        "method": exchange.method,
+        "url": exchange.url,
+        "httpVersion": exchange.http_version,
+        "cookies": [],
+        "headers": headers_json(&exchange.request_headers),
+        "queryString": query_json(&exchange.url),
+        "headersSize": -1,
+        "bodySize": exchange.request_body.len(),
+    });
+    if !exchange.request_body.is_empty() {
+        request["postData"] = json!({
+            "mimeType": request_mime,
+            "text": exchange.request_body,
+        });
+    }
+
+    json!({
+        "startedDateTime": exchange.started.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "time": millis(&(exchange.wait + exchange.receive)),
+        "request": request,
+        "response": {
+            "status": exchange.status,
+            "statusText": exchange.status_text,
+            "httpVersion": exchange.http_version,
+            "cookies": [],
+            "headers": headers_json(&exchange.response_headers),
+            "content": {
+                "size": exchange.response_body.len(),
+                "mimeType": response_mime,
+                "text": exchange.response_body,
+            },
+            "redirectURL": redirect_url,
+            "headersSize": -1,
+            "bodySize": exchange.response_body.len(),
+        },
+        "cache": {},
+        "timings": {
+            "blocked": -1,
+            "dns": -1,
+            "connect": -1,
+            "ssl": -1,
+            "send": 0,
+            "wait": millis(&exchange.wait),
+            "receive": millis(&exchange.receive),
+        },
+    })
+}
+
+// 送信履歴をHAR 1.2形式で書き出す
+pub fn export(history: &[Exchange], path: &Path) -> Result<(), String> {
+    let har = json!({
+        "log": {
+            "version": "1.2",
+            "creator": {
+                "name": "ruzz",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "entries": history.iter().map(export_entry).collect::<Vec<Value>>(),
+        }
+    });
+
+    let text = serde_json::to_string_pretty(&har).map_err(|e| e.to_string())?;
+    fs::write(path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(request: &str) -> SavedRequest {
+        import_entry(&serde_json::from_str(request).unwrap())
+    }
+
+    #[test]
+    fn query_moves_to_params_when_it_round_trips() {
+        let saved = entry(r#"{"method": "get", "url": "https://example.com/search?q=a%20b&page=2"}"#);
+        assert_eq!(saved.url, "https://example.com/search");
+        assert_eq!(saved.params, vec!["q=a b", "page=2"]);
+    }
+
+    #[test]
+    fn query_with_ampersands_or_repeated_keys_stays_in_the_url() {
+        let saved = entry(r#"{"method": "GET", "url": "https://example.com/?q=rock%26roll&x=a%3Db"}"#);
+        assert_eq!(saved.url, "https://example.com/?q=rock%26roll&x=a%3Db");
+        assert!(saved.params.is_empty());
+        let saved = entry(r#"{"method": "GET", "url": "https://example.com/?id=1&id=2"}"#);
+        assert_eq!(saved.url, "https://example.com/?id=1&id=2");
+        assert!(saved.params.is_empty());
+    }
+
+    #[test]
+    fn imports_post_data_and_cookies() {
+        let saved = entry(
+            r#"{
+              "method": "POST",
+              "url": "https://example.com/login",
+              "headers": [
+                {"name": ":authority", "value": "example.com"},
+                {"name": "Content-Length", "value": "27"},
+                {"name": "Accept", "value": "*/*"}
+              ],
+              "cookies": [{"name": "session", "value": "abc"}, {"name": "theme", "value": "dark"}],
+              "postData": {"mimeType": "application/json", "text": "{\"user\": \"alice\"}"}
+            }"#,
+        );
+        assert_eq!(saved.method, "POST");
+        assert_eq!(saved.body, "{\"user\": \"alice\"}");
+        assert_eq!(
+            saved.headers,
+            vec!["Accept:*/*", "Cookie:session=abc; theme=dark", "Content-Type:application/json"]
+        );
+
+        // Cookie ヘッダーがあればそちらを使う
+        let saved = entry(
+            r#"{
+              "method": "POST",
+              "url": "https://example.com/upload",
+              "headers": [{"name": "Cookie", "value": "session=abc"}, {"name": "Content-Type", "value": "multipart/form-data; boundary=x"}],
+              "cookies": [{"name": "session", "value": "abc"}],
+              "postData": {
+                "mimeType": "multipart/form-data; boundary=x",
+                "params": [{"name": "title", "value": "a=b"}, {"name": "file", "fileName": "photo.png"}]
+              }
+            }"#,
+        );
+        assert_eq!(saved.headers, vec!["Cookie:session=abc"]);
+        assert_eq!(saved.body_mode, "multipart");
+        assert_eq!(saved.body, "title=a=b\nfile=@photo.png");
+    }
+}
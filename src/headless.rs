@@ -0,0 +1,237 @@
+use super::app;
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+const USAGE: &str = "usage: ruzz [-X METHOD] [-p key=value]... [-H 'Name:value']... [-d BODY|@FILE|@-] [-m raw|json|form|multipart|graphql|wire] [--variables JSON] [-u USER:PASSWORD [--digest]] [--bearer TOKEN] [--auth MODE --auth-param key=value...] [--setting key=value]... [-k] [--cacert FILE] [--cert FILE] [--key FILE] [--cert-password PASSWORD] [-x PROXY] [--proxy-user USER:PASSWORD] [--noproxy HOSTS] [--resolve HOST:PORT:ADDR]... [--connect-to HOST:PORT:HOST:PORT]... [-4|-6] [--unix-socket PATH] [--proto FILE]... [--import-path DIR]... [-a ASSERTION]... [--wait SECONDS] URL";
+
+// WebSocket の応答を待つ秒数
+const DEFAULT_WEBSOCKET_WAIT: Duration = Duration::from_secs(1);
+
+// TUIを起動せずに1件のリクエストを送信する
+//
+// レスポンスボディは標準出力に、ステータスと検証結果は標準エラー出力に書き出す。
+// 終了コードは 0: 成功, 1: 検証失敗, 2: 引数エラーまたは送信失敗
+pub fn run(args: &[String]) -> i32 {
+    let mut app = app::App::default();
+    if let Err(err) = app.load_config() {
+        eprintln!("{}", err);
+        return 2;
+    }
+
+    let mut url = None;
+    let mut params = Vec::new();
+    let mut headers = Vec::new();
+    let mut assertions = Vec::new();
+    let mut digest = false;
+    let mut auth_params = Vec::new();
+    let mut settings = Vec::new();
+    let mut wait = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || match iter.next() {
+            Some(v) => Ok(v.to_string()),
+            None => Err(format!("{} requires a value", arg)),
+        };
+        let result = match arg.as_str() {
+            "-X" | "--request" => value().and_then(|method| {
+                if app.set_request_method(&method) {
+                    Ok(())
+                } else {
+                    Err(format!("unknown method: {}", method))
+                }
+            }),
+            "-p" | "--param" => value().map(|param| params.push(param)),
+            "-H" | "--header" => value().map(|header| headers.push(header)),
+            "-d" | "--data" => value().map(|body| app.set_request_body_text(&body)),
+            "--variables" => value().map(|variables| app.set_request_variables_text(&variables)),
+            "-m" | "--body-mode" => value().and_then(|mode| {
+                if app.set_request_body_mode(&mode) {
+                    Ok(())
+                } else {
+                    Err(format!("unknown body mode: {}", mode))
+                }
+            }),
+            "-u" | "--user" => value().map(|credentials| {
+                let mut parts = credentials.splitn(2, ':');
+                let username = parts.next().unwrap_or("");
+                let password = parts.next().unwrap_or("");
+                app.set_request_auth_mode(if digest { "digest" } else { "basic" });
+                app.set_request_auth_text(&format!("username={}\npassword={}", username, password));
+            }),
+            "--digest" => {
+                digest = true;
+                if app.is_request_auth_mode("basic") {
+                    app.set_request_auth_mode("digest");
+                }
+                Ok(())
+            }
+            "--auth" => value().and_then(|mode| {
+                if app.set_request_auth_mode(&mode) {
+                    Ok(())
+                } else {
+                    Err(format!("unknown auth mode: {}", mode))
+                }
+            }),
+            "--auth-param" => value().map(|param| auth_params.push(param)),
+            "--bearer" => value().map(|token| {
+                app.set_request_auth_mode("bearer");
+                app.set_request_auth_text(&format!("token={}", token));
+            }),
+            "--setting" => value().map(|setting| settings.push(setting)),
+            "-k" | "--insecure" => {
+                settings.push("insecure=true".to_string());
+                Ok(())
+            }
+            "--cacert" => value().map(|path| settings.push(format!("ca_cert={}", path))),
+            "--cert" => value().map(|path| settings.push(format!("client_cert={}", path))),
+            "--key" => value().map(|path| settings.push(format!("client_key={}", path))),
+            "-x" | "--proxy" => value().map(|proxy| settings.push(format!("proxy={}", proxy))),
+            "--proxy-user" => value().map(|auth| settings.push(format!("proxy_auth={}", auth))),
+            "--noproxy" => value().map(|hosts| settings.push(format!("no_proxy={}", hosts))),
+            "--resolve" => value().map(|entry| settings.push(format!("resolve={}", entry))),
+            "--connect-to" => value().map(|entry| settings.push(format!("connect_to={}", entry))),
+            "--unix-socket" => value().map(|path| settings.push(format!("unix_socket={}", path))),
+            "--proto" => value().map(|path| settings.push(format!("proto={}", path))),
+            "--import-path" => value().map(|dir| settings.push(format!("proto_path={}", dir))),
+            "-4" | "--ipv4" => {
+                settings.push("ip_version=ipv4".to_string());
+                Ok(())
+            }
+            "-6" | "--ipv6" => {
+                settings.push("ip_version=ipv6".to_string());
+                Ok(())
+            }
+            "--cert-password" => {
+                value().map(|password| settings.push(format!("client_cert_password={}", password)))
+            }
+            "-a" | "--assert" => value().map(|assertion| assertions.push(assertion)),
+            "--wait" => value().and_then(|seconds| {
+                seconds
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|s| Duration::try_from_secs_f64(s).ok())
+                    .map(|d| wait = Some(d))
+                    .ok_or_else(|| format!("invalid --wait: {}", seconds))
+            }),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return 0;
+            }
+            _ if url.is_none() && !arg.starts_with('-') => {
+                url = Some(arg.to_string());
+                Ok(())
+            }
+            _ => Err(format!("unexpected argument: {}", arg)),
+        };
+
+        if let Err(err) = result {
+            eprintln!("{}\n{}", err, USAGE);
+            return 2;
+        }
+    }
+
+    let url = match url {
+        Some(url) => url,
+        None => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    // TUI と違い、空のボディに雛形を入れて終わることはしない
+    if app.is_wire() && app.request_body_text().trim().is_empty() {
+        eprintln!("wire mode needs a request in the body (-d)\n{}", USAGE);
+        return 2;
+    }
+
+    app.set_request_url_text(&url);
+    app.set_request_params_text(&params.join("\n"));
+    app.set_request_header_text(&headers.join("\n"));
+    app.set_request_assertion_text(&assertions.join("\n"));
+    app.set_request_settings_text(&settings.join("\n"));
+    if !auth_params.is_empty() {
+        app.set_request_auth_text(&auth_params.join("\n"));
+    }
+    app.request();
+    // WebSocket なら -d のメッセージを送り、待ってから閉じる
+    if app.is_websocket_open() {
+        if !app.request_body_text().is_empty() {
+            app.request();
+        }
+        app.wait_websocket(wait.unwrap_or(DEFAULT_WEBSOCKET_WAIT));
+        app.close_websocket();
+        app.wait_websocket(Duration::from_secs(1));
+    }
+    // ストリームは読み終わるまで（--wait があればその秒数まで）待つ
+    app.wait_stream(wait);
+    app.stop_stream();
+    // TLS の情報も出力するので、ここで調べる
+    app.probe_tls();
+
+    report(&app)
+}
+
+// 直近のレスポンスと検証結果を出力し、終了コードを返す
+pub fn report(app: &app::App) -> i32 {
+    if app.tls_insecure() {
+        eprintln!("WARNING: TLS certificate verification is disabled");
+    }
+    // wire のレスポンスは受信したままのバイト列を出力する
+    if let Some(bytes) = app.response_raw_bytes() {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(bytes).and_then(|_| stdout.flush());
+        for line in app.response_header_text().lines() {
+            if line.starts_with("! ") {
+                eprintln!("{}", line);
+            }
+        }
+    }
+    if app.response_status_code().is_none() {
+        eprintln!("request failed: {}", app.response_status_text());
+        return 2;
+    }
+
+    if app.response_raw_bytes().is_none() {
+        println!("{}", app.response_body_text());
+    }
+    let redirects = app.redirect_lines();
+    if redirects.len() > 1 {
+        for line in redirects {
+            eprintln!("{}", line);
+        }
+    }
+    if let Some(remote) = app.response_remote() {
+        eprintln!("remote: {}", remote);
+    }
+    if let Some(proxy) = app.response_proxy() {
+        eprintln!("proxy: {}", proxy);
+    }
+    for line in app.tls_lines() {
+        eprintln!("{}", line);
+    }
+    for line in app.trailer_lines() {
+        eprintln!("trailer {}", line);
+    }
+    let events = app.event_lines();
+    if !events.is_empty() {
+        eprintln!("events: {}", events.len());
+    }
+    eprintln!("{}", app.response_status_text());
+
+    let mut failed = false;
+    for result in app.response_test_results() {
+        if result.passed {
+            eprintln!("PASS {}", result.source);
+        } else {
+            eprintln!("FAIL {} ({})", result.source, result.message);
+            failed = true;
+        }
+    }
+
+    if failed {
+        1
+    } else {
+        0
+    }
+}
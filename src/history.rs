@@ -0,0 +1,22 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use std::time::Duration;
+
+// 送信したリクエストとそのレスポンスの記録
+#[derive(Clone)]
+pub struct Exchange {
+    pub started: DateTime<Utc>,
+    pub method: String,
+    pub url: String,
+    pub request_headers: HeaderMap,
+    pub request_body: String,
+    pub status: u16,
+    pub status_text: String,
+    pub http_version: String,
+    pub response_headers: HeaderMap,
+    pub response_body: String,
+    // レスポンスヘッダーを受け取るまでの時間
+    pub wait: Duration,
+    // レスポンスボディを読み終えるまでの時間
+    pub receive: Duration,
+}
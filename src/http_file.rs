@@ -0,0 +1,376 @@
+use super::body;
+use super::collection::{self, SavedRequest};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+// `###` で区切られた1つの範囲
+#[derive(Clone)]
+struct Block {
+    // `### 名前` の行（先頭の範囲では空）
+    separator: Option<String>,
+    // リクエスト行より前のコメントや変数定義
+    head: Vec<String>,
+    // リクエストが書かれていない範囲は None
+    request: Option<usize>,
+    // リクエスト行に付いていた HTTP バージョン
+    version: Option<String>,
+    // ヘッダーの間のコメントやディレクティブ（前にあるヘッダーの数と行）
+    comments: Vec<(usize, String)>,
+    // リクエスト行から範囲の終わりまでの元の行と、そこから読んだリクエスト（変更がなければそのまま書き戻す）
+    raw: Vec<String>,
+    parsed: Option<SavedRequest>,
+    // レスポンスハンドラーなどボディの後ろの行
+    tail: Vec<String>,
+}
+
+// VS Code REST Client / JetBrains HTTP Client 形式のファイル
+#[derive(Clone)]
+pub struct HttpFile {
+    pub variables: BTreeMap<String, String>,
+    pub requests: Vec<SavedRequest>,
+    blocks: Vec<Block>,
+}
+
+fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('#') || line.starts_with("//")
+}
+
+// `@name = value` を変数定義として取り出す
+fn variable(line: &str) -> Option<(String, String)> {
+    let rest = line.trim().strip_prefix('@')?;
+    let mut parts = rest.splitn(2, '=');
+    let name = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    if name.is_empty() || name.contains(' ') {
+        return None;
+    }
+    Some((name.to_string(), value.to_string()))
+}
+
+// `# @name login` からリクエスト名を取り出す
+fn request_name(line: &str) -> Option<String> {
+    let line = line.trim_start().trim_start_matches('#').trim_start_matches('/');
+    let name = line.trim().strip_prefix("@name")?;
+    Some(name.trim().to_string())
+}
+
+fn is_tail(line: &str) -> bool {
+    line.starts_with("> ") || line.starts_with(">> ") || line.starts_with("<> ")
+}
+
+impl HttpFile {
+    pub fn parse(text: &str) -> HttpFile {
+        let mut file = HttpFile {
+            variables: BTreeMap::new(),
+            requests: Vec::new(),
+            blocks: Vec::new(),
+        };
+
+        let mut chunks: Vec<(Option<String>, Vec<&str>)> = vec![(None, Vec::new())];
+        for line in text.lines() {
+            if line.starts_with("###") {
+                chunks.push((Some(line.to_string()), Vec::new()));
+            } else if let Some(chunk) = chunks.last_mut() {
+                chunk.1.push(line);
+            }
+        }
+
+        for (separator, lines) in chunks {
+            if separator.is_none() && lines.is_empty() {
+                continue;
+            }
+            file.parse_block(separator, &lines);
+        }
+
+        file
+    }
+
+    fn parse_block(&mut self, separator: Option<String>, lines: &[&str]) {
+        let mut block = Block {
+            separator,
+            head: Vec::new(),
+            request: None,
+            version: None,
+            comments: Vec::new(),
+            raw: Vec::new(),
+            parsed: None,
+            tail: Vec::new(),
+        };
+        let mut name = block
+            .separator
+            .as_ref()
+            .map(|s| s.trim_start_matches('#').trim().to_string())
+            .unwrap_or_default();
+
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            if let Some((key, value)) = variable(line) {
+                // 先に定義された変数を参照できる
+                let value = collection::render(&value, &self.variables);
+                self.variables.insert(key, value);
+            } else if let Some(n) = request_name(line) {
+                name = n;
+            } else if !line.trim().is_empty() && !is_comment(line) {
+                break;
+            }
+            block.head.push(line.to_string());
+            i += 1;
+        }
+
+        if i == lines.len() {
+            self.blocks.push(block);
+            return;
+        }
+
+        block.raw = lines[i..].iter().map(|l| l.to_string()).collect();
+        // リクエスト行: `METHOD URL [HTTP/1.1]` または URL のみ
+        let mut parts: Vec<&str> = lines[i].split_whitespace().collect();
+        let method = if METHODS.contains(&parts[0].to_uppercase().as_str()) {
+            parts.remove(0).to_uppercase()
+        } else {
+            "GET".to_string()
+        };
+        if parts.len() > 1 && parts[parts.len() - 1].starts_with("HTTP/") {
+            block.version = parts.pop().map(|v| v.to_string());
+        }
+        let mut url = parts.join(" ");
+        i += 1;
+
+        // 複数行に分けて書かれたクエリ
+        while i < lines.len() {
+            let line = lines[i].trim();
+            if line.starts_with('?') || line.starts_with('&') {
+                url.push_str(line);
+                i += 1;
+            } else {
+                break;
+            }
+        }
+
+        let mut headers = Vec::new();
+        while i < lines.len() && !lines[i].trim().is_empty() {
+            if is_comment(lines[i]) {
+                block.comments.push((headers.len(), lines[i].to_string()));
+            } else {
+                let mut header = lines[i].splitn(2, ':');
+                let key = header.next().unwrap_or("").trim();
+                let value = header.next().unwrap_or("").trim();
+                headers.push(format!("{}:{}", key, value));
+            }
+            i += 1;
+        }
+        // ヘッダーとボディの間の空行
+        i += 1;
+
+        let mut body: Vec<&str> = Vec::new();
+        while i < lines.len() {
+            if is_tail(lines[i]) {
+                block.tail = lines[i..].iter().map(|l| l.to_string()).collect();
+                while block.tail.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+                    block.tail.pop();
+                }
+                break;
+            }
+            body.push(lines[i]);
+            i += 1;
+        }
+        while body.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+            body.pop();
+        }
+
+        // `< ./file` はファイルをボディにする
+        let mut body = body.join("\n");
+        if let Some(path) = body.strip_prefix("< ").filter(|b| !b.contains('\n')) {
+            body = format!("@{}", path.trim());
+        }
+
+        if name.is_empty() {
+            name = url.to_string();
+        }
+        let saved = SavedRequest {
+            name,
+            method,
+            url,
+            headers,
+            body,
+            ..SavedRequest::default()
+        };
+        block.request = Some(self.requests.len());
+        block.parsed = Some(saved.clone());
+        self.requests.push(saved);
+        self.blocks.push(block);
+    }
+
+    pub fn load(path: &Path) -> Result<HttpFile, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        Ok(HttpFile::parse(&text))
+    }
+
+    // 編集したリクエストを差し替えてファイルに書き戻す
+    pub fn save(&mut self, path: &Path, index: usize, saved: &SavedRequest) -> Result<(), String> {
+        if index >= self.requests.len() {
+            return Err(format!("request {} does not exist", index));
+        }
+        self.requests[index] = saved.clone();
+        fs::write(path, self.to_text())
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    fn to_text(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for block in &self.blocks {
+            if let Some(separator) = &block.separator {
+                lines.push(separator.to_string());
+            }
+            lines.extend(block.head.iter().cloned());
+
+            let saved = match block.request {
+                Some(index) => &self.requests[index],
+                None => continue,
+            };
+            if block.parsed.as_ref() == Some(saved) {
+                lines.extend(block.raw.iter().cloned());
+                continue;
+            }
+            let mut url = saved.url.to_string();
+            if !saved.params.is_empty() {
+                url.push(if url.contains('?') { '&' } else { '?' });
+                url.push_str(&saved.params.join("&"));
+            }
+            match &block.version {
+                Some(version) => lines.push(format!("{} {} {}", saved.method, url, version)),
+                None => lines.push(format!("{} {}", saved.method, url)),
+            }
+            // コメントは同じ数のヘッダーの後ろに戻す（ヘッダーが減っていれば最後に置く）
+            let mut comments = block.comments.iter();
+            let mut comment = comments.next();
+            for (i, header) in saved.headers.iter().enumerate() {
+                while let Some((_, line)) = comment.filter(|(before, _)| *before <= i) {
+                    lines.push(line.to_string());
+                    comment = comments.next();
+                }
+                let mut header = header.splitn(2, ':');
+                let key = header.next().unwrap_or("").trim();
+                let value = header.next().unwrap_or("").trim();
+                lines.push(format!("{}: {}", key, value));
+            }
+            lines.extend(comment.into_iter().chain(comments).map(|(_, line)| line.to_string()));
+            if let Some(path) = body::file_reference(&saved.body) {
+                lines.push("".to_string());
+                lines.push(format!("< {}", path));
+            } else if !saved.body.is_empty() {
+                lines.push("".to_string());
+                lines.extend(saved.body.lines().map(|l| l.to_string()));
+            }
+            if !block.tail.is_empty() {
+                lines.push("".to_string());
+                lines.extend(block.tail.iter().cloned());
+            }
+            lines.push("".to_string());
+        }
+
+        let mut text = lines.join("\n");
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "@host = https://api.example.com
+# 共通のトークン
+@token = abc
+
+### ログイン
+# @name login
+POST {{host}}/login HTTP/1.1
+Content-Type: application/json
+# @no-redirect
+// 認証なし
+Accept: application/json
+
+{
+  \"user\": \"alice\"
+}
+
+> {% client.global.set(\"token\", response.body.token); %}
+
+### 一覧
+GET {{host}}/items
+    ?page=1
+    &size=20
+Authorization: Bearer {{token}}
+
+###
+# ボディはファイル
+PUT {{host}}/items/1
+Content-Type: text/plain
+
+< ./item.txt
+";
+
+    #[test]
+    fn parse_reads_blocks_titles_and_bodies() {
+        let file = HttpFile::parse(FILE);
+        assert_eq!(file.variables["host"], "https://api.example.com");
+        assert_eq!(file.variables["token"], "abc");
+        let names: Vec<&str> = file.requests.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["login", "一覧", "{{host}}/items/1"]);
+
+        let login = &file.requests[0];
+        assert_eq!(login.method, "POST");
+        assert_eq!(login.url, "{{host}}/login");
+        assert_eq!(login.headers, vec!["Content-Type:application/json", "Accept:application/json"]);
+        assert_eq!(login.body, "{\n  \"user\": \"alice\"\n}");
+        assert_eq!(file.requests[1].url, "{{host}}/items?page=1&size=20");
+        assert_eq!(file.requests[2].body, "@./item.txt");
+    }
+
+    #[test]
+    fn unchanged_file_round_trips() {
+        let file = HttpFile::parse(FILE);
+        assert_eq!(file.to_text(), FILE);
+        assert_eq!(HttpFile::parse("GET https://example.com").to_text(), "GET https://example.com\n");
+    }
+
+    #[test]
+    fn edited_request_keeps_comments_and_other_blocks() {
+        let mut file = HttpFile::parse(FILE);
+        let mut login = file.requests[0].clone();
+        login.headers.push("X-Trace:1".to_string());
+        login.body = "{}".to_string();
+        file.requests[0] = login;
+
+        let text = file.to_text();
+        assert!(text.contains(
+            "POST {{host}}/login HTTP/1.1\nContent-Type: application/json\n# @no-redirect\n// 認証なし\n\
+             Accept: application/json\nX-Trace: 1\n\n{}\n\n> {% client.global.set"
+        ), "{}", text);
+        // 他の範囲はそのまま
+        assert!(text.contains("### 一覧\nGET {{host}}/items\n    ?page=1\n    &size=20\n"));
+        assert!(text.starts_with("@host = https://api.example.com\n# 共通のトークン\n"));
+
+        let reparsed = HttpFile::parse(&text);
+        assert_eq!(reparsed.requests, file.requests);
+    }
+
+    #[test]
+    fn comments_move_after_the_remaining_headers() {
+        let mut file = HttpFile::parse("GET https://example.com\nA: 1\n# keep\nB: 2\n");
+        file.requests[0].headers = vec!["A:1".to_string()];
+        assert_eq!(file.to_text(), "GET https://example.com\nA: 1\n# keep\n");
+    }
+}
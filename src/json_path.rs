@@ -0,0 +1,108 @@
+use serde_json::Value;
+
+// JSONパスの1要素
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+// `$.data.items[0].name` や `$["content-type"]` 形式のパスを分解する
+fn parse(path: &str) -> Result<Vec<Segment>, String> {
+    let path = path.trim();
+    let rest = path
+        .strip_prefix('$')
+        .ok_or_else(|| format!("json path must start with '$': {}", path))?;
+
+    let chars: Vec<char> = rest.chars().collect();
+    let mut segments = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && chars[end] != '.' && chars[end] != '[' {
+                    end += 1;
+                }
+                if start == end {
+                    return Err(format!("empty key in json path: {}", path));
+                }
+                segments.push(Segment::Key(chars[start..end].iter().collect()));
+                i = end;
+            }
+            '[' => {
+                let end = chars[i..]
+                    .iter()
+                    .position(|c| *c == ']')
+                    .map(|p| p + i)
+                    .ok_or_else(|| format!("unclosed '[' in json path: {}", path))?;
+                let inner: String = chars[i + 1..end].iter().collect();
+                let inner = inner.trim();
+                if let Ok(index) = inner.parse::<usize>() {
+                    segments.push(Segment::Index(index));
+                } else {
+                    let key = inner.trim_matches(|c| c == '"' || c == '\'');
+                    segments.push(Segment::Key(key.to_string()));
+                }
+                i = end + 1;
+            }
+            c => return Err(format!("unexpected '{}' in json path: {}", c, path)),
+        }
+    }
+
+    Ok(segments)
+}
+
+// JSONパスが指す値を取り出す
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Result<Option<&'a Value>, String> {
+    let mut current = value;
+    for segment in parse(path)? {
+        let next = match segment {
+            Segment::Key(key) => current.get(key.as_str()),
+            Segment::Index(index) => current.get(index),
+        };
+        match next {
+            Some(v) => current = v,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn lookup_follows_keys_indices_and_brackets() {
+        let value = json!({"data": {"items": [{"name": "a"}, {"name": "b"}], "content-type": "json"}});
+        assert_eq!(lookup(&value, "$").unwrap(), Some(&value));
+        assert_eq!(lookup(&value, "$.data.items[1].name").unwrap(), Some(&json!("b")));
+        assert_eq!(lookup(&value, "$.data[\"content-type\"]").unwrap(), Some(&json!("json")));
+        assert_eq!(lookup(&value, "$['data']['items'][ 0 ]").unwrap(), Some(&json!({"name": "a"})));
+        assert_eq!(lookup(&json!([1, [2, 3]]), "$[1][0]").unwrap(), Some(&json!(2)));
+    }
+
+    #[test]
+    fn missing_paths_are_none() {
+        let value = json!({"items": [1], "name": "a"});
+        assert_eq!(lookup(&value, "$.missing").unwrap(), None);
+        assert_eq!(lookup(&value, "$.items[1]").unwrap(), None);
+        assert_eq!(lookup(&value, "$.name.first").unwrap(), None);
+        assert_eq!(lookup(&value, "$.items.name").unwrap(), None);
+        // オブジェクトに数字の添字は使えない
+        assert_eq!(lookup(&json!({"0": "zero"}), "$[0]").unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_paths_are_errors() {
+        let value = json!({});
+        assert!(lookup(&value, "data").unwrap_err().contains("must start with '$'"));
+        assert!(lookup(&value, "$.").unwrap_err().contains("empty key"));
+        assert!(lookup(&value, "$.a..b").unwrap_err().contains("empty key"));
+        assert!(lookup(&value, "$[0").unwrap_err().contains("unclosed '['"));
+        assert!(lookup(&value, "$a").unwrap_err().contains("unexpected 'a'"));
+    }
+}
@@ -32,19 +32,117 @@ pub fn view_input_event(input: &Key, app: &mut app::App) -> bool {
 
         Key::F(7) => app.jump_response_body(),
 
+        Key::F(8) => app.jump_request_assertion(),
+
+        Key::F(9) => app.jump_request_body_mode(),
+
+        Key::F(10) => app.jump_request_auth(),
+
+        Key::F(11) => app.jump_request_auth_mode(),
+
+        Key::F(12) => app.jump_request_settings(),
+
         Key::Char('\n') => app.request(),
 
+        Key::Char('o') => app.open_request_list(),
+
+        Key::Char('c') => app.open_cookies(),
+
+        Key::Char('S') => app.open_settings(),
+
+        Key::Char('r') => app.open_redirects(),
+
+        Key::Char('t') => app.open_tls(),
+
+        Key::Char('p') => app.ping_websocket(),
+
+        Key::Char('x') => app.stop(),
+
+        Key::Char('v') => app.open_events(),
+
+        Key::Char('g') => app.fetch_graphql_schema(),
+
+        Key::Char('G') => app.open_graphql(),
+
+        Key::Char('m') => app.open_grpc(),
+
+        Key::Char('e') => app.export_har(),
+
+        Key::Char('s') => app.save_request(),
+
+        Key::Char('E') => app.open_external(),
+
         _ => {}
     }
 
-    return true;
+    true
+}
+
+// ポップアップ表示中の入力ハンドラー
+pub fn popup_input_event(input: &Key, app: &mut app::App) {
+    match app.popup() {
+        app::Popup::RequestList => match input {
+            Key::Down | Key::Char('j') => app.next_saved_request(),
+            Key::Up | Key::Char('k') => app.prev_saved_request(),
+            Key::Char('\n') => app.load_selected_request(),
+            Key::Esc | Key::Char('q') => app.close_popup(),
+            _ => {}
+        },
+        app::Popup::Cookies => match input {
+            Key::Down | Key::Char('j') => app.next_cookie(),
+            Key::Up | Key::Char('k') => app.prev_cookie(),
+            Key::Char('d') => app.delete_selected_cookie(),
+            Key::Char('D') => app.clear_cookies(),
+            Key::Char('E') => app.open_external(),
+            Key::Esc | Key::Char('q') => app.close_popup(),
+            _ => {}
+        },
+        app::Popup::Settings => match input {
+            Key::Char('E') => app.open_external(),
+            Key::Esc | Key::Char('q') => app.close_popup(),
+            _ => {}
+        },
+        app::Popup::Redirects => match input {
+            Key::Down | Key::Char('j') => app.next_redirect(),
+            Key::Up | Key::Char('k') => app.prev_redirect(),
+            Key::Char('\n') | Key::Esc | Key::Char('q') => app.close_popup(),
+            _ => {}
+        },
+        app::Popup::Events => match input {
+            Key::Down | Key::Char('j') => app.next_event(),
+            Key::Up | Key::Char('k') => app.prev_event(),
+            Key::Char('x') => app.stop_stream(),
+            Key::Char('\n') | Key::Esc | Key::Char('q') => app.close_popup(),
+            _ => {}
+        },
+        app::Popup::GraphQL => match input {
+            Key::Char('\n') | Key::Esc | Key::Char('q') => app.close_popup(),
+            _ => {}
+        },
+        app::Popup::Grpc => match input {
+            Key::Down | Key::Char('j') => app.next_grpc_method(),
+            Key::Up | Key::Char('k') => app.prev_grpc_method(),
+            Key::Char('\n') => app.select_grpc_method(),
+            Key::Esc | Key::Char('q') => app.close_popup(),
+            _ => {}
+        },
+        app::Popup::Tls => match input {
+            Key::Char('\n') | Key::Esc | Key::Char('q') => app.close_popup(),
+            _ => {}
+        },
+        app::Popup::NoPopup => {}
+    }
 }
 
 // edit状態の入力ハンドラー
 pub fn edit_input_event(input: &Key, app: &mut app::App) {
     match input {
         Key::Char('\n') => {
-            if app.is_request_url_edit() || app.is_request_method_edit() {
+            if app.is_request_url_edit()
+                || app.is_request_method_edit()
+                || app.is_request_auth_mode_edit()
+                || app.is_request_body_mode_edit()
+            {
                 app.request();
             } else {
                 app.insert_text('\n');
@@ -56,28 +154,37 @@ pub fn edit_input_event(input: &Key, app: &mut app::App) {
         Key::Esc => {
             app.change_view_mode();
         }
-        Key::Right => {
-            if app.is_request_method_edit() {
-                app.next_select_on_request_method();
-            }
+        Key::Right if app.is_request_method_edit() => {
+            app.next_select_on_request_method();
             // TODO: 2文字以上の移動+削除が想定外の動きをする
             // app.right_move_cursor();
         }
-        Key::Left => {
-            if app.is_request_method_edit() {
-                app.prev_select_on_request_method();
-            }
+        Key::Left if app.is_request_method_edit() => {
+            app.prev_select_on_request_method();
             // TODO: 2文字以上の移動+削除が想定外の動きをする
             // app.left_move_cursor();
         }
+        Key::Right if app.is_request_auth_mode_edit() => {
+            app.next_select_on_request_auth_mode();
+        }
+        Key::Left if app.is_request_auth_mode_edit() => {
+            app.prev_select_on_request_auth_mode();
+        }
+        Key::Right if app.is_request_body_mode_edit() => {
+            app.next_select_on_request_body_mode();
+        }
+        Key::Left if app.is_request_body_mode_edit() => {
+            app.prev_select_on_request_body_mode();
+        }
         Key::Backspace => {
             app.delete_text();
         }
+        Key::Char('\t') if app.is_request_body_edit() && app.is_graphql() => {
+            app.complete_graphql();
+        }
 
-        Key::Char(c) => {
-            if c.is_ascii_graphic() {
-                app.insert_text(*c)
-            }
+        Key::Char(c) if c.is_ascii_graphic() || *c == ' ' => {
+            app.insert_text(*c)
         }
         _ => {}
     }
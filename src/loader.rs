@@ -0,0 +1,137 @@
+use super::app;
+use super::collection::{Collection, Environment, SavedRequest};
+use super::har;
+use super::http_file::HttpFile;
+use super::openapi;
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage: ruzz open <collection | openapi spec | har | .http file> [--env NAME]";
+
+// 読み込んだファイル（編集したリクエストの保存先）
+#[derive(Clone)]
+pub enum Source {
+    Collection(PathBuf, Collection),
+    HttpFile(PathBuf, HttpFile),
+    // OpenAPI や HAR など書き戻せない形式
+    ReadOnly(PathBuf),
+}
+
+impl Source {
+    // index 番目のリクエストを差し替えて保存し、保存先を返す
+    pub fn save(&mut self, index: usize, saved: &SavedRequest) -> Result<PathBuf, String> {
+        match self {
+            Source::Collection(path, collection) => {
+                match collection.requests.get_mut(index) {
+                    Some(request) => *request = saved.clone(),
+                    None => return Err(format!("request {} does not exist", index)),
+                }
+                collection.save(path)?;
+                Ok(path.to_path_buf())
+            }
+            Source::HttpFile(path, file) => {
+                file.save(path, index, saved)?;
+                Ok(path.to_path_buf())
+            }
+            Source::ReadOnly(path) => Err(format!("{} cannot be saved", path.display())),
+        }
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)),
+        None => false,
+    }
+}
+
+// JSONまたはYAMLのファイルを読み込む
+fn read_document(path: &Path) -> Result<Value, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    if has_extension(path, &["yaml", "yml"]) {
+        serde_yaml::from_str(&text).map_err(|e| format!("invalid yaml {}: {}", path.display(), e))
+    } else {
+        serde_json::from_str(&text).map_err(|e| format!("invalid json {}: {}", path.display(), e))
+    }
+}
+
+// ファイルの内容から形式を判別して読み込む
+fn load_collection(path: &Path) -> Result<(Collection, Source), String> {
+    if has_extension(path, &["http", "rest"]) {
+        let file = HttpFile::load(path)?;
+        let collection = Collection {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            stop_on_failure: true,
+            variables: file.variables.clone(),
+            requests: file.requests.clone(),
+        };
+        return Ok((collection, Source::HttpFile(path.to_path_buf(), file)));
+    }
+
+    let document = read_document(path)?;
+    if openapi::is_spec(&document) {
+        return Ok((
+            openapi::import(&document),
+            Source::ReadOnly(path.to_path_buf()),
+        ));
+    }
+    if har::is_har(&document) {
+        return Ok((har::import(&document), Source::ReadOnly(path.to_path_buf())));
+    }
+
+    let collection: Collection = serde_json::from_value(document)
+        .map_err(|e| format!("invalid collection {}: {}", path.display(), e))?;
+    let source = Source::Collection(path.to_path_buf(), collection.clone());
+    Ok((collection, source))
+}
+
+// `ruzz open` サブコマンド
+//
+// ファイルを読み込んだ状態のAppを返す
+pub fn open(args: &[String]) -> Result<app::App, String> {
+    let mut path = None;
+    let mut env = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--env" | "-e" => {
+                env = Some(
+                    iter.next()
+                        .ok_or_else(|| format!("--env requires a value\n{}", USAGE))?,
+                )
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}\n{}", arg, USAGE)),
+        }
+    }
+    let path = path.ok_or_else(|| USAGE.to_string())?;
+
+    let mut app = app::App::default();
+    app.load_config()?;
+    let (collection, source) = load_collection(&path)?;
+    for (name, value) in &collection.variables {
+        app.set_variable(name, value);
+    }
+    if let Some(name) = env {
+        let environment = Environment::find(&path, name)?;
+        app.load_environment(&environment)?;
+    }
+
+    app.set_notice(&format!(
+        "loaded {} requests from {} (press o to select)",
+        collection.requests.len(),
+        collection.name
+    ));
+    app.set_saved_requests(collection.requests, source);
+    app.load_selected_request();
+    app.open_cookie_jar(env.map(|e| e.as_str()).unwrap_or("default"));
+
+    Ok(app)
+}
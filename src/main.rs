@@ -1,16 +1,113 @@
 mod app;
+mod assertion;
+mod auth;
+mod body;
+mod collection;
+mod connection;
+mod config;
+mod cookie;
 mod event;
+mod external;
+mod graphql;
+mod grpc;
+mod har;
+mod headless;
+mod history;
+mod http_file;
+mod json_path;
 mod key_handler;
+mod loader;
+mod oauth2;
+mod openapi;
+mod postman;
+mod protobuf;
+mod proxy;
+mod resolve;
+mod runner;
+mod settings;
+mod sigv4;
+mod stream;
+mod tls;
 mod ui;
+mod websocket;
+mod wire;
 
-use std::{error::Error, io};
-use termion::{input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
-use tui::{backend::TermionBackend, Terminal};
+use std::{env, error::Error, io, io::Write, process};
+use termion::{
+    input::MouseTerminal,
+    raw::{IntoRawMode, RawTerminal},
+    screen::{self, AlternateScreen},
+};
+use tui::{backend::Backend, backend::TermionBackend, Terminal};
+
+// マウス入力の受け付けを止める/再開する（termion の MouseTerminal と同じシーケンス）
+const EXIT_MOUSE_SEQUENCE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+const ENTER_MOUSE_SEQUENCE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+
+// TUIを一時停止して外部プログラムを実行し、終わったら画面を元に戻す
+fn suspend<B, F, T>(
+    terminal: &mut Terminal<B>,
+    raw: &RawTerminal<io::Stdout>,
+    events: &event::Events,
+    run: F,
+) -> Result<T, Box<dyn Error>>
+where
+    B: Backend + Write,
+    F: FnOnce() -> T,
+{
+    events.pause();
+    write!(terminal.backend_mut(), "{}{}", EXIT_MOUSE_SEQUENCE, screen::ToMainScreen)?;
+    terminal.show_cursor()?;
+    Write::flush(terminal.backend_mut())?;
+    raw.suspend_raw_mode()?;
+
+    let result = run();
+
+    raw.activate_raw_mode()?;
+    write!(terminal.backend_mut(), "{}{}", screen::ToAlternateScreen, ENTER_MOUSE_SEQUENCE)?;
+    terminal.hide_cursor()?;
+    Write::flush(terminal.backend_mut())?;
+    // 画面全体を描き直す
+    let size = terminal.size()?;
+    terminal.resize(size)?;
+    events.resume();
+
+    Ok(result)
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    // ターミナル初期化
-    let stdout = io::stdout().into_raw_mode()?;
-    let stdout = MouseTerminal::from(stdout);
+    // サブコマンドの振り分け（引数がなければ空の状態でTUIを起動する）
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut app = match args.first().map(|a| a.as_str()) {
+        Some("run") => process::exit(runner::run(&args[1..])),
+        Some("import") => match args.get(1).map(|a| a.as_str()) {
+            Some("postman") => process::exit(postman::run(&args[2..])),
+            _ => {
+                eprintln!("usage: ruzz import postman <collection.json>");
+                process::exit(2);
+            }
+        },
+        Some("open") => match loader::open(&args[1..]) {
+            Ok(app) => app,
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(2);
+            }
+        },
+        Some(_) => process::exit(headless::run(&args)),
+        None => {
+            let mut app = app::App::default();
+            if let Err(err) = app.load_config() {
+                app.set_notice(&err);
+            }
+            app.open_cookie_jar("default");
+            app
+        }
+    };
+
+    // ターミナル初期化（raw モードは外部プログラムの実行中だけ解除する）
+    let raw = io::stdout().into_raw_mode()?;
+    let stdout = MouseTerminal::from(io::stdout());
     let stdout = AlternateScreen::from(stdout);
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
@@ -18,27 +115,49 @@ fn main() -> Result<(), Box<dyn Error>> {
     // イベントハンドラーのセットアップ
     let events = event::Events::new();
 
-    // エディタ等の初期情報
-    let mut app = app::App::default();
-
     loop {
         // UIを描画する
         ui::draw(&mut terminal, &app);
 
         // キー入力のハンドラー
-        if let event::Event::Input(input) = events.next()? {
-            // editモードのキー入力
-            if app.is_mode_edit() {
-                key_handler::edit_input_event(&input, &mut app)
+        let input = match events.next()? {
+            event::Event::Input(input) => Some(input),
+            // WebSocket のフレームやストリームで届いた分を表示する
+            event::Event::Tick => {
+                app.poll_websocket();
+                app.poll_stream();
+                None
             }
+        };
+        if let Some(input) = input {
+            // ポップアップ表示中のキー入力
+            if app.is_popup_open() {
+                key_handler::popup_input_event(&input, &mut app);
+            } else {
+                // editモードのキー入力
+                if app.is_mode_edit() {
+                    key_handler::edit_input_event(&input, &mut app)
+                }
 
-            // viewモードのキー入力
-            if app.is_mode_view() {
-                let result = key_handler::view_input_event(&input, &mut app);
-                if result == false {
-                    break;
+                // viewモードのキー入力
+                if app.is_mode_view() {
+                    let result = key_handler::view_input_event(&input, &mut app);
+                    if !result {
+                        break;
+                    }
                 }
             }
+
+            // 外部エディター/ページャー
+            if let Some(external) = app.take_external() {
+                let result = suspend(&mut terminal, &raw, &events, || match external {
+                    external::External::Editor(text, extension) => {
+                        external::edit(&text, extension).map(Some)
+                    }
+                    external::External::Pager(text) => external::page(&text).map(|_| None),
+                })?;
+                app.finish_external(result);
+            }
         }
     }
 
@@ -0,0 +1,404 @@
+use chrono::{DateTime, Duration, Utc};
+use reqwest::blocking::Client;
+use reqwest::header::HeaderValue;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration as StdDuration, Instant};
+
+use super::auth;
+
+// 認可コードのリダイレクトを待つ時間
+const AUTHORIZE_TIMEOUT: StdDuration = StdDuration::from_secs(180);
+
+// 有効期限の少し前に取り直す
+const EXPIRY_MARGIN_SECONDS: i64 = 30;
+
+// Auth ペインに書かれた OAuth 2.0 の設定
+//
+//   grant:         client_credentials, password, refresh_token, authorization_code
+//   token_url:     トークンエンドポイント
+//   auth_url:      認可エンドポイント（authorization_code）
+//   client_id, client_secret, scope
+//   client_auth:   basic（既定）または body
+//   username, password: password グラント
+//   refresh_token: refresh_token グラント
+//   redirect_port: 認可コードを受け取るポート（既定は空いているポート）
+pub struct Settings {
+    fields: BTreeMap<String, String>,
+}
+
+// 取得したアクセストークン
+#[derive(Clone, Debug)]
+pub struct Token {
+    pub access_token: String,
+    pub token_type: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Token {
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Utc::now() + Duration::seconds(EXPIRY_MARGIN_SECONDS) >= expires_at,
+            None => false,
+        }
+    }
+
+    // Authorization ヘッダーの値
+    pub fn authorization(&self) -> Result<HeaderValue, String> {
+        let token_type = if self.token_type.is_empty() || self.token_type.eq_ignore_ascii_case("bearer") {
+            "Bearer"
+        } else {
+            &self.token_type
+        };
+        format!("{} {}", token_type, self.access_token)
+            .parse()
+            .map_err(|_| "invalid access token".to_string())
+    }
+}
+
+impl Settings {
+    pub fn parse(text: &str) -> Result<Settings, String> {
+        let settings = Settings {
+            fields: auth::fields(text),
+        };
+        if settings.get("token_url").is_empty() {
+            return Err("oauth2 token_url is empty".to_string());
+        }
+        match settings.grant() {
+            "client_credentials" | "password" | "refresh_token" | "authorization_code" => Ok(settings),
+            other => Err(format!("oauth2 grant {} is not supported", other)),
+        }
+    }
+
+    fn get(&self, key: &str) -> &str {
+        self.fields.get(key).map(|v| v.as_str()).unwrap_or("")
+    }
+
+    fn grant(&self) -> &str {
+        match self.get("grant") {
+            "" => "client_credentials",
+            grant => grant,
+        }
+    }
+
+    // キャッシュのキー（同じ設定なら同じトークンを使う）
+    pub fn cache_key(&self) -> String {
+        ["grant", "token_url", "auth_url", "client_id", "scope", "username", "refresh_token"]
+            .iter()
+            .map(|key| match *key {
+                "grant" => self.grant(),
+                key => self.get(key),
+            })
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    // 設定されたグラントでトークンを取得する（client はリクエストと同じ設定で作ったもの）
+    pub fn fetch(&self, client: &Client) -> Result<Token, String> {
+        let mut params = Vec::new();
+        match self.grant() {
+            "client_credentials" => {
+                params.push(("grant_type", "client_credentials".to_string()));
+            }
+            "password" => {
+                params.push(("grant_type", "password".to_string()));
+                params.push(("username", self.get("username").to_string()));
+                params.push(("password", self.get("password").to_string()));
+            }
+            "refresh_token" => return self.refresh(client, self.get("refresh_token")),
+            _ => return self.authorization_code(client),
+        }
+        if !self.get("scope").is_empty() {
+            params.push(("scope", self.get("scope").to_string()));
+        }
+        self.request_token(client, params)
+    }
+
+    // リフレッシュトークンで取り直す
+    pub fn refresh(&self, client: &Client, refresh_token: &str) -> Result<Token, String> {
+        if refresh_token.is_empty() {
+            return Err("oauth2 refresh_token is empty".to_string());
+        }
+        let mut params = vec![
+            ("grant_type", "refresh_token".to_string()),
+            ("refresh_token", refresh_token.to_string()),
+        ];
+        if !self.get("scope").is_empty() {
+            params.push(("scope", self.get("scope").to_string()));
+        }
+        let mut token = self.request_token(client, params)?;
+        // 新しいリフレッシュトークンが返らなければ元のものを使い続ける
+        if token.refresh_token.is_none() {
+            token.refresh_token = Some(refresh_token.to_string());
+        }
+        Ok(token)
+    }
+
+    fn request_token(&self, client: &Client, mut params: Vec<(&str, String)>) -> Result<Token, String> {
+        let client_id = self.get("client_id");
+        let client_secret = self.get("client_secret");
+
+        let mut request = client.post(self.get("token_url"));
+        if self.get("client_auth") == "body" || client_secret.is_empty() {
+            params.push(("client_id", client_id.to_string()));
+            if !client_secret.is_empty() {
+                params.push(("client_secret", client_secret.to_string()));
+            }
+        } else {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        let response = request
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&params)
+            .send()
+            .map_err(|e| format!("oauth2 token request failed: {}", e))?;
+        let status = response.status();
+        let text = response.text().unwrap_or_default();
+        let json: Value = serde_json::from_str(&text)
+            .map_err(|_| format!("oauth2 token endpoint returned {}: {}", status, text))?;
+        if !status.is_success() || json["error"].is_string() {
+            let error = json["error"].as_str().unwrap_or("");
+            let description = json["error_description"].as_str().unwrap_or("");
+            return Err(format!("oauth2 token endpoint returned {} {} {}", status, error, description)
+                .trim()
+                .to_string());
+        }
+
+        let access_token = json["access_token"]
+            .as_str()
+            .ok_or_else(|| "oauth2 token response has no access_token".to_string())?;
+        let expires_in = match &json["expires_in"] {
+            Value::Number(n) => n.as_i64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        };
+        Ok(Token {
+            access_token: access_token.to_string(),
+            token_type: json["token_type"].as_str().unwrap_or("Bearer").to_string(),
+            refresh_token: json["refresh_token"].as_str().map(|s| s.to_string()),
+            // 大きすぎて表せない期限は期限なしとして扱う
+            expires_at: expires_in
+                .and_then(Duration::try_seconds)
+                .and_then(|duration| Utc::now().checked_add_signed(duration)),
+        })
+    }
+
+    // 認可コード + PKCE
+    //
+    // ブラウザで認可ページを開き、ループバックで待ち受けたリダイレクトからコードを受け取る
+    fn authorization_code(&self, client: &Client) -> Result<Token, String> {
+        let auth_url = self.get("auth_url");
+        if auth_url.is_empty() {
+            return Err("oauth2 auth_url is empty".to_string());
+        }
+
+        let port = self.get("redirect_port");
+        let listener = TcpListener::bind(format!("127.0.0.1:{}", if port.is_empty() { "0" } else { port }))
+            .map_err(|e| format!("failed to listen for the oauth2 redirect: {}", e))?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+
+        let verifier = random_string(32)?;
+        let challenge = base64::encode_config(openssl::sha::sha256(verifier.as_bytes()), base64::URL_SAFE_NO_PAD);
+        let state = random_string(16)?;
+
+        let mut url = url::Url::parse(auth_url).map_err(|e| format!("invalid auth_url: {}", e))?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("response_type", "code")
+                .append_pair("client_id", self.get("client_id"))
+                .append_pair("redirect_uri", &redirect_uri)
+                .append_pair("state", &state)
+                .append_pair("code_challenge", &challenge)
+                .append_pair("code_challenge_method", "S256");
+            if !self.get("scope").is_empty() {
+                query.append_pair("scope", self.get("scope"));
+            }
+        }
+        open_browser(url.as_str())?;
+
+        let code = wait_for_code(&listener, &state)?;
+        let params = vec![
+            ("grant_type", "authorization_code".to_string()),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("code_verifier", verifier),
+        ];
+        self.request_token(client, params)
+    }
+}
+
+fn random_string(len: usize) -> Result<String, String> {
+    let mut bytes = vec![0; len];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(base64::encode_config(bytes, base64::URL_SAFE_NO_PAD))
+}
+
+// `$BROWSER` か OS の既定のブラウザで開く
+fn open_browser(url: &str) -> Result<(), String> {
+    let program = match env::var("BROWSER") {
+        Ok(browser) if !browser.trim().is_empty() => browser,
+        _ if cfg!(target_os = "macos") => "open".to_string(),
+        _ => "xdg-open".to_string(),
+    };
+    Command::new(&program)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("failed to start {} ({}); set $BROWSER or open {}", program, e, url))
+}
+
+// リダイレクトを受け取って認可コードを返す
+fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String, String> {
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + AUTHORIZE_TIMEOUT;
+
+    while Instant::now() < deadline {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                thread::sleep(StdDuration::from_millis(100));
+                continue;
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        if let Some(result) = handle_redirect(stream, state) {
+            return result;
+        }
+    }
+    Err("timed out waiting for the oauth2 redirect".to_string())
+}
+
+// `/callback` 以外へのアクセス（favicon など）は None
+fn handle_redirect(mut stream: TcpStream, state: &str) -> Option<Result<String, String>> {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(StdDuration::from_secs(5)));
+
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    let target = request.lines().next()?.split_whitespace().nth(1)?.to_string();
+    let url = url::Url::parse(&format!("http://127.0.0.1{}", target)).ok()?;
+    if url.path() != "/callback" {
+        let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+        return None;
+    }
+
+    let query: BTreeMap<String, String> = url.query_pairs().into_owned().collect();
+    let result = if let Some(error) = query.get("error") {
+        Err(format!(
+            "oauth2 authorization failed: {} {}",
+            error,
+            query.get("error_description").map(|s| s.as_str()).unwrap_or("")
+        )
+        .trim()
+        .to_string())
+    } else if query.get("state").map(|s| s.as_str()) != Some(state) {
+        Err("oauth2 redirect state does not match".to_string())
+    } else {
+        query
+            .get("code")
+            .cloned()
+            .ok_or_else(|| "oauth2 redirect has no code".to_string())
+    };
+
+    let message = match &result {
+        Ok(_) => "Authorization complete. You can close this window and return to ruzz.".to_string(),
+        Err(err) => err.to_string(),
+    };
+    let _ = stream.write_all(
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            message.len(),
+            message
+        )
+        .as_bytes(),
+    );
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ClientSettings;
+    use std::sync::mpsc;
+
+    // 1回だけ body を返すトークンエンドポイント（受け取ったリクエストを返す）
+    fn stub(body: &'static str) -> (u16, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            while !String::from_utf8_lossy(&request).contains("grant_type") {
+                let n = stream.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            sender.send(String::from_utf8_lossy(&request).to_string()).unwrap();
+        });
+        (port, receiver)
+    }
+
+    #[test]
+    fn client_credentials_through_client_settings() {
+        let (port, request) = stub(r#"{"access_token":"abc","token_type":"bearer","expires_in":3600}"#);
+        // 名前解決の設定が効いていればトークンエンドポイントに届く
+        let client_settings = ClientSettings::parse(&format!("resolve=token.test:{}:127.0.0.1", port)).unwrap();
+        let (client, _resolver) = client_settings.build().unwrap();
+        let settings = Settings::parse(&format!(
+            "grant=client_credentials\ntoken_url=http://token.test:{}/token\nclient_id=app\nclient_secret=secret\nscope=read",
+            port
+        ))
+        .unwrap();
+        let token = settings.fetch(&client).unwrap();
+        assert_eq!(token.access_token, "abc");
+        assert_eq!(token.authorization().unwrap(), "Bearer abc");
+        assert!(!token.is_expired());
+
+        let request = request.recv().unwrap();
+        assert!(request.starts_with("POST /token "));
+        assert!(request.contains(&format!("authorization: Basic {}", base64::encode("app:secret"))));
+        assert!(request.contains("grant_type=client_credentials&scope=read"));
+    }
+
+    #[test]
+    fn huge_expires_in_means_no_expiry() {
+        let (port, _request) = stub(r#"{"access_token":"abc","expires_in":99999999999999999}"#);
+        let (client, _resolver) = ClientSettings::default().build().unwrap();
+        let settings = Settings::parse(&format!(
+            "grant=client_credentials\ntoken_url=http://127.0.0.1:{}/token\nclient_id=app\nclient_auth=body",
+            port
+        ))
+        .unwrap();
+        let token = settings.fetch(&client).unwrap();
+        assert_eq!(token.expires_at, None);
+    }
+}
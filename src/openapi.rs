@@ -0,0 +1,403 @@
+use super::collection::{Collection, SavedRequest};
+
+use serde_json::{Map, Value};
+
+const METHODS: [&str; 8] = [
+    "get", "head", "post", "put", "delete", "options", "trace", "patch",
+];
+
+// 例を組み立てるときに辿る参照の深さの上限
+const MAX_DEPTH: usize = 8;
+
+// OpenAPI 3 / Swagger 2 の仕様かどうか
+pub fn is_spec(json: &Value) -> bool {
+    json["openapi"].is_string() || json["swagger"].is_string()
+}
+
+// `#/components/schemas/User` 形式の参照を解決する
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    let mut value = value;
+    for _ in 0..MAX_DEPTH {
+        let reference = match value["$ref"].as_str() {
+            Some(reference) => reference,
+            None => return value,
+        };
+        let pointer = reference.trim_start_matches('#');
+        value = match spec.pointer(pointer) {
+            Some(target) => target,
+            None => return &Value::Null,
+        };
+    }
+    value
+}
+
+// 仕様書の各オペレーションを保存済みリクエストに変換する
+pub fn import(spec: &Value) -> Collection {
+    let base_url = base_url(spec);
+    let mut requests = Vec::new();
+
+    let empty = Map::new();
+    for (path, item) in spec["paths"].as_object().unwrap_or(&empty) {
+        let item = resolve(spec, item);
+        for method in METHODS.iter() {
+            let operation = &item[*method];
+            if !operation.is_object() {
+                continue;
+            }
+            requests.push(operation_request(
+                spec, &base_url, path, method, item, operation,
+            ));
+        }
+    }
+
+    Collection {
+        name: spec["info"]["title"].as_str().unwrap_or("OpenAPI").to_string(),
+        stop_on_failure: true,
+        requests,
+        ..Collection::default()
+    }
+}
+
+// サーバーの URL が相対パス（仕様書を配信しているホストからの相対）なら、ホストは `{{baseUrl}}` 変数にする
+fn base_url(spec: &Value) -> String {
+    if spec["openapi"].is_string() {
+        let server = &spec["servers"][0];
+        let mut url = server["url"].as_str().unwrap_or("").to_string();
+        // サーバー変数はデフォルト値で埋める
+        if let Some(variables) = server["variables"].as_object() {
+            for (name, variable) in variables {
+                let default = variable["default"].as_str().unwrap_or("");
+                url = url.replace(&format!("{{{}}}", name), default);
+            }
+        }
+        if !url.contains("://") {
+            url = format!("{{{{baseUrl}}}}/{}", url.trim_start_matches('/'));
+        }
+        return url.trim_end_matches('/').to_string();
+    }
+
+    let base_path = spec["basePath"].as_str().unwrap_or("").trim_end_matches('/');
+    match spec["host"].as_str() {
+        Some(host) => {
+            let scheme = spec["schemes"][0].as_str().unwrap_or("https");
+            format!("{}://{}{}", scheme, host, base_path)
+        }
+        None => format!("{{{{baseUrl}}}}{}", base_path),
+    }
+}
+
+// `{id}` を ruzz の変数 `{{id}}` に置き換える
+fn path_template(path: &str) -> String {
+    path.replace('{', "{{").replace('}', "}}")
+}
+
+fn operation_request(
+    spec: &Value,
+    base_url: &str,
+    path: &str,
+    method: &str,
+    item: &Value,
+    operation: &Value,
+) -> SavedRequest {
+    let name = operation["operationId"]
+        .as_str()
+        .or_else(|| operation["summary"].as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("{} {}", method.to_uppercase(), path));
+
+    let mut saved = SavedRequest {
+        name,
+        folder: operation["tags"][0].as_str().unwrap_or("").to_string(),
+        method: method.to_uppercase(),
+        url: format!("{}{}", base_url, path_template(path)),
+        ..SavedRequest::default()
+    };
+
+    // パス共通のパラメータとオペレーションのパラメータをまとめる
+    let mut parameters: Vec<&Value> = Vec::new();
+    for list in [&item["parameters"], &operation["parameters"]].iter() {
+        if let Some(list) = list.as_array() {
+            for parameter in list {
+                parameters.push(resolve(spec, parameter));
+            }
+        }
+    }
+
+    for parameter in &parameters {
+        let name = parameter["name"].as_str().unwrap_or("");
+        let required = parameter["required"].as_bool().unwrap_or(false);
+        match parameter["in"].as_str().unwrap_or("") {
+            "query" if required => saved
+                .params
+                .push(format!("{}={}", name, parameter_value(spec, parameter))),
+            "header" if required => saved
+                .headers
+                .push(format!("{}:{}", name, parameter_value(spec, parameter))),
+            // Swagger 2 のリクエストボディ
+            "body" => {
+                let schema = &parameter["schema"];
+                saved.body = pretty(&example(spec, schema, 0));
+                saved.body_mode = "json".to_string();
+            }
+            _ => {}
+        }
+    }
+
+    // OpenAPI 3 のリクエストボディ
+    let request_body = resolve(spec, &operation["requestBody"]);
+    if let Some(content) = request_body["content"].as_object() {
+        let json = content
+            .iter()
+            .find(|(media_type, _)| media_type.contains("json"));
+        if let Some((media_type, media)) = json {
+            let body = if !media["example"].is_null() {
+                media["example"].clone()
+            } else {
+                example(spec, &media["schema"], 0)
+            };
+            saved.body = pretty(&body);
+            saved.body_mode = "json".to_string();
+            // JSON モードは application/json を付けるので、それ以外のメディアタイプだけヘッダーにする
+            if media_type != "application/json" {
+                saved.headers.push(format!("Content-Type:{}", media_type));
+            }
+        } else if let Some((media_type, _)) = content.iter().next() {
+            saved.headers.push(format!("Content-Type:{}", media_type));
+        }
+    }
+
+    apply_security(spec, operation, &mut saved);
+
+    saved
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+// パラメータの例があればそれを、なければ変数のプレースホルダーを返す
+fn parameter_value(spec: &Value, parameter: &Value) -> String {
+    let name = parameter["name"].as_str().unwrap_or("");
+    let schema = resolve(spec, &parameter["schema"]);
+    let candidates = [
+        &parameter["example"],
+        &schema["example"],
+        &schema["default"],
+        &parameter["default"],
+    ];
+    for candidate in candidates.iter() {
+        match candidate {
+            Value::Null => continue,
+            Value::String(s) => return s.to_string(),
+            v => return v.to_string(),
+        }
+    }
+    format!("{{{{{}}}}}", name)
+}
+
+// 認証方式に応じたヘッダーやクエリを追加する
+fn apply_security(spec: &Value, operation: &Value, saved: &mut SavedRequest) {
+    let requirements = if operation["security"].is_array() {
+        &operation["security"]
+    } else {
+        &spec["security"]
+    };
+    let schemes = if spec["openapi"].is_string() {
+        &spec["components"]["securitySchemes"]
+    } else {
+        &spec["securityDefinitions"]
+    };
+
+    // 複数の候補がある場合は最初の組み合わせを使う
+    let requirement = match requirements[0].as_object() {
+        Some(requirement) => requirement,
+        None => return,
+    };
+    for name in requirement.keys() {
+        let scheme = resolve(spec, &schemes[name.as_str()]);
+        match scheme["type"].as_str().unwrap_or("") {
+            "apiKey" => {
+                let key = scheme["name"].as_str().unwrap_or(name);
+                let placeholder = format!("{{{{{}}}}}", name);
+                match scheme["in"].as_str().unwrap_or("header") {
+                    "query" => saved.params.push(format!("{}={}", key, placeholder)),
+                    "cookie" => saved
+                        .headers
+                        .push(format!("Cookie:{}={}", key, placeholder)),
+                    _ => saved.headers.push(format!("{}:{}", key, placeholder)),
+                }
+            }
+            "http" => match scheme["scheme"].as_str().unwrap_or("").to_lowercase().as_str() {
+                "basic" => saved
+                    .headers
+                    .push("Authorization:Basic {{basic_credentials}}".to_string()),
+                _ => saved
+                    .headers
+                    .push("Authorization:Bearer {{token}}".to_string()),
+            },
+            "basic" => saved
+                .headers
+                .push("Authorization:Basic {{basic_credentials}}".to_string()),
+            "oauth2" | "openIdConnect" => saved
+                .headers
+                .push("Authorization:Bearer {{access_token}}".to_string()),
+            _ => {}
+        }
+    }
+}
+
+// スキーマから例となる値を組み立てる
+fn example(spec: &Value, schema: &Value, depth: usize) -> Value {
+    if depth > MAX_DEPTH {
+        return Value::Null;
+    }
+    let schema = resolve(spec, schema);
+
+    if !schema["example"].is_null() {
+        return schema["example"].clone();
+    }
+    if !schema["default"].is_null() {
+        return schema["default"].clone();
+    }
+    if let Some(first) = schema["enum"].as_array().and_then(|e| e.first()) {
+        return first.clone();
+    }
+    if let Some(all_of) = schema["allOf"].as_array() {
+        let mut merged = Map::new();
+        for part in all_of {
+            if let Value::Object(object) = example(spec, part, depth + 1) {
+                merged.extend(object);
+            }
+        }
+        return Value::Object(merged);
+    }
+    for key in ["oneOf", "anyOf"].iter() {
+        if let Some(first) = schema[*key].as_array().and_then(|s| s.first()) {
+            return example(spec, first, depth + 1);
+        }
+    }
+
+    let kind = match schema["type"].as_str() {
+        Some(kind) => kind,
+        None if schema["properties"].is_object() => "object",
+        None => "",
+    };
+    match kind {
+        "object" => {
+            let mut object = Map::new();
+            if let Some(properties) = schema["properties"].as_object() {
+                for (name, property) in properties {
+                    object.insert(name.to_string(), example(spec, property, depth + 1));
+                }
+            }
+            Value::Object(object)
+        }
+        "array" => Value::Array(vec![example(spec, &schema["items"], depth + 1)]),
+        "string" => Value::String(
+            match schema["format"].as_str().unwrap_or("") {
+                "date-time" => "1970-01-01T00:00:00Z",
+                "date" => "1970-01-01",
+                "email" => "user@example.com",
+                "uuid" => "00000000-0000-0000-0000-000000000000",
+                "uri" | "url" => "https://example.com",
+                _ => "string",
+            }
+            .to_string(),
+        ),
+        "integer" => Value::from(0),
+        "number" => Value::from(0.0),
+        "boolean" => Value::Bool(true),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r##"{
+      "openapi": "3.0.0",
+      "info": {"title": "Pets"},
+      "servers": [{
+        "url": "https://{region}.example.com/{version}/",
+        "variables": {"region": {"default": "eu"}, "version": {"default": "v2"}}
+      }],
+      "paths": {
+        "/pets/{petId}": {
+          "parameters": [{"name": "petId", "in": "path", "required": true, "schema": {"type": "integer"}}],
+          "get": {
+            "operationId": "getPet",
+            "tags": ["pets"],
+            "parameters": [
+              {"name": "fields", "in": "query", "required": true, "example": "name,tag"},
+              {"name": "limit", "in": "query", "required": true, "schema": {"type": "integer", "default": 10}},
+              {"name": "sort", "in": "query", "required": true},
+              {"name": "debug", "in": "query"},
+              {"name": "X-Request-Id", "in": "header", "required": true}
+            ]
+          },
+          "put": {
+            "operationId": "updatePet",
+            "requestBody": {"$ref": "#/components/requestBodies/Pet"}
+          },
+          "patch": {
+            "operationId": "patchPet",
+            "requestBody": {"content": {"application/merge-patch+json": {"schema": {"$ref": "#/components/schemas/Pet"}}}}
+          }
+        }
+      },
+      "components": {
+        "requestBodies": {
+          "Pet": {"content": {"application/json": {"example": {"name": "Rex", "tag": "dog"}}}}
+        },
+        "schemas": {
+          "Pet": {"type": "object", "properties": {"name": {"type": "string"}, "born": {"type": "string", "format": "date"}}}
+        }
+      }
+    }"##;
+
+    fn spec(text: &str) -> Value {
+        serde_json::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn imports_path_and_query_parameters() {
+        let collection = import(&spec(SPEC));
+        assert_eq!(collection.name, "Pets");
+        let get = &collection.requests[0];
+        assert_eq!((get.name.as_str(), get.folder.as_str(), get.method.as_str()), ("getPet", "pets", "GET"));
+        assert_eq!(get.url, "https://eu.example.com/v2/pets/{{petId}}");
+        assert_eq!(get.params, vec!["fields=name,tag", "limit=10", "sort={{sort}}"]);
+        assert_eq!(get.headers, vec!["X-Request-Id:{{X-Request-Id}}"]);
+    }
+
+    #[test]
+    fn example_bodies_use_json_mode() {
+        let collection = import(&spec(SPEC));
+        let put = &collection.requests[1];
+        assert_eq!(put.body_mode, "json");
+        assert!(put.headers.is_empty());
+        assert_eq!(serde_json::from_str::<Value>(&put.body).unwrap(), serde_json::json!({"name": "Rex", "tag": "dog"}));
+
+        // application/json 以外はヘッダーで指定する
+        let patch = &collection.requests[2];
+        assert_eq!(patch.body_mode, "json");
+        assert_eq!(patch.headers, vec!["Content-Type:application/merge-patch+json"]);
+        assert_eq!(
+            serde_json::from_str::<Value>(&patch.body).unwrap(),
+            serde_json::json!({"name": "string", "born": "1970-01-01"})
+        );
+    }
+
+    #[test]
+    fn relative_servers_keep_a_base_url_variable() {
+        let relative = spec(r#"{"openapi": "3.0.0", "servers": [{"url": "/api/v1"}]}"#);
+        assert_eq!(base_url(&relative), "{{baseUrl}}/api/v1");
+        assert_eq!(base_url(&spec(r#"{"openapi": "3.1.0"}"#)), "{{baseUrl}}");
+        assert_eq!(base_url(&spec(r#"{"swagger": "2.0", "basePath": "/v1/"}"#)), "{{baseUrl}}/v1");
+        assert_eq!(
+            base_url(&spec(r#"{"swagger": "2.0", "host": "api.example.com", "schemes": ["http"], "basePath": "/v1"}"#)),
+            "http://api.example.com/v1"
+        );
+    }
+}
@@ -0,0 +1,596 @@
+use super::collection::{Collection, Environment, SavedRequest};
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage: ruzz import postman <collection.json> [-e environment.json]... [-o output.json]";
+
+// 変換できなかった項目の一覧
+#[derive(Default)]
+pub struct ImportReport {
+    pub unsupported: Vec<String>,
+}
+
+impl ImportReport {
+    fn add(&mut self, location: &str, message: &str) {
+        self.unsupported.push(format!("{}: {}", location, message));
+    }
+}
+
+fn as_str(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        Value::Null => "".to_string(),
+        v => v.to_string(),
+    }
+}
+
+fn is_disabled(item: &Value) -> bool {
+    item["disabled"].as_bool().unwrap_or(false)
+}
+
+// Postman v2.1 のコレクションを変換する
+pub fn import_collection(json: &Value) -> Result<(Collection, ImportReport), String> {
+    let schema = json["info"]["schema"].as_str().unwrap_or("");
+    if !schema.contains("v2.1") && !schema.contains("v2.0") {
+        return Err(format!("unsupported postman schema: {}", schema));
+    }
+
+    let mut report = ImportReport::default();
+    let mut collection = Collection {
+        name: as_str(&json["info"]["name"]),
+        stop_on_failure: true,
+        ..Collection::default()
+    };
+
+    for variable in json["variable"].as_array().unwrap_or(&Vec::new()) {
+        if is_disabled(variable) {
+            continue;
+        }
+        collection
+            .variables
+            .insert(as_str(&variable["key"]), as_str(&variable["value"]));
+    }
+
+    check_events(json, &collection.name, &mut report);
+    import_items(
+        &json["item"],
+        "",
+        &json["auth"],
+        &mut collection.requests,
+        &mut report,
+    );
+
+    Ok((collection, report))
+}
+
+// Postman の環境ファイルを変換する
+pub fn import_environment(json: &Value) -> Environment {
+    let mut variables = BTreeMap::new();
+    for value in json["values"].as_array().unwrap_or(&Vec::new()) {
+        if !value["enabled"].as_bool().unwrap_or(true) {
+            continue;
+        }
+        variables.insert(as_str(&value["key"]), as_str(&value["value"]));
+    }
+
+    Environment {
+        name: as_str(&json["name"]),
+        variables,
+        settings: Vec::new(),
+    }
+}
+
+fn check_events(item: &Value, location: &str, report: &mut ImportReport) {
+    for event in item["event"].as_array().unwrap_or(&Vec::new()) {
+        let script = event["script"]["exec"]
+            .as_array()
+            .map(|lines| lines.iter().any(|l| !as_str(l).trim().is_empty()))
+            .unwrap_or(false);
+        if !script {
+            continue;
+        }
+        match event["listen"].as_str() {
+            Some("prerequest") => report.add(location, "pre-request script is not supported"),
+            Some("test") => report.add(location, "test script is not supported"),
+            Some(other) => report.add(location, &format!("{} script is not supported", other)),
+            None => {}
+        }
+    }
+}
+
+fn import_items(
+    items: &Value,
+    folder: &str,
+    auth: &Value,
+    requests: &mut Vec<SavedRequest>,
+    report: &mut ImportReport,
+) {
+    for item in items.as_array().unwrap_or(&Vec::new()) {
+        let name = as_str(&item["name"]);
+        let location = if folder.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", folder, name)
+        };
+        check_events(item, &location, report);
+
+        // 下位の項目に指定がなければ親の認証を引き継ぐ
+        let own_auth = if item["request"].is_object() {
+            &item["request"]["auth"]
+        } else {
+            &item["auth"]
+        };
+        let auth = if own_auth.is_null() || own_auth["type"].as_str() == Some("inherit") {
+            auth
+        } else {
+            own_auth
+        };
+
+        if item["item"].is_array() {
+            import_items(&item["item"], &location, auth, requests, report);
+        } else if !item["request"].is_null() {
+            let mut saved = import_request(&item["request"], &location, report);
+            saved.name = name;
+            saved.folder = folder.to_string();
+            import_auth(auth, &mut saved, &location, report);
+            import_settings(&item["protocolProfileBehavior"], &mut saved);
+            requests.push(saved);
+        }
+    }
+}
+
+// リダイレクトの設定を Settings ペインに移す
+fn import_settings(behavior: &Value, saved: &mut SavedRequest) {
+    if behavior["followRedirects"].as_bool() == Some(false) {
+        saved.settings.push("redirect=none".to_string());
+    }
+    if let Some(max) = behavior["maxRedirects"].as_u64() {
+        saved.settings.push(format!("max_redirects={}", max));
+    }
+}
+
+fn import_request(request: &Value, location: &str, report: &mut ImportReport) -> SavedRequest {
+    // 短縮形ではURL文字列だけが書かれている
+    if let Value::String(url) = request {
+        return SavedRequest {
+            url: url.to_string(),
+            method: "GET".to_string(),
+            ..SavedRequest::default()
+        };
+    }
+
+    let mut saved = SavedRequest {
+        method: request["method"].as_str().unwrap_or("GET").to_uppercase(),
+        ..SavedRequest::default()
+    };
+
+    match &request["url"] {
+        Value::String(raw) => saved.url = path_variables(raw, &BTreeMap::new(), location, report),
+        url => {
+            let raw = as_str(&url["raw"]);
+            let mut values = BTreeMap::new();
+            for variable in url["variable"].as_array().unwrap_or(&Vec::new()) {
+                values.insert(as_str(&variable["key"]), as_str(&variable["value"]));
+            }
+            // クエリは URL params ペインに移す
+            let path = raw.split('?').next().unwrap_or("");
+            saved.url = path_variables(path, &values, location, report);
+            for query in url["query"].as_array().unwrap_or(&Vec::new()) {
+                if is_disabled(query) {
+                    continue;
+                }
+                saved.params.push(format!(
+                    "{}={}",
+                    as_str(&query["key"]),
+                    as_str(&query["value"])
+                ));
+            }
+        }
+    }
+
+    for header in request["header"].as_array().unwrap_or(&Vec::new()) {
+        if is_disabled(header) {
+            continue;
+        }
+        saved.headers.push(format!(
+            "{}:{}",
+            as_str(&header["key"]),
+            as_str(&header["value"])
+        ));
+    }
+
+    import_body(&request["body"], &mut saved, location, report);
+
+    saved
+}
+
+// パス変数（`/users/:id`）に値を入れる。値がなければ `{{id}}` にして報告する
+fn path_variables(
+    url: &str,
+    values: &BTreeMap<String, String>,
+    location: &str,
+    report: &mut ImportReport,
+) -> String {
+    url.split('/')
+        .map(|segment| {
+            let name = match segment.strip_prefix(':') {
+                Some(name)
+                    if !name.is_empty()
+                        && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') =>
+                {
+                    name
+                }
+                _ => return segment.to_string(),
+            };
+            match values.get(name).filter(|value| !value.is_empty()) {
+                Some(value) => value.to_string(),
+                None => {
+                    report.add(
+                        location,
+                        &format!("path variable :{} has no value, set {{{{{}}}}} in an environment", name, name),
+                    );
+                    format!("{{{{{}}}}}", name)
+                }
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn has_header(saved: &SavedRequest, name: &str) -> bool {
+    saved.headers.iter().any(|h| {
+        h.split(':')
+            .next()
+            .map(|n| n.trim().eq_ignore_ascii_case(name))
+            .unwrap_or(false)
+    })
+}
+
+fn import_body(body: &Value, saved: &mut SavedRequest, location: &str, report: &mut ImportReport) {
+    if body.is_null() || body["disabled"].as_bool().unwrap_or(false) {
+        return;
+    }
+
+    match body["mode"].as_str().unwrap_or("") {
+        "raw" => {
+            saved.body = as_str(&body["raw"]);
+            let language = body["options"]["raw"]["language"].as_str().unwrap_or("");
+            if language == "json" && !has_header(saved, "Content-Type") {
+                saved.body_mode = "json".to_string();
+            }
+        }
+        "urlencoded" => {
+            let mut lines = Vec::new();
+            for field in body["urlencoded"].as_array().unwrap_or(&Vec::new()) {
+                if is_disabled(field) {
+                    continue;
+                }
+                lines.push(format!("{}={}", as_str(&field["key"]), as_str(&field["value"])));
+            }
+            saved.body = lines.join("\n");
+            saved.body_mode = "form".to_string();
+        }
+        "formdata" => {
+            let mut lines = Vec::new();
+            for field in body["formdata"].as_array().unwrap_or(&Vec::new()) {
+                if is_disabled(field) {
+                    continue;
+                }
+                let key = as_str(&field["key"]);
+                if field["type"].as_str() == Some("file") {
+                    let src = match &field["src"] {
+                        Value::Array(files) => files.first().map(as_str).unwrap_or_default(),
+                        src => as_str(src),
+                    };
+                    if src.is_empty() {
+                        report.add(location, &format!("form-data file part '{}' has no file", key));
+                        continue;
+                    }
+                    lines.push(format!("{}=@{}", key, src));
+                } else {
+                    lines.push(format!("{}={}", key, as_str(&field["value"])));
+                }
+            }
+            saved.body = lines.join("\n");
+            saved.body_mode = "multipart".to_string();
+            // 境界文字列は送信時に付けるので Content-Type は指定しない
+            saved.headers.retain(|h| {
+                !h.split(':')
+                    .next()
+                    .map(|n| n.trim().eq_ignore_ascii_case("Content-Type"))
+                    .unwrap_or(false)
+            });
+        }
+        "graphql" => {
+            saved.body = as_str(&body["graphql"]["query"]);
+            saved.graphql_variables = as_str(&body["graphql"]["variables"]);
+            saved.body_mode = "graphql".to_string();
+        }
+        "" => {}
+        mode => report.add(location, &format!("{} body is not supported", mode)),
+    }
+}
+
+// 認証の設定値を取り出す（v2.1 は配列、v2.0 はオブジェクト）
+fn auth_param(auth: &Value, kind: &str, key: &str) -> String {
+    match &auth[kind] {
+        Value::Array(params) => params
+            .iter()
+            .find(|p| p["key"].as_str() == Some(key))
+            .map(|p| as_str(&p["value"]))
+            .unwrap_or_default(),
+        params => as_str(&params[key]),
+    }
+}
+
+fn import_auth(auth: &Value, saved: &mut SavedRequest, location: &str, report: &mut ImportReport) {
+    if auth.is_null() {
+        return;
+    }
+
+    match auth["type"].as_str().unwrap_or("noauth") {
+        "noauth" => {}
+        kind @ "basic" | kind @ "digest" => {
+            saved.auth_mode = kind.to_string();
+            saved.auth = vec![
+                format!("username={}", auth_param(auth, kind, "username")),
+                format!("password={}", auth_param(auth, kind, "password")),
+            ];
+        }
+        "bearer" => {
+            saved.auth_mode = "bearer".to_string();
+            saved.auth = vec![format!("token={}", auth_param(auth, "bearer", "token"))];
+        }
+        "apikey" => {
+            let location = match auth_param(auth, "apikey", "in").as_str() {
+                "query" => "query",
+                _ => "header",
+            };
+            saved.auth_mode = "apikey".to_string();
+            saved.auth = vec![
+                format!("in={}", location),
+                format!("name={}", auth_param(auth, "apikey", "key")),
+                format!("value={}", auth_param(auth, "apikey", "value")),
+            ];
+        }
+        "awsv4" => {
+            saved.auth_mode = "aws".to_string();
+            saved.auth = vec![
+                format!("access_key={}", auth_param(auth, "awsv4", "accessKey")),
+                format!("secret_key={}", auth_param(auth, "awsv4", "secretKey")),
+                format!("session_token={}", auth_param(auth, "awsv4", "sessionToken")),
+                format!("region={}", auth_param(auth, "awsv4", "region")),
+                format!("service={}", auth_param(auth, "awsv4", "service")),
+            ];
+        }
+        "oauth2" => {
+            let grant = match auth_param(auth, "oauth2", "grant_type").as_str() {
+                "" | "authorization_code" | "authorization_code_with_pkce" => "authorization_code",
+                "client_credentials" => "client_credentials",
+                "password_credentials" => "password",
+                other => {
+                    report.add(location, &format!("oauth2 grant {} is not supported", other));
+                    return;
+                }
+            };
+            saved.auth_mode = "oauth2".to_string();
+            saved.auth = vec![
+                format!("grant={}", grant),
+                format!("token_url={}", auth_param(auth, "oauth2", "accessTokenUrl")),
+            ];
+            if grant == "authorization_code" {
+                saved
+                    .auth
+                    .push(format!("auth_url={}", auth_param(auth, "oauth2", "authUrl")));
+            }
+            saved.auth.push(format!("client_id={}", auth_param(auth, "oauth2", "clientId")));
+            saved.auth.push(format!(
+                "client_secret={}",
+                auth_param(auth, "oauth2", "clientSecret")
+            ));
+            saved.auth.push(format!("scope={}", auth_param(auth, "oauth2", "scope")));
+            if auth_param(auth, "oauth2", "client_authentication") == "body" {
+                saved.auth.push("client_auth=body".to_string());
+            }
+            if grant == "password" {
+                saved.auth.push(format!("username={}", auth_param(auth, "oauth2", "username")));
+                saved.auth.push(format!("password={}", auth_param(auth, "oauth2", "password")));
+            }
+        }
+        other => report.add(location, &format!("{} auth is not supported", other)),
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("invalid json {}: {}", path.display(), e))
+}
+
+// `ruzz import postman` サブコマンド
+pub fn run(args: &[String]) -> i32 {
+    let mut input = None;
+    let mut environments = Vec::new();
+    let mut output = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-e" | "--env" => match iter.next() {
+                Some(path) => environments.push(PathBuf::from(path)),
+                None => {
+                    eprintln!("{}", USAGE);
+                    return 2;
+                }
+            },
+            "-o" | "--output" => match iter.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("{}", USAGE);
+                    return 2;
+                }
+            },
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("unexpected argument: {}\n{}", arg, USAGE);
+                return 2;
+            }
+        }
+    }
+
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+    let output = output.unwrap_or_else(|| input.with_extension("ruzz.json"));
+
+    match import_files(&input, &environments, &output) {
+        Ok(report) => {
+            if report.unsupported.is_empty() {
+                println!("imported without warnings");
+            } else {
+                println!("unsupported features:");
+                for line in &report.unsupported {
+                    println!("  {}", line);
+                }
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            2
+        }
+    }
+}
+
+fn import_files(input: &Path, environments: &[PathBuf], output: &Path) -> Result<ImportReport, String> {
+    let (collection, report) = import_collection(&read_json(input)?)?;
+    collection.save(output)?;
+    println!(
+        "{} requests -> {}",
+        collection.requests.len(),
+        output.display()
+    );
+
+    let dir = output.parent().unwrap_or_else(|| Path::new("."));
+    for path in environments {
+        let environment = import_environment(&read_json(path)?);
+        let file_name: String = environment
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let env_path = dir.join(format!("{}.env.json", file_name));
+        environment.save(&env_path)?;
+        println!(
+            "environment {} -> {} (use --env {})",
+            environment.name,
+            env_path.display(),
+            file_name
+        );
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLLECTION: &str = r#"{
+      "info": {
+        "name": "Users API",
+        "schema": "https://schema.getpostman.com/json/collection/v2.1.0/collection.json"
+      },
+      "auth": {"type": "bearer", "bearer": [{"key": "token", "value": "{{token}}", "type": "string"}]},
+      "variable": [{"key": "baseUrl", "value": "https://api.example.com"}],
+      "item": [
+        {
+          "name": "Users",
+          "item": [
+            {
+              "name": "Get user",
+              "event": [{"listen": "test", "script": {"exec": ["pm.test('ok')"]}}],
+              "request": {
+                "method": "get",
+                "header": [
+                  {"key": "Accept", "value": "application/json"},
+                  {"key": "X-Debug", "value": "1", "disabled": true}
+                ],
+                "url": {
+                  "raw": "{{baseUrl}}/users/:id/posts/:postId?verbose=true&page=2",
+                  "host": ["{{baseUrl}}"],
+                  "path": ["users", ":id", "posts", ":postId"],
+                  "query": [
+                    {"key": "verbose", "value": "true"},
+                    {"key": "page", "value": "2", "disabled": true}
+                  ],
+                  "variable": [{"key": "id", "value": "42"}, {"key": "postId", "value": ""}]
+                }
+              }
+            }
+          ]
+        },
+        {
+          "name": "Create user",
+          "request": {
+            "auth": {"type": "basic", "basic": [
+              {"key": "username", "value": "alice"},
+              {"key": "password", "value": "secret"}
+            ]},
+            "method": "POST",
+            "url": "https://api.example.com:8443/users/:group",
+            "body": {"mode": "raw", "raw": "{\"name\": \"bob\"}", "options": {"raw": {"language": "json"}}}
+          },
+          "protocolProfileBehavior": {"followRedirects": false}
+        }
+      ]
+    }"#;
+
+    #[test]
+    fn imports_a_v21_collection() {
+        let json: Value = serde_json::from_str(COLLECTION).unwrap();
+        let (collection, report) = import_collection(&json).unwrap();
+        assert_eq!(collection.name, "Users API");
+        assert_eq!(collection.variables["baseUrl"], "https://api.example.com");
+        assert_eq!(collection.requests.len(), 2);
+
+        let get = &collection.requests[0];
+        assert_eq!((get.name.as_str(), get.folder.as_str(), get.method.as_str()), ("Get user", "Users", "GET"));
+        assert_eq!(get.url, "{{baseUrl}}/users/42/posts/{{postId}}");
+        assert_eq!(get.params, vec!["verbose=true"]);
+        assert_eq!(get.headers, vec!["Accept:application/json"]);
+        // 親の認証を引き継ぐ
+        assert_eq!(get.auth_mode, "bearer");
+        assert_eq!(get.auth, vec!["token={{token}}"]);
+
+        let create = &collection.requests[1];
+        assert_eq!(create.url, "https://api.example.com:8443/users/{{group}}");
+        assert_eq!(create.auth_mode, "basic");
+        assert_eq!(create.auth, vec!["username=alice", "password=secret"]);
+        assert_eq!(create.body, "{\"name\": \"bob\"}");
+        assert_eq!(create.body_mode, "json");
+        assert_eq!(create.settings, vec!["redirect=none"]);
+
+        assert_eq!(
+            report.unsupported,
+            vec![
+                "Users/Get user: test script is not supported",
+                "Users/Get user: path variable :postId has no value, set {{postId}} in an environment",
+                "Create user: path variable :group has no value, set {{group}} in an environment",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_schemas() {
+        let json: Value = serde_json::from_str(r#"{"info": {"schema": "v1"}}"#).unwrap();
+        assert!(import_collection(&json).is_err());
+    }
+}
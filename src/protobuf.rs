@@ -0,0 +1,2339 @@
+use serde_json::{Map, Number, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// テンプレートで入れ子のメッセージを展開する深さ
+const TEMPLATE_DEPTH: usize = 3;
+
+// フィールドの型（番号は FieldDescriptorProto.Type と同じ）
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kind {
+    Double = 1,
+    Float = 2,
+    Int64 = 3,
+    Uint64 = 4,
+    Int32 = 5,
+    Fixed64 = 6,
+    Fixed32 = 7,
+    Bool = 8,
+    String = 9,
+    Group = 10,
+    Message = 11,
+    Bytes = 12,
+    Uint32 = 13,
+    Enum = 14,
+    Sfixed32 = 15,
+    Sfixed64 = 16,
+    Sint32 = 17,
+    Sint64 = 18,
+    // .proto で書かれた型名（読み込んだ後で Message か Enum にする）
+    Named = 0,
+}
+
+const SCALARS: [(&str, Kind); 15] = [
+    ("double", Kind::Double),
+    ("float", Kind::Float),
+    ("int64", Kind::Int64),
+    ("uint64", Kind::Uint64),
+    ("int32", Kind::Int32),
+    ("fixed64", Kind::Fixed64),
+    ("fixed32", Kind::Fixed32),
+    ("bool", Kind::Bool),
+    ("string", Kind::String),
+    ("bytes", Kind::Bytes),
+    ("uint32", Kind::Uint32),
+    ("sfixed32", Kind::Sfixed32),
+    ("sfixed64", Kind::Sfixed64),
+    ("sint32", Kind::Sint32),
+    ("sint64", Kind::Sint64),
+];
+
+impl Kind {
+    fn from_number(number: u64) -> Option<Kind> {
+        SCALARS
+            .iter()
+            .map(|(_, kind)| *kind)
+            .chain(vec![Kind::Group, Kind::Message, Kind::Enum])
+            .find(|kind| *kind as u64 == number)
+    }
+
+    fn scalar(name: &str) -> Option<Kind> {
+        SCALARS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, kind)| *kind)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Group => "group",
+            Kind::Message | Kind::Enum | Kind::Named => "",
+            kind => SCALARS
+                .iter()
+                .find(|(_, k)| *k == kind)
+                .map(|(n, _)| *n)
+                .unwrap_or(""),
+        }
+    }
+
+    // packed で送れる型
+    fn packable(self) -> bool {
+        !matches!(
+            self,
+            Kind::String | Kind::Bytes | Kind::Message | Kind::Group | Kind::Named
+        )
+    }
+
+    fn wire_type(self) -> u8 {
+        match self {
+            Kind::Double | Kind::Fixed64 | Kind::Sfixed64 => 1,
+            Kind::String | Kind::Bytes | Kind::Message => 2,
+            Kind::Group => 3,
+            Kind::Float | Kind::Fixed32 | Kind::Sfixed32 => 5,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub name: String,
+    pub json_name: String,
+    pub number: u32,
+    pub kind: Kind,
+    pub repeated: bool,
+    // 明示された packed（なければ proto3 なら packed）
+    pub packed: Option<bool>,
+    // メッセージか列挙型の完全な名前
+    pub type_name: String,
+    pub oneof: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub map_entry: bool,
+    pub proto3: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Enum {
+    pub name: String,
+    pub values: Vec<(String, i32)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Method {
+    pub name: String,
+    pub input: String,
+    pub output: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Service {
+    pub name: String,
+    pub methods: Vec<Method>,
+}
+
+// 1つの .proto ファイル（メッセージと列挙型は入れ子も含めて完全な名前で並べる）
+#[derive(Clone, Debug, Default)]
+pub struct File {
+    pub name: String,
+    pub package: String,
+    pub dependencies: Vec<String>,
+    messages: Vec<Message>,
+    enums: Vec<Enum>,
+    services: Vec<Service>,
+}
+
+// 読み込んだすべての型とサービス
+#[derive(Clone, Debug, Default)]
+pub struct Pool {
+    files: BTreeSet<String>,
+    messages: BTreeMap<String, Message>,
+    enums: BTreeMap<String, Enum>,
+    pub services: Vec<Service>,
+}
+
+// 小文字で始まるキャメルケース（protoc の json_name と同じ）
+fn json_name(name: &str) -> String {
+    let mut json = String::new();
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            json.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            json.push(c);
+        }
+    }
+    json
+}
+
+fn join(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+fn parent(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(i) => &name[..i],
+        None => "",
+    }
+}
+
+impl Pool {
+    pub fn has_file(&self, name: &str) -> bool {
+        self.files.contains(name)
+    }
+
+    pub fn add(&mut self, file: File) {
+        if !self.files.insert(file.name.clone()) {
+            return;
+        }
+        for message in file.messages {
+            self.messages.insert(message.name.clone(), message);
+        }
+        for e in file.enums {
+            self.enums.insert(e.name.clone(), e);
+        }
+        self.services.extend(file.services);
+    }
+
+    // 読み込んだファイルが依存しているが、まだ読み込んでいないファイル
+    pub fn missing(&self, files: &[File]) -> Vec<String> {
+        files
+            .iter()
+            .flat_map(|file| file.dependencies.iter())
+            .filter(|dep| !self.has_file(dep))
+            .cloned()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect()
+    }
+
+    // scope から見た型名を完全な名前にする
+    fn resolve_name(&self, scope: &str, name: &str) -> Option<String> {
+        let exists = |name: &str| self.messages.contains_key(name) || self.enums.contains_key(name);
+        if let Some(name) = name.strip_prefix('.') {
+            return Some(name.to_string()).filter(|name| exists(name));
+        }
+        let mut scope = scope;
+        loop {
+            let candidate = join(scope, name);
+            if exists(&candidate) {
+                return Some(candidate);
+            }
+            if scope.is_empty() {
+                return None;
+            }
+            scope = parent(scope);
+        }
+    }
+
+    // 型名を完全な名前にして、メッセージか列挙型かを決める
+    pub fn resolve(&mut self) -> Result<(), String> {
+        let mut resolved = BTreeMap::new();
+        for message in self.messages.values() {
+            for (i, field) in message.fields.iter().enumerate() {
+                if field.type_name.is_empty() {
+                    continue;
+                }
+                let name = self
+                    .resolve_name(&message.name, &field.type_name)
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown type {} for {}.{}",
+                            field.type_name, message.name, field.name
+                        )
+                    })?;
+                let kind = match field.kind {
+                    Kind::Named if self.enums.contains_key(&name) => Kind::Enum,
+                    Kind::Named => Kind::Message,
+                    kind => kind,
+                };
+                resolved.insert((message.name.clone(), i), (name, kind));
+            }
+        }
+        for ((message, i), (name, kind)) in resolved {
+            if let Some(field) = self
+                .messages
+                .get_mut(&message)
+                .and_then(|m| m.fields.get_mut(i))
+            {
+                field.type_name = name;
+                field.kind = kind;
+            }
+        }
+
+        let mut services = std::mem::take(&mut self.services);
+        for service in &mut services {
+            let scope = parent(&service.name).to_string();
+            let service_name = service.name.clone();
+            for method in &mut service.methods {
+                let method_name = method.name.clone();
+                let resolve = |name: &str| {
+                    self.resolve_name(&scope, name)
+                        .filter(|full| self.messages.contains_key(full))
+                        .ok_or_else(|| {
+                            format!(
+                                "unknown message {} for {}/{}",
+                                name, service_name, method_name
+                            )
+                        })
+                };
+                let (input, output) = (resolve(&method.input)?, resolve(&method.output)?);
+                method.input = input;
+                method.output = output;
+            }
+        }
+        self.services = services;
+        Ok(())
+    }
+
+    pub fn message(&self, name: &str) -> Result<&Message, String> {
+        self.messages
+            .get(name)
+            .ok_or_else(|| format!("unknown message type {}", name))
+    }
+
+    // `package.Service/Method`（`package.Service.Method` も受け付ける）
+    pub fn method(&self, path: &str) -> Result<(&Service, &Method), String> {
+        let path = path.trim_matches('/');
+        let (service, method) = match path.rfind('/').or_else(|| path.rfind('.')) {
+            Some(i) => (&path[..i], &path[i + 1..]),
+            None => {
+                return Err(format!(
+                    "invalid method {} (expected package.Service/Method)",
+                    path
+                ))
+            }
+        };
+        let service = self
+            .services
+            .iter()
+            .find(|s| s.name == service)
+            .ok_or_else(|| format!("unknown service {}", service))?;
+        let method = service
+            .methods
+            .iter()
+            .find(|m| m.name == method)
+            .ok_or_else(|| format!("{} has no method {}", service.name, method))?;
+        Ok((service, method))
+    }
+
+    // メソッドの一覧の1行
+    pub fn signature(method: &Method) -> String {
+        let stream = |streaming: bool| if streaming { "stream " } else { "" };
+        format!(
+            "{}({}{}) returns ({}{})",
+            method.name,
+            stream(method.client_streaming),
+            method.input,
+            stream(method.server_streaming),
+            method.output
+        )
+    }
+
+    // メッセージの定義を .proto に近い形で表示する
+    pub fn describe(&self, name: &str) -> Vec<String> {
+        let message = match self.messages.get(name) {
+            Some(message) => message,
+            None => return vec![format!("unknown message type {}", name)],
+        };
+        let mut lines = vec![format!("message {} {{", message.name)];
+        for field in &message.fields {
+            let ty = self.type_label(field);
+            let label = if field.repeated && !self.is_map(field) {
+                "repeated "
+            } else {
+                ""
+            };
+            let oneof = field
+                .oneof
+                .as_ref()
+                .map(|oneof| format!("  // oneof {}", oneof))
+                .unwrap_or_default();
+            lines.push(format!(
+                "  {}{} {} = {};{}",
+                label, ty, field.name, field.number, oneof
+            ));
+        }
+        lines.push("}".to_string());
+        lines
+    }
+
+    fn type_label(&self, field: &Field) -> String {
+        if let Some((key, value)) = self.map_fields(field) {
+            return format!("map<{}, {}>", self.type_label(key), self.type_label(value));
+        }
+        match field.kind {
+            Kind::Enum => {
+                let values = self
+                    .enums
+                    .get(&field.type_name)
+                    .map(|e| {
+                        e.values
+                            .iter()
+                            .map(|(name, _)| name.as_str())
+                            .collect::<Vec<&str>>()
+                            .join("|")
+                    })
+                    .unwrap_or_default();
+                format!("{}({})", field.type_name, values)
+            }
+            Kind::Message | Kind::Named => field.type_name.clone(),
+            kind => kind.name().to_string(),
+        }
+    }
+
+    fn is_map(&self, field: &Field) -> bool {
+        self.map_fields(field).is_some()
+    }
+
+    // map<K, V> のフィールドならキーと値のフィールド
+    fn map_fields(&self, field: &Field) -> Option<(&Field, &Field)> {
+        if !field.repeated || field.kind != Kind::Message {
+            return None;
+        }
+        let entry = self
+            .messages
+            .get(&field.type_name)
+            .filter(|m| m.map_entry)?;
+        let key = entry.fields.iter().find(|f| f.number == 1)?;
+        let value = entry.fields.iter().find(|f| f.number == 2)?;
+        Some((key, value))
+    }
+}
+
+// ---- ワイヤーフォーマット ----
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_key(buf: &mut Vec<u8>, number: u32, wire_type: u8) {
+    put_varint(buf, (u64::from(number) << 3) | u64::from(wire_type));
+}
+
+fn put_bytes(buf: &mut Vec<u8>, number: u32, bytes: &[u8]) {
+    put_key(buf, number, 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+// 1つのフィールドの値
+#[derive(Clone, Copy, Debug)]
+pub enum Wire<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+    // 中身を読み飛ばしたグループ
+    Group,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..70).step_by(7) {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or_else(|| "truncated varint".to_string())?;
+            self.pos += 1;
+            if shift < 64 {
+                value |= u64::from(byte & 0x7f) << shift;
+            }
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err("varint is too long".to_string())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() - self.pos < len {
+            return Err("truncated message".to_string());
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn fixed32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn fixed64(&mut self) -> Result<u64, String> {
+        let bytes = self.take(8)?;
+        let mut buf = [0; 8];
+        buf.copy_from_slice(bytes);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn field(&mut self) -> Result<(u32, Wire<'a>), String> {
+        let key = self.varint()?;
+        let number = (key >> 3) as u32;
+        Ok((number, self.value(number, key & 7)?))
+    }
+
+    fn value(&mut self, number: u32, wire_type: u64) -> Result<Wire<'a>, String> {
+        Ok(match wire_type {
+            0 => Wire::Varint(self.varint()?),
+            1 => Wire::Fixed64(self.fixed64()?),
+            2 => {
+                let len = self.varint()? as usize;
+                Wire::Bytes(self.take(len)?)
+            }
+            3 => {
+                self.skip_group(number)?;
+                Wire::Group
+            }
+            5 => Wire::Fixed32(self.fixed32()?),
+            wire => return Err(format!("invalid wire type {} for field {}", wire, number)),
+        })
+    }
+
+    // 対応する END_GROUP まで読み飛ばす
+    fn skip_group(&mut self, number: u32) -> Result<(), String> {
+        loop {
+            if self.pos >= self.data.len() {
+                return Err(format!("unterminated group {}", number));
+            }
+            let key = self.varint()?;
+            if key & 7 == 4 {
+                return Ok(());
+            }
+            self.value((key >> 3) as u32, key & 7)?;
+        }
+    }
+}
+
+// メッセージのバイト列をフィールドの並びにする
+pub fn fields(data: &[u8]) -> Result<Vec<(u32, Wire<'_>)>, String> {
+    let mut reader = Reader { data, pos: 0 };
+    let mut fields = Vec::new();
+    while reader.pos < data.len() {
+        fields.push(reader.field()?);
+    }
+    Ok(fields)
+}
+
+pub fn wire_string(wire: Wire) -> Result<String, String> {
+    match wire {
+        Wire::Bytes(bytes) => Ok(String::from_utf8_lossy(bytes).to_string()),
+        _ => Err("expected a length-delimited field".to_string()),
+    }
+}
+
+fn wire_bool(wire: Wire) -> bool {
+    matches!(wire, Wire::Varint(v) if v != 0)
+}
+
+pub fn wire_u64(wire: Wire) -> u64 {
+    match wire {
+        Wire::Varint(v) | Wire::Fixed64(v) => v,
+        Wire::Fixed32(v) => u64::from(v),
+        _ => 0,
+    }
+}
+
+// 文字列を1つだけ持つメッセージ（サーバーリフレクションの要求など）
+pub fn string_field(number: u32, value: &str) -> Vec<u8> {
+    let mut buf = Vec::new();
+    put_bytes(&mut buf, number, value.as_bytes());
+    buf
+}
+
+// ---- FileDescriptorProto ----
+
+struct FieldDescriptor {
+    field: Field,
+    oneof_index: Option<usize>,
+}
+
+fn decode_field(data: &[u8]) -> Result<FieldDescriptor, String> {
+    let mut field = Field {
+        name: String::new(),
+        json_name: String::new(),
+        number: 0,
+        kind: Kind::Named,
+        repeated: false,
+        packed: None,
+        type_name: String::new(),
+        oneof: None,
+    };
+    let mut oneof_index = None;
+    let mut proto3_optional = false;
+    for (number, wire) in fields(data)? {
+        match number {
+            1 => field.name = wire_string(wire)?,
+            3 => field.number = wire_u64(wire) as u32,
+            4 => field.repeated = wire_u64(wire) == 3,
+            5 => {
+                field.kind = Kind::from_number(wire_u64(wire))
+                    .ok_or_else(|| format!("unknown field type {}", wire_u64(wire)))?
+            }
+            6 => field.type_name = wire_string(wire)?,
+            8 => {
+                if let Wire::Bytes(options) = wire {
+                    for (number, wire) in fields(options)? {
+                        if number == 2 {
+                            field.packed = Some(wire_bool(wire));
+                        }
+                    }
+                }
+            }
+            9 => oneof_index = Some(wire_u64(wire) as usize),
+            10 => field.json_name = wire_string(wire)?,
+            17 => proto3_optional = wire_bool(wire),
+            _ => {}
+        }
+    }
+    if field.json_name.is_empty() {
+        field.json_name = json_name(&field.name);
+    }
+    // proto3 の optional は合成された oneof に入るが、JSON では普通のフィールドとして扱う
+    if proto3_optional {
+        oneof_index = None;
+    }
+    Ok(FieldDescriptor { field, oneof_index })
+}
+
+fn decode_enum(data: &[u8], scope: &str) -> Result<Enum, String> {
+    let mut e = Enum {
+        name: String::new(),
+        values: Vec::new(),
+    };
+    for (number, wire) in fields(data)? {
+        match (number, wire) {
+            (1, wire) => e.name = join(scope, &wire_string(wire)?),
+            (2, Wire::Bytes(value)) => {
+                let mut name = String::new();
+                let mut n = 0;
+                for (number, wire) in fields(value)? {
+                    match number {
+                        1 => name = wire_string(wire)?,
+                        2 => n = wire_u64(wire) as i32,
+                        _ => {}
+                    }
+                }
+                e.values.push((name, n));
+            }
+            _ => {}
+        }
+    }
+    Ok(e)
+}
+
+fn decode_message(data: &[u8], scope: &str, proto3: bool, file: &mut File) -> Result<(), String> {
+    let mut name = String::new();
+    for (number, wire) in fields(data)? {
+        if number == 1 {
+            name = join(scope, &wire_string(wire)?);
+        }
+    }
+    let mut message = Message {
+        name: name.clone(),
+        fields: Vec::new(),
+        map_entry: false,
+        proto3,
+    };
+    let mut descriptors = Vec::new();
+    let mut oneofs = Vec::new();
+    for (number, wire) in fields(data)? {
+        let bytes = match wire {
+            Wire::Bytes(bytes) => bytes,
+            _ => continue,
+        };
+        match number {
+            2 => descriptors.push(decode_field(bytes)?),
+            3 => decode_message(bytes, &name, proto3, file)?,
+            4 => file.enums.push(decode_enum(bytes, &name)?),
+            7 => {
+                for (number, wire) in fields(bytes)? {
+                    if number == 7 {
+                        message.map_entry = wire_bool(wire);
+                    }
+                }
+            }
+            8 => {
+                let mut oneof = String::new();
+                for (number, wire) in fields(bytes)? {
+                    if number == 1 {
+                        oneof = wire_string(wire)?;
+                    }
+                }
+                oneofs.push(oneof);
+            }
+            _ => {}
+        }
+    }
+    for mut descriptor in descriptors {
+        descriptor.field.oneof = descriptor.oneof_index.and_then(|i| oneofs.get(i).cloned());
+        message.fields.push(descriptor.field);
+    }
+    file.messages.push(message);
+    Ok(())
+}
+
+fn decode_service(data: &[u8], package: &str) -> Result<Service, String> {
+    let mut service = Service {
+        name: String::new(),
+        methods: Vec::new(),
+    };
+    for (number, wire) in fields(data)? {
+        match (number, wire) {
+            (1, wire) => service.name = join(package, &wire_string(wire)?),
+            (2, Wire::Bytes(bytes)) => {
+                let mut method = Method {
+                    name: String::new(),
+                    input: String::new(),
+                    output: String::new(),
+                    client_streaming: false,
+                    server_streaming: false,
+                };
+                for (number, wire) in fields(bytes)? {
+                    match number {
+                        1 => method.name = wire_string(wire)?,
+                        2 => method.input = wire_string(wire)?,
+                        3 => method.output = wire_string(wire)?,
+                        5 => method.client_streaming = wire_bool(wire),
+                        6 => method.server_streaming = wire_bool(wire),
+                        _ => {}
+                    }
+                }
+                service.methods.push(method);
+            }
+            _ => {}
+        }
+    }
+    Ok(service)
+}
+
+// シリアライズされた FileDescriptorProto を読む
+pub fn decode_file(data: &[u8]) -> Result<File, String> {
+    let mut file = File::default();
+    let mut syntax = String::new();
+    for (number, wire) in fields(data)? {
+        match number {
+            1 => file.name = wire_string(wire)?,
+            2 => file.package = wire_string(wire)?,
+            3 => file.dependencies.push(wire_string(wire)?),
+            12 => syntax = wire_string(wire)?,
+            _ => {}
+        }
+    }
+    let proto3 = syntax != "proto2" && !syntax.is_empty();
+    for (number, wire) in fields(data)? {
+        let bytes = match wire {
+            Wire::Bytes(bytes) => bytes,
+            _ => continue,
+        };
+        match number {
+            4 => decode_message(bytes, &file.package.clone(), proto3, &mut file)?,
+            5 => {
+                let e = decode_enum(bytes, &file.package)?;
+                file.enums.push(e);
+            }
+            6 => {
+                let service = decode_service(bytes, &file.package)?;
+                file.services.push(service);
+            }
+            _ => {}
+        }
+    }
+    Ok(file)
+}
+
+// ---- .proto ファイル ----
+
+// import が見つからないときに使う Well-Known Types
+const WELL_KNOWN: [(&str, &str); 7] = [
+    (
+        "google/protobuf/timestamp.proto",
+        "syntax = \"proto3\"; package google.protobuf;
+         message Timestamp { int64 seconds = 1; int32 nanos = 2; }",
+    ),
+    (
+        "google/protobuf/duration.proto",
+        "syntax = \"proto3\"; package google.protobuf;
+         message Duration { int64 seconds = 1; int32 nanos = 2; }",
+    ),
+    (
+        "google/protobuf/empty.proto",
+        "syntax = \"proto3\"; package google.protobuf; message Empty {}",
+    ),
+    (
+        "google/protobuf/wrappers.proto",
+        "syntax = \"proto3\"; package google.protobuf;
+         message DoubleValue { double value = 1; } message FloatValue { float value = 1; }
+         message Int64Value { int64 value = 1; } message UInt64Value { uint64 value = 1; }
+         message Int32Value { int32 value = 1; } message UInt32Value { uint32 value = 1; }
+         message BoolValue { bool value = 1; } message StringValue { string value = 1; }
+         message BytesValue { bytes value = 1; }",
+    ),
+    (
+        "google/protobuf/struct.proto",
+        "syntax = \"proto3\"; package google.protobuf;
+         message Struct { map<string, Value> fields = 1; }
+         message Value { oneof kind { NullValue null_value = 1; double number_value = 2;
+           string string_value = 3; bool bool_value = 4; Struct struct_value = 5;
+           ListValue list_value = 6; } }
+         enum NullValue { NULL_VALUE = 0; }
+         message ListValue { repeated Value values = 1; }",
+    ),
+    (
+        "google/protobuf/any.proto",
+        "syntax = \"proto3\"; package google.protobuf;
+         message Any { string type_url = 1; bytes value = 2; }",
+    ),
+    (
+        "google/protobuf/field_mask.proto",
+        "syntax = \"proto3\"; package google.protobuf;
+         message FieldMask { repeated string paths = 1; }",
+    ),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Symbol(char),
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+            {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), line));
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                // 1e-5 のような指数
+                if (chars[i] == 'e' || chars[i] == 'E')
+                    && matches!(chars.get(i + 1), Some('-') | Some('+'))
+                {
+                    i += 1;
+                }
+                i += 1;
+            }
+            tokens.push((Token::Number(chars[start..i].iter().collect()), line));
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    i += 1;
+                    text.push(match chars[i] {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        '0' => '\0',
+                        other => other,
+                    });
+                } else {
+                    text.push(chars[i]);
+                }
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(format!("line {}: unterminated string", line));
+            }
+            i += 1;
+            tokens.push((Token::Str(text), line));
+        } else {
+            tokens.push((Token::Symbol(c), line));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    file: File,
+    proto3: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self
+            .tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map(|(_, line)| *line)
+            .unwrap_or(1);
+        format!("{}:{}: {}", self.file.name, line, message)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .map(|(token, _)| token.clone())
+            .ok_or_else(|| self.error("unexpected end of file"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            token => {
+                self.pos -= 1;
+                Err(self.error(&format!("expected a name, found {:?}", token)))
+            }
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.next()? {
+            Token::Symbol(c) if c == symbol => Ok(()),
+            token => {
+                self.pos -= 1;
+                Err(self.error(&format!("expected '{}', found {:?}", symbol, token)))
+            }
+        }
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Str(mut text) => {
+                // 隣り合う文字列はつなげる
+                while let Some(Token::Str(more)) = self.peek().cloned() {
+                    text.push_str(&more);
+                    self.pos += 1;
+                }
+                Ok(text)
+            }
+            token => {
+                self.pos -= 1;
+                Err(self.error(&format!("expected a string, found {:?}", token)))
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64, String> {
+        let negative = self.eat('-');
+        let text = match self.next()? {
+            Token::Number(text) => text,
+            token => {
+                self.pos -= 1;
+                return Err(self.error(&format!("expected a number, found {:?}", token)));
+            }
+        };
+        let value =
+            if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+                i64::from_str_radix(hex, 16)
+            } else if text.len() > 1 && text.starts_with('0') {
+                i64::from_str_radix(&text[1..], 8)
+            } else {
+                text.parse()
+            }
+            .map_err(|_| self.error(&format!("invalid number {}", text)))?;
+        Ok(if negative { -value } else { value })
+    }
+
+    // `;` か対応する `}` まで読み飛ばす（option の値など）
+    fn skip_statement(&mut self) -> Result<(), String> {
+        let mut depth = 0;
+        loop {
+            match self.next()? {
+                Token::Symbol('{') => depth += 1,
+                Token::Symbol('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.eat(';');
+                        return Ok(());
+                    }
+                }
+                Token::Symbol(';') if depth == 0 => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    // `[packed = true, json_name = "x"]` のうち使うものだけ返す
+    fn field_options(&mut self) -> Result<(Option<bool>, Option<String>), String> {
+        let mut packed = None;
+        let mut json = None;
+        if !self.eat('[') {
+            return Ok((packed, json));
+        }
+        loop {
+            let mut name = String::new();
+            // (custom.option).field のような名前
+            while !matches!(self.peek(), Some(Token::Symbol('=')) | None) {
+                match self.next()? {
+                    Token::Ident(part) => name.push_str(&part),
+                    Token::Symbol(c) => name.push(c),
+                    _ => return Err(self.error("invalid option name")),
+                }
+            }
+            self.expect('=')?;
+            let value = match self.peek() {
+                Some(Token::Symbol('{')) => {
+                    let mut depth = 0;
+                    loop {
+                        match self.next()? {
+                            Token::Symbol('{') => depth += 1,
+                            Token::Symbol('}') => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    Token::Ident(String::new())
+                }
+                _ => {
+                    self.eat('-');
+                    self.next()?
+                }
+            };
+            match (name.as_str(), value) {
+                ("packed", Token::Ident(v)) => packed = Some(v == "true"),
+                ("json_name", Token::Str(v)) => json = Some(v),
+                _ => {}
+            }
+            if self.eat(']') {
+                break;
+            }
+            self.expect(',')?;
+        }
+        Ok((packed, json))
+    }
+
+    fn parse(&mut self) -> Result<(), String> {
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Symbol(';') => self.pos += 1,
+                Token::Ident(word) => match word.as_str() {
+                    "syntax" | "edition" => {
+                        self.pos += 1;
+                        self.expect('=')?;
+                        let syntax = self.string()?;
+                        self.proto3 = syntax != "proto2";
+                        self.expect(';')?;
+                    }
+                    "package" => {
+                        self.pos += 1;
+                        self.file.package = self.ident()?;
+                        self.expect(';')?;
+                    }
+                    "import" => {
+                        self.pos += 1;
+                        if let Some(Token::Ident(word)) = self.peek() {
+                            if word == "public" || word == "weak" {
+                                self.pos += 1;
+                            }
+                        }
+                        let path = self.string()?;
+                        self.file.dependencies.push(path);
+                        self.expect(';')?;
+                    }
+                    "option" | "extend" => self.skip_statement()?,
+                    "message" => {
+                        self.pos += 1;
+                        let scope = self.file.package.clone();
+                        self.message(&scope)?;
+                    }
+                    "enum" => {
+                        self.pos += 1;
+                        let scope = self.file.package.clone();
+                        self.enumeration(&scope)?;
+                    }
+                    "service" => {
+                        self.pos += 1;
+                        self.service()?;
+                    }
+                    _ => return Err(self.error(&format!("unexpected '{}'", word))),
+                },
+                token => return Err(self.error(&format!("unexpected {:?}", token))),
+            }
+        }
+        Ok(())
+    }
+
+    fn message(&mut self, scope: &str) -> Result<(), String> {
+        let name = join(scope, &self.ident()?);
+        self.expect('{')?;
+        let mut message = Message {
+            name: name.clone(),
+            fields: Vec::new(),
+            map_entry: false,
+            proto3: self.proto3,
+        };
+        self.message_body(&mut message, None)?;
+        self.file.messages.push(message);
+        Ok(())
+    }
+
+    // `}` までのフィールドと入れ子の定義（oneof の中なら oneof の名前）
+    fn message_body(&mut self, message: &mut Message, oneof: Option<&str>) -> Result<(), String> {
+        loop {
+            let word = match self.next()? {
+                Token::Symbol('}') => return Ok(()),
+                Token::Symbol(';') => continue,
+                Token::Ident(word) => word,
+                token => {
+                    self.pos -= 1;
+                    return Err(self.error(&format!("unexpected {:?}", token)));
+                }
+            };
+            match word.as_str() {
+                "message" => self.message(&message.name.clone())?,
+                "enum" => self.enumeration(&message.name.clone())?,
+                "oneof" => {
+                    let name = self.ident()?;
+                    self.expect('{')?;
+                    self.message_body(message, Some(&name))?;
+                }
+                "option" | "extend" | "extensions" | "reserved" => {
+                    self.pos -= 1;
+                    self.skip_statement()?;
+                }
+                "map" => {
+                    self.expect('<')?;
+                    let key = self.ident()?;
+                    self.expect(',')?;
+                    let value = self.ident()?;
+                    self.expect('>')?;
+                    let field_name = self.ident()?;
+                    self.expect('=')?;
+                    let number = self.integer()? as u32;
+                    let (_, json) = self.field_options()?;
+                    self.expect(';')?;
+                    // protoc と同じく、キーと値を持つ入れ子のメッセージにする
+                    let entry_name = format!("{}Entry", upper_camel(&field_name));
+                    let entry = Message {
+                        name: join(&message.name, &entry_name),
+                        fields: vec![
+                            self.field("key", &key, 1, false)?,
+                            self.field("value", &value, 2, false)?,
+                        ],
+                        map_entry: true,
+                        proto3: self.proto3,
+                    };
+                    self.file.messages.push(entry);
+                    let mut field = self.field(&field_name, &entry_name, number, true)?;
+                    field.json_name = json.unwrap_or(field.json_name);
+                    message.fields.push(field);
+                }
+                _ => {
+                    let (repeated, ty) = match word.as_str() {
+                        "repeated" => (true, self.ident()?),
+                        "optional" | "required" => (false, self.ident()?),
+                        _ => (false, word),
+                    };
+                    if ty == "group" {
+                        return Err(self.error("groups are not supported"));
+                    }
+                    let field_name = self.ident()?;
+                    self.expect('=')?;
+                    let number = self.integer()? as u32;
+                    let (packed, json) = self.field_options()?;
+                    self.expect(';')?;
+                    let mut field = self.field(&field_name, &ty, number, repeated)?;
+                    field.packed = packed;
+                    field.json_name = json.unwrap_or(field.json_name);
+                    field.oneof = oneof.map(|o| o.to_string());
+                    message.fields.push(field);
+                }
+            }
+        }
+    }
+
+    fn field(&self, name: &str, ty: &str, number: u32, repeated: bool) -> Result<Field, String> {
+        let (kind, type_name) = match Kind::scalar(ty) {
+            Some(kind) => (kind, String::new()),
+            None => (Kind::Named, ty.to_string()),
+        };
+        Ok(Field {
+            name: name.to_string(),
+            json_name: json_name(name),
+            number,
+            kind,
+            repeated,
+            packed: None,
+            type_name,
+            oneof: None,
+        })
+    }
+
+    fn enumeration(&mut self, scope: &str) -> Result<(), String> {
+        let name = join(scope, &self.ident()?);
+        self.expect('{')?;
+        let mut values = Vec::new();
+        loop {
+            match self.next()? {
+                Token::Symbol('}') => break,
+                Token::Symbol(';') => {}
+                Token::Ident(word) if word == "option" || word == "reserved" => {
+                    self.pos -= 1;
+                    self.skip_statement()?;
+                }
+                Token::Ident(value) => {
+                    self.expect('=')?;
+                    let number = self.integer()? as i32;
+                    self.field_options()?;
+                    self.expect(';')?;
+                    values.push((value, number));
+                }
+                token => {
+                    self.pos -= 1;
+                    return Err(self.error(&format!("unexpected {:?}", token)));
+                }
+            }
+        }
+        self.file.enums.push(Enum { name, values });
+        Ok(())
+    }
+
+    fn service(&mut self) -> Result<(), String> {
+        let name = self.ident()?;
+        let name = join(&self.file.package, &name);
+        self.expect('{')?;
+        let mut methods = Vec::new();
+        loop {
+            match self.next()? {
+                Token::Symbol('}') => break,
+                Token::Symbol(';') => {}
+                Token::Ident(word) if word == "option" => {
+                    self.pos -= 1;
+                    self.skip_statement()?;
+                }
+                Token::Ident(word) if word == "rpc" => {
+                    let method = self.ident()?;
+                    let (client_streaming, input) = self.rpc_type()?;
+                    match self.ident()?.as_str() {
+                        "returns" => {}
+                        _ => return Err(self.error("expected 'returns'")),
+                    }
+                    let (server_streaming, output) = self.rpc_type()?;
+                    // `{ option ... }` か `;`
+                    if self.peek() == Some(&Token::Symbol('{')) {
+                        self.skip_statement()?;
+                    } else {
+                        self.expect(';')?;
+                    }
+                    methods.push(Method {
+                        name: method,
+                        input,
+                        output,
+                        client_streaming,
+                        server_streaming,
+                    });
+                }
+                token => {
+                    self.pos -= 1;
+                    return Err(self.error(&format!("unexpected {:?}", token)));
+                }
+            }
+        }
+        self.file.services.push(Service { name, methods });
+        Ok(())
+    }
+
+    // `(stream Type)`
+    fn rpc_type(&mut self) -> Result<(bool, String), String> {
+        self.expect('(')?;
+        let mut name = self.ident()?;
+        let stream = name == "stream" && matches!(self.peek(), Some(Token::Ident(_)));
+        if stream {
+            name = self.ident()?;
+        }
+        self.expect(')')?;
+        Ok((stream, name))
+    }
+}
+
+fn upper_camel(name: &str) -> String {
+    let mut camel = json_name(name);
+    if let Some(first) = camel.get(..1).map(|c| c.to_uppercase()) {
+        camel.replace_range(..1, &first);
+    }
+    camel
+}
+
+pub fn parse_file(name: &str, source: &str) -> Result<File, String> {
+    let tokens = tokenize(source).map_err(|e| format!("{}:{}", name, e))?;
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        file: File {
+            name: name.to_string(),
+            ..File::default()
+        },
+        proto3: false,
+    };
+    parser.parse()?;
+    Ok(parser.file)
+}
+
+// import のパスをインポートパスから探す
+fn find_import(import: &str, import_paths: &[PathBuf]) -> Option<PathBuf> {
+    import_paths
+        .iter()
+        .map(|dir| dir.join(import))
+        .find(|path| path.is_file())
+}
+
+// .proto ファイルを import も含めて読み込む（import はファイルのディレクトリと import_paths から探す）
+pub fn load(files: &[String], import_paths: &[String]) -> Result<Pool, String> {
+    let mut pool = Pool::default();
+    let mut dirs: Vec<PathBuf> = import_paths.iter().map(PathBuf::from).collect();
+    let mut queue: Vec<(String, Option<PathBuf>)> = Vec::new();
+    // 後ろから取り出すので逆順に積む
+    for file in files.iter().rev() {
+        let path = Path::new(file);
+        // ファイルの名前は import に書くパスと同じにする（インポートパスの下になければファイル名）
+        let name = dirs
+            .iter()
+            .find_map(|dir| path.strip_prefix(dir).ok())
+            .or_else(|| path.file_name().map(Path::new))
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| file.to_string());
+        queue.push((name, Some(path.to_path_buf())));
+    }
+    for file in files {
+        if let Some(dir) = Path::new(file).parent() {
+            dirs.push(if dir.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                dir.to_path_buf()
+            });
+        }
+    }
+
+    while let Some((name, path)) = queue.pop() {
+        if pool.has_file(&name) {
+            continue;
+        }
+        let source = match path.or_else(|| find_import(&name, &dirs)) {
+            Some(path) => fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))?,
+            None => match WELL_KNOWN.iter().find(|(n, _)| *n == name) {
+                Some((_, source)) => source.to_string(),
+                None => {
+                    return Err(format!(
+                        "import {} not found (add its directory to proto_path)",
+                        name
+                    ))
+                }
+            },
+        };
+        let file = parse_file(&name, &source)?;
+        for dep in &file.dependencies {
+            if !pool.has_file(dep) {
+                queue.push((dep.to_string(), None));
+            }
+        }
+        pool.add(file);
+    }
+    pool.resolve()?;
+    Ok(pool)
+}
+
+// ---- JSON との変換 ----
+
+fn is_wrapper(name: &str) -> bool {
+    matches!(
+        name,
+        "google.protobuf.DoubleValue"
+            | "google.protobuf.FloatValue"
+            | "google.protobuf.Int64Value"
+            | "google.protobuf.UInt64Value"
+            | "google.protobuf.Int32Value"
+            | "google.protobuf.UInt32Value"
+            | "google.protobuf.BoolValue"
+            | "google.protobuf.StringValue"
+            | "google.protobuf.BytesValue"
+    )
+}
+
+// JSON で特別な形をとる Well-Known Types
+fn is_special(name: &str) -> bool {
+    is_wrapper(name)
+        || matches!(
+            name,
+            "google.protobuf.Timestamp"
+                | "google.protobuf.Duration"
+                | "google.protobuf.Struct"
+                | "google.protobuf.Value"
+                | "google.protobuf.ListValue"
+                | "google.protobuf.FieldMask"
+                | "google.protobuf.Any"
+        )
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn parse_duration(text: &str) -> Option<(i64, i32)> {
+    let number = text.strip_suffix('s')?;
+    let negative = number.starts_with('-');
+    let number = number.trim_start_matches('-');
+    let (seconds, fraction) = match number.find('.') {
+        Some(i) => (&number[..i], &number[i + 1..]),
+        None => (number, ""),
+    };
+    if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let seconds: i64 = seconds.parse().ok()?;
+    let nanos: i32 = format!("{:0<9}", fraction).parse().ok()?;
+    Some(if negative {
+        (-seconds, -nanos)
+    } else {
+        (seconds, nanos)
+    })
+}
+
+fn type_from_url(url: &str) -> &str {
+    match url.rfind('/') {
+        Some(i) => &url[i + 1..],
+        None => url,
+    }
+}
+
+impl Pool {
+    // JSON を指定した型のメッセージにする
+    pub fn encode(&self, type_name: &str, value: &Value) -> Result<Vec<u8>, String> {
+        self.encode_message(type_name, value, type_name)
+    }
+
+    fn encode_message(
+        &self,
+        type_name: &str,
+        value: &Value,
+        path: &str,
+    ) -> Result<Vec<u8>, String> {
+        let regular;
+        let value = if is_special(type_name) {
+            regular = self.special_to_regular(type_name, value, path)?;
+            &regular
+        } else {
+            value
+        };
+        let message = self.message(type_name)?;
+        let object = match value {
+            Value::Object(object) => object,
+            _ => return Err(format!("{}: expected an object for {}", path, type_name)),
+        };
+        let mut buf = Vec::new();
+        for (key, value) in object {
+            let field = message
+                .fields
+                .iter()
+                .find(|f| f.json_name == *key || f.name == *key)
+                .ok_or_else(|| format!("{}: unknown field '{}' in {}", path, key, type_name))?;
+            let path = format!("{}.{}", path, key);
+            // google.protobuf.Value の null は値として送る
+            if value.is_null() && field.type_name != "google.protobuf.Value" {
+                continue;
+            }
+            if let Some((key_field, value_field)) = self.map_fields(field) {
+                let entries = match value {
+                    Value::Object(entries) => entries,
+                    _ => return Err(format!("{}: expected an object", path)),
+                };
+                for (k, v) in entries {
+                    let key_json = match key_field.kind {
+                        Kind::Bool => Value::Bool(k == "true"),
+                        _ => Value::String(k.to_string()),
+                    };
+                    let mut entry = Vec::new();
+                    self.encode_field(&mut entry, key_field, &key_json, &path, false)?;
+                    self.encode_field(
+                        &mut entry,
+                        value_field,
+                        v,
+                        &format!("{}.{}", path, k),
+                        false,
+                    )?;
+                    put_bytes(&mut buf, field.number, &entry);
+                }
+            } else if field.repeated {
+                let items = match value {
+                    Value::Array(items) => items,
+                    _ => return Err(format!("{}: expected an array", path)),
+                };
+                let packed = field.packed.unwrap_or(message.proto3) && field.kind.packable();
+                if packed {
+                    let mut packed_buf = Vec::new();
+                    for (i, item) in items.iter().enumerate() {
+                        self.encode_field(
+                            &mut packed_buf,
+                            field,
+                            item,
+                            &format!("{}[{}]", path, i),
+                            true,
+                        )?;
+                    }
+                    put_bytes(&mut buf, field.number, &packed_buf);
+                } else {
+                    for (i, item) in items.iter().enumerate() {
+                        self.encode_field(
+                            &mut buf,
+                            field,
+                            item,
+                            &format!("{}[{}]", path, i),
+                            false,
+                        )?;
+                    }
+                }
+            } else {
+                self.encode_field(&mut buf, field, value, &path, false)?;
+            }
+        }
+        Ok(buf)
+    }
+
+    // 1つの値を書き込む（packed なら番号と型を付けない）
+    fn encode_field(
+        &self,
+        buf: &mut Vec<u8>,
+        field: &Field,
+        value: &Value,
+        path: &str,
+        packed: bool,
+    ) -> Result<(), String> {
+        if !packed {
+            if field.kind == Kind::Message {
+                let bytes = self.encode_message(&field.type_name, value, path)?;
+                put_bytes(buf, field.number, &bytes);
+                return Ok(());
+            }
+            if field.kind == Kind::Group {
+                return Err(format!("{}: groups are not supported", path));
+            }
+            put_key(buf, field.number, field.kind.wire_type());
+        }
+        let invalid = |expected: &str| format!("{}: expected {}, got {}", path, expected, value);
+        match field.kind {
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => {
+                let n = json_i64(value)
+                    .filter(|n| i32::MIN as i64 <= *n && *n <= i32::MAX as i64)
+                    .ok_or_else(|| invalid("a 32-bit integer"))?;
+                match field.kind {
+                    Kind::Sint32 => put_varint(buf, u64::from(((n << 1) ^ (n >> 31)) as u32)),
+                    Kind::Sfixed32 => buf.extend_from_slice(&(n as i32).to_le_bytes()),
+                    _ => put_varint(buf, n as u64),
+                }
+            }
+            Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => {
+                let n = json_i64(value).ok_or_else(|| invalid("a 64-bit integer"))?;
+                match field.kind {
+                    Kind::Sint64 => put_varint(buf, ((n << 1) ^ (n >> 63)) as u64),
+                    Kind::Sfixed64 => buf.extend_from_slice(&n.to_le_bytes()),
+                    _ => put_varint(buf, n as u64),
+                }
+            }
+            Kind::Uint32 | Kind::Fixed32 => {
+                let n = json_u64(value)
+                    .filter(|n| *n <= u64::from(u32::MAX))
+                    .ok_or_else(|| invalid("an unsigned 32-bit integer"))?;
+                match field.kind {
+                    Kind::Fixed32 => buf.extend_from_slice(&(n as u32).to_le_bytes()),
+                    _ => put_varint(buf, n),
+                }
+            }
+            Kind::Uint64 | Kind::Fixed64 => {
+                let n = json_u64(value).ok_or_else(|| invalid("an unsigned 64-bit integer"))?;
+                match field.kind {
+                    Kind::Fixed64 => buf.extend_from_slice(&n.to_le_bytes()),
+                    _ => put_varint(buf, n),
+                }
+            }
+            Kind::Double => {
+                let n = json_f64(value).ok_or_else(|| invalid("a number"))?;
+                buf.extend_from_slice(&n.to_le_bytes());
+            }
+            Kind::Float => {
+                let n = json_f64(value).ok_or_else(|| invalid("a number"))?;
+                buf.extend_from_slice(&(n as f32).to_le_bytes());
+            }
+            Kind::Bool => {
+                let b = value.as_bool().ok_or_else(|| invalid("true or false"))?;
+                put_varint(buf, b as u64);
+            }
+            Kind::Enum => {
+                let n = match value {
+                    Value::String(name) => self
+                        .enums
+                        .get(&field.type_name)
+                        .and_then(|e| e.values.iter().find(|(n, _)| n == name))
+                        .map(|(_, n)| *n as i64)
+                        .ok_or_else(|| {
+                            format!("{}: {} has no value {}", path, field.type_name, name)
+                        })?,
+                    _ => json_i64(value).ok_or_else(|| invalid("an enum name or number"))?,
+                };
+                put_varint(buf, n as u64);
+            }
+            Kind::String => {
+                let s = value.as_str().ok_or_else(|| invalid("a string"))?;
+                put_varint(buf, s.len() as u64);
+                buf.extend_from_slice(s.as_bytes());
+            }
+            Kind::Bytes => {
+                let s = value.as_str().ok_or_else(|| invalid("a base64 string"))?;
+                let normalized: String = s
+                    .trim_end_matches('=')
+                    .chars()
+                    .map(|c| match c {
+                        '-' => '+',
+                        '_' => '/',
+                        c => c,
+                    })
+                    .collect();
+                let bytes = base64::decode_config(&normalized, base64::STANDARD_NO_PAD)
+                    .map_err(|_| invalid("a base64 string"))?;
+                put_varint(buf, bytes.len() as u64);
+                buf.extend_from_slice(&bytes);
+            }
+            Kind::Message | Kind::Group | Kind::Named => {
+                return Err(format!("{}: {} cannot be packed", path, field.type_name));
+            }
+        }
+        Ok(())
+    }
+
+    // Well-Known Types の JSON を通常のメッセージの形にする
+    fn special_to_regular(
+        &self,
+        type_name: &str,
+        value: &Value,
+        path: &str,
+    ) -> Result<Value, String> {
+        let invalid = |expected: &str| {
+            format!(
+                "{}: expected {} for {}, got {}",
+                path, expected, type_name, value
+            )
+        };
+        let seconds_nanos = |(seconds, nanos): (i64, i32)| serde_json::json!({"seconds": seconds.to_string(), "nanos": nanos});
+        Ok(match type_name {
+            _ if is_wrapper(type_name) => serde_json::json!({ "value": value }),
+            "google.protobuf.Timestamp" => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| invalid("an RFC 3339 string"))?;
+                let time = chrono::DateTime::parse_from_rfc3339(text)
+                    .map_err(|_| invalid("an RFC 3339 string"))?;
+                seconds_nanos((time.timestamp(), time.timestamp_subsec_nanos() as i32))
+            }
+            "google.protobuf.Duration" => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| invalid("a duration like \"1.5s\""))?;
+                seconds_nanos(
+                    parse_duration(text).ok_or_else(|| invalid("a duration like \"1.5s\""))?,
+                )
+            }
+            "google.protobuf.Struct" => match value {
+                Value::Object(_) => serde_json::json!({ "fields": value }),
+                _ => return Err(invalid("an object")),
+            },
+            "google.protobuf.ListValue" => match value {
+                Value::Array(_) => serde_json::json!({ "values": value }),
+                _ => return Err(invalid("an array")),
+            },
+            "google.protobuf.Value" => match value {
+                Value::Null => serde_json::json!({"nullValue": "NULL_VALUE"}),
+                Value::Number(n) => serde_json::json!({"numberValue": n}),
+                Value::String(s) => serde_json::json!({"stringValue": s}),
+                Value::Bool(b) => serde_json::json!({"boolValue": b}),
+                Value::Object(_) => serde_json::json!({"structValue": value}),
+                Value::Array(_) => serde_json::json!({"listValue": value}),
+            },
+            "google.protobuf.FieldMask" => {
+                let text = value
+                    .as_str()
+                    .ok_or_else(|| invalid("a comma-separated string"))?;
+                let paths: Vec<String> = text
+                    .split(',')
+                    .filter(|p| !p.is_empty())
+                    .map(snake_case)
+                    .collect();
+                serde_json::json!({ "paths": paths })
+            }
+            "google.protobuf.Any" => {
+                let object = value
+                    .as_object()
+                    .ok_or_else(|| invalid("an object with @type"))?;
+                let url = object
+                    .get("@type")
+                    .and_then(|t| t.as_str())
+                    .ok_or_else(|| invalid("an object with @type"))?;
+                let inner = type_from_url(url);
+                let bytes = if is_special(inner) {
+                    let inner_value = object.get("value").cloned().unwrap_or(Value::Null);
+                    self.encode_message(inner, &inner_value, path)?
+                } else {
+                    let mut rest = object.clone();
+                    rest.remove("@type");
+                    self.encode_message(inner, &Value::Object(rest), path)?
+                };
+                serde_json::json!({"typeUrl": url, "value": base64::encode(&bytes)})
+            }
+            _ => value.clone(),
+        })
+    }
+
+    // メッセージを JSON にする（既定値のフィールドは含まない）
+    pub fn decode(&self, type_name: &str, data: &[u8]) -> Result<Value, String> {
+        let message = self.message(type_name)?;
+        let mut object = Map::new();
+        for (number, wire) in fields(data)? {
+            let field = match message.fields.iter().find(|f| f.number == number) {
+                Some(field) => field,
+                // 知らないフィールドは無視する
+                None => continue,
+            };
+            let key = field.json_name.clone();
+            if let Some((key_field, value_field)) = self.map_fields(field) {
+                let entry = match wire {
+                    Wire::Bytes(bytes) => bytes,
+                    _ => return Err(format!("{}.{}: invalid map entry", type_name, field.name)),
+                };
+                let mut k = self.default_json(key_field);
+                let mut v = self.default_json(value_field);
+                for (number, wire) in fields(entry)? {
+                    match number {
+                        1 => k = self.decode_value(key_field, wire)?,
+                        2 => v = self.decode_value(value_field, wire)?,
+                        _ => {}
+                    }
+                }
+                let k = match k {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                if let Value::Object(entries) = object
+                    .entry(key)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                {
+                    entries.insert(k, v);
+                }
+            } else if field.repeated {
+                let mut values = Vec::new();
+                match wire {
+                    // packed
+                    Wire::Bytes(bytes) if field.kind.packable() => {
+                        let mut reader = Reader {
+                            data: bytes,
+                            pos: 0,
+                        };
+                        while reader.pos < bytes.len() {
+                            let wire = match field.kind.wire_type() {
+                                1 => Wire::Fixed64(reader.fixed64()?),
+                                5 => Wire::Fixed32(reader.fixed32()?),
+                                _ => Wire::Varint(reader.varint()?),
+                            };
+                            values.push(self.decode_value(field, wire)?);
+                        }
+                    }
+                    wire => values.push(self.decode_value(field, wire)?),
+                }
+                if let Value::Array(items) = object
+                    .entry(key)
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                {
+                    items.extend(values);
+                }
+            } else {
+                let value = self.decode_value(field, wire)?;
+                object.insert(key, value);
+            }
+        }
+        let value = Value::Object(object);
+        if is_special(type_name) {
+            return Ok(self.regular_to_special(type_name, value));
+        }
+        Ok(value)
+    }
+
+    fn decode_value(&self, field: &Field, wire: Wire) -> Result<Value, String> {
+        let mismatch = || format!("field {} has an unexpected wire type", field.name);
+        let varint = || match wire {
+            Wire::Varint(v) => Ok(v),
+            _ => Err(mismatch()),
+        };
+        Ok(match field.kind {
+            Kind::Int32 => Value::from(varint()? as i32),
+            Kind::Int64 => Value::String((varint()? as i64).to_string()),
+            Kind::Uint32 => Value::from(varint()? as u32),
+            Kind::Uint64 => Value::String(varint()?.to_string()),
+            Kind::Sint32 => {
+                let v = varint()? as u32;
+                Value::from(((v >> 1) as i32) ^ -((v & 1) as i32))
+            }
+            Kind::Sint64 => {
+                let v = varint()?;
+                Value::String((((v >> 1) as i64) ^ -((v & 1) as i64)).to_string())
+            }
+            Kind::Bool => Value::Bool(varint()? != 0),
+            Kind::Enum => {
+                let n = varint()? as i32;
+                self.enums
+                    .get(&field.type_name)
+                    .and_then(|e| e.values.iter().find(|(_, v)| *v == n))
+                    .map(|(name, _)| Value::String(name.clone()))
+                    .unwrap_or_else(|| Value::from(n))
+            }
+            Kind::Fixed32 | Kind::Sfixed32 | Kind::Float => {
+                let v = match wire {
+                    Wire::Fixed32(v) => v,
+                    _ => return Err(mismatch()),
+                };
+                match field.kind {
+                    Kind::Fixed32 => Value::from(v),
+                    Kind::Sfixed32 => Value::from(v as i32),
+                    _ => float_json(f64::from(f32::from_bits(v))),
+                }
+            }
+            Kind::Fixed64 | Kind::Sfixed64 | Kind::Double => {
+                let v = match wire {
+                    Wire::Fixed64(v) => v,
+                    _ => return Err(mismatch()),
+                };
+                match field.kind {
+                    Kind::Fixed64 => Value::String(v.to_string()),
+                    Kind::Sfixed64 => Value::String((v as i64).to_string()),
+                    _ => float_json(f64::from_bits(v)),
+                }
+            }
+            Kind::String | Kind::Bytes | Kind::Message => {
+                let bytes = match wire {
+                    Wire::Bytes(bytes) => bytes,
+                    _ => return Err(mismatch()),
+                };
+                match field.kind {
+                    Kind::String => Value::String(String::from_utf8_lossy(bytes).to_string()),
+                    Kind::Bytes => Value::String(base64::encode(bytes)),
+                    _ => self.decode(&field.type_name, bytes)?,
+                }
+            }
+            Kind::Group | Kind::Named => Value::Null,
+        })
+    }
+
+    fn regular_to_special(&self, type_name: &str, value: Value) -> Value {
+        let get = |key: &str| value.get(key).cloned();
+        let seconds_nanos = || {
+            let seconds = get("seconds")
+                .and_then(|s| s.as_str().and_then(|s| s.parse::<i64>().ok()))
+                .unwrap_or(0);
+            let nanos = get("nanos").and_then(|n| n.as_i64()).unwrap_or(0);
+            (seconds, nanos)
+        };
+        match type_name {
+            _ if is_wrapper(type_name) => get("value").unwrap_or_else(|| match type_name {
+                "google.protobuf.BoolValue" => Value::Bool(false),
+                "google.protobuf.StringValue" | "google.protobuf.BytesValue" => {
+                    Value::String(String::new())
+                }
+                "google.protobuf.Int64Value" | "google.protobuf.UInt64Value" => {
+                    Value::String("0".to_string())
+                }
+                _ => Value::from(0),
+            }),
+            "google.protobuf.Timestamp" => {
+                let (seconds, nanos) = seconds_nanos();
+                match chrono::DateTime::from_timestamp(seconds, nanos as u32) {
+                    Some(time) => {
+                        Value::String(format!("{}Z", time.format("%Y-%m-%dT%H:%M:%S%.f")))
+                    }
+                    None => value,
+                }
+            }
+            "google.protobuf.Duration" => {
+                let (seconds, nanos) = seconds_nanos();
+                let sign = if seconds < 0 || nanos < 0 { "-" } else { "" };
+                let text = if nanos == 0 {
+                    format!("{}{}s", sign, seconds.abs())
+                } else {
+                    let fraction = format!("{:09}", nanos.abs());
+                    format!(
+                        "{}{}.{}s",
+                        sign,
+                        seconds.abs(),
+                        fraction.trim_end_matches('0')
+                    )
+                };
+                Value::String(text)
+            }
+            "google.protobuf.Struct" => get("fields").unwrap_or_else(|| Value::Object(Map::new())),
+            "google.protobuf.ListValue" => {
+                get("values").unwrap_or_else(|| Value::Array(Vec::new()))
+            }
+            "google.protobuf.Value" => match value.as_object().and_then(|o| o.iter().next()) {
+                Some((key, v)) if key != "nullValue" => v.clone(),
+                _ => Value::Null,
+            },
+            "google.protobuf.FieldMask" => {
+                let paths: Vec<String> = get("paths")
+                    .and_then(|p| p.as_array().cloned())
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|p| p.as_str().map(json_name))
+                    .collect();
+                Value::String(paths.join(","))
+            }
+            "google.protobuf.Any" => {
+                let url = get("typeUrl").and_then(|u| u.as_str().map(|u| u.to_string()));
+                let bytes = get("value")
+                    .and_then(|v| v.as_str().and_then(|v| base64::decode(v).ok()))
+                    .unwrap_or_default();
+                let url = match url {
+                    Some(url) => url,
+                    None => return value,
+                };
+                let inner = type_from_url(&url);
+                match self.decode(inner, &bytes) {
+                    Ok(Value::Object(mut fields)) if !is_special(inner) => {
+                        fields.insert("@type".to_string(), Value::String(url));
+                        Value::Object(fields)
+                    }
+                    Ok(decoded) => serde_json::json!({"@type": url, "value": decoded}),
+                    // 型がわからなければそのまま
+                    Err(_) => value,
+                }
+            }
+            _ => value,
+        }
+    }
+
+    // 送られてこなかったフィールドの値（map のキーと値に使う）
+    fn default_json(&self, field: &Field) -> Value {
+        match field.kind {
+            Kind::Int64 | Kind::Uint64 | Kind::Sint64 | Kind::Fixed64 | Kind::Sfixed64 => {
+                Value::String("0".to_string())
+            }
+            Kind::String | Kind::Bytes => Value::String(String::new()),
+            Kind::Bool => Value::Bool(false),
+            Kind::Enum => self
+                .enums
+                .get(&field.type_name)
+                .and_then(|e| e.values.first())
+                .map(|(name, _)| Value::String(name.clone()))
+                .unwrap_or_else(|| Value::from(0)),
+            Kind::Message | Kind::Group | Kind::Named => Value::Object(Map::new()),
+            _ => Value::from(0),
+        }
+    }
+
+    // リクエストの雛形（すべてのフィールドに既定値を入れる。oneof は最初の1つだけ）
+    pub fn template(&self, type_name: &str) -> Value {
+        self.template_depth(type_name, 0)
+    }
+
+    fn template_depth(&self, type_name: &str, depth: usize) -> Value {
+        match type_name {
+            "google.protobuf.Timestamp" => {
+                return Value::String("1970-01-01T00:00:00Z".to_string())
+            }
+            "google.protobuf.Duration" => return Value::String("0s".to_string()),
+            "google.protobuf.FieldMask" => return Value::String(String::new()),
+            "google.protobuf.Value" => return Value::Null,
+            "google.protobuf.ListValue" => return Value::Array(Vec::new()),
+            "google.protobuf.Struct" => return Value::Object(Map::new()),
+            "google.protobuf.Any" => return serde_json::json!({"@type": ""}),
+            _ if is_wrapper(type_name) => return Value::Null,
+            _ => {}
+        }
+        let message = match self.messages.get(type_name) {
+            Some(message) => message,
+            None => return Value::Object(Map::new()),
+        };
+        let mut object = Map::new();
+        let mut oneofs = BTreeSet::new();
+        for field in &message.fields {
+            if let Some(oneof) = &field.oneof {
+                if !oneofs.insert(oneof.clone()) {
+                    continue;
+                }
+            }
+            let value = if self.is_map(field) {
+                Value::Object(Map::new())
+            } else if field.repeated {
+                Value::Array(Vec::new())
+            } else if field.kind == Kind::Message {
+                if depth < TEMPLATE_DEPTH {
+                    self.template_depth(&field.type_name, depth + 1)
+                } else {
+                    Value::Object(Map::new())
+                }
+            } else {
+                self.default_json(field)
+            };
+            object.insert(field.json_name.clone(), value);
+        }
+        Value::Object(object)
+    }
+}
+
+fn float_json(n: f64) -> Value {
+    if n.is_nan() {
+        Value::String("NaN".to_string())
+    } else if n.is_infinite() {
+        Value::String(if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string())
+    } else {
+        Number::from_f64(n)
+            .map(Value::Number)
+            .unwrap_or(Value::Null)
+    }
+}
+
+// 整数は数値でも文字列でもよい
+fn json_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64().or_else(|| {
+            n.as_f64()
+                .filter(|f| f.fract() == 0.0 && f.abs() < 9.2e18)
+                .map(|f| f as i64)
+        }),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn json_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64().or_else(|| {
+            n.as_f64()
+                .filter(|f| f.fract() == 0.0 && *f >= 0.0 && *f < 1.8e19)
+                .map(|f| f as u64)
+        }),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn json_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            s => s.trim().parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DEMO: &str = r#"
+        syntax = "proto3";
+        package demo.v1;
+
+        enum Color {
+            COLOR_UNSPECIFIED = 0;
+            RED = 1;
+            BLUE = 2;
+        }
+
+        message Scalars {
+            double d = 1;
+            float f = 2;
+            int64 i64 = 3;
+            uint64 u64 = 4;
+            int32 i32 = 5;
+            fixed64 f64 = 6;
+            fixed32 f32 = 7;
+            bool b = 8;
+            string s = 9;
+            bytes by = 12;
+            uint32 u32 = 13;
+            sfixed32 sf32 = 15;
+            sfixed64 sf64 = 16;
+            sint32 si32 = 17;
+            sint64 si64 = 18;
+            Color color = 19;
+        }
+
+        message Outer {
+            message Inner {
+                string name = 1;
+                Inner child = 2;
+            }
+            repeated int32 numbers = 1;
+            repeated sint64 signed = 2 [packed = false];
+            repeated string names = 3;
+            map<string, int32> counts = 4;
+            map<int64, Inner> by_id = 5;
+            oneof choice {
+                string text = 6;
+                Inner inner = 7;
+            }
+            repeated Inner items = 8;
+        }
+    "#;
+
+    fn pool() -> Pool {
+        let mut pool = Pool::default();
+        pool.add(parse_file("demo.proto", DEMO).unwrap());
+        pool.resolve().unwrap();
+        pool
+    }
+
+    fn round_trip(pool: &Pool, type_name: &str, value: Value) -> Vec<u8> {
+        let bytes = pool.encode(type_name, &value).unwrap();
+        assert_eq!(pool.decode(type_name, &bytes).unwrap(), value);
+        bytes
+    }
+
+    #[test]
+    fn scalars_round_trip() {
+        let pool = pool();
+        round_trip(
+            &pool,
+            "demo.v1.Scalars",
+            json!({
+                "d": 1.5,
+                "f": -0.25,
+                "i64": "-9223372036854775808",
+                "u64": "18446744073709551615",
+                "i32": -2147483648,
+                "f64": "18446744073709551615",
+                "f32": 4294967295u32,
+                "b": true,
+                "s": "héllo",
+                "by": "AAEC/w==",
+                "u32": 4294967295u32,
+                "sf32": -1,
+                "sf64": "-1",
+                "si32": -2147483648,
+                "si64": "9223372036854775807",
+                "color": "BLUE",
+            }),
+        );
+        // 1つずつの値の wire format
+        let one = |value: Value| pool.encode("demo.v1.Scalars", &value).unwrap();
+        assert_eq!(one(json!({"i32": -1})), [0x28, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        assert_eq!(one(json!({"si32": -1})), [0x88, 0x01, 0x01]);
+        assert_eq!(one(json!({"f32": 1})), [0x3d, 1, 0, 0, 0]);
+        assert_eq!(one(json!({"s": "hi"})), [0x4a, 2, b'h', b'i']);
+        // 数値で書いた列挙型と64ビットの整数も受け付ける
+        assert_eq!(
+            pool.decode("demo.v1.Scalars", &one(json!({"color": 1, "i64": 5}))).unwrap(),
+            json!({"color": "RED", "i64": "5"})
+        );
+    }
+
+    #[test]
+    fn repeated_fields_are_packed_in_proto3() {
+        let pool = pool();
+        let bytes = round_trip(&pool, "demo.v1.Outer", json!({"numbers": [1, 2, 300]}));
+        assert_eq!(bytes, [0x0a, 4, 1, 2, 0xac, 0x02]);
+        // packed = false なら1つずつ送る
+        let bytes = round_trip(&pool, "demo.v1.Outer", json!({"signed": ["-1", "1"]}));
+        assert_eq!(bytes, [0x10, 1, 0x10, 2]);
+        // 文字列は packed にしない
+        let bytes = round_trip(&pool, "demo.v1.Outer", json!({"names": ["a", "b"]}));
+        assert_eq!(bytes, [0x1a, 1, b'a', 0x1a, 1, b'b']);
+        // packed でない値も読める
+        assert_eq!(
+            pool.decode("demo.v1.Outer", &[0x08, 1, 0x08, 2]).unwrap(),
+            json!({"numbers": [1, 2]})
+        );
+    }
+
+    #[test]
+    fn maps_round_trip() {
+        let pool = pool();
+        let bytes = round_trip(&pool, "demo.v1.Outer", json!({"counts": {"a": 1}}));
+        assert_eq!(bytes, [0x22, 5, 0x0a, 1, b'a', 0x10, 1]);
+        round_trip(
+            &pool,
+            "demo.v1.Outer",
+            json!({"counts": {"a": 1, "b": 2}, "byId": {"7": {"name": "seven"}}}),
+        );
+    }
+
+    #[test]
+    fn oneof_round_trips() {
+        let pool = pool();
+        round_trip(&pool, "demo.v1.Outer", json!({"text": "t"}));
+        round_trip(&pool, "demo.v1.Outer", json!({"inner": {"name": "i"}}));
+        let message = pool.message("demo.v1.Outer").unwrap();
+        let oneof: Vec<&str> = message
+            .fields
+            .iter()
+            .filter(|f| f.oneof.as_deref() == Some("choice"))
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(oneof, ["text", "inner"]);
+    }
+
+    #[test]
+    fn nested_messages_round_trip() {
+        let pool = pool();
+        let bytes = round_trip(
+            &pool,
+            "demo.v1.Outer.Inner",
+            json!({"name": "a", "child": {"name": "b", "child": {"name": "c"}}}),
+        );
+        // フィールドは JSON のキーの順に書く
+        assert_eq!(bytes, [0x12, 8, 0x12, 3, 0x0a, 1, b'c', 0x0a, 1, b'b', 0x0a, 1, b'a']);
+        round_trip(
+            &pool,
+            "demo.v1.Outer",
+            json!({"items": [{"name": "x"}, {"child": {"name": "y"}}]}),
+        );
+        assert!(pool
+            .encode("demo.v1.Outer", &json!({"items": [{"nope": 1}]}))
+            .unwrap_err()
+            .contains("unknown field 'nope'"));
+    }
+
+    #[test]
+    fn load_follows_imports_across_packages() {
+        let dir = std::env::temp_dir().join(format!("ruzz-proto-{}", std::process::id()));
+        fs::create_dir_all(dir.join("common")).unwrap();
+        fs::write(
+            dir.join("common/types.proto"),
+            r#"
+            syntax = "proto3";
+            package common.types;
+            import "google/protobuf/timestamp.proto";
+            message Stamp { google.protobuf.Timestamp at = 1; }
+            enum Level { LOW = 0; HIGH = 1; }
+            "#,
+        )
+        .unwrap();
+        let main = dir.join("main.proto");
+        fs::write(
+            &main,
+            r#"
+            syntax = "proto3";
+            package app.v1;
+            import public "common/types.proto";
+            message Event {
+                common.types.Stamp stamp = 1;
+                common.types.Level level = 2;
+            }
+            service Events { rpc Get (Event) returns (stream Event); }
+            "#,
+        )
+        .unwrap();
+
+        let pool = load(&[main.to_string_lossy().to_string()], &[]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(pool.has_file("common/types.proto"));
+        assert!(pool.has_file("google/protobuf/timestamp.proto"));
+        let (service, method) = pool.method("/app.v1.Events/Get").unwrap();
+        assert_eq!(service.name, "app.v1.Events");
+        assert_eq!(Pool::signature(method), "Get(app.v1.Event) returns (stream app.v1.Event)");
+        let event = pool.message("app.v1.Event").unwrap();
+        assert_eq!(event.fields[0].type_name, "common.types.Stamp");
+        assert_eq!(event.fields[1].kind, Kind::Enum);
+        round_trip(
+            &pool,
+            "app.v1.Event",
+            json!({"stamp": {"at": "2024-01-02T03:04:05Z"}, "level": "HIGH"}),
+        );
+    }
+
+    #[test]
+    fn load_reports_missing_imports() {
+        let dir = std::env::temp_dir().join(format!("ruzz-proto-missing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("main.proto");
+        fs::write(&main, "syntax = \"proto3\";\nimport \"nowhere/x.proto\";\n").unwrap();
+        let err = load(&[main.to_string_lossy().to_string()], &[]).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(err.contains("import nowhere/x.proto not found"), "{}", err);
+    }
+
+    #[test]
+    fn parse_errors_have_line_numbers() {
+        let err = parse_file("bad.proto", "syntax = \"proto3\";\nmessage M {\n  int32 = 1;\n}\n").unwrap_err();
+        assert!(err.starts_with("bad.proto:3:"), "{}", err);
+    }
+}
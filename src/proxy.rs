@@ -0,0 +1,288 @@
+use std::env;
+use std::net::IpAddr;
+use url::Url;
+
+use super::settings::ClientSettings;
+
+// リクエストの URL ごとに使うプロキシ
+//
+// Settings の値がなければ環境変数（HTTP_PROXY, HTTPS_PROXY, ALL_PROXY, NO_PROXY）を使う。
+// proxy=none ならどちらも使わない
+#[derive(Clone, Debug, Default)]
+pub struct Proxies {
+    http: Option<Url>,
+    https: Option<Url>,
+    no_proxy: Vec<String>,
+}
+
+// 大文字と小文字のどちらの環境変数でもよい
+fn env_var(name: &str) -> Option<String> {
+    env::var(name)
+        .or_else(|_| env::var(name.to_lowercase()))
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+// スキームがなければ http:// とみなす
+fn parse_url(key: &str, value: &str) -> Result<Url, String> {
+    let text = if value.contains("://") {
+        value.to_string()
+    } else {
+        format!("http://{}", value)
+    };
+    let url = Url::parse(&text).map_err(|e| format!("invalid {}: {}: {}", key, value, e))?;
+    match url.scheme() {
+        "http" | "https" | "socks5" | "socks5h" => Ok(url),
+        scheme => Err(format!(
+            "{} must be an http, https, socks5 or socks5h URL: {}",
+            key, scheme
+        )),
+    }
+}
+
+// パスワードを伏せて表示する
+pub fn display(url: &Url) -> String {
+    let mut url = url.clone();
+    if url.password().is_some() {
+        let _ = url.set_password(Some("****"));
+    }
+    url.as_str().trim_end_matches('/').to_string()
+}
+
+// 10.0.0.0/8 のような CIDR に含まれるか
+fn in_cidr(ip: IpAddr, cidr: &str) -> bool {
+    let mut parts = cidr.splitn(2, '/');
+    let network = match parts.next().and_then(|n| n.parse::<IpAddr>().ok()) {
+        Some(network) => network,
+        None => return false,
+    };
+    let bits = match parts.next().map(|b| b.parse::<u32>()) {
+        Some(Ok(bits)) => bits,
+        Some(Err(_)) => return false,
+        None => return ip == network,
+    };
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) if bits <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - bits).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) if bits <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - bits).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+// NO_PROXY の `host:port` と `[::1]:port` からポートを分ける（角括弧のない IPv6 アドレスはそのまま）
+fn split_port(entry: &str) -> (&str, Option<u16>) {
+    if let Some(rest) = entry.strip_prefix('[') {
+        return match rest.split_once("]:") {
+            Some((host, port)) => (host, port.parse().ok()),
+            None => (rest.trim_end_matches(']'), None),
+        };
+    }
+    match entry.split_once(':') {
+        Some((host, port)) if !port.contains(':') => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (entry, None),
+        },
+        _ => (entry, None),
+    }
+}
+
+impl Proxies {
+    pub fn new(settings: &ClientSettings) -> Result<Proxies, String> {
+        Proxies::with_env(settings, env_var)
+    }
+
+    // env_var は環境変数を引く関数（テストでは差し替える）
+    fn with_env(settings: &ClientSettings, env_var: impl Fn(&str) -> Option<String>) -> Result<Proxies, String> {
+        if settings.proxy.as_deref() == Some("none") {
+            return Ok(Proxies::default());
+        }
+        // 値と、エラーに表示する設定か環境変数の名前
+        let pick = |key: &str, value: &Option<String>, name: &str| -> Result<Option<Url>, String> {
+            let found = value
+                .clone()
+                .map(|v| (key.to_string(), v))
+                .or_else(|| settings.proxy.clone().map(|v| ("proxy".to_string(), v)))
+                .or_else(|| env_var(name).map(|v| (name.to_string(), v)))
+                .or_else(|| env_var("ALL_PROXY").map(|v| ("ALL_PROXY".to_string(), v)));
+            match found {
+                Some((_, value)) if value == "none" => Ok(None),
+                Some((key, value)) => parse_url(&key, &value).map(Some),
+                None => Ok(None),
+            }
+        };
+        let mut http = pick("http_proxy", &settings.http_proxy, "HTTP_PROXY")?;
+        let mut https = pick("https_proxy", &settings.https_proxy, "HTTPS_PROXY")?;
+
+        if let Some(auth) = &settings.proxy_auth {
+            let mut parts = auth.splitn(2, ':');
+            let username = parts.next().unwrap_or("");
+            let password = parts.next().unwrap_or("");
+            for url in http.iter_mut().chain(https.iter_mut()) {
+                let _ = url.set_username(username);
+                let _ = url.set_password(Some(password));
+            }
+        }
+
+        let no_proxy = settings
+            .no_proxy
+            .clone()
+            .or_else(|| env_var("NO_PROXY"))
+            .unwrap_or_default()
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| entry.to_lowercase())
+            .collect();
+        Ok(Proxies {
+            http,
+            https,
+            no_proxy,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.http.is_none() && self.https.is_none()
+    }
+
+    // NO_PROXY に含まれるホストか（`*`、ドメインとそのサブドメイン、IP アドレスと CIDR、`:port` 付きならそのポートだけ）
+    fn bypass(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.trim_start_matches('[').trim_end_matches(']').to_lowercase(),
+            None => return false,
+        };
+        let ip = host.parse::<IpAddr>().ok();
+        self.no_proxy.iter().any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            let (entry, port) = split_port(entry);
+            if port.is_some() && port != url.port_or_known_default() {
+                return false;
+            }
+            if let Some(ip) = ip {
+                return in_cidr(ip, entry);
+            }
+            let domain = entry.trim_start_matches('.');
+            host == domain || host.ends_with(&format!(".{}", domain))
+        })
+    }
+
+    // URL の送信に使うプロキシ（使わなければ None）
+    pub fn for_url(&self, url: &Url) -> Option<Url> {
+        if self.bypass(url) {
+            return None;
+        }
+        match url.scheme() {
+            "https" | "wss" => self.https.clone(),
+            _ => self.http.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn proxies(settings: &str, env: &[(&str, &str)]) -> Proxies {
+        let env: BTreeMap<String, String> = env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Proxies::with_env(&ClientSettings::parse(settings).unwrap(), |name| env.get(name).cloned()).unwrap()
+    }
+
+    fn proxy_for(proxies: &Proxies, url: &str) -> Option<String> {
+        proxies.for_url(&Url::parse(url).unwrap()).map(|url| display(&url))
+    }
+
+    #[test]
+    fn no_proxy_matches_domains_and_subdomains() {
+        let proxies = proxies("proxy=http://p:3128\nno_proxy=example.com, .internal.test", &[]);
+        assert_eq!(proxy_for(&proxies, "http://example.com/"), None);
+        assert_eq!(proxy_for(&proxies, "https://api.EXAMPLE.com/"), None);
+        assert_eq!(proxy_for(&proxies, "http://a.internal.test/"), None);
+        assert_eq!(proxy_for(&proxies, "http://internal.test/"), None);
+        assert_eq!(proxy_for(&proxies, "http://notexample.com/").as_deref(), Some("http://p:3128"));
+        assert_eq!(proxy_for(&proxies, "http://example.com.evil.test/").as_deref(), Some("http://p:3128"));
+    }
+
+    #[test]
+    fn no_proxy_star_bypasses_everything() {
+        let proxies = proxies("proxy=http://p:3128\nno_proxy=*", &[]);
+        assert_eq!(proxy_for(&proxies, "https://example.com/"), None);
+        assert_eq!(proxy_for(&proxies, "http://10.0.0.1/"), None);
+    }
+
+    #[test]
+    fn no_proxy_ports_limit_the_bypass() {
+        let proxies = proxies("proxy=http://p:3128\nno_proxy=example.com:8080 [::1]:443 localhost:80", &[]);
+        assert_eq!(proxy_for(&proxies, "http://example.com:8080/"), None);
+        assert!(proxy_for(&proxies, "http://example.com/").is_some());
+        assert_eq!(proxy_for(&proxies, "https://[::1]/"), None);
+        assert!(proxy_for(&proxies, "http://[::1]:8443/").is_some());
+        assert_eq!(proxy_for(&proxies, "http://localhost/"), None);
+        assert!(proxy_for(&proxies, "https://localhost/").is_some());
+    }
+
+    #[test]
+    fn no_proxy_matches_ip_addresses_and_cidr() {
+        let proxies = proxies("proxy=http://p:3128\nno_proxy=10.0.0.0/8,192.168.1.5,fd00::/8,::1", &[]);
+        assert_eq!(proxy_for(&proxies, "http://10.20.30.40/"), None);
+        assert!(proxy_for(&proxies, "http://11.0.0.1/").is_some());
+        assert_eq!(proxy_for(&proxies, "http://192.168.1.5/"), None);
+        assert!(proxy_for(&proxies, "http://192.168.1.6/").is_some());
+        assert_eq!(proxy_for(&proxies, "http://[fd12:3456::1]/"), None);
+        assert!(proxy_for(&proxies, "http://[fe80::1]/").is_some());
+        assert_eq!(proxy_for(&proxies, "http://[::1]:8080/"), None);
+        // IPv4 の CIDR は IPv6 のアドレスに当たらない
+        assert!(!in_cidr("::ffff:10.0.0.1".parse().unwrap(), "10.0.0.0/8"));
+        assert!(in_cidr("10.0.0.1".parse().unwrap(), "0.0.0.0/0"));
+    }
+
+    #[test]
+    fn settings_take_precedence_over_environment_variables() {
+        let env = [
+            ("HTTP_PROXY", "http://env-http:1"),
+            ("HTTPS_PROXY", "env-https:2"),
+            ("ALL_PROXY", "socks5h://env-all:3"),
+            ("NO_PROXY", "skip.test"),
+        ];
+        let from_env = proxies("", &env);
+        assert_eq!(proxy_for(&from_env, "http://a.test/").as_deref(), Some("http://env-http:1"));
+        assert_eq!(proxy_for(&from_env, "https://a.test/").as_deref(), Some("http://env-https:2"));
+        assert_eq!(proxy_for(&from_env, "http://skip.test/"), None);
+
+        let all = proxies("", &[("ALL_PROXY", "socks5h://env-all:3")]);
+        assert_eq!(proxy_for(&all, "https://a.test/").as_deref(), Some("socks5h://env-all:3"));
+
+        let set = proxies("https_proxy=http://set-https:4\nno_proxy=other.test", &env);
+        assert_eq!(proxy_for(&set, "https://a.test/").as_deref(), Some("http://set-https:4"));
+        assert_eq!(proxy_for(&set, "http://a.test/").as_deref(), Some("http://env-http:1"));
+        // no_proxy の設定は NO_PROXY を置き換える
+        assert!(proxy_for(&set, "http://skip.test/").is_some());
+
+        let proxy = proxies("proxy=http://set:5", &env);
+        assert_eq!(proxy_for(&proxy, "http://a.test/").as_deref(), Some("http://set:5"));
+        assert_eq!(proxy_for(&proxy, "https://a.test/").as_deref(), Some("http://set:5"));
+    }
+
+    #[test]
+    fn proxy_none_ignores_the_environment() {
+        let none = proxies("proxy=none", &[("HTTP_PROXY", "http://env:1"), ("ALL_PROXY", "http://env:2")]);
+        assert!(none.is_empty());
+        assert_eq!(proxy_for(&none, "http://a.test/"), None);
+        let https_none = proxies("https_proxy=none", &[("ALL_PROXY", "http://env:2")]);
+        assert_eq!(proxy_for(&https_none, "https://a.test/"), None);
+        assert!(proxy_for(&https_none, "http://a.test/").is_some());
+    }
+
+    #[test]
+    fn proxy_auth_is_added_to_both_proxies() {
+        let proxies = proxies("proxy=http://p:3128\nproxy_auth=user:secret", &[]);
+        let url = proxies.for_url(&Url::parse("https://a.test/").unwrap()).unwrap();
+        assert_eq!(url.username(), "user");
+        assert_eq!(display(&url), "http://user:****@p:3128");
+    }
+}
@@ -0,0 +1,577 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::settings::ClientSettings;
+
+const DEFAULT_CONNECT_TIMEOUT: u64 = 30;
+
+// `--resolve host:port:addr[,addr]`
+#[derive(Clone, Debug)]
+struct Resolve {
+    host: String,
+    // `*` ならどのポートでも
+    port: Option<u16>,
+    addrs: Vec<IpAddr>,
+}
+
+// `--connect-to host:port:connect_host:connect_port`（空の部分はどれでも、または変えない）
+#[derive(Clone, Debug)]
+struct ConnectTo {
+    host: Option<String>,
+    port: Option<u16>,
+    to_host: Option<String>,
+    to_port: Option<u16>,
+}
+
+// 名前解決の上書きと IP のバージョン
+#[derive(Clone, Debug, Default)]
+pub struct Overrides {
+    resolve: Vec<Resolve>,
+    connect_to: Vec<ConnectTo>,
+    // ipv4 か ipv6（None ならどちらでも）
+    family: Option<String>,
+    // すべての接続をこの Unix ドメインソケットにつなぐ
+    unix_socket: Option<String>,
+    // 中継先に接続するときのタイムアウト
+    connect_timeout: Duration,
+}
+
+// `:` で区切る（[::1] のような角括弧の中は区切らない）
+fn split_colons(text: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut bracket = false;
+    for c in text.chars() {
+        match c {
+            '[' => bracket = true,
+            ']' => bracket = false,
+            ':' if !bracket => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(part) = parts.last_mut() {
+            part.push(c);
+        }
+    }
+    parts
+}
+
+fn strip_brackets(host: &str) -> String {
+    host.trim_start_matches('[').trim_end_matches(']').to_lowercase()
+}
+
+fn parse_port(entry: &str, port: &str) -> Result<Option<u16>, String> {
+    match port {
+        "" | "*" => Ok(None),
+        _ => port
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("invalid port in {}: {}", entry, port)),
+    }
+}
+
+impl Overrides {
+    pub fn new(settings: &ClientSettings) -> Result<Overrides, String> {
+        let mut overrides = Overrides {
+            family: settings.ip_version.clone().filter(|v| v != "auto"),
+            unix_socket: settings.unix_socket.clone(),
+            connect_timeout: connect_timeout(settings),
+            ..Overrides::default()
+        };
+        for entry in settings.resolve.iter().flat_map(|v| v.split_whitespace()) {
+            let parts = split_colons(entry);
+            if parts.len() != 3 || parts[0].is_empty() {
+                return Err(format!("resolve must be host:port:addr[,addr]: {}", entry));
+            }
+            let addrs = parts[2]
+                .split(',')
+                .map(|addr| {
+                    strip_brackets(addr)
+                        .parse::<IpAddr>()
+                        .map_err(|_| format!("invalid address in resolve {}: {}", entry, addr))
+                })
+                .collect::<Result<Vec<IpAddr>, String>>()?;
+            overrides.resolve.push(Resolve {
+                host: strip_brackets(&parts[0]),
+                port: parse_port(entry, &parts[1])?,
+                addrs,
+            });
+        }
+        for entry in settings.connect_to.iter().flat_map(|v| v.split_whitespace()) {
+            let parts = split_colons(entry);
+            if parts.len() != 4 {
+                return Err(format!("connect_to must be host:port:connect_host:connect_port: {}", entry));
+            }
+            let host = |part: &str| Some(strip_brackets(part)).filter(|h| !h.is_empty());
+            overrides.connect_to.push(ConnectTo {
+                host: host(&parts[0]),
+                port: parse_port(entry, &parts[1])?,
+                to_host: host(&parts[2]),
+                to_port: parse_port(entry, &parts[3])?,
+            });
+        }
+        Ok(overrides)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resolve.is_empty()
+            && self.connect_to.is_empty()
+            && self.family.is_none()
+            && self.unix_socket.is_none()
+    }
+
+    // URL の接続先を差し替えるか（Unix ドメインソケット、connect_to か resolve に当たるホスト）
+    pub fn applies(&self, url: &url::Url) -> bool {
+        if self.unix_socket.is_some() {
+            return true;
+        }
+        let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => (strip_brackets(host), port),
+            _ => return false,
+        };
+        self.connect_to.iter().any(|c| {
+            c.host.as_ref().is_none_or(|h| *h == host) && c.port.is_none_or(|p| p == port)
+        }) || self
+            .resolve
+            .iter()
+            .any(|r| r.host == host && r.port.is_none_or(|p| p == port))
+    }
+
+    // 接続先のアドレス（connect_to で置き換えてから resolve を引き、IP のバージョンで絞る）
+    pub fn addrs(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+        let host = strip_brackets(host);
+        let (host, port) = match self.connect_to.iter().find(|c| {
+            c.host.as_ref().is_none_or(|h| *h == host) && c.port.is_none_or(|p| p == port)
+        }) {
+            Some(c) => (c.to_host.clone().unwrap_or(host), c.to_port.unwrap_or(port)),
+            None => (host, port),
+        };
+        let addrs = match self
+            .resolve
+            .iter()
+            .find(|r| r.host == host && r.port.is_none_or(|p| p == port))
+        {
+            Some(r) => r.addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect(),
+            None => (host.as_str(), port)
+                .to_socket_addrs()
+                .map_err(|e| format!("failed to resolve {}: {}", host, e))?
+                .collect::<Vec<SocketAddr>>(),
+        };
+        let addrs: Vec<SocketAddr> = addrs
+            .into_iter()
+            .filter(|addr| match self.family.as_deref() {
+                Some("ipv4") => addr.is_ipv4(),
+                Some("ipv6") => addr.is_ipv6(),
+                _ => true,
+            })
+            .collect();
+        if addrs.is_empty() {
+            return Err(format!(
+                "no {} address for {}",
+                self.family.as_deref().unwrap_or("IP"),
+                host
+            ));
+        }
+        Ok(addrs)
+    }
+}
+
+// connect_timeout の設定（0 や未設定なら既定値）
+fn connect_timeout(settings: &ClientSettings) -> Duration {
+    Duration::from_secs_f64(
+        settings
+            .connect_timeout
+            .filter(|t| *t > 0.0)
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT as f64),
+    )
+}
+
+// reqwest を通さない接続（WebSocket や gRPC）の TCP 接続（名前解決の上書きと IP のバージョンに従う）
+pub fn connect_tcp(url: &url::Url, settings: &ClientSettings) -> Result<TcpStream, String> {
+    let host = url.host_str().ok_or_else(|| format!("no host in {}", url))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| format!("no port in {}", url))?;
+    let overrides = Overrides::new(settings)?;
+    let addrs: Vec<SocketAddr> = if overrides.is_empty() {
+        (host, port)
+            .to_socket_addrs()
+            .map_err(|e| format!("failed to resolve {}: {}", host, e))?
+            .collect()
+    } else {
+        overrides.addrs(host, port)?
+    };
+    let timeout = connect_timeout(settings);
+    let mut last = format!("no address for {}", host);
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last = format!("failed to connect to {}: {}", addr, e),
+        }
+    }
+    Err(last)
+}
+
+// reqwest には名前解決や接続先（Unix ドメインソケット）を差し替える方法がないので、プロセス内の SOCKS5 サーバーを
+// 経由させ、そこで上書きしたアドレスに接続する（TLS はそのまま通すので SNI と証明書の検証は変わらない）
+//
+// ループバックで待ち受けるので、他のユーザーが使えないよう起動ごとに乱数のユーザー名とパスワードを要求する
+#[derive(Clone)]
+pub struct Resolver {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    _guard: Arc<Guard>,
+}
+
+struct Shared {
+    overrides: Overrides,
+    username: String,
+    password: String,
+    // host:port ごとの最後に接続したアドレスと、接続できなかった理由
+    remotes: Mutex<BTreeMap<String, Result<SocketAddr, String>>>,
+}
+
+// 最後の Resolver が捨てられたら待ち受けを止める
+struct Guard {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn key(host: &str, port: u16) -> String {
+    format!("{}:{}", strip_brackets(host), port)
+}
+
+impl Resolver {
+    pub fn start(overrides: Overrides) -> Result<Resolver, String> {
+        let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
+        let addr = listener.local_addr().map_err(|e| e.to_string())?;
+        let shared = Arc::new(Shared {
+            overrides,
+            username: random_hex(16)?,
+            password: random_hex(16)?,
+            remotes: Mutex::new(BTreeMap::new()),
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let accept_shared = shared.clone();
+        let accept_stop = stop.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let shared = accept_shared.clone();
+                    thread::spawn(move || {
+                        let _ = serve(stream, &shared);
+                    });
+                }
+            }
+        });
+        Ok(Resolver {
+            addr,
+            shared,
+            _guard: Arc::new(Guard { addr, stop }),
+        })
+    }
+
+    // reqwest に渡すプロキシの URL（ホスト名のまま渡すので socks5h）
+    pub fn proxy_url(&self) -> url::Url {
+        url::Url::parse(&format!(
+            "socks5h://{}:{}@{}",
+            self.shared.username, self.shared.password, self.addr
+        ))
+        .expect("valid socks5h url")
+    }
+
+    // URL のホストに最後に接続したアドレス（接続できなければその理由）
+    pub fn remote(&self, url: &url::Url) -> Option<Result<SocketAddr, String>> {
+        let host = url.host_str()?;
+        let port = url.port_or_known_default()?;
+        self.shared
+            .remotes
+            .lock()
+            .ok()?
+            .get(&key(host, port))
+            .cloned()
+    }
+}
+
+fn random_hex(len: usize) -> Result<String, String> {
+    let mut bytes = vec![0; len];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// `unix:///var/run/docker.sock:/v1.41/containers/json` をソケットのパスと http://localhost の URL に分ける
+pub fn unix_url(text: &str) -> Option<(String, String)> {
+    let rest = text.strip_prefix("unix://")?;
+    match rest.find(":/") {
+        Some(i) => Some((rest[..i].to_string(), format!("http://localhost{}", &rest[i + 1..]))),
+        None => Some((rest.to_string(), "http://localhost/".to_string())),
+    }
+}
+
+// 中継する接続先（TCP か Unix ドメインソケット）
+trait Upstream: Read + Write + Send + Sized + 'static {
+    fn duplicate(&self) -> io::Result<Self>;
+    fn close_write(&self);
+}
+
+impl Upstream for TcpStream {
+    fn duplicate(&self) -> io::Result<Self> {
+        self.try_clone()
+    }
+
+    fn close_write(&self) {
+        let _ = self.shutdown(Shutdown::Write);
+    }
+}
+
+impl Upstream for UnixStream {
+    fn duplicate(&self) -> io::Result<Self> {
+        self.try_clone()
+    }
+
+    fn close_write(&self) {
+        let _ = self.shutdown(Shutdown::Write);
+    }
+}
+
+// 両方向にデータを流し、どちらかが閉じたら終わる
+fn relay<S: Upstream>(mut client: TcpStream, upstream: S) -> io::Result<()> {
+    client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])?;
+    let mut client_read = client.try_clone()?;
+    let mut upstream_write = upstream.duplicate()?;
+    let forward = thread::spawn(move || {
+        let _ = io::copy(&mut client_read, &mut upstream_write);
+        upstream_write.close_write();
+    });
+    let mut upstream_read = upstream;
+    let _ = io::copy(&mut upstream_read, &mut client);
+    let _ = client.shutdown(Shutdown::Write);
+    let _ = forward.join();
+    Ok(())
+}
+
+fn rejected(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, message)
+}
+
+// ユーザー名とパスワードの認証（RFC 1929）だけを受け付ける
+fn authenticate(client: &mut TcpStream, shared: &Shared) -> io::Result<()> {
+    let mut head = [0u8; 2];
+    client.read_exact(&mut head)?;
+    let mut methods = vec![0u8; head[1] as usize];
+    client.read_exact(&mut methods)?;
+    if head[0] != 5 || !methods.contains(&2) {
+        client.write_all(&[5, 0xff])?;
+        return Err(rejected("no acceptable authentication method"));
+    }
+    client.write_all(&[5, 2])?;
+
+    let mut version = [0u8; 2];
+    client.read_exact(&mut version)?;
+    let mut username = vec![0u8; version[1] as usize];
+    client.read_exact(&mut username)?;
+    let mut len = [0u8; 1];
+    client.read_exact(&mut len)?;
+    let mut password = vec![0u8; len[0] as usize];
+    client.read_exact(&mut password)?;
+    let matches = |given: &[u8], expected: &str| {
+        given.len() == expected.len() && openssl::memcmp::eq(given, expected.as_bytes())
+    };
+    // 両方を比べてから判定する
+    let username_ok = matches(&username, &shared.username);
+    let password_ok = matches(&password, &shared.password);
+    if version[0] != 1 || !username_ok || !password_ok {
+        client.write_all(&[1, 1])?;
+        return Err(rejected("invalid credentials"));
+    }
+    client.write_all(&[1, 0])
+}
+
+// SOCKS5 の CONNECT を受けて接続先につなぎ、あとは中継する
+fn serve(mut client: TcpStream, shared: &Shared) -> io::Result<()> {
+    authenticate(&mut client, shared)?;
+
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request)?;
+    // CONNECT 以外は使わない
+    if request[1] != 1 {
+        client.write_all(&[5, 7, 0, 1, 0, 0, 0, 0, 0, 0])?;
+        return Err(rejected("only CONNECT is supported"));
+    }
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            client.read_exact(&mut ip)?;
+            IpAddr::from(ip).to_string()
+        }
+        3 => {
+            let mut len = [0u8; 1];
+            client.read_exact(&mut len)?;
+            let mut name = vec![0u8; len[0] as usize];
+            client.read_exact(&mut name)?;
+            String::from_utf8_lossy(&name).to_string()
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            client.read_exact(&mut ip)?;
+            IpAddr::from(ip).to_string()
+        }
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown address type")),
+    };
+    let mut port = [0u8; 2];
+    client.read_exact(&mut port)?;
+    let port = u16::from_be_bytes(port);
+
+    if let Some(path) = &shared.overrides.unix_socket {
+        let connected = UnixStream::connect(path)
+            .map_err(|e| format!("failed to connect to unix socket {}: {}", path, e));
+        if let Ok(mut remotes) = shared.remotes.lock() {
+            match &connected {
+                Ok(_) => remotes.remove(&key(&host, port)),
+                Err(e) => remotes.insert(key(&host, port), Err(e.to_string())),
+            };
+        }
+        return match connected {
+            Ok(upstream) => relay(client, upstream),
+            Err(_) => client.write_all(&[5, 4, 0, 1, 0, 0, 0, 0, 0, 0]),
+        };
+    }
+
+    let connected = shared.overrides.addrs(&host, port).and_then(|addrs| {
+        let mut last = format!("failed to connect to {}", host);
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, shared.overrides.connect_timeout) {
+                Ok(stream) => return Ok((stream, addr)),
+                Err(e) => last = format!("failed to connect to {}: {}", addr, e),
+            }
+        }
+        Err(last)
+    });
+    if let Ok(mut remotes) = shared.remotes.lock() {
+        remotes.insert(
+            key(&host, port),
+            connected.as_ref().map(|(_, addr)| *addr).map_err(|e| e.to_string()),
+        );
+    }
+    match connected {
+        Ok((upstream, _)) => relay(client, upstream),
+        Err(_) => client.write_all(&[5, 4, 0, 1, 0, 0, 0, 0, 0, 0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    // Unix ドメインソケットで1回だけ "pong" を返す
+    fn unix_server() -> (Resolver, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("ruzz-resolve-{}-{}.sock", std::process::id(), random_hex(4).unwrap()));
+        let listener = UnixListener::bind(&path).unwrap();
+        thread::spawn(move || {
+            if let Ok((mut stream, _)) = listener.accept() {
+                let _ = stream.write_all(b"pong");
+            }
+        });
+        let settings = ClientSettings {
+            unix_socket: Some(path.to_string_lossy().to_string()),
+            ..ClientSettings::default()
+        };
+        (Resolver::start(Overrides::new(&settings).unwrap()).unwrap(), path)
+    }
+
+    fn connect_request(stream: &mut TcpStream) -> Vec<u8> {
+        stream.write_all(&[5, 1, 0, 3, 9]).unwrap();
+        stream.write_all(b"localhost").unwrap();
+        stream.write_all(&[0, 80]).unwrap();
+        let mut reply = [0u8; 10];
+        stream.read_exact(&mut reply).unwrap();
+        let mut rest = Vec::new();
+        let _ = stream.read_to_end(&mut rest);
+        rest
+    }
+
+    #[test]
+    fn relay_uses_the_connect_timeout_setting() {
+        let settings = ClientSettings {
+            connect_timeout: Some(0.25),
+            resolve: Some("a.test:80:127.0.0.1".to_string()),
+            ..ClientSettings::default()
+        };
+        assert_eq!(Overrides::new(&settings).unwrap().connect_timeout, Duration::from_millis(250));
+        let settings = ClientSettings {
+            connect_timeout: Some(0.0),
+            ..settings
+        };
+        assert_eq!(
+            Overrides::new(&settings).unwrap().connect_timeout,
+            Duration::from_secs(DEFAULT_CONNECT_TIMEOUT)
+        );
+    }
+
+    #[test]
+    fn relay_rejects_clients_without_credentials() {
+        let (resolver, path) = unix_server();
+        let mut stream = TcpStream::connect(resolver.addr).unwrap();
+        stream.write_all(&[5, 1, 0]).unwrap();
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, [5, 0xff]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn relay_rejects_wrong_credentials() {
+        let (resolver, path) = unix_server();
+        let mut stream = TcpStream::connect(resolver.addr).unwrap();
+        stream.write_all(&[5, 1, 2]).unwrap();
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, [5, 2]);
+        let username = resolver.shared.username.as_bytes();
+        stream.write_all(&[1, username.len() as u8]).unwrap();
+        stream.write_all(username).unwrap();
+        stream.write_all(&[5]).unwrap();
+        stream.write_all(b"wrong").unwrap();
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, [1, 1]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn relay_connects_with_credentials() {
+        let (resolver, path) = unix_server();
+        let url = resolver.proxy_url();
+        let mut stream = TcpStream::connect(resolver.addr).unwrap();
+        stream.write_all(&[5, 1, 2]).unwrap();
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).unwrap();
+        let (username, password) = (url.username(), url.password().unwrap());
+        stream.write_all(&[1, username.len() as u8]).unwrap();
+        stream.write_all(username.as_bytes()).unwrap();
+        stream.write_all(&[password.len() as u8]).unwrap();
+        stream.write_all(password.as_bytes()).unwrap();
+        stream.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, [1, 0]);
+        assert_eq!(connect_request(&mut stream), b"pong");
+        let _ = std::fs::remove_file(path);
+    }
+}
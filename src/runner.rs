@@ -0,0 +1,510 @@
+use super::app;
+use super::assertion::AssertionResult;
+use super::collection::{self, Collection, Environment};
+use super::config::Config;
+use super::har;
+
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+const USAGE: &str = "usage: ruzz run <collection> [--env NAME] [--junit PATH] [--report PATH] [--har PATH] [--stop-on-failure | --continue-on-failure]";
+
+// 1件のリクエストの実行結果
+#[derive(Serialize)]
+struct RequestReport {
+    name: String,
+    method: String,
+    url: String,
+    status: Option<u16>,
+    elapsed_ms: u128,
+    skipped: bool,
+    error: Option<String>,
+    assertions: Vec<AssertionResult>,
+}
+
+impl RequestReport {
+    fn failures(&self) -> usize {
+        self.assertions.iter().filter(|a| !a.passed).count()
+    }
+
+    fn passed(&self) -> bool {
+        !self.skipped && self.error.is_none() && self.failures() == 0
+    }
+}
+
+// コレクション全体の実行結果
+#[derive(Serialize)]
+struct RunReport {
+    collection: String,
+    environment: Option<String>,
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    elapsed_ms: u128,
+    requests: Vec<RequestReport>,
+}
+
+struct Options {
+    collection: PathBuf,
+    env: Option<String>,
+    junit: Option<PathBuf>,
+    report: Option<PathBuf>,
+    har: Option<PathBuf>,
+    stop_on_failure: Option<bool>,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut collection = None;
+    let mut options = Options {
+        collection: PathBuf::new(),
+        env: None,
+        junit: None,
+        report: None,
+        har: None,
+        stop_on_failure: None,
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .map(|v| v.to_string())
+                .ok_or_else(|| format!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "--env" | "-e" => options.env = Some(value()?),
+            "--junit" => options.junit = Some(PathBuf::from(value()?)),
+            "--report" => options.report = Some(PathBuf::from(value()?)),
+            "--har" => options.har = Some(PathBuf::from(value()?)),
+            "--stop-on-failure" => options.stop_on_failure = Some(true),
+            "--continue-on-failure" => options.stop_on_failure = Some(false),
+            _ if collection.is_none() && !arg.starts_with('-') => {
+                collection = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    options.collection = collection.ok_or_else(|| "collection is missing".to_string())?;
+    Ok(options)
+}
+
+// `ruzz run` サブコマンド
+//
+// コレクションのリクエストを順番に送信し、検証結果をレポートに書き出す。
+// 終了コードは 0: すべて成功, 1: 失敗あり, 2: 引数エラーまたは読み込み失敗
+pub fn run(args: &[String]) -> i32 {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    let collection = match Collection::load(&options.collection) {
+        Ok(collection) => collection,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 2;
+        }
+    };
+
+    let environment = match &options.env {
+        Some(name) => match Environment::find(&options.collection, name) {
+            Ok(environment) => Some(environment),
+            Err(err) => {
+                eprintln!("{}", err);
+                return 2;
+            }
+        },
+        None => None,
+    };
+
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 2;
+        }
+    };
+
+    let stop_on_failure = options
+        .stop_on_failure
+        .unwrap_or(collection.stop_on_failure);
+    let (report, app) = match run_collection(&collection, config, environment.as_ref(), stop_on_failure) {
+        Ok(run) => run,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 2;
+        }
+    };
+
+    if let Some(path) = &options.junit {
+        if let Err(err) = write_report(path, &junit_xml(&report)) {
+            eprintln!("{}", err);
+            return 2;
+        }
+    }
+    if let Some(path) = &options.report {
+        let json = serde_json::to_string_pretty(&report).unwrap_or_default();
+        if let Err(err) = write_report(path, &json) {
+            eprintln!("{}", err);
+            return 2;
+        }
+    }
+
+    if let Some(path) = &options.har {
+        if let Err(err) = har::export(app.history(), path) {
+            eprintln!("{}", err);
+            return 2;
+        }
+    }
+
+    println!(
+        "{} passed, {} failed, {} skipped ({}ms)",
+        report.passed, report.failed, report.skipped, report.elapsed_ms
+    );
+    if report.failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+fn run_collection(
+    collection: &Collection,
+    config: Config,
+    environment: Option<&Environment>,
+    stop_on_failure: bool,
+) -> Result<(RunReport, app::App), String> {
+    let started = Instant::now();
+    let mut app = app::App::default();
+    app.set_config(config);
+    for (name, value) in &collection.variables {
+        app.set_variable(name, value);
+    }
+    if let Some(environment) = environment {
+        app.load_environment(environment)?;
+    }
+
+    let mut requests = Vec::new();
+    let mut stopped = false;
+    for saved in &collection.requests {
+        let mut report = RequestReport {
+            name: saved.name.to_string(),
+            method: saved.method.to_string(),
+            url: collection::render(&saved.url, app.variables()),
+            status: None,
+            elapsed_ms: 0,
+            skipped: stopped,
+            error: None,
+            assertions: Vec::new(),
+        };
+
+        if stopped {
+            println!("SKIP {}", saved.name);
+            requests.push(report);
+            continue;
+        }
+
+        if app.load_request(saved) {
+            app.request();
+            app.wait_stream(None);
+            report.status = app.response_status_code();
+            report.elapsed_ms = app.response_elapsed().as_millis();
+            report.assertions = app.response_test_results().clone();
+            if report.status.is_none() {
+                report.error = Some(app.response_status_text().to_string());
+            }
+        } else {
+            report.error = Some(format!("unknown method: {}", saved.method));
+        }
+
+        // 後続のリクエストで使う変数を取り出す
+        if report.error.is_none() {
+            for expr in &saved.captures {
+                match collection::capture(expr, &app) {
+                    Ok((name, value)) => app.set_variable(&name, &value),
+                    Err(err) => {
+                        report.error = Some(format!("capture failed: {}", err));
+                        break;
+                    }
+                }
+            }
+        }
+
+        if report.passed() {
+            println!(
+                "PASS {} ({}, {}ms)",
+                saved.name,
+                report.status.unwrap_or(0),
+                report.elapsed_ms
+            );
+        } else {
+            println!("FAIL {}", saved.name);
+            if let Some(err) = &report.error {
+                println!("     {}", err);
+            }
+            for result in report.assertions.iter().filter(|a| !a.passed) {
+                println!("     {} ({})", result.source, result.message);
+            }
+            stopped = stop_on_failure;
+        }
+        requests.push(report);
+    }
+
+    let report = RunReport {
+        collection: collection.name.to_string(),
+        environment: environment.map(|e| e.name.to_string()),
+        passed: requests.iter().filter(|r| r.passed()).count(),
+        failed: requests.iter().filter(|r| !r.skipped && !r.passed()).count(),
+        skipped: requests.iter().filter(|r| r.skipped).count(),
+        elapsed_ms: started.elapsed().as_millis(),
+        requests,
+    };
+    Ok((report, app))
+}
+
+fn write_report(path: &Path, text: &str) -> Result<(), String> {
+    fs::write(path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn seconds(ms: u128) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
+
+// JUnit XML形式のレポート
+fn junit_xml(report: &RunReport) -> String {
+    let name = escape_xml(&report.collection);
+    let errors = report
+        .requests
+        .iter()
+        .filter(|r| r.error.is_some())
+        .count();
+    let failures = report.failed - errors;
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\">\n",
+        name,
+        report.requests.len(),
+        failures,
+        errors,
+        seconds(report.elapsed_ms)
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+        name,
+        report.requests.len(),
+        failures,
+        errors,
+        report.skipped,
+        seconds(report.elapsed_ms)
+    ));
+
+    for request in &report.requests {
+        xml.push_str(&format!(
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\">\n",
+            escape_xml(&request.name),
+            name,
+            seconds(request.elapsed_ms)
+        ));
+        if request.skipped {
+            xml.push_str("      <skipped/>\n");
+        } else if let Some(err) = &request.error {
+            xml.push_str(&format!(
+                "      <error message=\"{}\" type=\"error\">{} {}</error>\n",
+                escape_xml(err),
+                escape_xml(&request.method),
+                escape_xml(&request.url)
+            ));
+        } else if request.failures() > 0 {
+            let failed: Vec<&AssertionResult> =
+                request.assertions.iter().filter(|a| !a.passed).collect();
+            let body: Vec<String> = failed
+                .iter()
+                .map(|a| escape_xml(&format!("{} ({})", a.source, a.message)))
+                .collect();
+            xml.push_str(&format!(
+                "      <failure message=\"{} assertion(s) failed\" type=\"assertion\">{}</failure>\n",
+                failed.len(),
+                body.join("\n")
+            ));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+
+    xml.push_str("  </testsuite>\n");
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ClientSettings;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    // POST /login はトークンを返し、GET /items/7 は Bearer のトークンが合えば 200 を返すサーバー
+    //
+    // 受け取ったリクエストの行と Authorization ヘッダーを記録する
+    fn serve() -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let received = log.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut authorization = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let (name, value) = line.split_at(line.find(':').unwrap());
+                    let value = value[1..].trim();
+                    match name.to_lowercase().as_str() {
+                        "authorization" => authorization = value.to_string(),
+                        "content-length" => length = value.parse().unwrap(),
+                        _ => {}
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let request_line = request_line.trim_end().to_string();
+                received.lock().unwrap().push(format!("{} [{}]", request_line, authorization));
+
+                let (status, body) = match request_line.as_str() {
+                    "POST /login HTTP/1.1" => ("200 OK", r#"{"token":"abc123","user":{"id":7}}"#),
+                    "GET /items/7 HTTP/1.1" if authorization == "Bearer abc123" => {
+                        ("200 OK", r#"{"items":["a","b"]}"#)
+                    }
+                    _ => ("401 Unauthorized", r#"{"error":"unauthorized"}"#),
+                };
+                let mut stream = stream;
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nX-Request-Id: r-{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    received.lock().unwrap().len(),
+                    body.len(),
+                    body
+                );
+            }
+        });
+        (port, log)
+    }
+
+    // 開発者の設定ファイルや HTTP_PROXY などの環境変数を使わない
+    fn config() -> Config {
+        Config {
+            client: ClientSettings::parse("proxy=none").unwrap(),
+        }
+    }
+
+    fn collection(port: u16, items_assertion: &str) -> Collection {
+        serde_json::from_value(serde_json::json!({
+            "name": "chain",
+            "stop_on_failure": true,
+            "variables": {"base": format!("http://127.0.0.1:{}", port)},
+            "requests": [
+                {
+                    "name": "login",
+                    "method": "POST",
+                    "url": "{{base}}/login",
+                    "body": "{\"user\":\"alice\"}",
+                    "body_mode": "json",
+                    "assertions": ["status == 200", "json $.token exists"],
+                    "captures": ["token = json $.token", "user_id = json $.user.id", "request = header X-Request-Id"]
+                },
+                {
+                    "name": "items",
+                    "url": "{{base}}/items/{{user_id}}",
+                    "auth_mode": "bearer",
+                    "auth": ["token={{token}}"],
+                    "assertions": ["status == 200", items_assertion]
+                },
+                {
+                    "name": "after",
+                    "url": "{{base}}/items/{{user_id}}?after={{request}}"
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn captured_variables_chain_into_later_requests() {
+        let (port, log) = serve();
+        let (report, _) = run_collection(&collection(port, "json $.items[1] == \"b\""), config(), None, true).unwrap();
+
+        assert_eq!((report.passed, report.failed, report.skipped), (3, 0, 0));
+        assert_eq!(report.requests[1].url, format!("http://127.0.0.1:{}/items/7", port));
+        assert_eq!(report.requests[1].status, Some(200));
+        assert!(report.requests[1].assertions.iter().all(|a| a.passed));
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "POST /login HTTP/1.1 []",
+                "GET /items/7 HTTP/1.1 [Bearer abc123]",
+                "GET /items/7?after=r-1 HTTP/1.1 []",
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_assertions_stop_the_run() {
+        let (port, log) = serve();
+        let collection = collection(port, "json $.items[0] == \"z\"");
+        let (report, _) = run_collection(&collection, config(), None, true).unwrap();
+
+        assert_eq!((report.passed, report.failed, report.skipped), (1, 1, 1));
+        let failed = &report.requests[1];
+        assert_eq!(failed.status, Some(200));
+        assert_eq!(failed.failures(), 1);
+        assert_eq!(failed.assertions[1].source, "json $.items[0] == \"z\"");
+        assert!(!failed.assertions[1].passed);
+        assert!(report.requests[2].skipped);
+        assert_eq!(log.lock().unwrap().len(), 2);
+
+        let xml = junit_xml(&report);
+        assert!(xml.contains("tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\""), "{}", xml);
+        assert!(xml.contains("<failure message=\"1 assertion(s) failed\""), "{}", xml);
+
+        // 続けるなら3件目も送る
+        let (report, _) = run_collection(&collection, config(), None, false).unwrap();
+        assert_eq!((report.passed, report.failed, report.skipped), (2, 1, 0));
+    }
+
+    #[test]
+    fn failed_captures_are_errors() {
+        let (port, _) = serve();
+        let mut collection = collection(port, "status == 200");
+        collection.requests[0].captures = vec!["token = json $.missing".to_string()];
+        let (report, _) = run_collection(&collection, config(), None, true).unwrap();
+
+        assert_eq!(report.requests[0].error.as_deref(), Some("capture failed: $.missing not found"));
+        assert_eq!((report.passed, report.failed, report.skipped), (0, 1, 2));
+        assert!(junit_xml(&report).contains("<error message=\"capture failed: $.missing not found\" type=\"error\">POST "));
+    }
+}
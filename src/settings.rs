@@ -0,0 +1,502 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::auth;
+use super::proxy::Proxies;
+use super::resolve::{Overrides, Resolver};
+use super::tls;
+
+const DEFAULT_TIMEOUT: f64 = 30.0;
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+const DEFAULT_USER_AGENT: &str = concat!("ruzz/", env!("CARGO_PKG_VERSION"));
+
+pub const KEYS: [&str; 24] = [
+    "connect_timeout",
+    "timeout",
+    "redirect",
+    "max_redirects",
+    "http_version",
+    "gzip",
+    "brotli",
+    "user_agent",
+    "insecure",
+    "ca_cert",
+    "client_cert",
+    "client_key",
+    "client_cert_password",
+    "proxy",
+    "http_proxy",
+    "https_proxy",
+    "no_proxy",
+    "proxy_auth",
+    "resolve",
+    "connect_to",
+    "ip_version",
+    "unix_socket",
+    "proto",
+    "proto_path",
+];
+
+// 送信に使うクライアントの設定（None は既定値）
+//
+// 設定ファイルの既定値に、リクエストごとの Settings ペインの値を重ねて使う
+//
+//   connect_timeout, timeout: 秒（0 は無制限）
+//   redirect:      follow（既定）または none
+//   max_redirects: follow のときに追う回数
+//   http_version:  auto（既定）, http1, http2（prior knowledge）
+//   gzip, brotli:  レスポンスを自動で展開するか
+//   user_agent:    既定の User-Agent（ヘッダーペインで指定すればそちらが優先）
+//   insecure:      サーバー証明書を検証しない
+//   ca_cert:       追加で信頼する CA バンドル（PEM）
+//   client_cert, client_key, client_cert_password: クライアント証明書（PEM か PKCS#12）
+//   proxy:         http, https, socks5(h) のプロキシ（none なら環境変数も使わない）
+//   http_proxy, https_proxy: スキームごとのプロキシ
+//   no_proxy:      プロキシを使わないホスト（カンマ区切り）
+//   proxy_auth:    プロキシの認証（user:password）
+//   resolve:       host:port:addr（空白区切りで複数、行を重ねても追加）
+//   connect_to:    host:port:connect_host:connect_port（同上）
+//   ip_version:    auto（既定）, ipv4, ipv6
+//   unix_socket:   HTTP をこの Unix ドメインソケットで送る
+//   proto:         gRPC で使う .proto ファイル（空白区切りで複数、なければサーバーリフレクション）
+//   proto_path:    .proto の import を探すディレクトリ（同上）
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct ClientSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redirect: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_redirects: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gzip: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brotli: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insecure: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert_password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_proxy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https_proxy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_auth: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolve: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proto: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proto_path: Option<String>,
+}
+
+// タイムアウトに指定できる最大の秒数（tokio のタイマーは約2年より先を扱えない）
+const MAX_SECONDS: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+
+// Duration にできる秒数だけを受け付ける（負の数や大きすぎる値は使うときに失敗するので、ここで弾く）
+fn parse_seconds(key: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if Duration::try_from_secs_f64(seconds).is_ok() && seconds <= MAX_SECONDS => Ok(seconds),
+        _ => Err(format!("{} must be a number of seconds: {}", key, value)),
+    }
+}
+
+// 同じキーを重ねたら空白で区切って足す
+fn append(current: Option<String>, value: String) -> String {
+    match current {
+        Some(current) => format!("{} {}", current, value),
+        None => value,
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" | "yes" => Ok(true),
+        "false" | "off" | "no" => Ok(false),
+        _ => Err(format!("{} must be true or false: {}", key, value)),
+    }
+}
+
+impl ClientSettings {
+    // `key=value` 形式の行を読み込む（空の値は既定値に戻す）
+    pub fn parse(text: &str) -> Result<ClientSettings, String> {
+        let mut settings = ClientSettings::default();
+        for (key, value) in auth::field_lines(text) {
+            if key.starts_with('#') {
+                continue;
+            }
+            if value.is_empty() {
+                if !KEYS.contains(&key.as_str()) {
+                    return Err(format!("unknown setting: {}", key));
+                }
+                continue;
+            }
+            match key.as_str() {
+                "connect_timeout" => settings.connect_timeout = Some(parse_seconds(&key, &value)?),
+                "timeout" => settings.timeout = Some(parse_seconds(&key, &value)?),
+                "redirect" => match value.as_str() {
+                    "follow" | "none" => settings.redirect = Some(value),
+                    _ => return Err(format!("redirect must be follow or none: {}", value)),
+                },
+                "max_redirects" => {
+                    settings.max_redirects = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("max_redirects must be a number: {}", value))?,
+                    )
+                }
+                "http_version" => match value.as_str() {
+                    "auto" | "http1" | "http2" => settings.http_version = Some(value),
+                    _ => return Err(format!("http_version must be auto, http1 or http2: {}", value)),
+                },
+                "gzip" => settings.gzip = Some(parse_bool(&key, &value)?),
+                "brotli" => settings.brotli = Some(parse_bool(&key, &value)?),
+                "user_agent" => settings.user_agent = Some(value),
+                "insecure" => settings.insecure = Some(parse_bool(&key, &value)?),
+                "ca_cert" => settings.ca_cert = Some(value),
+                "client_cert" => settings.client_cert = Some(value),
+                "client_key" => settings.client_key = Some(value),
+                "client_cert_password" => settings.client_cert_password = Some(value),
+                "proxy" => settings.proxy = Some(value),
+                "http_proxy" => settings.http_proxy = Some(value),
+                "https_proxy" => settings.https_proxy = Some(value),
+                "no_proxy" => settings.no_proxy = Some(value),
+                "proxy_auth" => settings.proxy_auth = Some(value),
+                "resolve" => settings.resolve = Some(append(settings.resolve.take(), value)),
+                "connect_to" => settings.connect_to = Some(append(settings.connect_to.take(), value)),
+                "unix_socket" => settings.unix_socket = Some(value),
+                "proto" => settings.proto = Some(append(settings.proto.take(), value)),
+                "proto_path" => settings.proto_path = Some(append(settings.proto_path.take(), value)),
+                "ip_version" => match value.as_str() {
+                    "auto" | "ipv4" | "ipv6" => settings.ip_version = Some(value),
+                    _ => return Err(format!("ip_version must be auto, ipv4 or ipv6: {}", value)),
+                },
+                _ => return Err(format!("unknown setting: {}", key)),
+            }
+        }
+        Ok(settings)
+    }
+
+    // 設定されている値だけを `key=value` の行にする
+    pub fn to_text(&self) -> String {
+        KEYS.iter()
+            .filter_map(|key| self.get(key).map(|value| format!("{}={}", key, value)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "connect_timeout" => self.connect_timeout.map(|v| v.to_string()),
+            "timeout" => self.timeout.map(|v| v.to_string()),
+            "redirect" => self.redirect.clone(),
+            "max_redirects" => self.max_redirects.map(|v| v.to_string()),
+            "http_version" => self.http_version.clone(),
+            "gzip" => self.gzip.map(|v| v.to_string()),
+            "brotli" => self.brotli.map(|v| v.to_string()),
+            "user_agent" => self.user_agent.clone(),
+            "insecure" => self.insecure.map(|v| v.to_string()),
+            "ca_cert" => self.ca_cert.clone(),
+            "client_cert" => self.client_cert.clone(),
+            "client_key" => self.client_key.clone(),
+            "client_cert_password" => self.client_cert_password.clone(),
+            "proxy" => self.proxy.clone(),
+            "http_proxy" => self.http_proxy.clone(),
+            "https_proxy" => self.https_proxy.clone(),
+            "no_proxy" => self.no_proxy.clone(),
+            "proxy_auth" => self.proxy_auth.clone(),
+            "resolve" => self.resolve.clone(),
+            "connect_to" => self.connect_to.clone(),
+            "ip_version" => self.ip_version.clone(),
+            "unix_socket" => self.unix_socket.clone(),
+            "proto" => self.proto.clone(),
+            "proto_path" => self.proto_path.clone(),
+            _ => None,
+        }
+    }
+
+    // overrides で指定された値を優先する
+    pub fn merge(&self, overrides: &ClientSettings) -> ClientSettings {
+        ClientSettings {
+            connect_timeout: overrides.connect_timeout.or(self.connect_timeout),
+            timeout: overrides.timeout.or(self.timeout),
+            redirect: overrides.redirect.clone().or_else(|| self.redirect.clone()),
+            max_redirects: overrides.max_redirects.or(self.max_redirects),
+            http_version: overrides.http_version.clone().or_else(|| self.http_version.clone()),
+            gzip: overrides.gzip.or(self.gzip),
+            brotli: overrides.brotli.or(self.brotli),
+            user_agent: overrides.user_agent.clone().or_else(|| self.user_agent.clone()),
+            insecure: overrides.insecure.or(self.insecure),
+            ca_cert: overrides.ca_cert.clone().or_else(|| self.ca_cert.clone()),
+            client_cert: overrides.client_cert.clone().or_else(|| self.client_cert.clone()),
+            client_key: overrides.client_key.clone().or_else(|| self.client_key.clone()),
+            client_cert_password: overrides
+                .client_cert_password
+                .clone()
+                .or_else(|| self.client_cert_password.clone()),
+            proxy: overrides.proxy.clone().or_else(|| self.proxy.clone()),
+            http_proxy: overrides.http_proxy.clone().or_else(|| self.http_proxy.clone()),
+            https_proxy: overrides.https_proxy.clone().or_else(|| self.https_proxy.clone()),
+            no_proxy: overrides.no_proxy.clone().or_else(|| self.no_proxy.clone()),
+            proxy_auth: overrides.proxy_auth.clone().or_else(|| self.proxy_auth.clone()),
+            resolve: overrides.resolve.clone().or_else(|| self.resolve.clone()),
+            connect_to: overrides.connect_to.clone().or_else(|| self.connect_to.clone()),
+            ip_version: overrides.ip_version.clone().or_else(|| self.ip_version.clone()),
+            unix_socket: overrides.unix_socket.clone().or_else(|| self.unix_socket.clone()),
+            proto: overrides.proto.clone().or_else(|| self.proto.clone()),
+            proto_path: overrides.proto_path.clone().or_else(|| self.proto_path.clone()),
+        }
+    }
+
+    // 設定ポップアップに表示する行（値と、どこで設定されたか）
+    pub fn describe(
+        defaults: &ClientSettings,
+        environment: &ClientSettings,
+        overrides: &ClientSettings,
+    ) -> Vec<String> {
+        let builtin = ClientSettings::builtin();
+        KEYS.iter()
+            .map(|key| {
+                let (value, source) = match (overrides.get(key), environment.get(key), defaults.get(key)) {
+                    (Some(value), _, _) => (value, "request"),
+                    (None, Some(value), _) => (value, "environment"),
+                    (None, None, Some(value)) => (value, "config"),
+                    (None, None, None) => (builtin.get(key).unwrap_or_else(|| "-".to_string()), "default"),
+                };
+                // パスワードは表示しない
+                let secret = *key == "client_cert_password" || *key == "proxy_auth";
+                let value = if secret && source != "default" {
+                    "********".to_string()
+                } else {
+                    value
+                };
+                format!("{:<16} {:<32} ({})", key, value, source)
+            })
+            .collect()
+    }
+
+    // 追うリダイレクトの回数（none なら 0）
+    pub fn redirect_limit(&self) -> usize {
+        match self.redirect.as_deref() {
+            Some("none") => 0,
+            _ => self.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS),
+        }
+    }
+
+    // 何も設定されていないときの値
+    fn builtin() -> ClientSettings {
+        ClientSettings {
+            connect_timeout: None,
+            timeout: Some(DEFAULT_TIMEOUT),
+            redirect: Some("follow".to_string()),
+            max_redirects: Some(DEFAULT_MAX_REDIRECTS),
+            http_version: Some("auto".to_string()),
+            gzip: Some(true),
+            brotli: Some(true),
+            user_agent: Some(DEFAULT_USER_AGENT.to_string()),
+            insecure: Some(false),
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            client_cert_password: None,
+            proxy: None,
+            http_proxy: None,
+            https_proxy: None,
+            no_proxy: None,
+            proxy_auth: None,
+            resolve: None,
+            connect_to: None,
+            ip_version: Some("auto".to_string()),
+            unix_socket: None,
+            proto: None,
+            proto_path: None,
+        }
+    }
+
+    // 名前解決を上書きするときは Resolver も返す
+    pub fn build(&self) -> Result<(reqwest::blocking::Client, Option<Resolver>), String> {
+        let settings = ClientSettings::builtin().merge(self);
+        let seconds = |v: f64| {
+            if v == 0.0 {
+                None
+            } else {
+                Some(Duration::from_secs_f64(v))
+            }
+        };
+
+        let mut builder = reqwest::blocking::Client::builder()
+            .timeout(settings.timeout.and_then(seconds))
+            .gzip(settings.gzip.unwrap_or(true))
+            .brotli(settings.brotli.unwrap_or(true));
+        if let Some(timeout) = settings.connect_timeout.and_then(seconds) {
+            builder = builder.connect_timeout(timeout);
+        }
+        // 途中のレスポンスも記録するため、リダイレクトは App::request で追う
+        builder = builder.redirect(reqwest::redirect::Policy::none());
+        // native-tls では ALPN で h2 を選ばないので、auto と http1 はどちらも HTTP/1.1 になる
+        if settings.http_version.as_deref() == Some("http2") {
+            builder = builder.http2_prior_knowledge();
+        }
+        if settings.insecure == Some(true) {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        if let Some(path) = &settings.ca_cert {
+            for cert in tls::load_ca(path)? {
+                let der = cert.to_der().map_err(|e| e.to_string())?;
+                let cert = reqwest::Certificate::from_der(&der)
+                    .map_err(|e| format!("invalid CA certificate {}: {}", path, e))?;
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if let Some(identity) = tls::load_identity(&settings)? {
+            builder = builder.identity(identity.to_reqwest()?);
+        }
+        // 環境変数も Proxies で扱うので、reqwest による自動設定は使わない
+        // Unix ドメインソケットや名前解決の上書きが当たる URL はプロキシより先に Resolver を経由させる
+        let proxies = Proxies::new(&settings)?;
+        let overrides = Overrides::new(&settings)?;
+        let resolver = if overrides.is_empty() {
+            None
+        } else {
+            Some(Resolver::start(overrides.clone())?)
+        };
+        builder = builder.no_proxy();
+        if !proxies.is_empty() || resolver.is_some() {
+            let via = resolver.as_ref().map(|resolver| resolver.proxy_url());
+            builder = builder.proxy(reqwest::Proxy::custom(move |url| match &via {
+                Some(via) if overrides.applies(url) => Some(via.clone()),
+                _ => proxies.for_url(url).or_else(|| via.clone()),
+            }));
+        }
+        if let Some(user_agent) = settings.user_agent.filter(|ua| !ua.is_empty()) {
+            builder = builder.user_agent(user_agent);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("failed to build the http client: {}", e))?;
+        Ok((client, resolver))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_resolve_and_connect_to_accumulate() {
+        let settings = ClientSettings::parse(
+            "resolve=a.test:443:127.0.0.1\nconnect_to=x.test:80:y.test:8080\nresolve=b.test:443:127.0.0.2 c.test:80:::1\nconnect_to=z.test:80:w.test:81",
+        )
+        .unwrap();
+        assert_eq!(
+            settings.resolve.as_deref(),
+            Some("a.test:443:127.0.0.1 b.test:443:127.0.0.2 c.test:80:::1")
+        );
+        assert_eq!(
+            settings.connect_to.as_deref(),
+            Some("x.test:80:y.test:8080 z.test:80:w.test:81")
+        );
+        assert_eq!(ClientSettings::parse(&settings.to_text()).unwrap(), settings);
+    }
+
+    #[test]
+    fn repeated_proto_and_proto_path_accumulate() {
+        // headless の --proto と --import-path は1つずつ行になる
+        let settings =
+            ClientSettings::parse("proto=a.proto\nproto_path=api\nproto=b.proto c.proto\nproto_path=vendor").unwrap();
+        assert_eq!(settings.proto.as_deref(), Some("a.proto b.proto c.proto"));
+        assert_eq!(settings.proto_path.as_deref(), Some("api vendor"));
+    }
+
+    #[test]
+    fn seconds_must_fit_in_a_duration() {
+        assert_eq!(ClientSettings::parse("timeout=1.5").unwrap().timeout, Some(1.5));
+        for value in ["1e300", "1e18", "-1", "inf", "NaN", "x"] {
+            assert!(ClientSettings::parse(&format!("timeout={}", value)).is_err(), "{}", value);
+            assert!(ClientSettings::parse(&format!("connect_timeout={}", value)).is_err(), "{}", value);
+        }
+    }
+
+    // 1回だけ接続を受けて body を返す HTTP サーバー（TCP と Unix ドメインソケット）
+    fn reply<S: std::io::Read + std::io::Write>(mut stream: S, body: &str) {
+        let mut buf = [0; 4096];
+        let _ = stream.read(&mut buf);
+        let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+    }
+
+    #[test]
+    fn unix_socket_takes_priority_over_the_proxy() {
+        let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        std::thread::spawn(move || {
+            if let Ok((stream, _)) = proxy.accept() {
+                reply(stream, "proxy");
+            }
+        });
+        let path = std::env::temp_dir().join(format!("ruzz-settings-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || {
+            if let Ok((stream, _)) = socket.accept() {
+                reply(stream, "socket");
+            }
+        });
+
+        let settings = ClientSettings::parse(&format!(
+            "proxy=http://{}\nunix_socket={}",
+            proxy_addr,
+            path.display()
+        ))
+        .unwrap();
+        let (client, _resolver) = settings.build().unwrap();
+        let body = client
+            .get("http://localhost/v1.41/containers/json")
+            .send()
+            .and_then(|resp| resp.text())
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(body, "socket");
+    }
+
+    #[test]
+    fn resolve_overrides_only_bypass_the_proxy_for_their_hosts() {
+        let overrides = Overrides::new(
+            &ClientSettings::parse("resolve=a.test:443:127.0.0.1\nconnect_to=b.test::127.0.0.1:8080").unwrap(),
+        )
+        .unwrap();
+        let url = |text: &str| url::Url::parse(text).unwrap();
+        assert!(overrides.applies(&url("https://a.test/")));
+        assert!(!overrides.applies(&url("http://a.test/")));
+        assert!(overrides.applies(&url("http://b.test:9000/")));
+        assert!(!overrides.applies(&url("https://c.test/")));
+    }
+
+    #[test]
+    fn later_values_replace_earlier_ones() {
+        let settings = ClientSettings::parse("timeout=5\ntimeout=7\nproxy=http://a\nproxy=http://b").unwrap();
+        assert_eq!(settings.timeout, Some(7.0));
+        assert_eq!(settings.proxy.as_deref(), Some("http://b"));
+    }
+}
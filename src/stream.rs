@@ -0,0 +1,400 @@
+use chrono::{DateTime, Local};
+use reqwest::header::{HeaderMap, CONTENT_TYPE, TRANSFER_ENCODING};
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use super::connection;
+
+// 最初に待つミリ秒（すぐに読み終わるレスポンスはそのまま表示する）
+pub const INITIAL_WAIT: u64 = 200;
+// 1回に読むバイト数
+const READ_SIZE: usize = 8192;
+// 一覧に表示する data の最大文字数
+const DATA_PREVIEW: usize = 80;
+
+// Server-Sent Events のレスポンスか
+pub fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_lowercase().starts_with("text/event-stream"))
+        .unwrap_or(false)
+}
+
+// 届いた分から表示するレスポンスか（SSE と chunked）
+pub fn is_stream(headers: &HeaderMap) -> bool {
+    is_event_stream(headers)
+        || headers
+            .get(TRANSFER_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_lowercase().contains("chunked"))
+            .unwrap_or(false)
+}
+
+// 読み込みスレッドから届く知らせ
+pub enum Chunk {
+    Data(Vec<u8>),
+    // 読み終わった（エラーならその理由）
+    End(Option<String>),
+}
+
+// 読み込み中のレスポンスボディ
+pub struct Stream {
+    chunks: mpsc::Receiver<Chunk>,
+    stop: Arc<AtomicBool>,
+    // 読んでいる接続のアドレスの組（止めるときに shutdown する）
+    socket: Option<(SocketAddr, SocketAddr)>,
+}
+
+// 別スレッドでボディを読み、届いた分を知らせる
+pub fn start(mut resp: reqwest::blocking::Response, socket: Option<(SocketAddr, SocketAddr)>) -> Stream {
+    let (tx, rx) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    thread::spawn(move || {
+        let mut buf = vec![0; READ_SIZE];
+        loop {
+            let chunk = match resp.read(&mut buf) {
+                Ok(0) => Chunk::End(None),
+                Ok(n) => Chunk::Data(buf[..n].to_vec()),
+                Err(e) => Chunk::End(Some(e.to_string())),
+            };
+            // 止めたあとに届いた分や、shutdown による読み込みのエラーは知らせない
+            if stopped.load(Ordering::SeqCst) {
+                return;
+            }
+            let end = matches!(chunk, Chunk::End(_));
+            if tx.send(chunk).is_err() || end {
+                return;
+            }
+        }
+    });
+    Stream { chunks: rx, stop, socket }
+}
+
+impl Stream {
+    // 届いている分をすべて受け取る
+    pub fn poll(&self) -> Vec<Chunk> {
+        self.chunks.try_iter().collect()
+    }
+
+    // 届くまで最大 timeout 待つ
+    pub fn wait(&self, timeout: Duration) -> Option<Chunk> {
+        match self.chunks.recv_timeout(timeout) {
+            Ok(chunk) => Some(chunk),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => Some(Chunk::End(None)),
+        }
+    }
+
+    // 接続が分かれば shutdown して読み込みを終わらせる（分からなければ次のチャンクが届いたところで終える）
+    pub fn stop(&self) {
+        if self.stop.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if let Some(socket) = self.socket {
+            connection::shutdown(socket);
+        }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// チャンクの境目で分かれた UTF-8 の文字をつなげて文字列にする
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    self.pending.clear();
+                    return text;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    text.push_str(&String::from_utf8_lossy(&self.pending[..valid]));
+                    match e.error_len() {
+                        // 不正なバイトは置き換えて続ける
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            self.pending.drain(..valid + len);
+                        }
+                        // 続きのバイトを待つ
+                        None => {
+                            self.pending.drain(..valid);
+                            return text;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Server-Sent Events の1件
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub at: DateTime<Local>,
+    pub id: Option<String>,
+    pub event: String,
+    pub data: String,
+    pub retry: Option<u64>,
+}
+
+impl Event {
+    // 一覧の1行
+    pub fn summary(&self) -> String {
+        let data = self.data.replace('\n', "\\n");
+        let data = match data.char_indices().nth(DATA_PREVIEW) {
+            Some((i, _)) => format!("{}...", &data[..i]),
+            None => data,
+        };
+        let id = self
+            .id
+            .as_ref()
+            .map(|id| format!(" id={}", id))
+            .unwrap_or_default();
+        let retry = self
+            .retry
+            .map(|retry| format!(" retry={}", retry))
+            .unwrap_or_default();
+        format!(
+            "{} {}{}{} {}",
+            self.at.format("%H:%M:%S%.3f"),
+            self.event,
+            id,
+            retry,
+            data
+        )
+    }
+
+    // 選んだイベントの詳細
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("received: {}", self.at.format("%Y-%m-%d %H:%M:%S%.3f")),
+            format!("event: {}", self.event),
+        ];
+        if let Some(id) = &self.id {
+            lines.push(format!("id: {}", id));
+        }
+        if let Some(retry) = self.retry {
+            lines.push(format!("retry: {}", retry));
+        }
+        lines.push("data:".to_string());
+        lines.extend(self.data.lines().map(|line| line.to_string()));
+        lines
+    }
+}
+
+// text/event-stream を行ごとに読み、空行でイベントを区切る
+#[derive(Clone, Debug, Default)]
+pub struct EventParser {
+    line: String,
+    // 直前が \r だった（続く \n は同じ改行）
+    cr: bool,
+    event: Option<String>,
+    data: Option<String>,
+    // id と retry は次のイベントにも引き継ぐ
+    retry: Option<u64>,
+    last_id: Option<String>,
+}
+
+impl EventParser {
+    pub fn feed(&mut self, text: &str) -> Vec<Event> {
+        let mut events = Vec::new();
+        for c in text.chars() {
+            match c {
+                '\n' if self.cr => self.cr = false,
+                '\r' | '\n' => {
+                    self.cr = c == '\r';
+                    let line = std::mem::take(&mut self.line);
+                    events.extend(self.field(&line));
+                }
+                _ => {
+                    self.cr = false;
+                    self.line.push(c);
+                }
+            }
+        }
+        events
+    }
+
+    fn field(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // `:` で始まる行はコメント
+        if line.starts_with(':') {
+            return None;
+        }
+        let (name, value) = match line.find(':') {
+            Some(i) => {
+                let value = &line[i + 1..];
+                (&line[..i], value.strip_prefix(' ').unwrap_or(value))
+            }
+            None => (line, ""),
+        };
+        match name {
+            "event" => self.event = Some(value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.retry = Some(retry);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    // data のないイベントは捨てる
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = self.event.take();
+        let data = self.data.take()?;
+        Some(Event {
+            at: Local::now(),
+            id: self.last_id.clone().filter(|id| !id.is_empty()),
+            event: event
+                .filter(|e| !e.is_empty())
+                .unwrap_or_else(|| "message".to_string()),
+            data,
+            retry: self.retry,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::Sockets;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    fn summaries(events: &[Event]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| format!("{} id={:?} retry={:?} {:?}", e.event, e.id, e.retry, e.data))
+            .collect()
+    }
+
+    #[test]
+    fn events_split_on_lf_crlf_and_cr() {
+        let mut parser = EventParser::default();
+        let events = parser.feed("data: a\n\ndata: b\r\n\r\ndata: c\r\rdata: d\n\n");
+        assert_eq!(
+            events.iter().map(|e| e.data.as_str()).collect::<Vec<&str>>(),
+            ["a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn crlf_split_across_chunks_is_one_line_break() {
+        let mut parser = EventParser::default();
+        assert!(parser.feed("data: a\r").is_empty());
+        assert!(parser.feed("\n").is_empty());
+        let events = parser.feed("data: b\r\n\r\n");
+        assert_eq!(summaries(&events), ["message id=None retry=None \"a\\nb\""]);
+    }
+
+    #[test]
+    fn multi_line_data_and_event_names() {
+        let mut parser = EventParser::default();
+        let events = parser.feed("event: update\ndata: first\ndata:second\ndata\n\n");
+        assert_eq!(summaries(&events), ["update id=None retry=None \"first\\nsecond\\n\""]);
+    }
+
+    #[test]
+    fn id_and_retry_carry_over_to_later_events() {
+        let mut parser = EventParser::default();
+        let events = parser.feed(
+            "id: 1\nretry: 3000\ndata: a\n\ndata: b\n\nretry: x\nid\ndata: c\n\nid: 2\0\nretry: 500\n\ndata: d\n\n",
+        );
+        assert_eq!(
+            summaries(&events),
+            [
+                "message id=Some(\"1\") retry=Some(3000) \"a\"",
+                "message id=Some(\"1\") retry=Some(3000) \"b\"",
+                // 空の id で消え、数字でない retry は無視する
+                "message id=None retry=Some(3000) \"c\"",
+                // NUL を含む id は無視し、data のないイベントは送らない
+                "message id=None retry=Some(500) \"d\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_and_unknown_fields_are_ignored() {
+        let mut parser = EventParser::default();
+        let events = parser.feed(": keep-alive\n\n:\nfoo: bar\ndata: x\n\n");
+        assert_eq!(summaries(&events), ["message id=None retry=None \"x\""]);
+    }
+
+    #[test]
+    fn utf8_split_across_chunks() {
+        let mut decoder = Decoder::default();
+        let bytes = "データ".as_bytes();
+        assert_eq!(decoder.decode(&bytes[..4]), "デ");
+        assert_eq!(decoder.decode(&bytes[4..5]), "");
+        assert_eq!(decoder.decode(&bytes[5..]), "ータ");
+        // 不正なバイトは置き換える
+        assert_eq!(decoder.decode(&[b'a', 0xff, b'b']), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn stop_ends_a_quiet_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (closed_tx, closed_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 4096];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n9\r\ndata: a\n\n\r\n",
+            );
+            // 何も送らずに、クライアントが閉じるのを待つ
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+            }
+            let _ = closed_tx.send(());
+        });
+
+        let client = reqwest::blocking::Client::builder().no_proxy().timeout(None).build().unwrap();
+        let resp = client.get(&format!("http://{}/events", addr)).send().unwrap();
+        let socket = Sockets::snapshot().and_then(|sockets| sockets.only_pair_to(addr));
+        let stream = start(resp, socket);
+        match stream.wait(Duration::from_secs(5)) {
+            Some(Chunk::Data(data)) => assert_eq!(data, b"data: a\n\n"),
+            _ => panic!("no first chunk"),
+        }
+        stream.stop();
+        assert!(matches!(stream.wait(Duration::from_secs(5)), Some(Chunk::End(None))));
+        if cfg!(target_os = "linux") {
+            assert!(closed_rx.recv_timeout(Duration::from_secs(5)).is_ok());
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use openssl::asn1::Asn1Time;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::stack::Stack;
+use openssl::x509::{X509NameRef, X509};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+use super::settings::ClientSettings;
+
+const INSPECT_TIMEOUT: u64 = 10;
+
+// 直近のレスポンスの TLS 接続の情報
+#[derive(Clone, Debug)]
+pub struct TlsInfo {
+    pub version: String,
+    pub cipher: String,
+    // サーバーから送られた証明書（先頭がサーバー自身のもの）
+    pub chain: Vec<CertInfo>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CertInfo {
+    pub subject: String,
+    pub issuer: String,
+    pub sans: Vec<String>,
+    pub not_before: String,
+    pub not_after: String,
+    // 期限までの日数（過ぎていれば負）
+    pub days_left: Option<i32>,
+}
+
+// クライアント証明書と秘密鍵
+pub struct ClientIdentity {
+    key: PKey<Private>,
+    cert: X509,
+    chain: Vec<X509>,
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("failed to read {}: {}", path, e))
+}
+
+// PEM の CA バンドルを読み込む（複数の証明書を含んでよい）
+pub fn load_ca(path: &str) -> Result<Vec<X509>, String> {
+    let certs = X509::stack_from_pem(&read(path)?)
+        .map_err(|e| format!("invalid CA bundle {}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", path));
+    }
+    Ok(certs)
+}
+
+// .p12 / .pfx なら PKCS#12、それ以外は PEM として読み込む
+//
+// PEM では鍵を client_key で別のファイルにしてもよい（省略すれば証明書と同じファイルから読む）
+pub fn load_identity(settings: &ClientSettings) -> Result<Option<ClientIdentity>, String> {
+    let cert_path = match &settings.client_cert {
+        Some(path) => path,
+        None if settings.client_key.is_some() => {
+            return Err("client_key requires client_cert".to_string())
+        }
+        None => return Ok(None),
+    };
+    let password = settings.client_cert_password.clone().unwrap_or_default();
+    let extension = Path::new(cert_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    if let Some("p12") | Some("pfx") = extension.as_deref() {
+        let parsed = Pkcs12::from_der(&read(cert_path)?)
+            .and_then(|p12| p12.parse2(&password))
+            .map_err(|e| format!("invalid PKCS#12 file {}: {}", cert_path, e))?;
+        return match (parsed.pkey, parsed.cert) {
+            (Some(key), Some(cert)) => Ok(Some(ClientIdentity {
+                key,
+                cert,
+                chain: parsed.ca.map(|ca| ca.into_iter().collect()).unwrap_or_default(),
+            })),
+            _ => Err(format!("{} does not contain a certificate and a key", cert_path)),
+        };
+    }
+
+    let mut certs = X509::stack_from_pem(&read(cert_path)?)
+        .map_err(|e| format!("invalid client certificate {}: {}", cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", cert_path));
+    }
+    let cert = certs.remove(0);
+    let key_path = settings.client_key.as_ref().unwrap_or(cert_path);
+    let key_pem = read(key_path)?;
+    let key = if password.is_empty() {
+        PKey::private_key_from_pem(&key_pem)
+    } else {
+        PKey::private_key_from_pem_passphrase(&key_pem, password.as_bytes())
+    }
+    .map_err(|e| format!("invalid client key {}: {}", key_path, e))?;
+    Ok(Some(ClientIdentity {
+        key,
+        cert,
+        chain: certs,
+    }))
+}
+
+impl ClientIdentity {
+    // native-tls は PKCS#12 しか受け付けないので、PEM もまとめ直して渡す（パスワードは空）
+    fn to_pkcs12_der(&self) -> Result<Vec<u8>, String> {
+        let mut builder = Pkcs12::builder();
+        builder.name("ruzz").pkey(&self.key).cert(&self.cert);
+        if !self.chain.is_empty() {
+            let mut ca = Stack::new().map_err(|e| e.to_string())?;
+            for cert in &self.chain {
+                ca.push(cert.clone()).map_err(|e| e.to_string())?;
+            }
+            builder.ca(ca);
+        }
+        builder
+            .build2("")
+            .and_then(|p12| p12.to_der())
+            .map_err(|e| format!("failed to load the client certificate: {}", e))
+    }
+
+    pub fn to_reqwest(&self) -> Result<reqwest::Identity, String> {
+        reqwest::Identity::from_pkcs12_der(&self.to_pkcs12_der()?, "")
+            .map_err(|e| format!("failed to load the client certificate: {}", e))
+    }
+}
+
+// reqwest を通さない接続（WebSocket など）に使う、同じ設定の TLS コネクター
+pub fn native_connector(settings: &ClientSettings) -> Result<native_tls::TlsConnector, String> {
+    native_builder(settings)?
+        .build()
+        .map_err(|e| format!("failed to build the TLS connector: {}", e))
+}
+
+// ALPN などを足してから組み立てるときに使う
+pub fn native_builder(settings: &ClientSettings) -> Result<native_tls::TlsConnectorBuilder, String> {
+    let mut builder = native_tls::TlsConnector::builder();
+    if settings.insecure == Some(true) {
+        builder.danger_accept_invalid_certs(true);
+    }
+    if let Some(path) = &settings.ca_cert {
+        for cert in load_ca(path)? {
+            let der = cert.to_der().map_err(|e| e.to_string())?;
+            let cert = native_tls::Certificate::from_der(&der)
+                .map_err(|e| format!("invalid CA certificate {}: {}", path, e))?;
+            builder.add_root_certificate(cert);
+        }
+    }
+    if let Some(identity) = load_identity(settings)? {
+        let identity = native_tls::Identity::from_pkcs12(&identity.to_pkcs12_der()?, "")
+            .map_err(|e| format!("failed to load the client certificate: {}", e))?;
+        builder.identity(identity);
+    }
+    Ok(builder)
+}
+
+// CN=..., O=... の形にする
+fn name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry.data().to_string().unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn ip_address(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => {
+            let mut octets = [0; 4];
+            octets.copy_from_slice(bytes);
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(bytes);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+fn cert_info(cert: &X509) -> CertInfo {
+    let sans = cert
+        .subject_alt_names()
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|san| match (san.dnsname(), san.ipaddress()) {
+                    (Some(dns), _) => Some(format!("DNS:{}", dns)),
+                    (None, Some(ip)) => ip_address(ip).map(|ip| format!("IP:{}", ip)),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let days_left = Asn1Time::days_from_now(0)
+        .and_then(|now| now.diff(cert.not_after()))
+        .ok()
+        .map(|diff| diff.days);
+    CertInfo {
+        subject: name(cert.subject_name()),
+        issuer: name(cert.issuer_name()),
+        sans,
+        not_before: cert.not_before().to_string(),
+        not_after: cert.not_after().to_string(),
+        days_left,
+    }
+}
+
+// 同じ設定で別に接続し、そのハンドシェイクを調べる（プローブなので実際のリクエストの接続とは異なりうる）
+pub fn inspect(host: &str, remote: SocketAddr, settings: &ClientSettings) -> Result<TlsInfo, String> {
+    let mut builder = SslConnector::builder(SslMethod::tls_client()).map_err(|e| e.to_string())?;
+    let insecure = settings.insecure.unwrap_or(false);
+    if insecure {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+    if let Some(path) = &settings.ca_cert {
+        for cert in load_ca(path)? {
+            builder.cert_store_mut().add_cert(cert).map_err(|e| e.to_string())?;
+        }
+    }
+    if let Some(identity) = load_identity(settings)? {
+        builder.set_certificate(&identity.cert).map_err(|e| e.to_string())?;
+        builder.set_private_key(&identity.key).map_err(|e| e.to_string())?;
+        for cert in identity.chain {
+            builder.add_extra_chain_cert(cert).map_err(|e| e.to_string())?;
+        }
+    }
+    let config = builder
+        .build()
+        .configure()
+        .map_err(|e| e.to_string())?
+        .verify_hostname(!insecure);
+
+    let timeout = Duration::from_secs(INSPECT_TIMEOUT);
+    let stream = TcpStream::connect_timeout(&remote, timeout).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    let stream = config
+        .connect(host, stream)
+        .map_err(|e| format!("TLS handshake failed: {}", e))?;
+
+    let ssl = stream.ssl();
+    Ok(TlsInfo {
+        version: ssl.version_str().to_string(),
+        cipher: ssl
+            .current_cipher()
+            .map(|cipher| cipher.name().to_string())
+            .unwrap_or_default(),
+        chain: ssl
+            .peer_cert_chain()
+            .map(|chain| chain.iter().map(|cert| cert_info(&cert.to_owned())).collect())
+            .unwrap_or_default(),
+    })
+}
+
+impl TlsInfo {
+    // TLS ポップアップやヘッドレスモードで表示する行
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("version: {}", self.version),
+            format!("cipher:  {}", self.cipher),
+        ];
+        for (i, cert) in self.chain.iter().enumerate() {
+            let expiry = match cert.days_left {
+                Some(days) if days < 0 => format!(" (expired {} days ago)", -days),
+                Some(days) => format!(" ({} days left)", days),
+                None => "".to_string(),
+            };
+            lines.push(format!("[{}] subject: {}", i, cert.subject));
+            lines.push(format!("    issuer:  {}", cert.issuer));
+            if !cert.sans.is_empty() {
+                lines.push(format!("    SANs:    {}", cert.sans.join(", ")));
+            }
+            lines.push(format!(
+                "    valid:   {} - {}{}",
+                cert.not_before, cert.not_after, expiry
+            ));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Integer;
+    use openssl::bn::BigNum;
+    use openssl::hash::MessageDigest;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::{SslAcceptor, SslFiletype};
+    use openssl::x509::extension::SubjectAlternativeName;
+    use openssl::x509::X509NameBuilder;
+    use std::net::TcpListener;
+
+    // localhost の自己署名証明書で、受け付けた接続ごとにハンドシェイクだけする TLS サーバー
+    fn serve(dir: &Path) -> (SocketAddr, String) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", "localhost").unwrap();
+        name.append_entry_by_text("O", "Ruzz Test").unwrap();
+        let name = name.build();
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        let serial = Asn1Integer::from_bn(&BigNum::from_u32(1).unwrap()).unwrap();
+        cert.set_serial_number(&serial).unwrap();
+        cert.set_subject_name(&name).unwrap();
+        cert.set_issuer_name(&name).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(30).unwrap()).unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns("localhost")
+            .ip("127.0.0.1")
+            .build(&cert.x509v3_context(None, None))
+            .unwrap();
+        cert.append_extension(san).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = cert.build();
+
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        fs::write(&cert_path, cert.to_pem().unwrap()).unwrap();
+        fs::write(&key_path, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_private_key_file(&key_path, SslFiletype::PEM).unwrap();
+        acceptor.set_certificate_chain_file(&cert_path).unwrap();
+        let acceptor = acceptor.build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Ok(mut stream) = acceptor.accept(stream) {
+                    let _ = stream.shutdown();
+                }
+            }
+        });
+        (addr, cert_path.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn inspect_reads_the_handshake() {
+        let dir = std::env::temp_dir().join(format!("ruzz-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (addr, ca) = serve(&dir);
+
+        let settings = ClientSettings::parse(&format!("ca_cert={}", ca)).unwrap();
+        let info = inspect("localhost", addr, &settings).unwrap();
+        assert!(info.version.starts_with("TLSv1."), "{}", info.version);
+        assert!(!info.cipher.is_empty());
+        let cert = &info.chain[0];
+        assert_eq!(cert.subject, "CN=localhost, O=Ruzz Test");
+        assert_eq!(cert.issuer, cert.subject);
+        assert_eq!(cert.sans, ["DNS:localhost", "IP:127.0.0.1"]);
+        assert!(matches!(cert.days_left, Some(29..=30)), "{:?}", cert.days_left);
+        let lines = info.lines();
+        assert_eq!(lines[2], "[0] subject: CN=localhost, O=Ruzz Test");
+        assert_eq!(lines[4], "    SANs:    DNS:localhost, IP:127.0.0.1");
+
+        // 証明書を検証できなければ失敗する
+        let err = inspect("localhost", addr, &ClientSettings::default()).unwrap_err();
+        assert!(err.starts_with("TLS handshake failed"), "{}", err);
+        let err = inspect("example.com", addr, &settings).unwrap_err();
+        assert!(err.starts_with("TLS handshake failed"), "{}", err);
+        // insecure なら名前が合わなくても調べられる
+        let settings = ClientSettings::parse("insecure=true").unwrap();
+        assert_eq!(inspect("example.com", addr, &settings).unwrap().chain.len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
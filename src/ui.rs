@@ -5,27 +5,57 @@ use std::io::Stdout;
 use termion::{input::MouseTerminal, screen::AlternateScreen};
 use tui::{
     backend::TermionBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color, Style},
-    text::Spans,
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
     Terminal,
 };
 
 // use unicode_width::UnicodeWidthStr;
 
+// 画面中央の矩形を返す
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_y) / 2),
+                Constraint::Percentage(percent_y),
+                Constraint::Percentage((100 - percent_y) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(r);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage((100 - percent_x) / 2),
+                Constraint::Percentage(percent_x),
+                Constraint::Percentage((100 - percent_x) / 2),
+            ]
+            .as_ref(),
+        )
+        .split(vertical[1])[1]
+}
+
 pub fn draw(
     terminal: &mut Terminal<
-        TermionBackend<AlternateScreen<MouseTerminal<termion::raw::RawTerminal<Stdout>>>>,
+        TermionBackend<AlternateScreen<MouseTerminal<Stdout>>>,
     >,
     app: &app::App,
 ) {
     let request_url_text = app.request_url_text();
     let request_params_text = app.request_params_text();
     let request_header_text = app.request_header_text();
-    let request_body_text = app.request_body_text();
+    let request_auth_text = app.request_auth_text();
+    let request_body_text = app.request_body_display();
+    let request_assertion_text = app.request_assertion_text();
+    let request_settings_text = app.request_settings_text();
 
-    let response_status_text = app.response_status_text();
+    let response_status_text = app.response_status_display();
     let response_header_text = app.response_header_text();
     let response_body_text = app.response_body_text();
 
@@ -33,6 +63,13 @@ pub fn draw(
     let edit = app.edit_mode();
 
     let mut request_method_state = app.request_method_state().clone();
+    let mut request_auth_mode_state = app.request_auth_mode_state().clone();
+    let mut request_body_mode_state = app.request_body_mode_state().clone();
+    let mut saved_request_state = app.saved_request_state().clone();
+    let mut cookie_state = app.cookie_state().clone();
+    let mut redirect_state = app.redirect_state();
+    let mut event_state = app.event_state();
+    let mut grpc_state = app.grpc_state();
 
     let _ = terminal.draw(|f| {
         let size = f.size();
@@ -40,7 +77,14 @@ pub fn draw(
         // 上下レイアウト
         let main_chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(13), Constraint::Percentage(87)].as_ref())
+            .constraints(
+                [
+                    Constraint::Percentage(13),
+                    Constraint::Min(0),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
             .split(size);
 
         // 下の左右のレイアウト
@@ -54,10 +98,12 @@ pub fn draw(
             .direction(Direction::Vertical)
             .constraints(
                 [
-                    Constraint::Percentage(25),
                     Constraint::Percentage(15),
-                    Constraint::Percentage(30),
-                    Constraint::Percentage(30),
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(20),
+                    Constraint::Percentage(15),
+                    Constraint::Percentage(20),
+                    Constraint::Percentage(15),
                 ]
                 .as_ref(),
             )
@@ -76,6 +122,51 @@ pub fn draw(
             )
             .split(bottom_chunks[1]);
 
+        // 認証と認証方式の左右のレイアウト
+        let auth_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+            .split(request_chunks[3]);
+
+        // ボディとボディの種類の左右のレイアウト
+        let body_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+            .split(request_chunks[4]);
+
+        // graphql ならボディをクエリと変数の上下に分ける
+        let query_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+            .split(body_chunks[0]);
+        let query_area = if app.is_graphql() {
+            query_chunks[0]
+        } else {
+            body_chunks[0]
+        };
+
+        // 検証と設定の左右のレイアウト
+        let assertion_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+            .split(request_chunks[5]);
+
+        // ステータスと検証結果の左右のレイアウト
+        let status_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(response_chunks[0]);
+
+        // wire のレスポンスは受信したバイト列と解釈したボディの左右に分ける
+        let raw_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(response_chunks[2]);
+        let response_body_area = match app.response_raw_text() {
+            Some(_) => raw_chunks[1],
+            None => response_chunks[2],
+        };
+
         // Request URL
         let request_url = Paragraph::new(request_url_text.as_ref())
             .style(match view {
@@ -153,8 +244,44 @@ pub fn draw(
             );
         f.render_widget(request_header, request_chunks[2]);
 
+        // Request Auth
+        let request_auth = Paragraph::new(request_auth_text.as_ref())
+            .style(match view {
+                app::ViewMode::RequestAuth => {
+                    if *edit == app::EditMode::RequestAuth {
+                        Style::default().fg(Color::Green)
+                    } else {
+                        Style::default().fg(Color::Yellow)
+                    }
+                }
+                _ => Style::default(),
+            })
+            .block(Block::default().borders(Borders::ALL).title("Auth"));
+        f.render_widget(request_auth, auth_chunks[0]);
+
+        // Auth Mode
+        let items: Vec<ListItem> = app
+            .request_auth_mode_items_vec()
+            .iter()
+            .map(|i| ListItem::new(vec![Spans::from(i.to_string())]))
+            .collect();
+        let items = List::new(items)
+            .style(match view {
+                app::ViewMode::RequestAuthMode => {
+                    if *edit == app::EditMode::RequestAuthMode {
+                        Style::default().fg(Color::Green)
+                    } else {
+                        Style::default().fg(Color::Yellow)
+                    }
+                }
+                _ => Style::default(),
+            })
+            .block(Block::default().borders(Borders::ALL).title("Auth Mode"))
+            .highlight_symbol(">> ");
+        f.render_stateful_widget(items, auth_chunks[1], &mut request_auth_mode_state);
+
         // Request Body
-        let request_body = Paragraph::new(request_body_text.as_ref())
+        let request_body = Paragraph::new(request_body_text.as_str())
             .style(match view {
                 app::ViewMode::RequestBody => {
                     if *edit == app::EditMode::RequestBody {
@@ -165,18 +292,120 @@ pub fn draw(
                 }
                 _ => Style::default(),
             })
-            .block(Block::default().borders(Borders::ALL).title("Request Body"));
-        f.render_widget(request_body, request_chunks[3]);
+            .block(Block::default().borders(Borders::ALL).title(if app.is_graphql() {
+                match app.graphql_status() {
+                    Some(status) => format!("GraphQL Query ({}, G: list)", status),
+                    None => "GraphQL Query".to_string(),
+                }
+            } else {
+                "Request Body".to_string()
+            }));
+        f.render_widget(request_body, query_area);
+
+        // GraphQL Variables
+        if app.is_graphql() {
+            let request_variables = Paragraph::new(app.request_variables_text().as_str())
+                .style(match view {
+                    app::ViewMode::RequestVariables => {
+                        if *edit == app::EditMode::RequestVariables {
+                            Style::default().fg(Color::Green)
+                        } else {
+                            Style::default().fg(Color::Yellow)
+                        }
+                    }
+                    _ => Style::default(),
+                })
+                .block(Block::default().borders(Borders::ALL).title("GraphQL Variables"));
+            f.render_widget(request_variables, query_chunks[1]);
+        }
 
-        // Request Status
+        // Body Mode
+        let items: Vec<ListItem> = app
+            .request_body_mode_items_vec()
+            .iter()
+            .map(|i| ListItem::new(vec![Spans::from(i.to_string())]))
+            .collect();
+        let items = List::new(items)
+            .style(match view {
+                app::ViewMode::RequestBodyMode => {
+                    if *edit == app::EditMode::RequestBodyMode {
+                        Style::default().fg(Color::Green)
+                    } else {
+                        Style::default().fg(Color::Yellow)
+                    }
+                }
+                _ => Style::default(),
+            })
+            .block(Block::default().borders(Borders::ALL).title("Body Mode"))
+            .highlight_symbol(">> ");
+        f.render_stateful_widget(items, body_chunks[1], &mut request_body_mode_state);
+
+        // Request Assertions
+        let request_assertion = Paragraph::new(request_assertion_text.as_ref())
+            .style(match view {
+                app::ViewMode::RequestAssertion => {
+                    if *edit == app::EditMode::RequestAssertion {
+                        Style::default().fg(Color::Green)
+                    } else {
+                        Style::default().fg(Color::Yellow)
+                    }
+                }
+                _ => Style::default(),
+            })
+            .block(Block::default().borders(Borders::ALL).title("Assertions"));
+        f.render_widget(request_assertion, assertion_chunks[0]);
+
+        // Request Settings
+        let request_settings = Paragraph::new(request_settings_text.as_ref())
+            .style(match view {
+                app::ViewMode::RequestSettings => {
+                    if *edit == app::EditMode::RequestSettings {
+                        Style::default().fg(Color::Green)
+                    } else {
+                        Style::default().fg(Color::Yellow)
+                    }
+                }
+                _ => Style::default(),
+            })
+            .block(Block::default().borders(Borders::ALL).title("Settings"));
+        f.render_widget(request_settings, assertion_chunks[1]);
+
+        // Request Status（証明書を検証していなければ赤く表示する）
         let response_status = Paragraph::new(response_status_text.as_ref())
-            .style(Style::default())
+            .style(if app.tls_insecure() {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            })
             .block(
                 Block::default()
                     .borders(Borders::ALL)
                     .title("Response Status"),
             );
-        f.render_widget(response_status, response_chunks[0]);
+        f.render_widget(response_status, status_chunks[0]);
+
+        // Test Results
+        let test_results: Vec<Spans> = app
+            .response_test_results()
+            .iter()
+            .map(|r| {
+                if r.passed {
+                    Spans::from(Span::styled(
+                        format!("PASS {}", r.source),
+                        Style::default().fg(Color::Green),
+                    ))
+                } else {
+                    Spans::from(Span::styled(
+                        format!("FAIL {} ({})", r.source, r.message),
+                        Style::default().fg(Color::Red),
+                    ))
+                }
+            })
+            .collect();
+        let test_results = Paragraph::new(test_results)
+            .style(Style::default())
+            .block(Block::default().borders(Borders::ALL).title("Test Results"));
+        f.render_widget(test_results, status_chunks[1]);
 
         // Response Header
         let response_header = Paragraph::new(response_header_text.as_ref())
@@ -191,8 +420,40 @@ pub fn draw(
             );
         f.render_widget(response_header, response_chunks[1]);
 
-        // Response Body
-        let response_body = Paragraph::new(response_body_text.as_ref())
+        // Response Body（WebSocket のログは最新のフレームが見えるよう末尾までスクロールする）
+        let scroll = if app.follows_response_body() {
+            let lines = response_body_text.lines().count() as u16;
+            lines.saturating_sub(response_body_area.height.saturating_sub(2))
+        } else {
+            0
+        };
+        // GraphQL のレスポンスは errors を赤、data を緑で表示する
+        let response_body_lines: Vec<Spans> = match app.response_graphql_sections() {
+            Some(sections) => sections
+                .into_iter()
+                .flat_map(|(title, lines)| {
+                    let color = if title.starts_with("errors") {
+                        Color::Red
+                    } else if title == "data" {
+                        Color::Green
+                    } else {
+                        Color::Reset
+                    };
+                    let title = Spans::from(Span::styled(
+                        title,
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    ));
+                    std::iter::once(title).chain(
+                        lines
+                            .into_iter()
+                            .map(move |line| Spans::from(Span::styled(line, Style::default().fg(color)))),
+                    )
+                })
+                .collect(),
+            None => response_body_text.lines().map(Spans::from).collect(),
+        };
+        let response_body = Paragraph::new(response_body_lines)
+            .scroll((scroll, 0))
             .style(match view {
                 app::ViewMode::ResponseBody => Style::default().fg(Color::Yellow),
                 _ => Style::default(),
@@ -202,7 +463,22 @@ pub fn draw(
                     .borders(Borders::ALL)
                     .title("Response Body"),
             );
-        f.render_widget(response_body, response_chunks[2]);
+        f.render_widget(response_body, response_body_area);
+
+        // Raw Response
+        if let Some(raw) = app.response_raw_text() {
+            let raw_response = Paragraph::new(raw.as_ref())
+                .style(match view {
+                    app::ViewMode::ResponseBody => Style::default().fg(Color::Yellow),
+                    _ => Style::default(),
+                })
+                .block(Block::default().borders(Borders::ALL).title("Raw Response"));
+            f.render_widget(raw_response, raw_chunks[0]);
+        }
+
+        // お知らせ
+        let notice = Paragraph::new(app.notice().as_ref()).style(Style::default().fg(Color::Cyan));
+        f.render_widget(notice, main_chunks[2]);
 
         match app.edit_mode() {
             app::EditMode::RequestUrl => f.set_cursor(
@@ -217,11 +493,195 @@ pub fn draw(
                 request_chunks[2].x + app.request_header_cursor_x() + 1,
                 request_chunks[2].y + 1,
             ),
+            app::EditMode::RequestAuth => f.set_cursor(
+                auth_chunks[0].x + app.request_auth_cursor_x() + 1,
+                auth_chunks[0].y + 1,
+            ),
             app::EditMode::RequestBody => f.set_cursor(
-                request_chunks[3].x + app.request_body_cursor_x() + 1,
-                request_chunks[3].y + 1,
+                query_area.x + app.request_body_cursor_x() + 1,
+                query_area.y + 1,
+            ),
+            app::EditMode::RequestVariables => f.set_cursor(
+                query_chunks[1].x + app.request_variables_cursor_x() + 1,
+                query_chunks[1].y + 1,
+            ),
+            app::EditMode::RequestAssertion => f.set_cursor(
+                assertion_chunks[0].x + app.request_assertion_cursor_x() + 1,
+                assertion_chunks[0].y + 1,
+            ),
+            app::EditMode::RequestSettings => f.set_cursor(
+                assertion_chunks[1].x + app.request_settings_cursor_x() + 1,
+                assertion_chunks[1].y + 1,
             ),
             _ => {}
         }
+
+        // Requests
+        if *app.popup() == app::Popup::RequestList {
+            let area = centered_rect(60, 60, size);
+            let items: Vec<ListItem> = app
+                .saved_request_titles()
+                .into_iter()
+                .map(|title| ListItem::new(vec![Spans::from(title)]))
+                .collect();
+            let items = List::new(items)
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("Requests"))
+                .highlight_symbol(">> ");
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(items, area, &mut saved_request_state);
+        }
+
+        // Cookies
+        if *app.popup() == app::Popup::Cookies {
+            let area = centered_rect(80, 60, size);
+            let items: Vec<ListItem> = app
+                .cookie_summaries()
+                .into_iter()
+                .map(|summary| ListItem::new(vec![Spans::from(summary)]))
+                .collect();
+            let items = List::new(items)
+                .style(Style::default().fg(Color::Yellow))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Cookies (d: delete, D: clear, E: edit)"),
+                )
+                .highlight_symbol(">> ");
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(items, area, &mut cookie_state);
+        }
+
+        // Redirects
+        if *app.popup() == app::Popup::Redirects {
+            let area = centered_rect(80, 40, size);
+            let items: Vec<ListItem> = app
+                .redirect_lines()
+                .into_iter()
+                .map(|line| ListItem::new(vec![Spans::from(line)]))
+                .collect();
+            let items = List::new(items)
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("Redirects"))
+                .highlight_symbol(">> ");
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(items, area, &mut redirect_state);
+        }
+
+        // Server-Sent Events（上に一覧、下に選んだイベントの詳細）
+        if *app.popup() == app::Popup::Events {
+            let area = centered_rect(80, 80, size);
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
+                .split(area);
+            let lines = app.event_lines();
+            let items: Vec<ListItem> = lines
+                .iter()
+                .map(|line| ListItem::new(vec![Spans::from(line.as_str())]))
+                .collect();
+            let title = if app.is_streaming() {
+                format!("Events ({}, streaming, x: stop)", lines.len())
+            } else {
+                format!("Events ({})", lines.len())
+            };
+            let items = List::new(items)
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .highlight_symbol(">> ");
+            let detail: Vec<ListItem> = app
+                .event_detail_lines()
+                .into_iter()
+                .map(|line| ListItem::new(vec![Spans::from(line)]))
+                .collect();
+            let detail = List::new(detail)
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("Event"));
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(items, chunks[0], &mut event_state);
+            f.render_widget(detail, chunks[1]);
+        }
+
+        // gRPC のメソッド（上に一覧、下に選んだメソッドのメッセージ）
+        if *app.popup() == app::Popup::Grpc {
+            let area = centered_rect(80, 80, size);
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+                .split(area);
+            let items: Vec<ListItem> = app
+                .grpc_lines()
+                .into_iter()
+                .map(|line| ListItem::new(vec![Spans::from(line)]))
+                .collect();
+            let items = List::new(items)
+                .style(Style::default().fg(Color::Yellow))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("gRPC Methods (Enter: use)"),
+                )
+                .highlight_symbol(">> ");
+            let detail: Vec<ListItem> = app
+                .grpc_detail_lines()
+                .into_iter()
+                .map(|line| ListItem::new(vec![Spans::from(line)]))
+                .collect();
+            let detail = List::new(detail)
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("Messages"));
+            f.render_widget(Clear, area);
+            f.render_stateful_widget(items, chunks[0], &mut grpc_state);
+            f.render_widget(detail, chunks[1]);
+        }
+
+        // GraphQL の検証の結果
+        if *app.popup() == app::Popup::GraphQL {
+            let area = centered_rect(80, 40, size);
+            let items: Vec<ListItem> = app
+                .graphql_lines()
+                .into_iter()
+                .map(|line| ListItem::new(vec![Spans::from(line)]))
+                .collect();
+            let items = List::new(items)
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("GraphQL"));
+            f.render_widget(Clear, area);
+            f.render_widget(items, area);
+        }
+
+        // TLS
+        if *app.popup() == app::Popup::Tls {
+            let area = centered_rect(80, 60, size);
+            let items: Vec<ListItem> = app
+                .tls_lines()
+                .into_iter()
+                .map(|line| ListItem::new(vec![Spans::from(line)]))
+                .collect();
+            let items = List::new(items)
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL).title("TLS"));
+            f.render_widget(Clear, area);
+            f.render_widget(items, area);
+        }
+
+        // Client Settings
+        if *app.popup() == app::Popup::Settings {
+            let area = centered_rect(60, 40, size);
+            let items: Vec<ListItem> = app
+                .settings_lines()
+                .into_iter()
+                .map(|line| ListItem::new(vec![Spans::from(line)]))
+                .collect();
+            let items = List::new(items)
+                .style(Style::default().fg(Color::Yellow))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Client Settings (E: edit defaults)"),
+                );
+            f.render_widget(Clear, area);
+            f.render_widget(items, area);
+        }
     });
 }
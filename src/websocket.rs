@@ -0,0 +1,397 @@
+use chrono::{DateTime, Local};
+use std::io::{self, Read};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::header::{HeaderName, HeaderValue};
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::CloseFrame;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Connector, Message, WebSocket};
+
+use super::body;
+use super::resolve;
+use super::settings::ClientSettings;
+use super::tls;
+
+// 受信を待つ間隔（この間に送信の指示を確認する）
+const POLL_INTERVAL: u64 = 50;
+// ログに表示するバイナリの最大バイト数
+const BINARY_PREVIEW: usize = 64;
+
+// 送ったフレームか受け取ったフレームか
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+// ログの1行
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub at: DateTime<Local>,
+    pub direction: Direction,
+    // text, binary, ping, pong, close
+    pub kind: &'static str,
+    pub payload: String,
+}
+
+impl Frame {
+    fn new(direction: Direction, kind: &'static str, payload: String) -> Frame {
+        Frame {
+            at: Local::now(),
+            direction,
+            kind,
+            payload,
+        }
+    }
+
+    fn from_message(direction: Direction, message: &Message) -> Option<Frame> {
+        let (kind, payload) = match message {
+            Message::Text(text) => ("text", text.replace('\n', "\\n")),
+            Message::Binary(data) => ("binary", preview(data)),
+            Message::Ping(data) => ("ping", preview(data)),
+            Message::Pong(data) => ("pong", preview(data)),
+            Message::Close(Some(frame)) => (
+                "close",
+                format!("{} {}", u16::from(frame.code), frame.reason).trim_end().to_string(),
+            ),
+            Message::Close(None) => ("close", "".to_string()),
+            Message::Frame(_) => return None,
+        };
+        Some(Frame::new(direction, kind, payload))
+    }
+
+    pub fn line(&self) -> String {
+        let arrow = match self.direction {
+            Direction::Sent => ">",
+            Direction::Received => "<",
+        };
+        format!(
+            "{} {} {:<6} {}",
+            self.at.format("%H:%M:%S%.3f"),
+            arrow,
+            self.kind,
+            self.payload
+        )
+        .trim_end()
+        .to_string()
+    }
+}
+
+// バイナリはバイト数と先頭の 16 進数で表示する
+fn preview(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "".to_string();
+    }
+    let hex = data
+        .iter()
+        .take(BINARY_PREVIEW)
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let more = if data.len() > BINARY_PREVIEW { " ..." } else { "" };
+    format!("({} bytes) {}{}", data.len(), hex, more)
+}
+
+// セッションへの指示
+pub enum Command {
+    Send(Message),
+    Ping,
+    Close(u16),
+}
+
+// セッションから届く知らせ
+pub enum Event {
+    Frame(Frame),
+    // 接続が終わった（理由）
+    Closed(String),
+}
+
+// ハンドシェイクのレスポンス
+pub struct Handshake {
+    pub status: u16,
+    pub status_text: String,
+    pub headers: Vec<(String, String)>,
+    pub remote: Option<SocketAddr>,
+    // 接続できた時刻
+    pub at: DateTime<Local>,
+}
+
+// 接続中の WebSocket（読み書きは別スレッドで行う）
+pub struct Session {
+    pub url: String,
+    commands: mpsc::Sender<Command>,
+    events: mpsc::Receiver<Event>,
+}
+
+fn tcp_stream(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Option<&mut TcpStream> {
+    match socket.get_mut() {
+        MaybeTlsStream::Plain(stream) => Some(stream),
+        MaybeTlsStream::NativeTls(stream) => Some(stream.get_mut()),
+        _ => None,
+    }
+}
+
+// ハンドシェイクをしてセッションを始める（ヘッダーペインの値はハンドシェイクに付ける）
+pub fn connect(
+    url: &url::Url,
+    headers: &[(String, String)],
+    settings: &ClientSettings,
+) -> Result<(Session, Handshake), String> {
+    if settings.unix_socket.is_some() {
+        return Err("WebSocket over a unix socket is not supported".to_string());
+    }
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .map_err(|e| format!("invalid WebSocket url {}: {}", url, e))?;
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| format!("invalid header name {}: {}", name, e))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| format!("invalid header value {}: {}", value, e))?;
+        request.headers_mut().append(name, value);
+    }
+
+    let stream = resolve::connect_tcp(url, settings)?;
+    let remote = stream.peer_addr().ok();
+    let connector = match url.scheme() {
+        "wss" => Connector::NativeTls(tls::native_connector(settings)?),
+        _ => Connector::Plain,
+    };
+    let (mut socket, response) =
+        tungstenite::client_tls_with_config(request, stream, None, Some(connector))
+            .map_err(|e| format!("WebSocket handshake failed: {}", e))?;
+    if let Some(stream) = tcp_stream(&mut socket) {
+        stream
+            .set_read_timeout(Some(Duration::from_millis(POLL_INTERVAL)))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let handshake = Handshake {
+        status: response.status().as_u16(),
+        status_text: response
+            .status()
+            .canonical_reason()
+            .unwrap_or("")
+            .to_string(),
+        headers: response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).to_string(),
+                )
+            })
+            .collect(),
+        remote,
+        at: Local::now(),
+    };
+
+    let (command_tx, command_rx) = mpsc::channel();
+    let (event_tx, event_rx) = mpsc::channel();
+    thread::spawn(move || run(socket, command_rx, event_tx));
+    Ok((
+        Session {
+            url: url.to_string(),
+            commands: command_tx,
+            events: event_rx,
+        },
+        handshake,
+    ))
+}
+
+// 指示されたフレームを送り、届いたフレームを知らせる
+fn run(
+    mut socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    commands: mpsc::Receiver<Command>,
+    events: mpsc::Sender<Event>,
+) {
+    // 相手から close を受け取ったら、その後のエラーは切断として扱う
+    let mut closing = false;
+    let reason = loop {
+        let mut failed = None;
+        loop {
+            let command = match commands.try_recv() {
+                Ok(command) => command,
+                Err(mpsc::TryRecvError::Empty) => break,
+                // セッションが捨てられたら閉じる
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    let _ = socket.close(None);
+                    let _ = socket.flush();
+                    return;
+                }
+            };
+            let message = match command {
+                Command::Send(message) => message,
+                Command::Ping => Message::Ping(Vec::new()),
+                Command::Close(code) => Message::Close(Some(CloseFrame {
+                    code: CloseCode::from(code),
+                    reason: "".into(),
+                })),
+            };
+            let frame = Frame::from_message(Direction::Sent, &message);
+            match socket.send(message) {
+                Ok(()) => {
+                    if let Some(frame) = frame {
+                        let _ = events.send(Event::Frame(frame));
+                    }
+                }
+                Err(e) => failed = Some(e.to_string()),
+            }
+        }
+        if let Some(reason) = failed {
+            break reason;
+        }
+
+        match socket.read() {
+            Ok(message) => {
+                let pong = matches!(message, Message::Ping(_));
+                closing |= matches!(message, Message::Close(_));
+                if let Some(frame) = Frame::from_message(Direction::Received, &message) {
+                    let _ = events.send(Event::Frame(frame));
+                }
+                // ping には tungstenite が自動で pong を返す
+                if pong {
+                    let _ = events.send(Event::Frame(Frame::new(
+                        Direction::Sent,
+                        "pong",
+                        "(auto)".to_string(),
+                    )));
+                }
+            }
+            Err(tungstenite::Error::Io(e))
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {}
+            Err(tungstenite::Error::ConnectionClosed) => break "connection closed".to_string(),
+            Err(_) if closing => break "connection closed".to_string(),
+            Err(e) => break e.to_string(),
+        }
+    };
+    let _ = events.send(Event::Closed(reason));
+}
+
+impl Session {
+    pub fn send(&self, command: Command) -> Result<(), String> {
+        self.commands
+            .send(command)
+            .map_err(|_| "the WebSocket connection is closed".to_string())
+    }
+
+    // 届いている知らせをすべて受け取る
+    pub fn poll(&self) -> Vec<Event> {
+        self.events.try_iter().collect()
+    }
+
+    // 知らせが届くまで最大 timeout 待つ
+    pub fn wait(&self, timeout: Duration) -> Option<Event> {
+        self.events.recv_timeout(timeout).ok()
+    }
+}
+
+// 送るメッセージ（`@path` はファイルの中身、`hex:` は 16 進数をバイナリで送る。`@` の扱いはボディと同じ）
+pub fn message(text: &str) -> Result<Message, String> {
+    if let Some(path) = body::file_reference(text) {
+        let mut data = Vec::new();
+        if path == "-" {
+            if termion::is_tty(&io::stdin()) {
+                return Err("stdin is a terminal".to_string());
+            }
+            io::stdin()
+                .read_to_end(&mut data)
+                .map_err(|e| format!("failed to read stdin: {}", e))?;
+        } else {
+            data = std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+        }
+        return Ok(Message::Binary(data));
+    }
+    if let Some(hex) = text.trim().strip_prefix("hex:") {
+        let digits: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+        if !digits.is_ascii() || !digits.len().is_multiple_of(2) {
+            return Err(format!("invalid hex: {}", hex.trim()));
+        }
+        let data = (0..digits.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&digits[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| format!("invalid hex: {}", hex.trim()))?;
+        return Ok(Message::Binary(data));
+    }
+    Ok(Message::Text(body::unescape(text).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn message_escapes_match_the_body() {
+        let path = std::env::temp_dir().join(format!("ruzz-ws-{}.bin", std::process::id()));
+        std::fs::write(&path, [0u8, 1, 2]).unwrap();
+        let reference = format!("@{}", path.display());
+        assert_eq!(message(&reference).unwrap(), Message::Binary(vec![0, 1, 2]));
+        assert_eq!(body::file_reference(&reference), Some(path.to_str().unwrap()));
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(message("@@handle").unwrap(), Message::Text("@handle".to_string()));
+        assert_eq!(body::unescape("@@handle"), "@handle");
+        // 複数行や `@` だけならそのまま送る
+        assert_eq!(message("@a\nb").unwrap(), Message::Text("@a\nb".to_string()));
+        assert_eq!(message("@").unwrap(), Message::Text("@".to_string()));
+        assert_eq!(message("hex: 01 ff").unwrap(), Message::Binary(vec![1, 255]));
+        assert!(message("hex:abc").is_err());
+    }
+
+    // 届いたテキストとバイナリを送り返すサーバー（ping と close には tungstenite が応える）
+    fn echo_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            while let Ok(message) = socket.read() {
+                if message.is_text() || message.is_binary() {
+                    let _ = socket.send(message);
+                }
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn session_sends_text_binary_ping_and_close() {
+        let port = echo_server();
+        let url = url::Url::parse(&format!("ws://127.0.0.1:{}/echo", port)).unwrap();
+        let (session, handshake) = connect(&url, &[], &ClientSettings::default()).unwrap();
+        assert_eq!(handshake.status, 101);
+
+        session.send(Command::Send(message("hello").unwrap())).unwrap();
+        session.send(Command::Send(message("hex:00ff").unwrap())).unwrap();
+        session.send(Command::Ping).unwrap();
+        session.send(Command::Close(1000)).unwrap();
+
+        let mut frames = Vec::new();
+        let closed = loop {
+            match session.wait(Duration::from_secs(5)) {
+                Some(Event::Frame(frame)) => frames.push(format!(
+                    "{} {} {}",
+                    if frame.direction == Direction::Sent { ">" } else { "<" },
+                    frame.kind,
+                    frame.payload
+                )),
+                Some(Event::Closed(reason)) => break reason,
+                None => panic!("no close after {:?}", frames),
+            }
+        };
+        assert_eq!(closed, "connection closed");
+        let sent: Vec<&String> = frames.iter().filter(|f| f.starts_with('>')).collect();
+        assert_eq!(sent, ["> text hello", "> binary (2 bytes) 00 ff", "> ping ", "> close 1000"]);
+        for received in ["< text hello", "< binary (2 bytes) 00 ff", "< pong ", "< close 1000"] {
+            assert!(frames.iter().any(|f| f == received), "{} not in {:?}", received, frames);
+        }
+    }
+}
@@ -0,0 +1,741 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::os::unix::net::UnixStream;
+use std::time::{Duration, Instant};
+
+use super::resolve;
+use super::settings::ClientSettings;
+use super::tls;
+
+const DEFAULT_TIMEOUT: f64 = 30.0;
+// 1つ目のレスポンスを読み終えてから、続くレスポンス（パイプライン）を待つ時間
+const GRACE: u64 = 200;
+const READ_SIZE: usize = 16 * 1024;
+
+// ペインの内容を送るバイト列にする（`@path` は body::encode がファイルとして扱う）
+//
+// 改行は CRLF にし、`\r` `\n` `\t` `\0` `\\` `\xHH` を解釈する。行末の `\` は改行を送らずに次の行とつなぐ
+pub fn encode(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => bytes.extend_from_slice(b"\r\n"),
+            '\\' => match chars.next() {
+                Some('r') => bytes.push(b'\r'),
+                Some('n') => bytes.push(b'\n'),
+                Some('t') => bytes.push(b'\t'),
+                Some('0') => bytes.push(0),
+                Some('\\') => bytes.push(b'\\'),
+                Some('\n') => {}
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    let byte = u8::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|_| hex.len() == 2)
+                        .ok_or_else(|| format!("invalid escape \\x{}", hex))?;
+                    bytes.push(byte);
+                }
+                Some(other) => return Err(format!("invalid escape \\{}", other)),
+                None => return Err("the request ends with a lone \\".to_string()),
+            },
+            c => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+// ボディが空のときに入れる雛形
+pub fn template(method: &str, url: &url::Url, headers: &[String]) -> String {
+    let mut target = url.path().to_string();
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(query);
+    }
+    let host = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        _ => "localhost".to_string(),
+    };
+    let mut lines = vec![format!("{} {} HTTP/1.1", method, target), format!("Host: {}", host)];
+    lines.extend(headers.iter().cloned());
+    lines.push("Connection: close".to_string());
+    lines.push("".to_string());
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+// 受信したバイト列をそのまま見えるようにする（改行の前に \r \n を表示し、表示できないバイトは \xHH）
+pub fn escape(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (valid, invalid) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, 0),
+            Err(e) => (
+                std::str::from_utf8(&rest[..e.valid_up_to()]).unwrap_or(""),
+                e.error_len().unwrap_or(rest.len() - e.valid_up_to()),
+            ),
+        };
+        for c in valid.chars() {
+            match c {
+                '\r' => text.push_str("\\r"),
+                '\n' => text.push_str("\\n\n"),
+                '\t' => text.push_str("\\t"),
+                '\\' => text.push_str("\\\\"),
+                c if c.is_control() => {
+                    let mut buf = [0; 4];
+                    for byte in c.encode_utf8(&mut buf).bytes() {
+                        text.push_str(&format!("\\x{:02x}", byte));
+                    }
+                }
+                c => text.push(c),
+            }
+        }
+        let start = valid.len();
+        for byte in &rest[start..start + invalid] {
+            text.push_str(&format!("\\x{:02x}", byte));
+        }
+        rest = &rest[start + invalid..];
+    }
+    text
+}
+
+// 解釈したレスポンス
+#[derive(Clone, Debug, Default)]
+pub struct Parsed {
+    pub status_line: String,
+    pub code: u16,
+    // 受信した順と大文字小文字のまま
+    pub headers: Vec<(String, String)>,
+    // chunked を外したボディ
+    pub body: Vec<u8>,
+    // 仕様から外れている点や、ボディの区切り方
+    pub notes: Vec<String>,
+    // 受信したバイト列の中での終わり（足りなければ None）
+    pub end: Option<usize>,
+}
+
+// 1行を取り出す（CRLF でも LF だけでもよい）
+fn line(bytes: &[u8], pos: usize) -> Option<(&[u8], usize, bool)> {
+    let len = bytes[pos..].iter().position(|b| *b == b'\n')?;
+    let end = pos + len;
+    let crlf = len > 0 && bytes[end - 1] == b'\r';
+    let content = if crlf { &bytes[pos..end - 1] } else { &bytes[pos..end] };
+    Some((content, end + 1, crlf))
+}
+
+// pos から始まるステータス行とヘッダー（ボディの始まりも返す）
+fn head_section(bytes: &[u8], pos: usize) -> Option<(Parsed, usize)> {
+    let mut parsed = Parsed::default();
+    let (status, mut at, crlf) = line(bytes, pos)?;
+    parsed.status_line = String::from_utf8_lossy(status).to_string();
+    if !crlf {
+        parsed.notes.push("status line ends with a bare LF".to_string());
+    }
+    let mut parts = parsed.status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or("");
+    if !version.starts_with("HTTP/") {
+        parsed
+            .notes
+            .push(format!("not an HTTP status line: {}", parsed.status_line));
+    }
+    parsed.code = parts.next().and_then(|code| code.parse().ok()).unwrap_or(0);
+
+    loop {
+        let (content, next, crlf) = line(bytes, at)?;
+        at = next;
+        if content.is_empty() {
+            if !crlf {
+                parsed.notes.push("header section ends with a bare LF".to_string());
+            }
+            break;
+        }
+        let text = String::from_utf8_lossy(content).to_string();
+        if !crlf {
+            parsed.notes.push(format!("bare LF after: {}", text));
+        }
+        // obs-fold（空白で始まる行は前のヘッダーの続き）
+        if text.starts_with(' ') || text.starts_with('\t') {
+            match parsed.headers.last_mut() {
+                Some((name, value)) => {
+                    parsed.notes.push(format!("obs-fold in {}", name));
+                    value.push(' ');
+                    value.push_str(text.trim());
+                }
+                None => parsed
+                    .notes
+                    .push(format!("continuation line before any header: {}", text)),
+            }
+            continue;
+        }
+        match text.find(':') {
+            Some(i) => {
+                let name = &text[..i];
+                if name.ends_with(' ') || name.ends_with('\t') {
+                    parsed.notes.push(format!("whitespace before colon in {}", name.trim()));
+                }
+                parsed
+                    .headers
+                    .push((name.trim().to_string(), text[i + 1..].trim().to_string()));
+            }
+            None => parsed.notes.push(format!("header without a colon: {}", text)),
+        }
+    }
+    Some((parsed, at))
+}
+
+// ボディの区切り方
+enum Framing {
+    Empty,
+    Chunked,
+    // ボディの終わり
+    Length(usize),
+    // 接続が閉じるまで
+    Close,
+}
+
+// ヘッダーからボディの区切り方を決める（at はボディの始まり、head なら HEAD へのレスポンス）
+fn framing(parsed: &mut Parsed, at: usize, head: bool) -> Framing {
+    let values = |wanted: &str| -> Vec<String> {
+        parsed
+            .headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(wanted))
+            .map(|(_, value)| value.to_string())
+            .collect()
+    };
+    let lengths = values("content-length");
+    let chunked = values("transfer-encoding")
+        .iter()
+        .flat_map(|v| v.split(','))
+        .last()
+        .map(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false);
+    if lengths.len() > 1 {
+        let mut distinct = lengths.clone();
+        distinct.dedup();
+        parsed.notes.push(if distinct.len() > 1 {
+            format!("conflicting Content-Length: {}", lengths.join(", "))
+        } else {
+            format!("duplicate Content-Length: {}", lengths.join(", "))
+        });
+    }
+    if chunked && !lengths.is_empty() {
+        parsed
+            .notes
+            .push("both Transfer-Encoding: chunked and Content-Length (chunked wins)".to_string());
+    }
+
+    if head || parsed.code / 100 == 1 || parsed.code == 204 || parsed.code == 304 {
+        return Framing::Empty;
+    }
+    if chunked {
+        return Framing::Chunked;
+    }
+    match lengths.first() {
+        Some(length) => match length.parse::<usize>().ok().and_then(|length| at.checked_add(length)) {
+            Some(end) => Framing::Length(end),
+            None => {
+                parsed.notes.push(format!("invalid Content-Length: {}", length));
+                Framing::Close
+            }
+        },
+        None => Framing::Close,
+    }
+}
+
+// チャンクの大きさの行（chunk-ext は無視する）
+fn chunk_size(size_line: &[u8]) -> Result<usize, String> {
+    let text = String::from_utf8_lossy(size_line);
+    let text = text.split(';').next().unwrap_or("").trim();
+    usize::from_str_radix(text, 16).map_err(|_| text.to_string())
+}
+
+// pos から始まる1つのレスポンス（head なら HEAD へのレスポンスでボディがない、eof なら接続が閉じている）
+pub fn parse(bytes: &[u8], pos: usize, head: bool, eof: bool) -> Option<Parsed> {
+    let (mut parsed, mut at) = head_section(bytes, pos)?;
+    match framing(&mut parsed, at, head) {
+        Framing::Empty => parsed.end = Some(at),
+        Framing::Chunked => {
+            let mut chunks = 0;
+            loop {
+                let (size_line, next, _) = line(bytes, at)?;
+                let size = chunk_size(size_line);
+                // 大きすぎるチャンクも不正な大きさとして扱う
+                let end = size.as_ref().ok().and_then(|size| next.checked_add(*size));
+                let (size, end) = match (size, end) {
+                    (Ok(size), Some(end)) => (size, end),
+                    (Ok(_), None) => {
+                        parsed
+                            .notes
+                            .push(format!("invalid chunk size: {}", String::from_utf8_lossy(size_line).trim()));
+                        parsed.end = Some(bytes.len()).filter(|_| eof);
+                        return Some(parsed);
+                    }
+                    (Err(text), _) => {
+                        parsed.notes.push(format!("invalid chunk size: {}", text));
+                        parsed.end = Some(bytes.len()).filter(|_| eof);
+                        return Some(parsed);
+                    }
+                };
+                at = next;
+                if size == 0 {
+                    // トレーラーと最後の空行
+                    loop {
+                        let (content, next, _) = line(bytes, at)?;
+                        at = next;
+                        if content.is_empty() {
+                            break;
+                        }
+                        parsed
+                            .notes
+                            .push(format!("trailer {}", String::from_utf8_lossy(content)));
+                    }
+                    break;
+                }
+                if bytes.len() < end {
+                    return if eof {
+                        parsed.body.extend_from_slice(&bytes[at..]);
+                        parsed.notes.push("response ended inside a chunk".to_string());
+                        parsed.end = Some(bytes.len());
+                        Some(parsed)
+                    } else {
+                        None
+                    };
+                }
+                parsed.body.extend_from_slice(&bytes[at..end]);
+                chunks += 1;
+                at = end;
+                let (rest, next, _) = line(bytes, at)?;
+                if !rest.is_empty() {
+                    parsed.notes.push(format!("chunk {} is longer than its size", chunks));
+                }
+                at = next;
+            }
+            parsed
+                .notes
+                .push(format!("body: {} bytes in {} chunks", parsed.body.len(), chunks));
+            parsed.end = Some(at);
+        }
+        Framing::Length(end) if bytes.len() >= end => {
+            parsed.body = bytes[at..end].to_vec();
+            parsed
+                .notes
+                .push(format!("body: {} bytes (Content-Length)", parsed.body.len()));
+            parsed.end = Some(end);
+        }
+        Framing::Length(end) if eof => {
+            parsed.body = bytes[at..].to_vec();
+            parsed.notes.push(format!(
+                "response ended after {} of {} bytes",
+                parsed.body.len(),
+                end - at
+            ));
+            parsed.end = Some(bytes.len());
+        }
+        Framing::Length(_) => return None,
+        Framing::Close => {
+            // 接続が閉じるまでがボディ
+            if !eof {
+                return None;
+            }
+            parsed.body = bytes[at..].to_vec();
+            parsed.notes.push(format!(
+                "body: {} bytes (until the connection closed)",
+                parsed.body.len()
+            ));
+            parsed.end = Some(bytes.len());
+        }
+    }
+    Some(parsed)
+}
+
+// 受信しながらレスポンスの区切りを追う（読み終えたところは読み直さない）
+struct Framer {
+    head: bool,
+    // 読み終えていないレスポンスの始まり
+    start: usize,
+    state: Frame,
+    // 最後に読み終えたのが 1xx 以外のレスポンスか
+    complete: bool,
+}
+
+enum Frame {
+    Head,
+    // ボディの終わりを待っている
+    Body(usize),
+    // at から始まるチャンクを待っている
+    Chunk(usize),
+    // 接続が閉じるまで読む
+    Close,
+}
+
+impl Framer {
+    fn new(head: bool) -> Framer {
+        Framer {
+            head,
+            start: 0,
+            state: Frame::Head,
+            complete: false,
+        }
+    }
+
+    // 届いた分だけ読み進める
+    fn advance(&mut self, bytes: &[u8]) {
+        loop {
+            match self.state {
+                Frame::Head => {
+                    if self.start >= bytes.len() {
+                        return;
+                    }
+                    let (mut parsed, at) = match head_section(bytes, self.start) {
+                        Some(section) => section,
+                        None => return,
+                    };
+                    self.complete = false;
+                    match framing(&mut parsed, at, self.head) {
+                        Framing::Empty => {
+                            self.start = at;
+                            self.complete = parsed.code / 100 != 1;
+                        }
+                        Framing::Chunked => self.state = Frame::Chunk(at),
+                        Framing::Length(end) => self.state = Frame::Body(end),
+                        Framing::Close => self.state = Frame::Close,
+                    }
+                }
+                Frame::Body(end) => {
+                    if bytes.len() < end {
+                        return;
+                    }
+                    self.finish(end);
+                }
+                Frame::Chunk(at) => {
+                    let (size_line, next, _) = match line(bytes, at) {
+                        Some(size_line) => size_line,
+                        None => return,
+                    };
+                    let size = match chunk_size(size_line) {
+                        Ok(size) => size,
+                        Err(_) => {
+                            self.state = Frame::Close;
+                            return;
+                        }
+                    };
+                    if size == 0 {
+                        let mut at = next;
+                        loop {
+                            match line(bytes, at) {
+                                Some((content, next, _)) => {
+                                    at = next;
+                                    if content.is_empty() {
+                                        break;
+                                    }
+                                }
+                                None => return,
+                            }
+                        }
+                        self.finish(at);
+                        continue;
+                    }
+                    let end = match next.checked_add(size) {
+                        Some(end) if end < bytes.len() => end,
+                        Some(_) => return,
+                        None => {
+                            self.state = Frame::Close;
+                            return;
+                        }
+                    };
+                    match line(bytes, end) {
+                        Some((_, next, _)) => self.state = Frame::Chunk(next),
+                        None => return,
+                    }
+                }
+                Frame::Close => return,
+            }
+        }
+    }
+
+    fn finish(&mut self, end: usize) {
+        self.start = end;
+        self.state = Frame::Head;
+        self.complete = true;
+    }
+
+    // 最後のレスポンスまで読み終えているか
+    fn done(&self, bytes: &[u8]) -> bool {
+        match self.state {
+            Frame::Head => self.complete && self.start == bytes.len(),
+            _ => false,
+        }
+    }
+}
+
+// 受信したバイト列の中のレスポンスを順に解釈する（1xx も含む）
+pub fn parse_all(bytes: &[u8], head: bool, eof: bool) -> Vec<Parsed> {
+    let mut responses = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match parse(bytes, pos, head, eof) {
+            Some(parsed) => {
+                let end = parsed.end;
+                responses.push(parsed);
+                match end {
+                    Some(end) if end > pos => pos = end,
+                    _ => break,
+                }
+            }
+            None => break,
+        }
+    }
+    responses
+}
+
+enum Connection {
+    Tcp(TcpStream),
+    Tls(Box<native_tls::TlsStream<TcpStream>>),
+    Unix(UnixStream),
+}
+
+impl Connection {
+    fn set_read_timeout(&self, timeout: Duration) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.set_read_timeout(Some(timeout)),
+            Connection::Tls(stream) => stream.get_ref().set_read_timeout(Some(timeout)),
+            Connection::Unix(stream) => stream.set_read_timeout(Some(timeout)),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.read(buf),
+            Connection::Tls(stream) => stream.read(buf),
+            Connection::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.write(buf),
+            Connection::Tls(stream) => stream.write(buf),
+            Connection::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.flush(),
+            Connection::Tls(stream) => stream.flush(),
+            Connection::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+// 送受信の結果
+pub struct Exchange {
+    // 受信したバイト列そのもの
+    pub bytes: Vec<u8>,
+    pub remote: Option<SocketAddr>,
+    // 接続が閉じられたか
+    pub eof: bool,
+    // 読み終わる前にタイムアウトした
+    pub timed_out: bool,
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut
+}
+
+// URL のホストに接続して request をそのまま送り、レスポンスを読む
+//
+// 最後のレスポンスを読み終えるか、接続が閉じるか、timeout が過ぎるまで読む
+pub fn send(url: &url::Url, request: &[u8], settings: &ClientSettings) -> Result<Exchange, String> {
+    let https = match url.scheme() {
+        "http" => false,
+        "https" => true,
+        scheme => return Err(format!("raw requests need an http or https url, not {}", scheme)),
+    };
+    let timeout = match settings.timeout.unwrap_or(DEFAULT_TIMEOUT) {
+        t if t > 0.0 => Some(Duration::from_secs_f64(t)),
+        _ => None,
+    };
+    let (mut connection, remote) = match &settings.unix_socket {
+        Some(_) if https => return Err("https over a unix socket is not supported for raw requests".to_string()),
+        Some(path) => {
+            let stream = UnixStream::connect(path).map_err(|e| format!("failed to connect to {}: {}", path, e))?;
+            (Connection::Unix(stream), None)
+        }
+        None => {
+            let stream = resolve::connect_tcp(url, settings)?;
+            let remote = stream.peer_addr().ok();
+            stream.set_write_timeout(timeout).map_err(|e| e.to_string())?;
+            if https {
+                stream.set_read_timeout(timeout).map_err(|e| e.to_string())?;
+                let host = url
+                    .host_str()
+                    .unwrap_or("")
+                    .trim_start_matches('[')
+                    .trim_end_matches(']');
+                let stream = tls::native_connector(settings)?
+                    .connect(host, stream)
+                    .map_err(|e| format!("TLS handshake failed: {}", e))?;
+                (Connection::Tls(Box::new(stream)), remote)
+            } else {
+                (Connection::Tcp(stream), remote)
+            }
+        }
+    };
+
+    connection
+        .write_all(request)
+        .and_then(|_| connection.flush())
+        .map_err(|e| format!("failed to send the request: {}", e))?;
+
+    // HEAD へのレスポンスにはボディがない
+    let head = request.starts_with(b"HEAD ");
+    let started = Instant::now();
+    let mut bytes = Vec::new();
+    let mut buf = vec![0; READ_SIZE];
+    let mut eof = false;
+    let mut timed_out = false;
+    let mut framer = Framer::new(head);
+    loop {
+        // 最後のレスポンスまで読み終えていれば、続きは少しだけ待つ
+        framer.advance(&bytes);
+        let done = framer.done(&bytes);
+        let wait = match timeout {
+            Some(timeout) => match timeout.checked_sub(started.elapsed()) {
+                Some(left) if left > Duration::from_millis(0) => left,
+                _ => {
+                    timed_out = !done;
+                    break;
+                }
+            },
+            None => Duration::from_secs(3600),
+        };
+        let wait = if done {
+            wait.min(Duration::from_millis(GRACE))
+        } else {
+            wait
+        };
+        connection.set_read_timeout(wait).map_err(|e| e.to_string())?;
+        match connection.read(&mut buf) {
+            Ok(0) => {
+                eof = true;
+                break;
+            }
+            Ok(n) => bytes.extend_from_slice(&buf[..n]),
+            Err(e) if is_timeout(&e) => {
+                if !done {
+                    timed_out = true;
+                }
+                break;
+            }
+            // TLS の close_notify なしで閉じられたときなど
+            Err(_) if !bytes.is_empty() => {
+                eof = true;
+                break;
+            }
+            Err(e) => return Err(format!("failed to read the response: {}", e)),
+        }
+    }
+    if bytes.is_empty() && timed_out {
+        return Err(format!("no response within {:?}", timeout.unwrap_or_default()));
+    }
+    Ok(Exchange {
+        bytes,
+        remote,
+        eof,
+        timed_out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(bytes: &[u8]) -> bool {
+        let mut framer = Framer::new(false);
+        framer.advance(bytes);
+        framer.done(bytes)
+    }
+
+    #[test]
+    fn huge_chunk_size_is_invalid() {
+        let bytes = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\nabc";
+        let parsed = parse(bytes, 0, false, true).unwrap();
+        assert!(parsed.notes.iter().any(|note| note.starts_with("invalid chunk size")));
+        assert_eq!(parsed.end, Some(bytes.len()));
+        assert!(!read_all(bytes));
+    }
+
+    #[test]
+    fn huge_content_length_is_invalid() {
+        let bytes = b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\nabc";
+        let parsed = parse(bytes, 0, false, true).unwrap();
+        assert!(parsed
+            .notes
+            .contains(&"invalid Content-Length: 18446744073709551615".to_string()));
+        assert_eq!(parsed.body, b"abc");
+        assert!(parse(bytes, 0, false, false).is_none());
+    }
+
+    #[test]
+    fn chunked_body() {
+        let bytes = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n1;x=y\r\n!\r\n0\r\nX-T: 1\r\n\r\n";
+        let parsed = parse(bytes, 0, false, false).unwrap();
+        assert_eq!(parsed.body, b"hello!");
+        assert_eq!(parsed.end, Some(bytes.len()));
+        assert!(parsed.notes.contains(&"trailer X-T: 1".to_string()));
+    }
+
+    #[test]
+    fn framer_reads_in_pieces() {
+        let bytes: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n";
+        let mut framer = Framer::new(false);
+        let mut done = Vec::new();
+        for end in 0..=bytes.len() {
+            framer.advance(&bytes[..end]);
+            if framer.done(&bytes[..end]) {
+                done.push(end);
+            }
+        }
+        let after_200 = bytes.len() - b"HTTP/1.1 204 No Content\r\n\r\n".len();
+        assert_eq!(done, vec![after_200, bytes.len()]);
+        let responses = parse_all(bytes, false, false);
+        let codes: Vec<u16> = responses.iter().map(|r| r.code).collect();
+        assert_eq!(codes, vec![100, 200, 204]);
+    }
+
+    #[test]
+    fn header_anomalies() {
+        let bytes = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\ncontent-length: 3\r\nX-Fold: a\r\n  b\r\nX-Bare: 1\n\r\nok";
+        let parsed = parse(bytes, 0, false, false).unwrap();
+        assert_eq!(parsed.headers[2], ("X-Fold".to_string(), "a b".to_string()));
+        assert!(parsed.notes.contains(&"conflicting Content-Length: 2, 3".to_string()));
+        assert!(parsed.notes.contains(&"obs-fold in X-Fold".to_string()));
+        assert!(parsed.notes.contains(&"bare LF after: X-Bare: 1".to_string()));
+        assert_eq!(parsed.body, b"ok");
+    }
+
+    #[test]
+    fn encode_escapes() {
+        assert_eq!(
+            encode("GET / HTTP/1.1\nHost: x\\n\\\nA: \\x01\\\\\n\n").unwrap(),
+            b"GET / HTTP/1.1\r\nHost: x\nA: \x01\\\r\n\r\n".to_vec()
+        );
+        assert!(encode("\\q").is_err());
+        assert!(encode("\\x1").is_err());
+    }
+}